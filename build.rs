@@ -0,0 +1,29 @@
+use std::process::Command;
+
+fn main() {
+	let commit = Command::new("git")
+		.args(["rev-parse", "--short=12", "HEAD"])
+		.output()
+		.ok()
+		.filter(|output| output.status.success())
+		.map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+	if let Some(commit) = commit {
+		println!("cargo:rustc-env=FORGE_GIT_COMMIT={}", commit);
+	}
+
+	let mut features: Vec<String> = std::env::vars()
+		.filter_map(|(key, _)| {
+			key.strip_prefix("CARGO_FEATURE_")
+				.map(|feature| feature.to_lowercase().replace('_', "-"))
+		})
+		.collect();
+	features.sort();
+	println!("cargo:rustc-env=FORGE_FEATURES={}", features.join(","));
+	println!(
+		"cargo:rustc-env=FORGE_BUILD_TARGET={}",
+		std::env::var("TARGET").unwrap_or_default()
+	);
+
+	println!("cargo:rerun-if-changed=.git/HEAD");
+	println!("cargo:rerun-if-changed=.git/refs");
+}
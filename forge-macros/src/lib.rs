@@ -1,8 +1,11 @@
 use proc_macro::TokenStream;
 use proc_macro2::{Ident, Span};
 use quote::quote;
+use std::collections::HashSet;
 use syn::{
 	Attribute, Expr, ExprLit, FnArg, ImplItem, ItemImpl, Lit, LitStr, Meta, MetaNameValue, ReturnType, Type,
+	ext::IdentExt,
+	parse::{Parse, ParseStream},
 	parse_macro_input,
 };
 
@@ -21,12 +24,28 @@ pub fn lua_api(args: TokenStream, input: TokenStream) -> TokenStream {
 	};
 
 	let lua_functions = extract_lua_functions(&input);
+	let lua_consts = extract_lua_consts(&input);
 
-	let create_table_fn = generate_create_table_function(&api_name, &lua_functions, &type_name);
+	let create_table_fn = generate_create_table_function(&api_name, &lua_functions, &lua_consts, &type_name);
 
-	let type_definitions_fn = generate_type_definitions_function(&api_name, &lua_functions, &type_name);
+	let type_definitions_fn = generate_type_definitions_function(&api_name, &lua_functions, &lua_consts, &type_name);
 
-	let original_impl = &input;
+	let mut original_impl = input.clone();
+	for item in &mut original_impl.items {
+		if let ImplItem::Const(constant) = item {
+			constant.attrs.retain(|attr| !attr.path().is_ident("lua_const"));
+		}
+		if let ImplItem::Fn(method) = item {
+			method
+				.attrs
+				.retain(|attr| !attr.path().is_ident("lua_table") && !attr.path().is_ident("lua"));
+			for input in &mut method.sig.inputs {
+				if let FnArg::Typed(pat_type) = input {
+					pat_type.attrs.retain(|attr| !attr.path().is_ident("lua"));
+				}
+			}
+		}
+	}
 
 	let output = quote! {
 		#original_impl
@@ -39,6 +58,284 @@ pub fn lua_api(args: TokenStream, input: TokenStream) -> TokenStream {
 	output.into()
 }
 
+/// Register a lua_api table as `forge.<name>`: `forge_lua_module!(fs, FsApi, "File system operations")`
+/// Expects `create_<name>_table(lua)` in the calling module and submits a `crate::lua_api::LuaModule` to inventory,
+/// so lua_api::init builds the forge table and types.lua from every registered module
+#[proc_macro]
+pub fn forge_lua_module(input: TokenStream) -> TokenStream {
+	let ModuleRegistration { name, api, description } = parse_macro_input!(input as ModuleRegistration);
+
+	let name_str = name.to_string();
+	let create_fn = Ident::new(&format!("create_{}_table", name), name.span());
+	let type_def_fn = Ident::new(&format!("{}_lua_type_definitions", name), name.span());
+
+	quote! {
+		inventory::submit! {
+			crate::lua_api::LuaModule {
+				name: #name_str,
+				description: #description,
+				create_table: #create_fn,
+				type_definitions: #api::#type_def_fn,
+			}
+		}
+	}
+	.into()
+}
+
+struct ModuleRegistration {
+	name: Ident,
+	api: Ident,
+	description: LitStr,
+}
+
+impl Parse for ModuleRegistration {
+	fn parse(input: ParseStream) -> syn::Result<Self> {
+		let name = input.parse()?;
+		input.parse::<syn::Token![,]>()?;
+		let api = input.parse()?;
+		input.parse::<syn::Token![,]>()?;
+		let description = input.parse()?;
+		let _ = input.parse::<Option<syn::Token![,]>>()?;
+		Ok(ModuleRegistration { name, api, description })
+	}
+}
+
+/// Describe a serde table struct as a LuaLS class, so lua_api parameters of this type are typed by name
+/// Field docs become field descriptions, `#[serde(rename)]`, `#[serde(default)]` and `#[serde(skip)]` are honored
+/// and `#[serde(flatten)]` fields become parent classes
+#[proc_macro_derive(LuaClass, attributes(serde))]
+pub fn lua_class(input: TokenStream) -> TokenStream {
+	let input = parse_macro_input!(input as syn::DeriveInput);
+	let type_name = &input.ident;
+
+	let syn::Data::Struct(syn::DataStruct {
+		fields: syn::Fields::Named(fields),
+		..
+	}) = &input.data
+	else {
+		panic!("LuaClass can only be derived for structs with named fields");
+	};
+
+	let mut parents = Vec::new();
+	let mut nested = Vec::new();
+	let mut field_lines = String::new();
+	for field in &fields.named {
+		let serde = extract_serde_attrs(&field.attrs);
+		if serde.skip {
+			continue;
+		}
+		if let Some(class) = class_type(&field.ty) {
+			nested.push(class.clone());
+		}
+		if serde.flatten {
+			let Some(class) = class_type(&field.ty) else {
+				panic!(
+					"flattened field `{}` must be a LuaClass struct",
+					field.ident.as_ref().unwrap()
+				);
+			};
+			parents.push(class.to_string());
+			continue;
+		}
+
+		let name = serde
+			.rename
+			.unwrap_or_else(|| field.ident.as_ref().unwrap().unraw().to_string());
+		let mut lua_type = type_to_lua_class_type(&field.ty);
+		if serde.default && !lua_type.ends_with('?') {
+			lua_type.push('?');
+		}
+		let doc = extract_doc_comment(&field.attrs).description.join(" ");
+		if doc.is_empty() {
+			field_lines.push_str(&format!("---@field {} {}\n", name, lua_type));
+		} else {
+			field_lines.push_str(&format!("---@field {} {} {}\n", name, lua_type, doc));
+		}
+	}
+
+	let mut definition = String::new();
+	push_description(&mut definition, &extract_doc_comment(&input.attrs));
+	if parents.is_empty() {
+		definition.push_str(&format!("---@class {}\n", type_name));
+	} else {
+		definition.push_str(&format!("---@class {}: {}\n", type_name, parents.join(", ")));
+	}
+	definition.push_str(&field_lines);
+
+	let definition_lit = LitStr::new(&definition, Span::call_site());
+	let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+	quote! {
+		impl #impl_generics #type_name #ty_generics #where_clause {
+			/// Push the LuaLS class of this type and of the table types it contains, each once
+			pub fn lua_class_definitions(definitions: &mut Vec<&'static str>) {
+				const DEFINITION: &str = #definition_lit;
+				if definitions.contains(&DEFINITION) {
+					return;
+				}
+				definitions.push(DEFINITION);
+				#(<#nested>::lua_class_definitions(definitions);)*
+			}
+		}
+	}
+	.into()
+}
+
+/// Expose a fieldless enum to Lua as a set of strings: FromLua and IntoLua convert variants to and from their
+/// snake_case names (or `#[lua(name = "...")]`), and lua_api parameters of the enum are typed by a `---@alias` union
+#[proc_macro_attribute]
+pub fn lua_enum(_args: TokenStream, input: TokenStream) -> TokenStream {
+	let mut input = parse_macro_input!(input as syn::ItemEnum);
+	let type_name = input.ident.clone();
+
+	let mut variants = Vec::new();
+	let mut names = Vec::new();
+	for variant in &mut input.variants {
+		if !matches!(variant.fields, syn::Fields::Unit) {
+			panic!("lua_enum variant `{}` must not have fields", variant.ident);
+		}
+		let name = extract_lua_attrs(&variant.attrs)
+			.name
+			.unwrap_or_else(|| to_snake_case(&variant.ident.to_string()));
+		variant.attrs.retain(|attr| !attr.path().is_ident("lua"));
+		variants.push(variant.ident.clone());
+		names.push(name);
+	}
+
+	let mut definition = String::new();
+	push_description(&mut definition, &extract_doc_comment(&input.attrs));
+	let union = names
+		.iter()
+		.map(|name| format!("\"{}\"", name))
+		.collect::<Vec<_>>()
+		.join(" | ");
+	definition.push_str(&format!("---@alias {} {}\n", type_name, union));
+	let definition_lit = LitStr::new(&definition, Span::call_site());
+	let expected = names.join(", ");
+
+	quote! {
+		#input
+
+		impl #type_name {
+			/// Name of the variant in Lua
+			pub fn as_lua_str(&self) -> &'static str {
+				match self {
+					#(Self::#variants => #names,)*
+				}
+			}
+
+			/// Push the LuaLS alias of this enum, once
+			pub fn lua_class_definitions(definitions: &mut Vec<&'static str>) {
+				const DEFINITION: &str = #definition_lit;
+				if !definitions.contains(&DEFINITION) {
+					definitions.push(DEFINITION);
+				}
+			}
+		}
+
+		impl mlua::FromLua for #type_name {
+			fn from_lua(value: mlua::Value, _: &mlua::Lua) -> mlua::Result<Self> {
+				let name = match &value {
+					mlua::Value::String(name) => name.to_string_lossy(),
+					other => {
+						return Err(mlua::Error::RuntimeError(format!(
+							"expected {} (one of: {}), got {}",
+							stringify!(#type_name), #expected, other.type_name()
+						)));
+					}
+				};
+				match name.as_str() {
+					#(#names => Ok(Self::#variants),)*
+					other => Err(mlua::Error::RuntimeError(format!(
+						"unknown {} '{}' (expected one of: {})",
+						stringify!(#type_name), other, #expected
+					))),
+				}
+			}
+		}
+
+		impl mlua::IntoLua for #type_name {
+			fn into_lua(self, lua: &mlua::Lua) -> mlua::Result<mlua::Value> {
+				lua.create_string(self.as_lua_str()).map(mlua::Value::String)
+			}
+		}
+	}
+	.into()
+}
+
+fn to_snake_case(name: &str) -> String {
+	let mut snake = String::new();
+	for (i, c) in name.chars().enumerate() {
+		if c.is_uppercase() {
+			if i > 0 {
+				snake.push('_');
+			}
+			snake.extend(c.to_lowercase());
+		} else {
+			snake.push(c);
+		}
+	}
+	snake
+}
+
+#[derive(Default)]
+struct SerdeAttrs {
+	rename: Option<String>,
+	flatten: bool,
+	skip: bool,
+	default: bool,
+}
+
+fn extract_serde_attrs(attrs: &[Attribute]) -> SerdeAttrs {
+	let mut serde = SerdeAttrs::default();
+	for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+		let _ = attr.parse_nested_meta(|meta| {
+			if meta.path.is_ident("rename") {
+				serde.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+			} else if meta.path.is_ident("flatten") {
+				serde.flatten = true;
+			} else if meta.path.is_ident("skip") || meta.path.is_ident("skip_deserializing") {
+				serde.skip = true;
+			} else if meta.path.is_ident("default") {
+				serde.default = true;
+				if meta.input.peek(syn::Token![=]) {
+					meta.value()?.parse::<LitStr>()?;
+				}
+			} else if meta.input.peek(syn::Token![=]) {
+				meta.value()?.parse::<Expr>()?;
+			}
+			Ok(())
+		});
+	}
+	serde
+}
+
+/// The struct behind a parameter or field that is a LuaClass table, looking through `Option` and `Vec`
+/// Any other single-name type without a Lua mapping is assumed to derive LuaClass
+fn class_type(ty: &Type) -> Option<&Ident> {
+	let Type::Path(path) = ty else {
+		return None;
+	};
+	if path.qself.is_some() || path.path.segments.len() != 1 {
+		return None;
+	}
+	let segment = &path.path.segments[0];
+	match &segment.arguments {
+		syn::PathArguments::None => {
+			let builtin = type_to_lua_type(ty) != "any"
+				|| ["Value", "MultiValue", "AnyUserData"].contains(&segment.ident.to_string().as_str());
+			(!builtin).then_some(&segment.ident)
+		}
+		syn::PathArguments::AngleBracketed(args) if segment.ident == "Option" || segment.ident == "Vec" => {
+			match args.args.first() {
+				Some(syn::GenericArgument::Type(inner)) => class_type(inner),
+				_ => None,
+			}
+		}
+		_ => None,
+	}
+}
+
 fn extract_api_name_from_args(args: &syn::punctuated::Punctuated<Meta, syn::Token![,]>) -> String {
 	for arg in args {
 		if let Meta::NameValue(MetaNameValue { path, value, .. }) = arg {
@@ -54,12 +351,160 @@ fn extract_api_name_from_args(args: &syn::punctuated::Punctuated<Meta, syn::Toke
 
 struct LuaFunction {
 	name: String,
-	doc_comment: Option<String>,
-	args: Vec<(String, String)>,
-	return_type: Option<String>,
+	docs: FunctionDocs,
+	/// What to use instead, from `#[lua(deprecated = "...")]`
+	deprecated: Option<String>,
+	args: Vec<LuaArg>,
+	/// Lua types of the returned values, empty when nothing is returned
+	return_types: Vec<String>,
+	returns_result: bool,
+	is_async: bool,
 	fn_ident: Ident,
 	has_self: bool,
 	has_lua_context: bool,
+	schemas: Vec<TableSchema>,
+}
+
+/// An associated const marked `#[lua_const]`, set as a field of the API table (e.g. `platform.SEPARATOR`)
+struct LuaConst {
+	ident: Ident,
+	lua_type: String,
+	doc: String,
+}
+
+fn extract_lua_consts(input: &ItemImpl) -> Vec<LuaConst> {
+	input
+		.items
+		.iter()
+		.filter_map(|item| match item {
+			ImplItem::Const(constant) if constant.attrs.iter().any(|attr| attr.path().is_ident("lua_const")) => {
+				Some(LuaConst {
+					ident: constant.ident.clone(),
+					lua_type: type_to_lua_type(&constant.ty),
+					doc: extract_doc_comment(&constant.attrs).description.join(" "),
+				})
+			}
+			_ => None,
+		})
+		.collect()
+}
+
+struct LuaArg {
+	name: String,
+	lua_type: String,
+	ty: Type,
+	/// Value used when the argument is nil or missing, from `#[lua(default = ...)]`
+	default: Option<Lit>,
+	/// Whether Lua callers may leave the argument out, shown as `name?` in the type definitions
+	optional: bool,
+	/// Element type when the argument collects the remaining Lua arguments as a `Variadic<T>`
+	variadic: Option<Type>,
+}
+
+impl LuaArg {
+	/// Name and type as they appear in the type definitions, `name?` and the bare type for optional arguments
+	fn display(&self) -> (String, &str) {
+		if self.variadic.is_some() {
+			("...".to_string(), &self.lua_type)
+		} else if self.optional {
+			(
+				format!("{}?", self.name),
+				self.lua_type.strip_suffix('?').unwrap_or(&self.lua_type),
+			)
+		} else {
+			(self.name.clone(), &self.lua_type)
+		}
+	}
+}
+
+/// Options from a `#[lua(...)]` attribute: `default` on parameters, `name`, `skip`, `varargs` and `deprecated` on
+/// functions
+#[derive(Default)]
+struct LuaAttrs {
+	default: Option<Lit>,
+	name: Option<String>,
+	skip: bool,
+	varargs: bool,
+	deprecated: Option<String>,
+}
+
+fn extract_lua_attrs(attrs: &[Attribute]) -> LuaAttrs {
+	let mut lua_attrs = LuaAttrs::default();
+	for attr in attrs.iter().filter(|attr| attr.path().is_ident("lua")) {
+		attr.parse_nested_meta(|meta| {
+			if meta.path.is_ident("default") {
+				lua_attrs.default = Some(meta.value()?.parse()?);
+			} else if meta.path.is_ident("name") {
+				lua_attrs.name = Some(meta.value()?.parse::<LitStr>()?.value());
+			} else if meta.path.is_ident("skip") {
+				lua_attrs.skip = true;
+			} else if meta.path.is_ident("varargs") {
+				lua_attrs.varargs = true;
+			} else if meta.path.is_ident("deprecated") {
+				lua_attrs.deprecated = Some(meta.value()?.parse::<LitStr>()?.value());
+			} else {
+				return Err(meta.error("unknown lua attribute option"));
+			}
+			Ok(())
+		})
+		.unwrap_or_else(|e| panic!("invalid lua attribute: {}", e));
+	}
+	lua_attrs
+}
+
+/// Expected shape of a table parameter, declared with
+/// `#[lua_table(options: ExecRunOptions { command: String, args: Option<Vec<String>> })]`
+struct TableSchema {
+	param: Ident,
+	class_name: Ident,
+	fields: Vec<SchemaField>,
+}
+
+struct SchemaField {
+	name: Ident,
+	ty: Type,
+	doc: String,
+}
+
+impl SchemaField {
+	fn lua_type(&self) -> String {
+		type_to_lua_type(&self.ty)
+	}
+
+	fn is_optional(&self) -> bool {
+		self.lua_type().ends_with('?')
+	}
+}
+
+impl Parse for TableSchema {
+	fn parse(input: ParseStream) -> syn::Result<Self> {
+		let param: Ident = input.parse()?;
+		input.parse::<syn::Token![:]>()?;
+		let class_name: Ident = input.parse()?;
+
+		let content;
+		syn::braced!(content in input);
+		let fields = content.parse_terminated(SchemaField::parse, syn::Token![,])?;
+
+		Ok(TableSchema {
+			param,
+			class_name,
+			fields: fields.into_iter().collect(),
+		})
+	}
+}
+
+impl Parse for SchemaField {
+	fn parse(input: ParseStream) -> syn::Result<Self> {
+		let attrs = input.call(Attribute::parse_outer)?;
+		let name = Ident::parse_any(input)?;
+		input.parse::<syn::Token![:]>()?;
+		let ty: Type = input.parse()?;
+
+		let doc = extract_doc_comment(&attrs).description.join(" ");
+
+		Ok(SchemaField { name, ty, doc })
+	}
 }
 
 fn extract_lua_functions(input: &ItemImpl) -> Vec<LuaFunction> {
@@ -67,10 +512,14 @@ fn extract_lua_functions(input: &ItemImpl) -> Vec<LuaFunction> {
 
 	for item in &input.items {
 		if let ImplItem::Fn(method) = item {
-			let name = method.sig.ident.to_string();
+			let lua_attrs = extract_lua_attrs(&method.attrs);
+			if lua_attrs.skip {
+				continue;
+			}
+			let name = lua_attrs.name.unwrap_or_else(|| method.sig.ident.to_string());
 			let fn_ident = method.sig.ident.clone();
 
-			let doc_comment = extract_doc_comment(&method.attrs);
+			let docs = extract_doc_comment(&method.attrs);
 
 			let has_self = method.sig.inputs.iter().any(|arg| matches!(arg, FnArg::Receiver(_)));
 
@@ -87,18 +536,53 @@ fn extract_lua_functions(input: &ItemImpl) -> Vec<LuaFunction> {
 				false
 			});
 
-			let args = extract_function_args(&method.sig.inputs, has_self, has_lua_context);
+			let mut args = extract_function_args(&method.sig.inputs, has_lua_context);
+
+			let schemas = extract_table_schemas(&method.attrs);
+			for schema in &schemas {
+				let param = schema.param.to_string();
+				let Some(arg) = args.iter_mut().find(|arg| arg.name == param) else {
+					panic!("lua_table attribute on `{}` refers to unknown parameter `{}`", name, param);
+				};
+				arg.lua_type = if arg.lua_type.ends_with('?') {
+					format!("{}?", schema.class_name)
+				} else {
+					schema.class_name.to_string()
+				};
+			}
+			if lua_attrs.varargs {
+				let Some(last) = args.last_mut() else {
+					panic!("`{}` is marked varargs but takes no arguments", name);
+				};
+				let Some(element) = variadic_element_type(&last.ty) else {
+					panic!(
+						"the last parameter of varargs function `{}` must be a mlua::Variadic<T>",
+						name
+					);
+				};
+				last.lua_type = type_to_lua_class_type(&element);
+				last.variadic = Some(element);
+			}
+			mark_optional_args(&mut args);
 
-			let return_type = extract_return_type(&method.sig.output);
+			let return_types = extract_return_types(&method.sig.output);
+			let returns_result = match &method.sig.output {
+				ReturnType::Type(_, ty) => result_ok_type(ty).is_some(),
+				ReturnType::Default => false,
+			};
 
 			functions.push(LuaFunction {
 				name,
-				doc_comment,
+				docs,
+				deprecated: lua_attrs.deprecated,
 				args,
-				return_type,
+				return_types,
+				returns_result,
+				is_async: method.sig.asyncness.is_some(),
 				fn_ident,
 				has_self,
 				has_lua_context,
+				schemas,
 			});
 		}
 	}
@@ -106,8 +590,21 @@ fn extract_lua_functions(input: &ItemImpl) -> Vec<LuaFunction> {
 	functions
 }
 
-fn extract_doc_comment(attrs: &[Attribute]) -> Option<String> {
-	let mut comment = String::new();
+#[derive(Default, Debug, PartialEq)]
+struct FunctionDocs {
+	description: Vec<String>,
+	params: Vec<(String, String)>,
+	returns: Option<String>,
+}
+
+impl FunctionDocs {
+	fn param(&self, name: &str) -> Option<&str> {
+		self.params.iter().find(|(n, _)| n == name).map(|(_, doc)| doc.as_str())
+	}
+}
+
+fn extract_doc_comment(attrs: &[Attribute]) -> FunctionDocs {
+	let mut lines = Vec::new();
 
 	for attr in attrs {
 		if attr.path().is_ident("doc") {
@@ -116,29 +613,62 @@ fn extract_doc_comment(attrs: &[Attribute]) -> Option<String> {
 					lit: Lit::Str(lit_str), ..
 				}) = &name_value.value
 				{
-					let line = lit_str.value().trim().to_string();
-					if !comment.is_empty() {
-						comment.push(' ');
-					}
-					comment.push_str(&line);
+					lines.push(lit_str.value());
 				}
 			}
 		}
 	}
 
-	if comment.is_empty() { None } else { Some(comment) }
+	parse_doc_lines(&lines)
+}
+
+fn extract_table_schemas(attrs: &[Attribute]) -> Vec<TableSchema> {
+	attrs
+		.iter()
+		.filter(|attr| attr.path().is_ident("lua_table"))
+		.map(|attr| {
+			attr.parse_args::<TableSchema>()
+				.unwrap_or_else(|e| panic!("invalid lua_table attribute: {}", e))
+		})
+		.collect()
+}
+
+fn parse_doc_lines(lines: &[String]) -> FunctionDocs {
+	let mut docs = FunctionDocs::default();
+
+	for line in lines {
+		let line = line.trim();
+
+		if let Some(rest) = line.strip_prefix("@param") {
+			let rest = rest.trim();
+			let (name, doc) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+			docs.params.push((name.to_string(), doc.trim().to_string()));
+		} else if let Some(rest) = line.strip_prefix("@return") {
+			docs.returns = Some(rest.trim_start_matches('s').trim().to_string());
+		} else {
+			docs.description.push(line.to_string());
+		}
+	}
+
+	while docs.description.last().is_some_and(|l| l.is_empty()) {
+		docs.description.pop();
+	}
+	while docs.description.first().is_some_and(|l| l.is_empty()) {
+		docs.description.remove(0);
+	}
+
+	docs
 }
 
 fn extract_function_args(
 	inputs: &syn::punctuated::Punctuated<FnArg, syn::Token![,]>,
-	exclude_self: bool,
 	exclude_lua_context: bool,
-) -> Vec<(String, String)> {
+) -> Vec<LuaArg> {
 	let mut args = Vec::new();
 
 	for input in inputs {
 		match input {
-			FnArg::Receiver(_) if exclude_self => {
+			FnArg::Receiver(_) => {
 				continue;
 			}
 			FnArg::Typed(pat_type) => {
@@ -157,34 +687,114 @@ fn extract_function_args(
 						}
 					}
 
-					let type_str = type_to_lua_type(&pat_type.ty);
-					args.push((name, type_str));
+					args.push(LuaArg {
+						name,
+						lua_type: type_to_lua_class_type(&pat_type.ty),
+						ty: (*pat_type.ty).clone(),
+						default: extract_lua_attrs(&pat_type.attrs).default,
+						optional: false,
+						variadic: None,
+					});
 				}
 			}
-			FnArg::Receiver(_) => {
-				args.push(("self".to_string(), "table".to_string()));
-			}
 		}
 	}
 
 	args
 }
 
-fn extract_return_type(output: &ReturnType) -> Option<String> {
-	match output {
-		ReturnType::Default => None,
-		ReturnType::Type(_, ty) => Some(type_to_lua_type(ty)),
+fn variadic_element_type(ty: &Type) -> Option<Type> {
+	let Type::Path(path) = ty else {
+		return None;
+	};
+	let segment = path.path.segments.last()?;
+	if segment.ident != "Variadic" {
+		return None;
+	}
+	match &segment.arguments {
+		syn::PathArguments::AngleBracketed(args) => match args.args.first() {
+			Some(syn::GenericArgument::Type(element)) => Some(element.clone()),
+			_ => None,
+		},
+		_ => None,
+	}
+}
+
+/// Arguments with a default are optional, and so are `Option` arguments with only optional arguments after them;
+/// an `Option` followed by a required argument still has to be passed, if only as nil
+fn mark_optional_args(args: &mut [LuaArg]) {
+	let mut trailing = true;
+	for arg in args.iter_mut().rev() {
+		trailing &= arg.default.is_some() || arg.variadic.is_some() || arg.lua_type.ends_with('?');
+		arg.optional = arg.variadic.is_none() && (arg.default.is_some() || trailing);
+	}
+}
+
+/// Lua types of what the function returns; for `Result<T, E>` that is `T`, since errors are raised instead of
+/// returned, and a tuple returns each of its elements as a separate value
+fn extract_return_types(output: &ReturnType) -> Vec<String> {
+	let ReturnType::Type(_, ty) = output else {
+		return Vec::new();
+	};
+	match result_ok_type(ty).unwrap_or(ty) {
+		Type::Tuple(tuple) => tuple.elems.iter().map(type_to_lua_type).collect(),
+		ty => vec![type_to_lua_type(ty)],
+	}
+}
+
+/// The `T` of a `Result<T, E>`, `mlua::Result<T>` or `LuaResult<T>` return type
+fn result_ok_type(ty: &Type) -> Option<&Type> {
+	let Type::Path(path) = ty else {
+		return None;
+	};
+	let segment = path.path.segments.last()?;
+	if segment.ident != "Result" && segment.ident != "LuaResult" {
+		return None;
+	}
+	match &segment.arguments {
+		syn::PathArguments::AngleBracketed(args) => match args.args.first() {
+			Some(syn::GenericArgument::Type(ok_ty)) => Some(ok_ty),
+			_ => None,
+		},
+		_ => None,
 	}
 }
 
 fn type_to_lua_type(ty: &Type) -> String {
 	match ty {
+		Type::Reference(reference) => type_to_lua_type(&reference.elem),
+		Type::Tuple(tuple) if tuple.elems.is_empty() => "nil".to_string(),
+		Type::Tuple(tuple) => format!(
+			"[{}]",
+			tuple.elems.iter().map(type_to_lua_type).collect::<Vec<_>>().join(", ")
+		),
 		Type::Path(path) => {
 			let segment = path.path.segments.last().unwrap();
+			let type_args: Vec<&Type> = match &segment.arguments {
+				syn::PathArguments::AngleBracketed(args) => args
+					.args
+					.iter()
+					.filter_map(|arg| match arg {
+						syn::GenericArgument::Type(ty) => Some(ty),
+						_ => None,
+					})
+					.collect(),
+				_ => Vec::new(),
+			};
 			match segment.ident.to_string().as_str() {
-				"String" => "string".to_string(),
+				"String" | "ProjectPath" | "str" | "PathBuf" | "Path" | "OsString" => "string".to_string(),
+				"Cow" | "Box" | "Arc" | "Rc" if type_args.len() == 1 => type_to_lua_type(type_args[0]),
+				"HashMap" | "BTreeMap" if type_args.len() == 2 => format!(
+					"table<{}, {}>",
+					type_to_lua_type(type_args[0]),
+					type_to_lua_type(type_args[1])
+				),
 				"bool" => "boolean".to_string(),
-				"i32" | "i64" | "u32" | "u64" | "f32" | "f64" | "usize" | "isize" => "number".to_string(),
+				"Table" => "table".to_string(),
+				"Function" => "function".to_string(),
+				"i8" | "i16" | "i32" | "i64" | "u8" | "u16" | "u32" | "u64" | "f32" | "f64" | "usize" | "isize" => {
+					"number".to_string()
+				}
 				"Vec" => {
 					if let syn::PathArguments::AngleBracketed(args) = &segment.arguments {
 						if let Some(syn::GenericArgument::Type(inner_ty)) = args.args.first() {
@@ -210,41 +820,39 @@ fn type_to_lua_type(ty: &Type) -> String {
 	}
 }
 
-fn generate_create_table_function(api_name: &str, functions: &[LuaFunction], type_name: &Ident) -> proc_macro2::TokenStream {
+/// Like type_to_lua_type, with LuaClass tables named by their class
+fn type_to_lua_class_type(ty: &Type) -> String {
+	let lua_type = type_to_lua_type(ty);
+	match class_type(ty) {
+		Some(class) => lua_type.replacen("any", &class.to_string(), 1),
+		None => lua_type,
+	}
+}
+
+fn generate_create_table_function(
+	api_name: &str,
+	functions: &[LuaFunction],
+	consts: &[LuaConst],
+	type_name: &Ident,
+) -> proc_macro2::TokenStream {
 	let static_methods: Vec<_> = functions.iter().filter(|f| !f.has_self).collect();
 	let instance_methods: Vec<_> = functions.iter().filter(|f| f.has_self).collect();
 
-	let static_bindings = static_methods
+	let mut static_bindings = consts
 		.iter()
-		.map(|func| {
-			let func_name = &func.name;
-			let func_ident = &func.fn_ident;
-
-			if func.has_lua_context {
-				let param_pattern = generate_param_pattern(&func.args);
-				let call_args = generate_call_args(&func.args);
-
-				quote! {
-					let #func_ident = lua.create_function(|lua, #param_pattern| {
-						let result = #type_name::#func_ident(lua, #call_args);
-						Ok(result)
-					})?;
-					tbl.set(#func_name, #func_ident)?;
-				}
-			} else {
-				let param_pattern = generate_param_pattern(&func.args);
-				let call_args = generate_call_args(&func.args);
-
-				quote! {
-					let #func_ident = lua.create_function(|_, #param_pattern| {
-						let result = #type_name::#func_ident(#call_args);
-						Ok(result)
-					})?;
-					tbl.set(#func_name, #func_ident)?;
-				}
+		.map(|constant| {
+			let ident = &constant.ident;
+			let name = ident.to_string();
+			quote! {
+				tbl.set(#name, #type_name::#ident)?;
 			}
 		})
 		.collect::<Vec<_>>();
+	static_bindings.extend(
+		static_methods
+			.iter()
+			.map(|func| generate_static_binding(api_name, func, type_name)),
+	);
 
 	let create_fn_name = Ident::new(&format!("create_{}_table", api_name), Span::call_site());
 
@@ -266,105 +874,49 @@ fn generate_create_table_function(api_name: &str, functions: &[LuaFunction], typ
 			.map(|func| {
 				let func_name = &func.name;
 				let func_ident = &func.fn_ident;
-
-				let args_without_self: Vec<_> = func.args.iter().filter(|(name, _)| name != "self").collect();
-				let param_pattern = if args_without_self.is_empty() {
-					quote! { _: () }
-				} else if args_without_self.len() == 1 {
-					let (name, _) = &args_without_self[0];
-					let param_ident = Ident::new(name, Span::call_site());
-					quote! { #param_ident }
-				} else {
-					let params: Vec<_> = args_without_self
-						.iter()
-						.map(|(name, _)| Ident::new(name, Span::call_site()))
-						.collect();
-					quote! { (#(#params),*) }
-				};
+				let (lua_pattern, args_pattern) = generate_param_patterns(func);
+				let arg_conversions = generate_arg_conversions(func);
+				let deprecation = generate_deprecation_warning(api_name, func);
+				let call_args = generate_call_args(&func.args);
 
 				let method_args = if func.has_lua_context {
-					if args_without_self.is_empty() {
-						quote! { lua }
-					} else if args_without_self.len() == 1 {
-						let (name, _) = &args_without_self[0];
-						let arg_ident = Ident::new(name, Span::call_site());
-						quote! { lua, #arg_ident }
-					} else {
-						let args: Vec<_> = args_without_self
-							.iter()
-							.map(|(name, _)| Ident::new(name, Span::call_site()))
-							.collect();
-						quote! { lua, #(#args),* }
-					}
+					quote! { lua, #call_args }
 				} else {
-					if args_without_self.is_empty() {
-						quote! {}
-					} else if args_without_self.len() == 1 {
-						let (name, _) = &args_without_self[0];
-						let arg_ident = Ident::new(name, Span::call_site());
-						quote! { #arg_ident }
-					} else {
-						let args: Vec<_> = args_without_self
-							.iter()
-							.map(|(name, _)| Ident::new(name, Span::call_site()))
-							.collect();
-						quote! { #(#args),* }
-					}
+					call_args
 				};
+				let propagate = generate_propagate(func);
 
-				if func.has_lua_context {
-					quote! {
+				if func.is_async {
+					let lua_ref = generate_async_lua_ref(func);
+					return quote! {
 						let #func_ident = {
 							let instance = self.clone();
-							lua.create_function(move |lua, #param_pattern| {
-								let result = instance.#func_ident(#method_args);
-								Ok(result)
-							})?
-						};
-						tbl.set(#func_name, #func_ident)?;
-					}
-				} else {
-					quote! {
-						let #func_ident = {
-							let instance = self.clone();
-							lua.create_function(move |_, #param_pattern| {
-								let result = instance.#func_ident(#method_args);
-								Ok(result)
+							lua.create_async_function(move |__lua: mlua::Lua, #args_pattern| {
+								let instance = instance.clone();
+								async move {
+									#lua_ref
+									#deprecation
+									#arg_conversions
+									let result = instance.#func_ident(#method_args).await #propagate;
+									Ok::<_, mlua::Error>(result)
+								}
 							})?
 						};
 						tbl.set(#func_name, #func_ident)?;
-					}
+					};
 				}
-			})
-			.collect::<Vec<_>>();
-		let static_bindings_for_static_table = static_methods
-			.iter()
-			.map(|func| {
-				let func_name = &func.name;
-				let func_ident = &func.fn_ident;
-
-				if func.has_lua_context {
-					let param_pattern = generate_param_pattern(&func.args);
-					let call_args = generate_call_args(&func.args);
 
-					quote! {
-						let #func_ident = lua.create_function(|lua, #param_pattern| {
-							let result = #type_name::#func_ident(lua, #call_args);
-							Ok(result)
-						})?;
-						tbl.set(#func_name, #func_ident)?;
-					}
-				} else {
-					let param_pattern = generate_param_pattern(&func.args);
-					let call_args = generate_call_args(&func.args);
-
-					quote! {
-						let #func_ident = lua.create_function(|_, #param_pattern| {
-							let result = #type_name::#func_ident(#call_args);
+				quote! {
+					let #func_ident = {
+						let instance = self.clone();
+						lua.create_function(move |#lua_pattern, #args_pattern| {
+							#deprecation
+							#arg_conversions
+							let result = instance.#func_ident(#method_args)#propagate;
 							Ok(result)
-						})?;
-						tbl.set(#func_name, #func_ident)?;
-					}
+						})?
+					};
+					tbl.set(#func_name, #func_ident)?;
 				}
 			})
 			.collect::<Vec<_>>();
@@ -383,7 +935,7 @@ fn generate_create_table_function(api_name: &str, functions: &[LuaFunction], typ
 				pub fn create_static_table(lua: &mlua::Lua) -> mlua::Result<mlua::Table> {
 					let tbl = lua.create_table()?;
 
-					#(#static_bindings_for_static_table)*
+					#(#static_bindings)*
 
 					Ok(tbl)
 				}
@@ -392,53 +944,284 @@ fn generate_create_table_function(api_name: &str, functions: &[LuaFunction], typ
 	}
 }
 
-fn generate_param_pattern(args: &[(String, String)]) -> proc_macro2::TokenStream {
-	if args.is_empty() {
-		quote! { _: () }
-	} else if args.len() == 1 && args[0].0 != "self" {
-		let param_name = Ident::new(&args[0].0, Span::call_site());
-		quote! { #param_name }
+fn generate_static_binding(api_name: &str, func: &LuaFunction, type_name: &Ident) -> proc_macro2::TokenStream {
+	let func_name = &func.name;
+	let func_ident = &func.fn_ident;
+	let (lua_pattern, args_pattern) = generate_param_patterns(func);
+	let arg_conversions = generate_arg_conversions(func);
+	let deprecation = generate_deprecation_warning(api_name, func);
+	let call_args = generate_call_args(&func.args);
+
+	let call = if func.has_lua_context {
+		quote! { #type_name::#func_ident(lua, #call_args) }
 	} else {
-		let params: Vec<_> = args
-			.iter()
-			.filter(|(name, _)| name != "self")
-			.map(|(name, _)| Ident::new(name, Span::call_site()))
-			.collect();
-		if params.is_empty() {
-			quote! { _: () }
-		} else {
-			quote! { (#(#params),*) }
-		}
+		quote! { #type_name::#func_ident(#call_args) }
+	};
+	let propagate = generate_propagate(func);
+
+	if func.is_async {
+		let lua_ref = generate_async_lua_ref(func);
+		return quote! {
+			let #func_ident = lua.create_async_function(|__lua: mlua::Lua, #args_pattern| async move {
+				#lua_ref
+				#deprecation
+				#arg_conversions
+				let result = #call.await #propagate;
+				Ok::<_, mlua::Error>(result)
+			})?;
+			tbl.set(#func_name, #func_ident)?;
+		};
+	}
+
+	quote! {
+		let #func_ident = lua.create_function(|#lua_pattern, #args_pattern| {
+			#deprecation
+			#arg_conversions
+			let result = #call #propagate;
+			Ok(result)
+		})?;
+		tbl.set(#func_name, #func_ident)?;
 	}
 }
 
-fn generate_call_args(args: &[(String, String)]) -> proc_macro2::TokenStream {
-	let filtered_args: Vec<_> = args.iter().filter(|(name, _)| name != "self").collect();
+/// Warn the first time a deprecated function is called, naming it as Lua code sees it
+fn generate_deprecation_warning(api_name: &str, func: &LuaFunction) -> proc_macro2::TokenStream {
+	let Some(replacement) = &func.deprecated else {
+		return quote! {};
+	};
+	let message = format!("{}.{} is deprecated: {}", api_name, func.name, replacement);
+	quote! {
+		static __DEPRECATION_WARNING: std::sync::Once = std::sync::Once::new();
+		__DEPRECATION_WARNING.call_once(|| log::warn!("{}", #message));
+	}
+}
 
-	if filtered_args.is_empty() {
+/// `?` for functions returning a Result, so their errors are raised in Lua instead of returned as a value
+fn generate_propagate(func: &LuaFunction) -> proc_macro2::TokenStream {
+	if func.returns_result {
+		quote! { ? }
+	} else {
 		quote! {}
-	} else if filtered_args.len() == 1 {
-		let arg_name = Ident::new(&filtered_args[0].0, Span::call_site());
-		quote! { #arg_name }
+	}
+}
+
+/// Whether the binding needs the Lua context, to convert arguments or to pass it on
+fn uses_lua(func: &LuaFunction) -> bool {
+	!func.args.is_empty() || func.has_lua_context
+}
+
+/// Closure parameters for a binding: the Lua context (named only when used) and the raw arguments,
+/// which are converted one by one so conversion failures can name the offending argument
+fn generate_param_patterns(func: &LuaFunction) -> (proc_macro2::TokenStream, proc_macro2::TokenStream) {
+	let lua_pattern = if uses_lua(func) {
+		quote! { lua }
+	} else {
+		quote! { _ }
+	};
+	if func.args.is_empty() {
+		(lua_pattern, quote! { _: () })
 	} else {
-		let args: Vec<_> = filtered_args
+		(lua_pattern, quote! { mut __args: mlua::MultiValue })
+	}
+}
+
+/// Async bindings receive the Lua context by value; borrow it as `lua` so the rest of the binding reads the same
+fn generate_async_lua_ref(func: &LuaFunction) -> proc_macro2::TokenStream {
+	if uses_lua(func) {
+		quote! { let lua = &__lua; }
+	} else {
+		quote! {}
+	}
+}
+
+fn generate_arg_conversions(func: &LuaFunction) -> proc_macro2::TokenStream {
+	if func.args.is_empty() {
+		return quote! {};
+	}
+
+	let func_name = &func.name;
+	let conversions = func.args.iter().enumerate().map(|(index, arg)| {
+		let ident = Ident::new(&arg.name, Span::call_site());
+		let ty = &arg.ty;
+		let position = index + 1;
+		let name = &arg.name;
+		let lua_type = &arg.lua_type;
+		let schema_check = func
+			.schemas
 			.iter()
-			.map(|(name, _)| Ident::new(name, Span::call_site()))
-			.collect();
-		quote! { #(#args),* }
+			.find(|schema| schema.param == arg.name)
+			.map(|schema| generate_schema_check(func, schema));
+		if let Some(element) = &arg.variadic {
+			return quote! {
+				let mut #ident: #ty = mlua::Variadic::new();
+				for (offset, __value) in std::mem::take(&mut __args).into_iter().enumerate() {
+					match <#element as mlua::FromLua>::from_lua(__value.clone(), lua) {
+						Ok(converted) => #ident.push(converted),
+						Err(_) => {
+							return Err(mlua::Error::RuntimeError(format!(
+								"{}: bad argument #{} '...' (expected {}, got {})",
+								#func_name, #position + offset, #lua_type, __describe(&__value)
+							)));
+						}
+					}
+				}
+			};
+		}
+		let default = arg.default.as_ref().map(|default| {
+			quote! {
+				let __value = if __value.is_nil() {
+					mlua::IntoLua::into_lua(#default, lua)?
+				} else {
+					__value
+				};
+			}
+		});
+
+		quote! {
+			let __value = __args.pop_front().unwrap_or(mlua::Value::Nil);
+			#default
+			#schema_check
+			let #ident: #ty = match <#ty as mlua::FromLua>::from_lua(__value.clone(), lua) {
+				Ok(converted) => converted,
+				Err(_) => {
+					return Err(mlua::Error::RuntimeError(format!(
+						"{}: bad argument #{} '{}' (expected {}, got {})",
+						#func_name, #position, #name, #lua_type, __describe(&__value)
+					)));
+				}
+			};
+		}
+	});
+
+	quote! {
+		let __describe = |value: &mlua::Value| match value {
+			mlua::Value::String(s) => format!("string \"{}\"", s.to_string_lossy()),
+			mlua::Value::Integer(i) => format!("integer {}", i),
+			mlua::Value::Number(n) => format!("number {}", n),
+			mlua::Value::Boolean(b) => format!("boolean {}", b),
+			other => other.type_name().to_string(),
+		};
+		#(#conversions)*
 	}
 }
 
+/// Validate a table argument against its declared schema before it is converted,
+/// so callers get a field-level error instead of a bare conversion failure
+fn generate_schema_check(func: &LuaFunction, schema: &TableSchema) -> proc_macro2::TokenStream {
+	let param_name = schema.param.to_string();
+	let func_name = &func.name;
+
+	let known_fields: Vec<String> = schema.fields.iter().map(|f| f.name.unraw().to_string()).collect();
+	let expected = known_fields.join(", ");
+
+	let field_checks = schema.fields.iter().map(|field| {
+		let field_name = field.name.unraw().to_string();
+		let ty = &field.ty;
+		let lua_type = field.lua_type();
+		let required_check = if field.is_optional() {
+			quote! {}
+		} else {
+			quote! {
+				if value.is_nil() {
+					return Err(mlua::Error::RuntimeError(format!(
+						"{}: missing required field '{}.{}' ({})",
+						#func_name, #param_name, #field_name, #lua_type
+					)));
+				}
+			}
+		};
+
+		quote! {
+			let value: mlua::Value = table.raw_get(#field_name)?;
+			#required_check
+			if <#ty as mlua::FromLua>::from_lua(value.clone(), lua).is_err() {
+				return Err(mlua::Error::RuntimeError(format!(
+					"{}: invalid field '{}.{}': expected {}, got {}",
+					#func_name, #param_name, #field_name, #lua_type, value.type_name()
+				)));
+			}
+		}
+	});
+
+	quote! {
+		if let mlua::Value::Table(table) = &__value {
+			for pair in table.pairs::<mlua::Value, mlua::Value>() {
+				let (key, _) = pair?;
+				let key = match key {
+					mlua::Value::String(key) => key.to_string_lossy(),
+					other => {
+						return Err(mlua::Error::RuntimeError(format!(
+							"{}: '{}' only accepts string keys, got {}",
+							#func_name, #param_name, other.type_name()
+						)));
+					}
+				};
+				if ![#(#known_fields),*].contains(&key.as_str()) {
+					return Err(mlua::Error::RuntimeError(format!(
+						"{}: unknown field '{}.{}' (expected one of: {})",
+						#func_name, #param_name, key, #expected
+					)));
+				}
+			}
+			#(#field_checks)*
+		}
+	}
+}
+
+fn generate_call_args(args: &[LuaArg]) -> proc_macro2::TokenStream {
+	let idents: Vec<_> = args.iter().map(|arg| Ident::new(&arg.name, Span::call_site())).collect();
+	quote! { #(#idents),* }
+}
+
 fn generate_type_definitions_function(
 	api_name: &str,
 	functions: &[LuaFunction],
+	consts: &[LuaConst],
 	type_name: &Ident,
 ) -> proc_macro2::TokenStream {
 	let class_name = capitalize_first_letter(api_name);
-	let mut type_def = format!("---@class {}\n", class_name);
+	let mut type_def = String::new();
+
+	let mut emitted_schemas = HashSet::new();
+	for schema in functions.iter().flat_map(|f| &f.schemas) {
+		if !emitted_schemas.insert(schema.class_name.to_string()) {
+			continue;
+		}
+
+		type_def.push_str(&format!("---@class {}\n", schema.class_name));
+		for field in &schema.fields {
+			let name = field.name.unraw().to_string();
+			if field.doc.is_empty() {
+				type_def.push_str(&format!("---@field {} {}\n", name, field.lua_type()));
+			} else {
+				type_def.push_str(&format!("---@field {} {} {}\n", name, field.lua_type(), field.doc));
+			}
+		}
+		type_def.push('\n');
+	}
+
+	type_def.push_str(&format!("---@class {}\n", class_name));
+
+	for constant in consts {
+		if constant.doc.is_empty() {
+			type_def.push_str(&format!("---@field {} {}\n", constant.ident, constant.lua_type));
+		} else {
+			type_def.push_str(&format!(
+				"---@field {} {} {}\n",
+				constant.ident, constant.lua_type, constant.doc
+			));
+		}
+	}
 
 	for func in functions {
-		let display_args: Vec<_> = func.args.iter().filter(|(name, _)| name != "self").collect();
+		let mut display_args: Vec<(String, String)> = Vec::new();
+		if func.has_self {
+			display_args.push(("self".to_string(), class_name.clone()));
+		}
+		display_args.extend(func.args.iter().map(|arg| {
+			let (name, lua_type) = arg.display();
+			(name, lua_type.to_string())
+		}));
 
 		let args_str = display_args
 			.iter()
@@ -446,34 +1229,131 @@ fn generate_type_definitions_function(
 			.collect::<Vec<_>>()
 			.join(", ");
 
-		let return_str = func.return_type.as_deref().unwrap_or("nil");
+		let return_str = if func.return_types.is_empty() {
+			"nil".to_string()
+		} else {
+			func.return_types.join(", ")
+		};
 
-		if let Some(comment) = &func.doc_comment {
-			type_def.push_str(&format!("--- {}\n", comment));
+		push_description(&mut type_def, &func.docs);
+		if let Some(replacement) = &func.deprecated {
+			type_def.push_str(&format!("---@deprecated {}\n", replacement));
 		}
+		type_def.push_str(&format!("---@field {} fun({}): {}\n", func.name, args_str, return_str));
+	}
 
-		if func.has_self {
-			type_def.push_str(&format!("---@field {} fun({}): {}\n", func.name, args_str, return_str));
-		} else {
-			type_def.push_str(&format!("---@field {} fun({}): {}\n", func.name, args_str, return_str));
+	type_def.push_str(&format!("local {} = {{}}\n", class_name));
+
+	for func in functions {
+		type_def.push('\n');
+		push_description(&mut type_def, &func.docs);
+		for arg in &func.args {
+			let (name, lua_type) = arg.display();
+			let mut doc = func.docs.param(&arg.name).unwrap_or_default().to_string();
+			if let Some(default) = &arg.default {
+				let default = match default {
+					Lit::Str(s) => format!("\"{}\"", s.value()),
+					other => quote!(#other).to_string(),
+				};
+				doc = format!("{} (default {})", doc, default).trim_start().to_string();
+			}
+			if doc.is_empty() {
+				type_def.push_str(&format!("---@param {} {}\n", name, lua_type));
+			} else {
+				type_def.push_str(&format!("---@param {} {} {}\n", name, lua_type, doc));
+			}
+		}
+		for (index, return_type) in func.return_types.iter().enumerate() {
+			match func.docs.returns.as_deref() {
+				Some(doc) if index == 0 && !doc.is_empty() => {
+					type_def.push_str(&format!("---@return {} {}\n", return_type, doc))
+				}
+				_ => type_def.push_str(&format!("---@return {}\n", return_type)),
+			}
+		}
+
+		if func.is_async {
+			type_def.push_str("---@async\n");
 		}
+		if let Some(replacement) = &func.deprecated {
+			type_def.push_str(&format!("---@deprecated {}\n", replacement));
+		}
+
+		let separator = if func.has_self { ":" } else { "." };
+		let param_names = func
+			.args
+			.iter()
+			.map(|arg| if arg.variadic.is_some() { "..." } else { arg.name.as_str() })
+			.collect::<Vec<_>>()
+			.join(", ");
+		type_def.push_str(&format!(
+			"function {}{}{}({}) end\n",
+			class_name, separator, func.name, param_names
+		));
 	}
 
 	type_def.push('\n');
 	type_def.push_str(&format!("---@type {}\n", class_name));
+	type_def.push_str(&format!("local {} = nil\n", api_name));
 
 	let type_def_lit = LitStr::new(&type_def, Span::call_site());
 	let type_def_fn_name = Ident::new(&format!("{}_lua_type_definitions", api_name), Span::call_site());
 
+	let mut classes: Vec<&Ident> = Vec::new();
+	for func in functions {
+		for arg in &func.args {
+			if func.schemas.iter().any(|schema| schema.param == arg.name) {
+				continue;
+			}
+			if let Some(class) = class_type(arg.variadic.as_ref().unwrap_or(&arg.ty))
+				&& !classes.contains(&class)
+			{
+				classes.push(class);
+			}
+		}
+	}
+
+	if classes.is_empty() {
+		return quote! {
+			impl #type_name {
+				pub fn #type_def_fn_name() -> &'static str {
+					#type_def_lit
+				}
+			}
+		};
+	}
+
 	quote! {
 		impl #type_name {
 			pub fn #type_def_fn_name() -> &'static str {
-				#type_def_lit
+				static DEFINITIONS: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+				DEFINITIONS.get_or_init(|| {
+					let mut classes = Vec::new();
+					#(<#classes>::lua_class_definitions(&mut classes);)*
+
+					let mut definitions = String::new();
+					for class in classes {
+						definitions.push_str(class);
+						definitions.push('\n');
+					}
+					definitions.push_str(#type_def_lit);
+					definitions
+				})
 			}
 		}
 	}
 }
 
+fn push_description(type_def: &mut String, docs: &FunctionDocs) {
+	for line in &docs.description {
+		if line.is_empty() {
+			type_def.push_str("---\n");
+		} else {
+			type_def.push_str(&format!("--- {}\n", line));
+		}
+	}
+}
+
 fn capitalize_first_letter(s: &str) -> String {
 	let mut chars = s.chars();
 	match chars.next() {
@@ -510,6 +1390,124 @@ mod tests {
 
 		let ty: Type = parse_quote!(Option<String>);
 		assert_eq!(type_to_lua_type(&ty), "string?");
+
+		let ty: Type = parse_quote!(HashMap<String, Vec<u32>>);
+		assert_eq!(type_to_lua_type(&ty), "table<string, number[]>");
+
+		let ty: Type = parse_quote!(Option<BTreeMap<String, String>>);
+		assert_eq!(type_to_lua_type(&ty), "table<string, string>?");
+
+		let ty: Type = parse_quote!((String, bool));
+		assert_eq!(type_to_lua_type(&ty), "[string, boolean]");
+
+		let strings: [Type; 4] = [
+			parse_quote!(&str),
+			parse_quote!(PathBuf),
+			parse_quote!(&Path),
+			parse_quote!(Cow<'_, str>),
+		];
+		for ty in strings {
+			assert_eq!(type_to_lua_type(&ty), "string");
+		}
+	}
+
+	#[test]
+	fn test_extract_return_types_unwraps_result() {
+		let output: ReturnType = parse_quote!(-> mlua::Result<Vec<String>>);
+		assert_eq!(extract_return_types(&output), vec!["string[]"]);
+
+		let output: ReturnType = parse_quote!(-> Result<String, std::io::Error>);
+		assert_eq!(extract_return_types(&output), vec!["string"]);
+
+		let output: ReturnType = parse_quote!(-> Result<()>);
+		assert!(extract_return_types(&output).is_empty());
+
+		let output: ReturnType = parse_quote!(-> bool);
+		assert_eq!(extract_return_types(&output), vec!["boolean"]);
+
+		let output: ReturnType = parse_quote!(-> mlua::Result<(bool, Option<String>)>);
+		assert_eq!(extract_return_types(&output), vec!["boolean", "string?"]);
+	}
+
+	#[test]
+	fn test_parse_doc_lines() {
+		let lines: Vec<String> = vec![
+			" Copy a file".to_string(),
+			" Overwrites the destination".to_string(),
+			"".to_string(),
+			" @param src Absolute source path".to_string(),
+			" @param dest Absolute destination path".to_string(),
+			" @return Nothing useful".to_string(),
+		];
+		let docs = parse_doc_lines(&lines);
+
+		assert_eq!(docs.description, vec!["Copy a file", "Overwrites the destination"]);
+		assert_eq!(docs.param("src"), Some("Absolute source path"));
+		assert_eq!(docs.param("dest"), Some("Absolute destination path"));
+		assert_eq!(docs.param("missing"), None);
+		assert_eq!(docs.returns.as_deref(), Some("Nothing useful"));
+	}
+
+	#[test]
+	fn test_parse_table_schema() {
+		let attr: Attribute = parse_quote!(#[lua_table(options: RunOptions {
+			/// Program to run
+			command: String,
+			args: Option<Vec<String>>,
+			type: Option<String>,
+		})]);
+		let schema = attr.parse_args::<TableSchema>().unwrap();
+
+		assert_eq!(schema.param, "options");
+		assert_eq!(schema.class_name, "RunOptions");
+		assert_eq!(schema.fields.len(), 3);
+		assert_eq!(schema.fields[0].doc, "Program to run");
+		assert!(!schema.fields[0].is_optional());
+		assert_eq!(schema.fields[1].lua_type(), "string[]?");
+		assert_eq!(schema.fields[2].name, "type");
+	}
+
+	#[test]
+	fn test_mark_optional_args() {
+		let method: syn::ImplItemFn = parse_quote! {
+			fn f(a: Option<String>, b: String, c: Option<u32>, #[lua(default = "debug")] d: String, e: Option<bool>) {}
+		};
+		let mut args = extract_function_args(&method.sig.inputs, false);
+		mark_optional_args(&mut args);
+
+		let optional: Vec<bool> = args.iter().map(|arg| arg.optional).collect();
+		assert_eq!(optional, vec![false, false, true, true, true]);
+		assert_eq!(args[0].display(), ("a".to_string(), "string?"));
+		assert_eq!(args[2].display(), ("c?".to_string(), "number"));
+		assert_eq!(args[3].display(), ("d?".to_string(), "string"));
+		assert!(matches!(&args[3].default, Some(Lit::Str(s)) if s.value() == "debug"));
+	}
+
+	#[test]
+	fn test_class_type() {
+		let ty: Type = parse_quote!(HttpGetRequest);
+		assert_eq!(type_to_lua_class_type(&ty), "HttpGetRequest");
+
+		let ty: Type = parse_quote!(Option<Vec<DockerMount>>);
+		assert_eq!(class_type(&ty).unwrap().to_string(), "DockerMount");
+		assert_eq!(type_to_lua_class_type(&ty), "DockerMount[]?");
+
+		let builtins: [Type; 4] = [
+			parse_quote!(String),
+			parse_quote!(Value),
+			parse_quote!(serde_json::Value),
+			parse_quote!(Option<HashMap<String, String>>),
+		];
+		for ty in builtins {
+			assert!(class_type(&ty).is_none());
+		}
+	}
+
+	#[test]
+	fn test_to_snake_case() {
+		assert_eq!(to_snake_case("Zstd"), "zstd");
+		assert_eq!(to_snake_case("ThinLto"), "thin_lto");
+		assert_eq!(to_snake_case("X86_64"), "x86_64");
 	}
 
 	#[test]
@@ -1,14 +1,26 @@
-use forge_macros::lua_api;
+use forge_macros::{LuaClass, lua_api, lua_enum};
 use mlua::{UserData, UserDataMethods};
 
 struct StringUtils;
 
 #[lua_api(name = "string_utils")]
 impl StringUtils {
+	/// Split a string into parts
+	/// Empty parts are kept
+	/// @param input The string to split
+	/// @param delimiter Separator between parts
+	/// @return The parts in order
 	fn split(input: String, delimiter: String) -> Vec<String> {
 		input.split(&delimiter).map(|s| s.to_string()).collect()
 	}
 
+	/// Delimiter used when none is given
+	#[lua_const]
+	const DEFAULT_DELIMITER: &str = ",";
+
+	#[lua_const]
+	const MAX_PARTS: u32 = 64;
+
 	fn join(parts: Vec<String>, delimiter: String) -> String {
 		parts.join(&delimiter)
 	}
@@ -16,6 +28,162 @@ impl StringUtils {
 	fn to_upper(input: String) -> String {
 		input.to_uppercase()
 	}
+
+	/// @param width Length to pad to
+	fn pad(input: String, #[lua(default = 8)] width: usize, fill: Option<String>) -> String {
+		let fill = fill.unwrap_or_else(|| " ".to_string());
+		let mut padded = input;
+		while padded.chars().count() < width {
+			padded.push_str(&fill);
+		}
+		padded
+	}
+}
+
+struct Runner;
+
+#[lua_api(name = "runner")]
+impl Runner {
+	/// Describe a command invocation
+	#[lua_table(options: RunnerOptions {
+		/// Program to run
+		command: String,
+		args: Option<Vec<String>>,
+	})]
+	fn describe(options: mlua::Table) -> String {
+		let command: String = options.get("command").unwrap();
+		let args: Vec<String> = options.get::<Option<Vec<String>>>("args").unwrap().unwrap_or_default();
+		format!("{} {}", command, args.join(" ")).trim().to_string()
+	}
+}
+
+struct Joiner;
+
+#[lua_api(name = "joiner")]
+impl Joiner {
+	/// Join all parts with a separator
+	#[lua(varargs)]
+	fn join(separator: String, parts: mlua::Variadic<String>) -> String {
+		parts.join(&separator)
+	}
+
+	#[lua(name = "type")]
+	fn kind() -> String {
+		"joiner".to_string()
+	}
+
+	#[lua(deprecated = "use joiner.join")]
+	fn concat(parts: Vec<String>) -> String {
+		parts.concat()
+	}
+
+	#[lua(skip)]
+	fn helper() -> String {
+		String::new()
+	}
+}
+
+/// Compression used for archives
+#[lua_enum]
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Codec {
+	Gzip,
+	ZstdFast,
+	#[lua(name = "none")]
+	Stored,
+}
+
+struct Compressor;
+
+#[lua_api(name = "compressor")]
+impl Compressor {
+	fn extension(codec: Codec) -> String {
+		match codec {
+			Codec::Gzip => ".gz".to_string(),
+			Codec::ZstdFast => ".zst".to_string(),
+			Codec::Stored => String::new(),
+		}
+	}
+
+	fn fastest() -> Codec {
+		Codec::ZstdFast
+	}
+}
+
+struct Waiter;
+
+#[lua_api(name = "waiter")]
+impl Waiter {
+	/// Wait, then echo the message back
+	async fn delayed(message: String, millis: u64) -> mlua::Result<String> {
+		tokio::time::sleep(std::time::Duration::from_millis(millis)).await;
+		Ok(message)
+	}
+}
+
+struct Parser;
+
+#[lua_api(name = "parser")]
+impl Parser {
+	fn parse_int(input: String) -> mlua::Result<i64> {
+		input
+			.trim()
+			.parse()
+			.map_err(|e| mlua::Error::RuntimeError(format!("not an integer: {}", e)))
+	}
+
+	fn check(input: String) -> mlua::Result<()> {
+		if input.is_empty() {
+			return Err(mlua::Error::RuntimeError("input is empty".to_string()));
+		}
+		Ok(())
+	}
+}
+
+/// Where to copy from and to
+#[derive(LuaClass)]
+struct CopySpec {
+	/// Source path
+	from: String,
+	#[serde(rename = "to")]
+	dest: Option<String>,
+	#[serde(flatten)]
+	options: CopyOptions,
+	#[serde(skip)]
+	resolved: bool,
+}
+
+#[derive(LuaClass)]
+struct CopyOptions {
+	#[serde(default)]
+	overwrite: bool,
+}
+
+impl mlua::FromLua for CopySpec {
+	fn from_lua(value: mlua::Value, lua: &mlua::Lua) -> mlua::Result<Self> {
+		let table = <mlua::Table as mlua::FromLua>::from_lua(value, lua)?;
+		Ok(CopySpec {
+			from: table.get("from")?,
+			dest: table.get("to")?,
+			options: CopyOptions {
+				overwrite: table.get::<Option<bool>>("overwrite")?.unwrap_or_default(),
+			},
+			resolved: false,
+		})
+	}
+}
+
+struct Copier;
+
+#[lua_api(name = "copier")]
+impl Copier {
+	fn describe(spec: CopySpec, extra: Option<Vec<CopySpec>>) -> String {
+		let count = extra.map(|extra| extra.len()).unwrap_or_default();
+		format!(
+			"{} -> {:?} (+{}, overwrite: {}, resolved: {})",
+			spec.from, spec.dest, count, spec.options.overwrite, spec.resolved
+		)
+	}
 }
 
 #[derive(Clone)]
@@ -106,3 +274,290 @@ fn test_type_definitions_format() {
 	assert!(calc_type_defs.contains("---@type Calculator"));
 	assert!(calc_type_defs.contains("calculator = nil"));
 }
+
+#[test]
+fn test_structured_doc_comments() {
+	let type_defs = StringUtils::string_utils_lua_type_definitions();
+
+	assert!(type_defs.contains("--- Split a string into parts\n--- Empty parts are kept\n"));
+	assert!(type_defs.contains("---@param input string The string to split\n"));
+	assert!(type_defs.contains("---@param delimiter string Separator between parts\n"));
+	assert!(type_defs.contains("---@return string[] The parts in order\n"));
+	assert!(type_defs.contains("function String_utils.split(input, delimiter) end"));
+	assert!(!type_defs.contains("@param input The string"));
+
+	let calc_type_defs = Calculator::calculator_lua_type_definitions();
+	assert!(calc_type_defs.contains("function Calculator:add(value) end"));
+}
+
+#[test]
+fn test_table_schema_definitions() {
+	let type_defs = Runner::runner_lua_type_definitions();
+
+	assert!(
+		type_defs.contains("---@class RunnerOptions\n---@field command string Program to run\n---@field args string[]?\n")
+	);
+	assert!(type_defs.contains("describe fun(options: RunnerOptions): string"));
+	assert!(type_defs.contains("---@param options RunnerOptions\n"));
+}
+
+#[test]
+fn test_table_schema_validation() {
+	let lua = mlua::Lua::new();
+	lua.globals()
+		.set("runner", Runner::create_runner_table(&lua).unwrap())
+		.unwrap();
+
+	let ok: String = lua
+		.load(r#"return runner.describe({ command = "echo", args = { "hi" } })"#)
+		.eval()
+		.unwrap();
+	assert_eq!(ok, "echo hi");
+
+	let missing = lua
+		.load(r#"return runner.describe({ args = {} })"#)
+		.eval::<String>()
+		.unwrap_err();
+	assert!(
+		missing
+			.to_string()
+			.contains("missing required field 'options.command' (string)")
+	);
+
+	let wrong_type = lua
+		.load(r#"return runner.describe({ command = "echo", args = 5 })"#)
+		.eval::<String>()
+		.unwrap_err();
+	assert!(
+		wrong_type
+			.to_string()
+			.contains("invalid field 'options.args': expected string[]?, got integer")
+	);
+
+	let unknown = lua
+		.load(r#"return runner.describe({ command = "echo", cwd = "/" })"#)
+		.eval::<String>()
+		.unwrap_err();
+	assert!(
+		unknown
+			.to_string()
+			.contains("unknown field 'options.cwd' (expected one of: command, args)")
+	);
+}
+
+#[test]
+fn test_argument_validation_errors() {
+	let lua = mlua::Lua::new();
+	lua.globals()
+		.set("string_utils", StringUtils::create_string_utils_table(&lua).unwrap())
+		.unwrap();
+
+	let parts: Vec<String> = lua.load(r#"return string_utils.split("a,b", ",")"#).eval().unwrap();
+	assert_eq!(parts, vec!["a", "b"]);
+
+	let missing = lua
+		.load(r#"return string_utils.split("a,b")"#)
+		.eval::<Vec<String>>()
+		.unwrap_err();
+	assert!(
+		missing
+			.to_string()
+			.contains("split: bad argument #2 'delimiter' (expected string, got nil)")
+	);
+
+	let wrong_type = lua
+		.load(r#"return string_utils.join("a", ",")"#)
+		.eval::<String>()
+		.unwrap_err();
+	assert!(
+		wrong_type
+			.to_string()
+			.contains("join: bad argument #1 'parts' (expected string[], got string \"a\")")
+	);
+}
+
+#[test]
+fn test_result_returns() {
+	let type_defs = Parser::parser_lua_type_definitions();
+	assert!(type_defs.contains("parse_int fun(input: string): number"));
+	assert!(type_defs.contains("check fun(input: string): nil"));
+	assert!(!type_defs.contains("any"));
+
+	let lua = mlua::Lua::new();
+	lua.globals()
+		.set("parser", Parser::create_parser_table(&lua).unwrap())
+		.unwrap();
+
+	let value: i64 = lua.load(r#"return parser.parse_int(" 42 ")"#).eval().unwrap();
+	assert_eq!(value, 42);
+
+	let error = lua.load(r#"return parser.parse_int("x")"#).eval::<i64>().unwrap_err();
+	assert!(error.to_string().contains("not an integer"));
+
+	let error = lua.load(r#"parser.check("")"#).exec().unwrap_err();
+	assert!(error.to_string().contains("input is empty"));
+}
+
+#[test]
+fn test_optional_and_default_args() {
+	let type_defs = StringUtils::string_utils_lua_type_definitions();
+	assert!(type_defs.contains("pad fun(input: string, width?: number, fill?: string): string"));
+	assert!(type_defs.contains("---@param width? number Length to pad to (default 8)\n"));
+	assert!(type_defs.contains("---@param fill? string\n"));
+
+	let lua = mlua::Lua::new();
+	lua.globals()
+		.set("string_utils", StringUtils::create_string_utils_table(&lua).unwrap())
+		.unwrap();
+
+	let padded: String = lua.load(r#"return string_utils.pad("ab")"#).eval().unwrap();
+	assert_eq!(padded, "ab      ");
+	let padded: String = lua.load(r#"return string_utils.pad("ab", 4, ".")"#).eval().unwrap();
+	assert_eq!(padded, "ab..");
+	let padded: String = lua.load(r#"return string_utils.pad("ab", nil, "-")"#).eval().unwrap();
+	assert_eq!(padded, "ab------");
+}
+
+#[test]
+fn test_lua_class_definitions() {
+	let type_defs = Copier::copier_lua_type_definitions();
+
+	assert!(type_defs.contains(
+		"--- Where to copy from and to\n---@class CopySpec: CopyOptions\n---@field from string Source path\n---@field to string?\n"
+	));
+	assert!(type_defs.contains("---@class CopyOptions\n---@field overwrite boolean?\n"));
+	assert!(!type_defs.contains("resolved"));
+	assert!(type_defs.contains("describe fun(spec: CopySpec, extra?: CopySpec[]): string"));
+	assert_eq!(type_defs.matches("---@class CopyOptions").count(), 1);
+
+	let lua = mlua::Lua::new();
+	lua.globals()
+		.set("copier", Copier::create_copier_table(&lua).unwrap())
+		.unwrap();
+
+	let described: String = lua
+		.load(r#"return copier.describe({ from = "a", to = "b", overwrite = true }, { { from = "c" } })"#)
+		.eval()
+		.unwrap();
+	assert_eq!(described, r#"a -> Some("b") (+1, overwrite: true, resolved: false)"#);
+}
+
+#[test]
+fn test_rename_skip_and_varargs() {
+	let type_defs = Joiner::joiner_lua_type_definitions();
+	assert!(type_defs.contains("join fun(separator: string, ...: string): string"));
+	assert!(type_defs.contains("---@param ... string\n"));
+	assert!(type_defs.contains("function Joiner.join(separator, ...) end"));
+	assert!(type_defs.contains("---@field type fun(): string"));
+	assert!(!type_defs.contains("kind"));
+	assert!(!type_defs.contains("helper"));
+
+	let lua = mlua::Lua::new();
+	lua.globals()
+		.set("joiner", Joiner::create_joiner_table(&lua).unwrap())
+		.unwrap();
+
+	let joined: String = lua.load(r#"return joiner.join("-", "a", "b", "c")"#).eval().unwrap();
+	assert_eq!(joined, "a-b-c");
+	let joined: String = lua.load(r#"return joiner.join(",")"#).eval().unwrap();
+	assert_eq!(joined, "");
+	let kind: String = lua.load(r#"return joiner.type()"#).eval().unwrap();
+	assert_eq!(kind, "joiner");
+	assert!(lua.load(r#"return joiner.helper"#).eval::<mlua::Value>().unwrap().is_nil());
+	assert_eq!(Joiner::helper(), "");
+
+	let error = lua.load(r#"return joiner.join("-", "a", {})"#).eval::<String>().unwrap_err();
+	assert!(
+		error
+			.to_string()
+			.contains("join: bad argument #3 '...' (expected string, got table)")
+	);
+}
+
+#[test]
+fn test_async_functions() {
+	let type_defs = Waiter::waiter_lua_type_definitions();
+	assert!(type_defs.contains("delayed fun(message: string, millis: number): string"));
+	assert!(type_defs.contains("---@async\nfunction Waiter.delayed(message, millis) end"));
+
+	let lua = mlua::Lua::new();
+	lua.globals()
+		.set("waiter", Waiter::create_waiter_table(&lua).unwrap())
+		.unwrap();
+
+	let runtime = tokio::runtime::Builder::new_current_thread().enable_time().build().unwrap();
+	let message: String = runtime
+		.block_on(lua.load(r#"return waiter.delayed("done", 5)"#).eval_async())
+		.unwrap();
+	assert_eq!(message, "done");
+
+	let error = runtime
+		.block_on(lua.load(r#"return waiter.delayed("done", "soon")"#).eval_async::<String>())
+		.unwrap_err();
+	assert!(error.to_string().contains("delayed: bad argument #2 'millis'"));
+}
+
+#[test]
+fn test_lua_enum() {
+	let type_defs = Compressor::compressor_lua_type_definitions();
+	assert!(type_defs.contains("--- Compression used for archives\n---@alias Codec \"gzip\" | \"zstd_fast\" | \"none\"\n"));
+	assert!(type_defs.contains("extension fun(codec: Codec): string"));
+	assert_eq!(Codec::Stored.as_lua_str(), "none");
+
+	let lua = mlua::Lua::new();
+	lua.globals()
+		.set("compressor", Compressor::create_compressor_table(&lua).unwrap())
+		.unwrap();
+
+	let extension: String = lua.load(r#"return compressor.extension("gzip")"#).eval().unwrap();
+	assert_eq!(extension, ".gz");
+	let codec: Codec = lua.load(r#"return compressor.fastest()"#).eval().unwrap();
+	assert_eq!(codec, Codec::ZstdFast);
+	let name: String = lua.load(r#"return compressor.fastest()"#).eval().unwrap();
+	assert_eq!(name, "zstd_fast");
+
+	let error = lua
+		.load(r#"return compressor.extension("brotli")"#)
+		.eval::<String>()
+		.unwrap_err();
+	assert!(
+		error
+			.to_string()
+			.contains("bad argument #1 'codec' (expected Codec, got string \"brotli\")")
+	);
+}
+
+#[test]
+fn test_lua_consts() {
+	let type_defs = StringUtils::string_utils_lua_type_definitions();
+	assert!(type_defs.contains("---@field DEFAULT_DELIMITER string Delimiter used when none is given\n"));
+	assert!(type_defs.contains("---@field MAX_PARTS number\n"));
+
+	let lua = mlua::Lua::new();
+	lua.globals()
+		.set("string_utils", StringUtils::create_string_utils_table(&lua).unwrap())
+		.unwrap();
+	let (delimiter, max): (String, u32) = lua
+		.load(r#"return string_utils.DEFAULT_DELIMITER, string_utils.MAX_PARTS"#)
+		.eval()
+		.unwrap();
+	assert_eq!(delimiter, ",");
+	assert_eq!(max, 64);
+}
+
+#[test]
+fn test_deprecated_functions() {
+	let type_defs = Joiner::joiner_lua_type_definitions();
+	assert!(type_defs.contains("---@deprecated use joiner.join\n---@field concat fun(parts: string[]): string"));
+	assert!(type_defs.contains("---@deprecated use joiner.join\nfunction Joiner.concat(parts) end"));
+
+	let lua = mlua::Lua::new();
+	lua.globals()
+		.set("joiner", Joiner::create_joiner_table(&lua).unwrap())
+		.unwrap();
+	let joined: String = lua
+		.load(r#"joiner.concat({ "a" }) return joiner.concat({ "a", "b" })"#)
+		.eval()
+		.unwrap();
+	assert_eq!(joined, "ab");
+}
@@ -137,8 +137,6 @@ function M.run_inline(config)
 		forge.log.warn(("Inline command '%s' has no outputs specified - will always run"):format(config.name))
 	end
 
-	local args = { "-c", config.code }
-
 	local inputs = {}
 	if config.inputs then
 		for _, input in ipairs(config.inputs) do
@@ -166,8 +164,8 @@ function M.run_inline(config)
 
 	forge.rule({
 		name = config.name,
-		command = "bash",
-		args = args,
+		script = config.code,
+		shell = { "bash" },
 		env = env,
 		inputs = inputs,
 		outputs = outputs,
@@ -19,6 +19,7 @@ function M.define_program_rules_for_target(program_info, target_name, target_con
 	local compiler_path = target_config.compiler_path or program_info.compiler_path
 
 	local compiler_info = common.get_compiler_for_target(compiler_name, target, compiler_path)
+	local sysroot_flags = compiler_common.get_sysroot_flags(target, target_config)
 
 	local out_dir = forge.path.join({
 		forge.project.root,
@@ -81,6 +82,10 @@ function M.define_program_rules_for_target(program_info, target_name, target_con
 		table.insert(args, arg)
 	end
 
+	for _, flag in ipairs(sysroot_flags.compile) do
+		table.insert(args, flag)
+	end
+
 	for _, src in ipairs(sources) do
 		table.insert(args, to_absolute_path(src, program_path))
 	end
@@ -157,6 +162,10 @@ function M.define_program_rules_for_target(program_info, target_name, target_con
 		end
 	end
 
+	for _, flag in ipairs(sysroot_flags.link) do
+		table.insert(args, flag)
+	end
+
 	local inputs = {}
 	for _, src in ipairs(sources) do
 		table.insert(inputs, to_absolute_path(src, program_path))
@@ -170,6 +179,7 @@ function M.define_program_rules_for_target(program_info, target_name, target_con
 		name = ("%s-compile-%s"):format(program_info.name, target_name),
 		command = compiler_info.command,
 		args = args,
+		rsp_format = "gcc",
 		inputs = inputs,
 		outputs = { output_path },
 		dependencies = dep_rules,
@@ -187,6 +197,7 @@ function M.define_library_rules_for_target(library_info, target_name, target_con
 	local compiler_path = target_config.compiler_path or library_info.compiler_path
 
 	local compiler_info = common.get_compiler_for_target(compiler_name, target, compiler_path)
+	local sysroot_flags = compiler_common.get_sysroot_flags(target, target_config)
 
 	local out_dir = forge.path.join({
 		forge.project.root,
@@ -232,6 +243,9 @@ function M.define_library_rules_for_target(library_info, target_name, target_con
 		for _, arg in ipairs(compiler_info.args) do
 			table.insert(compile_args, arg)
 		end
+		for _, flag in ipairs(sysroot_flags.compile) do
+			table.insert(compile_args, flag)
+		end
 
 		table.insert(compile_args, "-c")
 		table.insert(compile_args, to_absolute_path(src, library_path))
@@ -298,6 +312,7 @@ function M.define_library_rules_for_target(library_info, target_name, target_con
 		name = ("%s-lib-%s"):format(library_info.name, target_name),
 		command = "ar",
 		args = ar_args,
+		rsp_format = "gcc",
 		inputs = object_files,
 		outputs = { output_path },
 		dependencies = compile_rules,
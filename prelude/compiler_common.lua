@@ -91,6 +91,27 @@ function M.ensure_dir(path)
 	end
 end
 
+function M.get_sysroot_flags(target, target_config)
+	local sysroot = target_config.sysroot
+	if sysroot then
+		sysroot = M.to_absolute_path(sysroot)
+	elseif forge.sysroot then
+		sysroot = forge.sysroot.get(M.get_target_triple_string(target))
+	end
+
+	if not sysroot then
+		return { compile = {}, link = {} }
+	end
+
+	local link = {}
+	if target.os == "linux" then
+		table.insert(link, "-Wl,-rpath-link," .. forge.path.join({ sysroot, "lib" }))
+		table.insert(link, "-Wl,-rpath-link," .. forge.path.join({ sysroot, "usr", "lib" }))
+	end
+
+	return { compile = { "--sysroot=" .. sysroot }, link = link }
+end
+
 function M.get_gcc_cross_compiler(target, is_cpp)
 	local host_target = M.get_host_target()
 	local gcc_cmd = is_cpp and "g++" or "gcc"
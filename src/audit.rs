@@ -0,0 +1,204 @@
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::{BTreeMap, HashMap},
+	fs::{File, OpenOptions},
+	io::{BufRead, BufReader, Write},
+	path::{Path, PathBuf},
+	sync::Mutex,
+};
+
+/// Name of the audit log in the cache directory
+pub const AUDIT_LOG_NAME: &str = "audit.jsonl";
+
+/// Recorded in place of the value of a variable matching [build] audit_redact
+pub const REDACTED: &str = "<redacted>";
+
+/// One command a rule ran, a line of the audit log
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+	pub time: String,
+	pub rule: String,
+	/// "local", or where the command ran
+	pub executor: String,
+	pub command: String,
+	pub args: Vec<String>,
+	pub cwd: String,
+	/// Variables the rule set to something else than forge's own environment, REDACTED for those matching
+	/// [build] audit_redact
+	pub env: BTreeMap<String, String>,
+	pub duration_ms: u128,
+	/// None when the command was killed by a signal
+	pub exit_code: Option<i32>,
+	/// blake3 of each input file before the command ran
+	pub inputs: BTreeMap<String, String>,
+	/// blake3 of each output, empty when the command failed
+	pub outputs: BTreeMap<String, String>,
+}
+
+/// <cache_dir>/audit.jsonl, appended to by every build and never truncated
+#[derive(Debug)]
+pub struct AuditLog {
+	path: PathBuf,
+	file: Mutex<File>,
+}
+
+impl AuditLog {
+	pub fn open(path: &Path) -> std::io::Result<Self> {
+		let file = OpenOptions::new().create(true).append(true).open(path)?;
+		Ok(Self {
+			path: path.to_path_buf(),
+			file: Mutex::new(file),
+		})
+	}
+
+	/// Append entry; failures are only reported, like the build log's
+	pub fn record(&self, entry: &AuditEntry) {
+		let written = serde_json::to_string(entry)
+			.map_err(std::io::Error::other)
+			.and_then(|json| writeln!(self.file.lock().unwrap(), "{}", json));
+		if let Err(e) = written {
+			log::warn!("Failed to write audit log {}: {}", self.path.display(), e);
+		}
+	}
+
+	/// Make sure every entry recorded so far is on disk
+	pub fn sync(&self) {
+		if let Err(e) = self.file.lock().unwrap().sync_data() {
+			log::warn!("Failed to write audit log {}: {}", self.path.display(), e);
+		}
+	}
+}
+
+/// The variables of env that differ from forge's own environment, with the values of those whose name matches one
+/// of the redact patterns (globs, case-insensitive) replaced by REDACTED
+pub fn env_diff(env: &HashMap<String, String>, redact: &[String]) -> BTreeMap<String, String> {
+	let options = glob::MatchOptions {
+		case_sensitive: false,
+		..Default::default()
+	};
+	let patterns: Vec<glob::Pattern> = redact
+		.iter()
+		.filter_map(|pattern| match glob::Pattern::new(pattern) {
+			Ok(pattern) => Some(pattern),
+			Err(e) => {
+				log::warn!("Ignoring audit_redact pattern '{}': {}", pattern, e);
+				None
+			}
+		})
+		.collect();
+	env.iter()
+		.filter(|(key, value)| std::env::var(key).ok().as_deref() != Some(value.as_str()))
+		.map(|(key, value)| {
+			let value = if patterns.iter().any(|pattern| pattern.matches_with(key, options)) {
+				REDACTED.to_string()
+			} else {
+				value.clone()
+			};
+			(key.clone(), value)
+		})
+		.collect()
+}
+
+/// The entries of the audit log at path, oldest first, only those of rule when given
+/// Lines that do not parse, such as one cut short by a crash, are skipped
+pub fn read_entries(path: &Path, rule: Option<&str>) -> std::io::Result<Vec<AuditEntry>> {
+	let file = File::open(path)?;
+	let mut entries = Vec::new();
+	for line in BufReader::new(file).lines() {
+		let Ok(entry) = serde_json::from_str::<AuditEntry>(&line?) else {
+			continue;
+		};
+		if rule.is_none_or(|rule| entry.rule == rule) {
+			entries.push(entry);
+		}
+	}
+	Ok(entries)
+}
+
+/// Human-readable form of an entry, for forge audit show
+pub fn format_entry(entry: &AuditEntry) -> String {
+	let mut text = format!(
+		"{} {} on {} ({}, {} ms)\n  $ {} {}\n  cwd {}",
+		entry.time,
+		entry.rule,
+		entry.executor,
+		match entry.exit_code {
+			Some(code) => format!("exit {}", code),
+			None => "killed".to_string(),
+		},
+		entry.duration_ms,
+		entry.command,
+		entry.args.join(" "),
+		entry.cwd
+	);
+	for (section, values) in [("env", &entry.env), ("inputs", &entry.inputs), ("outputs", &entry.outputs)] {
+		if values.is_empty() {
+			continue;
+		}
+		text.push_str(&format!("\n  {}", section));
+		for (key, value) in values {
+			let separator = if section == "env" { "=" } else { "  " };
+			text.push_str(&format!("\n    {}{}{}", key, separator, value));
+		}
+	}
+	text
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_audit_log_round_trip() {
+		let dir = std::env::temp_dir().join(format!("forge-audit-test-{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let path = dir.join(AUDIT_LOG_NAME);
+		let _ = std::fs::remove_file(&path);
+
+		let entry = |rule: &str, exit_code| AuditEntry {
+			time: "2024-01-01T00:00:00+00:00".to_string(),
+			rule: rule.to_string(),
+			executor: "local".to_string(),
+			command: "cc".to_string(),
+			args: vec!["-c".to_string(), "main.c".to_string()],
+			cwd: "/src".to_string(),
+			env: BTreeMap::from([("CFLAGS".to_string(), "-O2".to_string())]),
+			duration_ms: 12,
+			exit_code,
+			inputs: BTreeMap::from([("main.c".to_string(), "abc".to_string())]),
+			outputs: BTreeMap::new(),
+		};
+		let log = AuditLog::open(&path).unwrap();
+		log.record(&entry("app:main.o", Some(0)));
+		log.record(&entry("app:util.o", Some(1)));
+		drop(log);
+		// A second build appends rather than starting over
+		AuditLog::open(&path).unwrap().record(&entry("app:main.o", None));
+
+		assert_eq!(read_entries(&path, None).unwrap().len(), 3);
+		let main = read_entries(&path, Some("app:main.o")).unwrap();
+		assert_eq!(main.len(), 2);
+		assert_eq!(main[1].exit_code, None);
+		assert!(format_entry(&main[0]).contains("$ cc -c main.c"));
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn test_env_diff_redacts_secrets() {
+		let env = HashMap::from([
+			("GITHUB_TOKEN".to_string(), "ghp_123".to_string()),
+			("aws_secret_access_key".to_string(), "abc".to_string()),
+			("FORGE_AUDIT_TEST_CFLAGS".to_string(), "-O2".to_string()),
+		]);
+		let redact = ["*TOKEN*".to_string(), "*SECRET*".to_string(), "*KEY*".to_string()];
+		assert_eq!(
+			env_diff(&env, &redact),
+			BTreeMap::from([
+				("FORGE_AUDIT_TEST_CFLAGS".to_string(), "-O2".to_string()),
+				("GITHUB_TOKEN".to_string(), REDACTED.to_string()),
+				("aws_secret_access_key".to_string(), REDACTED.to_string()),
+			])
+		);
+	}
+}
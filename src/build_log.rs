@@ -0,0 +1,276 @@
+use crate::diagnostic::Diagnostic;
+use serde::Serialize;
+use std::{
+	collections::HashMap,
+	fs::{File, OpenOptions},
+	io::Write,
+	path::{Path, PathBuf},
+	sync::Mutex,
+};
+
+/// Build logs kept in the logs directory, older ones are removed when a build starts
+const KEPT_BUILD_LOGS: usize = 20;
+
+/// One line of the build log, written as JSON
+#[derive(Debug, Serialize)]
+pub struct LogEvent<'a> {
+	pub event: &'a str,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub rule: Option<&'a str>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub exit_code: Option<i32>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub duration_ms: Option<u128>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub level: Option<&'a str>,
+	/// Scope of a log.scope logger
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub scope: Option<&'a str>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub message: Option<String>,
+	/// Structured fields from log.kv
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub fields: Option<serde_json::Value>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub diagnostic: Option<Diagnostic>,
+}
+
+impl<'a> LogEvent<'a> {
+	pub fn new(event: &'a str) -> Self {
+		Self {
+			event,
+			rule: None,
+			exit_code: None,
+			duration_ms: None,
+			level: None,
+			scope: None,
+			message: None,
+			fields: None,
+			diagnostic: None,
+		}
+	}
+
+	pub fn rule(mut self, rule: &'a str) -> Self {
+		self.rule = Some(rule);
+		self
+	}
+
+	pub fn exit_code(mut self, exit_code: Option<i32>) -> Self {
+		self.exit_code = exit_code;
+		self
+	}
+
+	pub fn duration_ms(mut self, duration_ms: u128) -> Self {
+		self.duration_ms = Some(duration_ms);
+		self
+	}
+
+	pub fn level(mut self, level: &'a str) -> Self {
+		self.level = Some(level);
+		self
+	}
+
+	pub fn scope(mut self, scope: &'a str) -> Self {
+		self.scope = Some(scope);
+		self
+	}
+
+	pub fn message(mut self, message: impl Into<String>) -> Self {
+		self.message = Some(message.into());
+		self
+	}
+
+	pub fn fields(mut self, fields: serde_json::Value) -> Self {
+		self.fields = Some(fields);
+		self
+	}
+
+	pub fn diagnostic(mut self, diagnostic: Diagnostic) -> Self {
+		self.diagnostic = Some(diagnostic);
+		self
+	}
+}
+
+/// Structured log of one build in <cache_dir>/logs/build-<timestamp>.log, plus the captured output of every rule that ran
+#[derive(Debug)]
+pub struct BuildLog {
+	dir: PathBuf,
+	path: PathBuf,
+	file: Mutex<File>,
+	/// First error line of each rule whose output was saved in this build
+	error_lines: Mutex<HashMap<String, String>>,
+}
+
+impl BuildLog {
+	pub fn create(dir: &Path) -> std::io::Result<Self> {
+		std::fs::create_dir_all(dir)?;
+		prune_build_logs(dir, KEPT_BUILD_LOGS.saturating_sub(1));
+
+		let path = dir.join(format!("build-{}.log", chrono::Local::now().format("%Y%m%d-%H%M%S%.3f")));
+		let file = OpenOptions::new().create(true).append(true).open(&path)?;
+
+		Ok(Self {
+			dir: dir.to_path_buf(),
+			path,
+			file: Mutex::new(file),
+			error_lines: Mutex::new(HashMap::new()),
+		})
+	}
+
+	pub fn path(&self) -> &Path {
+		&self.path
+	}
+
+	/// Append an event; failures are only reported, a broken log must not fail the build
+	pub fn record(&self, event: LogEvent) {
+		#[derive(Serialize)]
+		struct Line<'a> {
+			time: String,
+			#[serde(flatten)]
+			event: LogEvent<'a>,
+		}
+
+		let line = Line {
+			time: chrono::Local::now().to_rfc3339(),
+			event,
+		};
+		let written = serde_json::to_string(&line)
+			.map_err(std::io::Error::other)
+			.and_then(|json| writeln!(self.file.lock().unwrap(), "{}", json));
+		if let Err(e) = written {
+			log::warn!("Failed to write build log {}: {}", self.path.display(), e);
+		}
+	}
+
+	/// Replace the saved stdout / stderr of a rule with the output of its latest run
+	pub fn save_rule_output(&self, rule: &str, stdout: &[u8], stderr: &[u8]) {
+		let (stdout_path, stderr_path) = rule_output_paths(&self.dir, rule);
+		for (path, content) in [(stdout_path, stdout), (stderr_path, stderr)] {
+			if let Err(e) = std::fs::write(&path, content) {
+				log::warn!("Failed to save output of rule '{}' to {}: {}", rule, path.display(), e);
+			}
+		}
+		if let Some(line) = first_error_line(stdout, stderr) {
+			self.error_lines.lock().unwrap().insert(rule.to_string(), line);
+		}
+	}
+
+	/// The line of the output rule printed in this build that best explains a failure
+	pub fn error_line(&self, rule: &str) -> Option<String> {
+		self.error_lines.lock().unwrap().get(rule).cloned()
+	}
+}
+
+/// Where the last stdout and stderr of a rule are saved
+pub fn rule_output_paths(logs_dir: &Path, rule: &str) -> (PathBuf, PathBuf) {
+	let stem = rule_file_stem(rule);
+	(
+		logs_dir.join(format!("{}.stdout", stem)),
+		logs_dir.join(format!("{}.stderr", stem)),
+	)
+}
+
+/// The first line mentioning an error, in stderr then stdout, or else the first non-empty line of stderr then stdout
+fn first_error_line(stdout: &[u8], stderr: &[u8]) -> Option<String> {
+	let stderr = String::from_utf8_lossy(stderr);
+	let stdout = String::from_utf8_lossy(stdout);
+	let lines = || {
+		stderr
+			.lines()
+			.chain(stdout.lines())
+			.map(str::trim)
+			.filter(|line| !line.is_empty())
+	};
+	lines()
+		.find(|line| line.to_lowercase().contains("error"))
+		.or_else(|| lines().next())
+		.map(str::to_string)
+}
+
+/// The most recent build log in logs_dir
+pub fn latest_build_log(logs_dir: &Path) -> Option<PathBuf> {
+	build_logs(logs_dir).pop()
+}
+
+/// Rule names contain "/" and ":", so everything outside [A-Za-z0-9._-] is percent-encoded
+fn rule_file_stem(rule: &str) -> String {
+	let mut stem = String::with_capacity(rule.len());
+	for byte in rule.bytes() {
+		if byte.is_ascii_alphanumeric() || matches!(byte, b'.' | b'_' | b'-') {
+			stem.push(byte as char);
+		} else {
+			stem.push_str(&format!("%{:02X}", byte));
+		}
+	}
+	stem
+}
+
+/// Build logs in logs_dir, oldest first (the timestamped names sort chronologically)
+fn build_logs(logs_dir: &Path) -> Vec<PathBuf> {
+	let Ok(entries) = std::fs::read_dir(logs_dir) else {
+		return Vec::new();
+	};
+	let mut logs: Vec<PathBuf> = entries
+		.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+		.filter(|path| {
+			path.file_name()
+				.and_then(|name| name.to_str())
+				.is_some_and(|name| name.starts_with("build-") && name.ends_with(".log"))
+		})
+		.collect();
+	logs.sort();
+	logs
+}
+
+fn prune_build_logs(logs_dir: &Path, keep: usize) {
+	let logs = build_logs(logs_dir);
+	let excess = logs.len().saturating_sub(keep);
+	for old in &logs[..excess] {
+		let _ = std::fs::remove_file(old);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_rule_file_stem() {
+		assert_eq!(rule_file_stem("compile_main"), "compile_main");
+		assert_eq!(rule_file_stem("cc:src/main.c"), "cc%3Asrc%2Fmain.c");
+	}
+
+	#[test]
+	fn test_first_error_line() {
+		assert_eq!(
+			first_error_line(
+				b"compiling a.c\n",
+				b"a.c: In function 'main':\na.c:3:1: error: expected ';'\n"
+			)
+			.as_deref(),
+			Some("a.c:3:1: error: expected ';'")
+		);
+		assert_eq!(first_error_line(b"\n  failed\n", b"").as_deref(), Some("failed"));
+		assert_eq!(first_error_line(b"", b""), None);
+	}
+
+	#[test]
+	fn test_build_log_keeps_latest() {
+		let dir = std::env::temp_dir().join(format!("forge-build-log-test-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+
+		let log = BuildLog::create(&dir).unwrap();
+		log.record(LogEvent::new("rule_finished").rule("a").exit_code(Some(0)));
+		log.save_rule_output("cc:a.c", b"out", b"err");
+
+		assert_eq!(latest_build_log(&dir).as_deref(), Some(log.path()));
+		let content = std::fs::read_to_string(log.path()).unwrap();
+		assert!(content.contains("\"event\":\"rule_finished\""));
+		let (stdout, stderr) = rule_output_paths(&dir, "cc:a.c");
+		assert_eq!(std::fs::read(stdout).unwrap(), b"out");
+		assert_eq!(std::fs::read(stderr).unwrap(), b"err");
+		assert_eq!(log.error_line("cc:a.c").as_deref(), Some("err"));
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
@@ -1,6 +1,12 @@
 use dashmap::DashMap;
 use serde::{Deserialize, Serialize};
-use std::{fs::File, io::BufReader, path::Path, time::SystemTime};
+use std::{
+	collections::HashMap,
+	fs::File,
+	io::{BufReader, BufWriter},
+	path::{Path, PathBuf},
+	time::SystemTime,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BuildCache {
@@ -19,6 +25,33 @@ pub struct ArtifactMetadata {
 	pub created: SystemTime,
 	pub compressed: bool,
 	pub dependencies: Vec<String>,
+	#[serde(default)]
+	pub output_hashes: HashMap<String, String>,
+	/// Modification time of each output when it was stored, kept when a rebuild produces the same content
+	#[serde(default)]
+	pub output_mtimes: HashMap<String, SystemTime>,
+	/// The CAS artifact output_hashes describe
+	#[serde(default)]
+	pub artifact: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RestoreMarker {
+	pub rule: String,
+	pub outputs: Vec<String>,
+}
+
+impl RestoreMarker {
+	pub fn path_for(marker_dir: &Path, rule: &str) -> PathBuf {
+		marker_dir.join(format!("{}.json", blake3::hash(rule.as_bytes()).to_hex()))
+	}
+
+	pub fn write(&self, marker_dir: &Path) -> anyhow::Result<PathBuf> {
+		std::fs::create_dir_all(marker_dir)?;
+		let path = Self::path_for(marker_dir, &self.rule);
+		std::fs::write(&path, serde_json::to_vec(self)?)?;
+		Ok(path)
+	}
 }
 
 impl BuildCache {
@@ -67,12 +100,47 @@ impl BuildCache {
 		}
 	}
 
+	pub fn recover_interrupted_restores(&self, project_path: &Path, marker_dir: &Path) {
+		let Ok(entries) = std::fs::read_dir(marker_dir) else {
+			return;
+		};
+
+		for entry in entries.filter_map(|e| e.ok()) {
+			let marker_path = entry.path();
+			let marker = std::fs::read(&marker_path)
+				.ok()
+				.and_then(|content| serde_json::from_slice::<RestoreMarker>(&content).ok());
+
+			if let Some(marker) = marker {
+				log::warn!(
+					"Restore of rule '{}' was interrupted by a previous build; its outputs will be restored again",
+					marker.rule
+				);
+
+				for output in &marker.outputs {
+					let output_path = project_path.join(output);
+					if output_path.is_file() {
+						let _ = std::fs::remove_file(&output_path);
+					}
+					self.file_hashes.remove(output);
+					self.mtimes.remove(output);
+				}
+				self.rule_hashes.remove(&marker.rule);
+			}
+
+			let _ = std::fs::remove_file(&marker_path);
+		}
+	}
+
 	pub fn save(&self, path: &Path) -> anyhow::Result<()> {
 		if let Some(parent) = path.parent() {
 			std::fs::create_dir_all(parent)?;
 		}
-		let file = File::create(path)?;
-		serde_json::to_writer_pretty(file, self)?;
+		// Written beside path and moved over it, so an interrupted save leaves the previous cache intact
+		let partial = path.with_extension("json.partial");
+		let file = File::create(&partial)?;
+		serde_json::to_writer_pretty(BufWriter::new(file), self)?;
+		std::fs::rename(&partial, path)?;
 		Ok(())
 	}
 }
@@ -7,6 +7,30 @@ pub struct Config {
 	pub target_filters: Vec<String>,
 	pub component_filters: Vec<String>,
 	pub test_mode: bool,
+	pub offline: bool,
+	/// Name of the build profile, such as debug or release
+	pub profile: String,
+}
+
+impl Config {
+	pub fn output_mode(&self) -> OutputMode {
+		match self.verbosity.0.log_level() {
+			None => OutputMode::Quiet,
+			Some(log::Level::Error) => OutputMode::Normal,
+			Some(_) => OutputMode::Verbose,
+		}
+	}
+}
+
+/// What the terminal shows of a build, from -q / -v
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputMode {
+	/// Only failures: diagnostics and the output of failed rules
+	Quiet,
+	/// Also the build summary
+	Normal,
+	/// Also the output of rules that succeeded
+	Verbose,
 }
 
 #[derive(Debug, Clone)]
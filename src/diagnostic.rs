@@ -0,0 +1,380 @@
+use serde::Serialize;
+use std::{fmt, io::IsTerminal, path::Path, sync::OnceLock};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+	Error,
+	Warning,
+}
+
+impl Severity {
+	fn label(self) -> &'static str {
+		match self {
+			Severity::Error => "error",
+			Severity::Warning => "warning",
+		}
+	}
+
+	fn color(self) -> &'static str {
+		match self {
+			Severity::Error => "\x1b[1;31m",
+			Severity::Warning => "\x1b[1;33m",
+		}
+	}
+}
+
+/// A problem to report to the user, optionally pointing at a location in a FORGE file
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnostic {
+	pub severity: Severity,
+	pub message: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub file: Option<String>,
+	/// 1-based line in file
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub line: Option<usize>,
+	/// 1-based column in line
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub column: Option<usize>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub help: Option<String>,
+	/// Extra context, such as the Lua call stack
+	#[serde(skip_serializing_if = "Vec::is_empty")]
+	pub notes: Vec<String>,
+}
+
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+impl Diagnostic {
+	pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+		Self {
+			severity,
+			message: message.into(),
+			file: None,
+			line: None,
+			column: None,
+			help: None,
+			notes: Vec::new(),
+		}
+	}
+
+	pub fn error(message: impl Into<String>) -> Self {
+		Self::new(Severity::Error, message)
+	}
+
+	pub fn warning(message: impl Into<String>) -> Self {
+		Self::new(Severity::Warning, message)
+	}
+
+	pub fn with_file(mut self, file: impl Into<String>) -> Self {
+		self.file = Some(file.into());
+		self
+	}
+
+	pub fn at(mut self, line: usize, column: Option<usize>) -> Self {
+		self.line = Some(line);
+		self.column = column;
+		self
+	}
+
+	pub fn with_help(mut self, help: impl Into<String>) -> Self {
+		self.help = Some(help.into());
+		self
+	}
+
+	pub fn with_note(mut self, note: impl Into<String>) -> Self {
+		self.notes.push(note.into());
+		self
+	}
+
+	/// Diagnostic for a Lua error raised while running file
+	/// Lua prefixes messages with "chunkname:line:" and chunks are named after their files, so the error points at the
+	/// line that raised it (possibly in a prelude module), with the stack traceback mapped to notes
+	pub fn from_lua_error(error: &mlua::Error, file: &str) -> Self {
+		let text = lua_error_message(error);
+		let (message, traceback) = text
+			.split_once("stack traceback:")
+			.map(|(message, traceback)| (message.trim_end(), traceback))
+			.unwrap_or((text.as_str(), ""));
+		let frames: Vec<StackFrame> = traceback.lines().filter_map(|line| StackFrame::parse(line, file)).collect();
+
+		let mut diagnostic = match parse_location(message, file) {
+			Some((location, rest)) => Self::error(rest).with_file(location.file).at(location.line, None),
+			// Errors raised by forge's Rust functions carry no location, the innermost Lua frame is where they were called
+			None => match frames.first() {
+				Some(frame) => Self::error(message).with_file(frame.file.clone()).at(frame.line, None),
+				None => Self::error(message).with_file(file),
+			},
+		};
+
+		// When the error comes from a module, point back at the FORGE file line that led there
+		let mut shown = vec![(diagnostic.file.clone(), diagnostic.line)];
+		if diagnostic.file.as_deref() != Some(file)
+			&& let Some(frame) = frames.iter().find(|frame| frame.file == file)
+		{
+			diagnostic = diagnostic.with_note(format!(
+				"called from {}:{}{}",
+				frame.file,
+				frame.line,
+				source_line(&frame.file, frame.line)
+					.map(|source| format!(": {}", source.trim()))
+					.unwrap_or_default()
+			));
+			shown.push((Some(frame.file.clone()), Some(frame.line)));
+		}
+		diagnostic.notes.extend(
+			frames
+				.iter()
+				.filter(|frame| !shown.contains(&(Some(frame.file.clone()), Some(frame.line))))
+				.take(MAX_TRACEBACK_NOTES)
+				.map(|frame| format!("{} at {}:{}", frame.function, frame.file, frame.line)),
+		);
+
+		diagnostic
+	}
+
+	/// Print to stderr, colored when it is a terminal
+	pub fn emit(&self) {
+		eprint!("{}", self.render(use_color()));
+	}
+
+	/// Render like a compiler diagnostic: header, location, source line with a marker, then help
+	pub fn render(&self, color: bool) -> String {
+		let paint = |style: &str, text: &str| {
+			if color {
+				format!("{}{}{}", style, text, RESET)
+			} else {
+				text.to_string()
+			}
+		};
+
+		let mut message_lines = self.message.lines();
+		let mut out = format!(
+			"{}{} {}\n",
+			paint(self.severity.color(), self.severity.label()),
+			paint(BOLD, ":"),
+			paint(BOLD, message_lines.next().unwrap_or_default())
+		);
+		for line in message_lines {
+			out.push_str(&format!("  {}\n", line));
+		}
+
+		let source_line = self
+			.file
+			.as_ref()
+			.zip(self.line)
+			.and_then(|(file, line)| source_line(file, line));
+		let gutter = self.line.map(|line| line.to_string().len()).unwrap_or(1);
+		let pad = " ".repeat(gutter);
+
+		if let Some(file) = &self.file {
+			let mut location = file.clone();
+			if let Some(line) = self.line {
+				location.push_str(&format!(":{}", line));
+				if let Some(column) = self.column {
+					location.push_str(&format!(":{}", column));
+				}
+			}
+			out.push_str(&format!("{}{} {}\n", pad, paint(BLUE, "-->"), location));
+		}
+
+		if let (Some(line), Some(source)) = (self.line, &source_line) {
+			out.push_str(&format!("{} {}\n", pad, paint(BLUE, "|")));
+			out.push_str(&format!(
+				"{} {} {}\n",
+				paint(BLUE, &line.to_string()),
+				paint(BLUE, "|"),
+				source
+			));
+			let marker = match self.column {
+				Some(column) => format!("{}^", " ".repeat(column.saturating_sub(1))),
+				None => {
+					let indent = source.len() - source.trim_start().len();
+					format!("{}{}", &source[..indent], "^".repeat(source.trim().len().max(1)))
+				}
+			};
+			out.push_str(&format!(
+				"{} {} {}\n",
+				pad,
+				paint(BLUE, "|"),
+				paint(self.severity.color(), &marker)
+			));
+		}
+
+		for note in &self.notes {
+			out.push_str(&format!("{} {} {} {}\n", pad, paint(BLUE, "="), paint(BOLD, "note:"), note));
+		}
+
+		if let Some(help) = &self.help {
+			let mut help_lines = help.lines();
+			out.push_str(&format!(
+				"{} {} {} {}\n",
+				pad,
+				paint(BLUE, "="),
+				paint(BOLD, "help:"),
+				help_lines.next().unwrap_or_default()
+			));
+			for line in help_lines {
+				out.push_str(&format!("{}         {}\n", pad, line));
+			}
+		}
+
+		out
+	}
+}
+
+impl fmt::Display for Diagnostic {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.render(false))
+	}
+}
+
+/// Stack frames shown as notes after the error itself
+const MAX_TRACEBACK_NOTES: usize = 8;
+
+struct Location {
+	file: String,
+	line: usize,
+}
+
+/// A Lua traceback line such as "\tprelude/cc.lua:12: in function 'compile'"
+struct StackFrame {
+	file: String,
+	line: usize,
+	function: String,
+}
+
+impl StackFrame {
+	fn parse(line: &str, file: &str) -> Option<Self> {
+		let (location, rest) = parse_location(line.trim(), file)?;
+		let function = rest.strip_prefix("in ").unwrap_or(rest);
+		Some(Self {
+			file: location.file,
+			line: location.line,
+			function: match function {
+				"main chunk" => "in the main chunk".to_string(),
+				function => format!("in {}", function),
+			},
+		})
+	}
+}
+
+/// Split a leading "chunk:line:" off a Lua message, mapping the chunk name to a file
+/// Lua shortens long chunk names to "...tail", which is matched against file; other chunks are used if they exist on disk
+fn parse_location<'a>(text: &'a str, file: &str) -> Option<(Location, &'a str)> {
+	for (colon, _) in text.match_indices(':') {
+		let rest = &text[colon + 1..];
+		let digits = rest.chars().take_while(char::is_ascii_digit).count();
+		if digits == 0 || !rest[digits..].starts_with(':') {
+			continue;
+		}
+
+		let chunk = &text[..colon];
+		let shortened = chunk.trim_start_matches("...");
+		let chunk_file = if !shortened.is_empty() && file.ends_with(shortened) {
+			file.to_string()
+		} else if !chunk.starts_with('[') && !chunk.starts_with("...") && Path::new(chunk).is_file() {
+			chunk.to_string()
+		} else {
+			return None;
+		};
+
+		let line = rest[..digits].parse().ok()?;
+		return Some((Location { file: chunk_file, line }, rest[digits + 1..].trim_start()));
+	}
+	None
+}
+
+/// Line (1-based) of file, if it can be read
+fn source_line(file: &str, line: usize) -> Option<String> {
+	std::fs::read_to_string(file)
+		.ok()
+		.and_then(|content| content.lines().nth(line.checked_sub(1)?).map(str::to_string))
+}
+
+/// The innermost message of a Lua error, without mlua's "runtime error:" / "callback error" wrapping
+fn lua_error_message(error: &mlua::Error) -> String {
+	match error {
+		mlua::Error::RuntimeError(message) => message.clone(),
+		mlua::Error::SyntaxError { message, .. } => message.clone(),
+		mlua::Error::CallbackError { cause, traceback } => {
+			let message = lua_error_message(cause);
+			if message.contains("stack traceback:") {
+				message
+			} else {
+				format!("{}\n{}", message, traceback)
+			}
+		}
+		mlua::Error::WithContext { cause, .. } => lua_error_message(cause),
+		other => other.to_string(),
+	}
+}
+
+static COLOR: OnceLock<bool> = OnceLock::new();
+
+/// Force colors on or off (--color), instead of detecting them
+pub fn set_color(color: bool) {
+	let _ = COLOR.set(color);
+}
+
+/// Whether diagnostics on stderr should be colored
+pub fn use_color() -> bool {
+	*COLOR.get_or_init(detect_color)
+}
+
+/// Colors are used when stderr is a terminal and NO_COLOR is not set
+pub fn detect_color() -> bool {
+	std::env::var_os("NO_COLOR").is_none() && std::io::stderr().is_terminal()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_lua_error_location() {
+		let error = mlua::Error::RuntimeError("src/FORGE:12: attempt to call a nil value (global 'rul')".to_string());
+		let diagnostic = Diagnostic::from_lua_error(&error, "src/FORGE");
+		assert_eq!(diagnostic.line, Some(12));
+		assert_eq!(diagnostic.message, "attempt to call a nil value (global 'rul')");
+
+		let error = mlua::Error::RuntimeError("...ct/src/FORGE:3: boom".to_string());
+		assert_eq!(
+			Diagnostic::from_lua_error(&error, "/home/user/project/src/FORGE").line,
+			Some(3)
+		);
+
+		let error = mlua::Error::RuntimeError("something else went wrong".to_string());
+		let diagnostic = Diagnostic::from_lua_error(&error, "src/FORGE");
+		assert_eq!(diagnostic.line, None);
+		assert_eq!(diagnostic.message, "something else went wrong");
+	}
+
+	#[test]
+	fn test_lua_traceback_notes() {
+		let error = mlua::Error::RuntimeError(
+			"bad argument\nstack traceback:\n\t[C]: in ?\n\tsrc/FORGE:7: in function 'helper'\n\tsrc/FORGE:12: in main chunk"
+				.to_string(),
+		);
+		let diagnostic = Diagnostic::from_lua_error(&error, "src/FORGE");
+		assert_eq!(diagnostic.message, "bad argument");
+		assert_eq!(diagnostic.line, Some(7));
+		assert_eq!(diagnostic.notes, vec!["in the main chunk at src/FORGE:12"]);
+	}
+
+	#[test]
+	fn test_render_without_source() {
+		let rendered = Diagnostic::error("No build rules found")
+			.with_file("missing/FORGE")
+			.at(3, Some(5))
+			.with_help("Add at least one rule() call")
+			.render(false);
+		assert_eq!(
+			rendered,
+			"error: No build rules found\n --> missing/FORGE:3:5\n  = help: Add at least one rule() call\n"
+		);
+	}
+}
@@ -1,5 +1,66 @@
+use crate::diagnostic::Diagnostic;
+use serde::Serialize;
 use thiserror::Error;
 
+/// What went wrong, by the exit code forge ends with; the codes are stable so wrappers and CI can tell them apart
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+	/// FORGE_ROOT, a FORGE file or the prelude is wrong, or the toolchains and downloads they pin are unavailable
+	Config,
+	/// The rules do not form a valid graph
+	Graph,
+	/// A rule ran and failed
+	RuleFailed,
+	/// Ctrl-C stopped the build
+	Interrupted,
+	/// Anything else, such as I/O errors
+	Internal,
+}
+
+impl ErrorKind {
+	pub fn exit_code(self) -> i32 {
+		match self {
+			ErrorKind::RuleFailed => 1,
+			ErrorKind::Config => 2,
+			ErrorKind::Graph => 3,
+			ErrorKind::Internal => 101,
+			ErrorKind::Interrupted => crate::interrupt::EXIT_CODE,
+		}
+	}
+}
+
+/// The final error of a run as --error-format json prints it
+#[derive(Debug, Serialize)]
+pub struct ErrorReport {
+	pub kind: ErrorKind,
+	pub exit_code: i32,
+	pub message: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub rule: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub diagnostic: Option<Diagnostic>,
+}
+
+impl ErrorReport {
+	pub fn new(error: &anyhow::Error) -> Self {
+		let forge_error = error.downcast_ref::<ForgeError>();
+		let kind = forge_error.map_or(ErrorKind::Internal, ForgeError::kind);
+		Self {
+			kind,
+			exit_code: kind.exit_code(),
+			message: format!("{:#}", error),
+			rule: match forge_error {
+				Some(ForgeError::BuildFailed { rule, .. } | ForgeError::UndeclaredDependencies { rule, .. }) => {
+					Some(rule.clone())
+				}
+				_ => None,
+			},
+			diagnostic: forge_error.and_then(ForgeError::diagnostic),
+		}
+	}
+}
+
 #[derive(Error, Debug)]
 pub enum ForgeError {
 	#[error(
@@ -47,6 +108,13 @@ pub enum ForgeError {
 	#[error("HTTP request failed: {0}")]
 	RequestError(#[from] ureq::Error),
 
+	#[error(
+		"Network access to {url} is disabled by --offline\n\nSuggestion: Run once without --offline to populate the download cache, or pin the download with a checksum that is already cached."
+	)]
+	Offline {
+		url: String,
+	},
+
 	#[error("Archive extraction failed: {0}")]
 	ExtractionError(String),
 
@@ -59,6 +127,41 @@ pub enum ForgeError {
 		actual: String,
 	},
 
+	#[error(
+		"{url} no longer matches FORGE.lock. Expected blake3 {expected}, got {actual}\n\nSuggestion: If the download is expected to change, run `forge lock update`; otherwise pin it with a checksum in the FORGE file."
+	)]
+	LockfileMismatch {
+		url: String,
+		expected: String,
+		actual: String,
+	},
+
+	#[error(
+		"Remote execution failed: {0}\n\nSuggestion: Check [build.remote] or [build.remote_hosts] in FORGE_ROOT and that the server or hosts are reachable, or set remote = false on the rule to run it locally."
+	)]
+	RemoteExecution(String),
+
+	#[error(
+		"Failed to provision toolchain '{name}': {error}\n\nSuggestion: Check [toolchains.{name}] in FORGE_ROOT; its url and checksum must match an archive for this platform."
+	)]
+	Toolchain {
+		name: String,
+		error: String,
+	},
+
+	#[error(
+		"Build interrupted\n\nSuggestion: Rules that finished before Ctrl-C were cached; run the build again to continue from there."
+	)]
+	Interrupted,
+
+	#[error(
+		"Rule '{rule}' accessed files it does not declare:\n{accesses}\n\nSuggestion: Add the files it reads to the rule's inputs and the files it writes to its outputs, or set [build] enforce_deps = \"warn\" in FORGE_ROOT."
+	)]
+	UndeclaredDependencies {
+		rule: String,
+		accesses: String,
+	},
+
 	#[error(
 		"Build failed for rule '{rule}': {error}\n\nSuggestion: Check the command, arguments, and input files for rule '{rule}'."
 	)]
@@ -97,6 +200,56 @@ pub enum ForgeError {
 	Other(#[from] anyhow::Error),
 }
 
+impl ForgeError {
+	pub fn kind(&self) -> ErrorKind {
+		match self {
+			ForgeError::ForgeRootNotFound { .. }
+			| ForgeError::ForgeRootConfigError(_)
+			| ForgeError::NoForgeFilesFound { .. }
+			| ForgeError::LuaError { .. }
+			| ForgeError::LuaExecutionError(_)
+			| ForgeError::Offline { .. }
+			| ForgeError::ChecksumMismatch { .. }
+			| ForgeError::LockfileMismatch { .. }
+			| ForgeError::Toolchain { .. }
+			| ForgeError::PreludeNotFound(_)
+			| ForgeError::InvalidForgeFile { .. } => ErrorKind::Config,
+			ForgeError::CircularDependency { .. } | ForgeError::DependencyConflict { .. } => ErrorKind::Graph,
+			ForgeError::BuildFailed { .. } | ForgeError::RemoteExecution(_) | ForgeError::UndeclaredDependencies { .. } => {
+				ErrorKind::RuleFailed
+			}
+			ForgeError::Interrupted => ErrorKind::Interrupted,
+			ForgeError::IoError(_)
+			| ForgeError::SystemTimeError(_)
+			| ForgeError::RequestError(_)
+			| ForgeError::ExtractionError(_)
+			| ForgeError::Other(_) => ErrorKind::Internal,
+		}
+	}
+
+	/// Structured form of errors that point at a FORGE file or a rule
+	pub fn diagnostic(&self) -> Option<Diagnostic> {
+		match self {
+			ForgeError::InvalidForgeFile { file, error, suggestion } => Some(
+				Diagnostic::error(error.clone())
+					.with_file(file.clone())
+					.with_help(suggestion.clone()),
+			),
+			ForgeError::LuaError { file, error } => Some(
+				Diagnostic::from_lua_error(error, file)
+					.with_help("Check your FORGE file syntax and ensure all required variables are defined."),
+			),
+			ForgeError::BuildFailed { rule, error } => Some(
+				Diagnostic::error(format!("rule '{}' failed\n{}", rule, error.trim_end())).with_help(format!(
+					"Check the output above, or run `forge log {}` to see it again.",
+					rule
+				)),
+			),
+			_ => None,
+		}
+	}
+}
+
 impl From<ForgeError> for mlua::Error {
 	fn from(err: ForgeError) -> Self {
 		mlua::Error::external(err)
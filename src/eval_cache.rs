@@ -0,0 +1,155 @@
+use crate::project::Rule;
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use std::{
+	fs::File,
+	io::BufReader,
+	path::{Path, PathBuf},
+};
+
+/// The rules each FORGE file registered on earlier builds, reused while nothing its evaluation read has changed
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct EvalCache {
+	entries: DashMap<PathBuf, EvalCacheEntry>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EvalCacheEntry {
+	/// Hash of the FORGE file, the forge version and the configuration FORGE files can read
+	pub key: String,
+	pub observations: Vec<Observation>,
+	pub rules: Vec<Rule>,
+}
+
+/// Something evaluating a FORGE file read from the host, checked again before its cached rules are reused
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Observation {
+	/// A Lua file loaded through require, dofile or loadfile, by the hash of its content
+	Module {
+		path: PathBuf,
+		hash: String,
+	},
+	/// fs.glob, by the hash of the paths it matched
+	Glob {
+		pattern: String,
+		matches: String,
+	},
+	/// fs.exists
+	Exists {
+		path: PathBuf,
+		exists: bool,
+	},
+	/// os.getenv
+	Env {
+		name: String,
+		value: Option<String>,
+	},
+}
+
+impl Observation {
+	pub fn module(path: &Path, content: &[u8]) -> Self {
+		Observation::Module {
+			path: path.to_path_buf(),
+			hash: blake3::hash(content).to_hex().to_string(),
+		}
+	}
+
+	pub fn glob(pattern: &str, matches: &[PathBuf]) -> Self {
+		Observation::Glob {
+			pattern: pattern.to_string(),
+			matches: hash_paths(matches),
+		}
+	}
+
+	/// Whether the host still reads the same as when this was recorded
+	pub fn holds(&self) -> bool {
+		match self {
+			Observation::Module { path, hash } => {
+				std::fs::read(path).is_ok_and(|content| blake3::hash(&content).to_hex().as_str() == hash)
+			}
+			Observation::Glob { pattern, matches } => glob::glob(pattern).is_ok_and(|paths| {
+				let paths: Vec<PathBuf> = paths.filter_map(Result::ok).collect();
+				hash_paths(&paths) == *matches
+			}),
+			Observation::Exists { path, exists } => path.exists() == *exists,
+			Observation::Env { name, value } => std::env::var(name).ok() == *value,
+		}
+	}
+}
+
+fn hash_paths(paths: &[PathBuf]) -> String {
+	let mut hasher = blake3::Hasher::new();
+	for path in paths {
+		hasher.update(path.to_string_lossy().as_bytes());
+		hasher.update(b"\n");
+	}
+	hasher.finalize().to_hex().to_string()
+}
+
+impl EvalCache {
+	pub fn load(path: &Path) -> Self {
+		if let Ok(file) = File::open(path)
+			&& let Ok(cache) = serde_json::from_reader::<_, EvalCache>(BufReader::new(file))
+		{
+			return cache;
+		}
+		Self::default()
+	}
+
+	pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+		std::fs::write(path, serde_json::to_vec(self)?)?;
+		Ok(())
+	}
+
+	/// The rules cached for forge_file, if they were stored under key and every observation still holds
+	pub fn lookup(&self, forge_file: &Path, key: &str) -> Option<Vec<Rule>> {
+		let entry = self.entries.get(forge_file)?;
+		(entry.key == key && entry.observations.iter().all(Observation::holds)).then(|| entry.rules.clone())
+	}
+
+	pub fn store(&self, forge_file: &Path, entry: EvalCacheEntry) {
+		self.entries.insert(forge_file.to_path_buf(), entry);
+	}
+
+	pub fn remove(&self, forge_file: &Path) {
+		self.entries.remove(forge_file);
+	}
+
+	/// Drop the entries of FORGE files that no longer exist
+	pub fn retain(&self, forge_files: &[PathBuf]) {
+		self.entries.retain(|forge_file, _| forge_files.contains(forge_file));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_observations_hold() {
+		let dir = std::env::temp_dir().join(format!("forge-eval-cache-{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let module = dir.join("module.lua");
+		std::fs::write(&module, "return {}").unwrap();
+
+		let pattern = dir.join("*.lua").to_string_lossy().to_string();
+		let observations = [
+			Observation::module(&module, b"return {}"),
+			Observation::glob(&pattern, std::slice::from_ref(&module)),
+			Observation::Exists {
+				path: dir.join("missing"),
+				exists: false,
+			},
+		];
+		assert!(observations.iter().all(Observation::holds));
+
+		std::fs::write(dir.join("other.lua"), "").unwrap();
+		std::fs::write(&module, "return { changed = true }").unwrap();
+		assert!(!observations[0].holds());
+		assert!(!observations[1].holds());
+		assert!(observations[2].holds());
+
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
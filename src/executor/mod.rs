@@ -0,0 +1,136 @@
+use crate::{error::ForgeError, interrupt, project::Rule};
+use std::path::{Component, Path, PathBuf};
+
+/// The messages of the Remote Execution API, written out with the field numbers of remote_execution.proto so no
+/// protoc step is needed
+mod reapi;
+mod remote;
+mod ssh;
+mod trace;
+
+pub use remote::RemoteExecutor;
+pub use ssh::SshExecutor;
+pub use trace::{FileAccesses, TracingExecutor};
+
+/// How a rule's command ran, wherever it ran
+pub struct Execution {
+	/// None when the command was killed by a signal
+	pub exit_code: Option<i32>,
+	/// How the command ended, for error messages
+	pub status: String,
+	pub stdout: Vec<u8>,
+	pub stderr: Vec<u8>,
+	/// The files the command touched, when it ran under a TracingExecutor
+	pub accesses: Option<FileAccesses>,
+}
+
+impl Execution {
+	pub fn success(&self) -> bool {
+		self.exit_code == Some(0)
+	}
+}
+
+impl From<std::process::Output> for Execution {
+	fn from(output: std::process::Output) -> Self {
+		Self {
+			exit_code: output.status.code(),
+			status: output.status.to_string(),
+			stdout: output.stdout,
+			stderr: output.stderr,
+			accesses: None,
+		}
+	}
+}
+
+/// Runs the command of a rule with its args already expanded, leaving its outputs in the project
+pub trait Executor: Send + Sync {
+	/// Shown in logs, "local" or where the command runs
+	fn name(&self) -> &str;
+
+	fn execute(&self, rule: &Rule, args: &[String]) -> Result<Execution, ForgeError>;
+}
+
+/// Runs commands as child processes of forge
+pub struct LocalExecutor;
+
+impl Executor for LocalExecutor {
+	fn name(&self) -> &str {
+		"local"
+	}
+
+	fn execute(&self, rule: &Rule, args: &[String]) -> Result<Execution, ForgeError> {
+		let (program, args) = script_command(&rule.command, args);
+		let output = interrupt::output(
+			std::process::Command::new(program)
+				.args(args)
+				.envs(&rule.env)
+				.current_dir(&rule.workdir),
+		)?;
+		Ok(output.into())
+	}
+}
+
+/// The program and arguments that run command: .bat and .cmd scripts run through cmd and .ps1 scripts through
+/// PowerShell, which are not executables themselves
+pub fn script_command(command: &str, args: &[String]) -> (String, Vec<String>) {
+	let extension = Path::new(command)
+		.extension()
+		.and_then(|extension| extension.to_str())
+		.map(str::to_ascii_lowercase);
+	let (program, mut script_args): (&str, Vec<String>) = match extension.as_deref() {
+		Some("bat" | "cmd") => ("cmd", vec!["/C".to_string()]),
+		Some("ps1") => (
+			if cfg!(windows) { "powershell" } else { "pwsh" },
+			["-NoProfile", "-NonInteractive", "-ExecutionPolicy", "Bypass", "-File"]
+				.map(str::to_string)
+				.to_vec(),
+		),
+		_ => return (command.to_string(), args.to_vec()),
+	};
+	script_args.push(command.to_string());
+	script_args.extend(args.iter().cloned());
+	(program.to_string(), script_args)
+}
+
+/// path relative to root with "/" separators, "" for root itself, None when it is not under root
+fn relative_path(root: &Path, path: &Path) -> Option<String> {
+	let path: PathBuf = root.join(path).components().collect();
+	let relative = path.strip_prefix(root).ok()?;
+	let mut names = Vec::new();
+	for component in relative.components() {
+		match component {
+			Component::Normal(name) => names.push(name.to_string_lossy().to_string()),
+			_ => return None,
+		}
+	}
+	Some(names.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_relative_path() {
+		let root = Path::new("/project");
+		assert_eq!(relative_path(root, Path::new("./src/../src/a.c")), None);
+		assert_eq!(relative_path(root, Path::new("./src/a.c")).as_deref(), Some("src/a.c"));
+		assert_eq!(relative_path(root, Path::new("/project")).as_deref(), Some(""));
+		assert_eq!(relative_path(root, Path::new("/elsewhere")), None);
+	}
+
+	#[test]
+	fn test_script_command() {
+		let args = vec!["--release".to_string()];
+		assert_eq!(script_command("cc", &args), ("cc".to_string(), args.clone()));
+		assert_eq!(
+			script_command("tools\\gen.BAT", &args),
+			(
+				"cmd".to_string(),
+				vec!["/C".to_string(), "tools\\gen.BAT".to_string(), "--release".to_string()]
+			)
+		);
+		let (_, ps1_args) = script_command("gen.ps1", &[]);
+		assert_eq!(ps1_args[ps1_args.len() - 2..], ["-File".to_string(), "gen.ps1".to_string()]);
+	}
+}
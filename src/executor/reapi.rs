@@ -0,0 +1,274 @@
+use prost::Message;
+
+pub const FIND_MISSING_BLOBS: &str = "/build.bazel.remote.execution.v2.ContentAddressableStorage/FindMissingBlobs";
+pub const BATCH_UPDATE_BLOBS: &str = "/build.bazel.remote.execution.v2.ContentAddressableStorage/BatchUpdateBlobs";
+pub const BATCH_READ_BLOBS: &str = "/build.bazel.remote.execution.v2.ContentAddressableStorage/BatchReadBlobs";
+pub const EXECUTE: &str = "/build.bazel.remote.execution.v2.Execution/Execute";
+pub const BYTESTREAM_READ: &str = "/google.bytestream.ByteStream/Read";
+pub const BYTESTREAM_WRITE: &str = "/google.bytestream.ByteStream/Write";
+
+#[derive(Clone, PartialEq, Eq, Hash, Message)]
+pub struct Digest {
+	#[prost(string, tag = "1")]
+	pub hash: String,
+	#[prost(int64, tag = "2")]
+	pub size_bytes: i64,
+}
+
+impl Digest {
+	/// SHA-256 digest of data, the digest function every Remote Execution API server supports
+	pub fn of(data: &[u8]) -> Self {
+		use sha2::{Digest as _, Sha256};
+		Self {
+			hash: format!("{:x}", Sha256::digest(data)),
+			size_bytes: data.len() as i64,
+		}
+	}
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Action {
+	#[prost(message, optional, tag = "1")]
+	pub command_digest: Option<Digest>,
+	#[prost(message, optional, tag = "2")]
+	pub input_root_digest: Option<Digest>,
+	#[prost(bool, tag = "7")]
+	pub do_not_cache: bool,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Command {
+	#[prost(string, repeated, tag = "1")]
+	pub arguments: Vec<String>,
+	/// Sorted by name
+	#[prost(message, repeated, tag = "2")]
+	pub environment_variables: Vec<EnvironmentVariable>,
+	#[prost(string, tag = "6")]
+	pub working_directory: String,
+	/// Relative to working_directory, sorted
+	#[prost(string, repeated, tag = "7")]
+	pub output_paths: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct EnvironmentVariable {
+	#[prost(string, tag = "1")]
+	pub name: String,
+	#[prost(string, tag = "2")]
+	pub value: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct Directory {
+	/// Sorted by name
+	#[prost(message, repeated, tag = "1")]
+	pub files: Vec<FileNode>,
+	/// Sorted by name
+	#[prost(message, repeated, tag = "2")]
+	pub directories: Vec<DirectoryNode>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct FileNode {
+	#[prost(string, tag = "1")]
+	pub name: String,
+	#[prost(message, optional, tag = "2")]
+	pub digest: Option<Digest>,
+	#[prost(bool, tag = "4")]
+	pub is_executable: bool,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct DirectoryNode {
+	#[prost(string, tag = "1")]
+	pub name: String,
+	#[prost(message, optional, tag = "2")]
+	pub digest: Option<Digest>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct FindMissingBlobsRequest {
+	#[prost(string, tag = "1")]
+	pub instance_name: String,
+	#[prost(message, repeated, tag = "2")]
+	pub blob_digests: Vec<Digest>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct FindMissingBlobsResponse {
+	#[prost(message, repeated, tag = "2")]
+	pub missing_blob_digests: Vec<Digest>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct BatchUpdateBlobsRequest {
+	#[prost(string, tag = "1")]
+	pub instance_name: String,
+	#[prost(message, repeated, tag = "2")]
+	pub requests: Vec<UpdateBlob>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct UpdateBlob {
+	#[prost(message, optional, tag = "1")]
+	pub digest: Option<Digest>,
+	#[prost(bytes = "vec", tag = "2")]
+	pub data: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct BatchUpdateBlobsResponse {
+	#[prost(message, repeated, tag = "1")]
+	pub responses: Vec<UpdatedBlob>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct UpdatedBlob {
+	#[prost(message, optional, tag = "1")]
+	pub digest: Option<Digest>,
+	#[prost(message, optional, tag = "2")]
+	pub status: Option<Status>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct BatchReadBlobsRequest {
+	#[prost(string, tag = "1")]
+	pub instance_name: String,
+	#[prost(message, repeated, tag = "2")]
+	pub digests: Vec<Digest>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct BatchReadBlobsResponse {
+	#[prost(message, repeated, tag = "1")]
+	pub responses: Vec<ReadBlob>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ReadBlob {
+	#[prost(message, optional, tag = "1")]
+	pub digest: Option<Digest>,
+	#[prost(bytes = "vec", tag = "2")]
+	pub data: Vec<u8>,
+	#[prost(message, optional, tag = "3")]
+	pub status: Option<Status>,
+}
+
+/// ByteStream messages, for blobs too large for a batch call
+#[derive(Clone, PartialEq, Message)]
+pub struct ReadRequest {
+	#[prost(string, tag = "1")]
+	pub resource_name: String,
+	#[prost(int64, tag = "2")]
+	pub read_offset: i64,
+	#[prost(int64, tag = "3")]
+	pub read_limit: i64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ReadResponse {
+	#[prost(bytes = "vec", tag = "10")]
+	pub data: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct WriteRequest {
+	#[prost(string, tag = "1")]
+	pub resource_name: String,
+	#[prost(int64, tag = "2")]
+	pub write_offset: i64,
+	#[prost(bool, tag = "3")]
+	pub finish_write: bool,
+	#[prost(bytes = "vec", tag = "10")]
+	pub data: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct WriteResponse {
+	#[prost(int64, tag = "1")]
+	pub committed_size: i64,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ExecuteRequest {
+	#[prost(string, tag = "1")]
+	pub instance_name: String,
+	#[prost(bool, tag = "3")]
+	pub skip_cache_lookup: bool,
+	#[prost(message, optional, tag = "6")]
+	pub action_digest: Option<Digest>,
+}
+
+/// google.longrunning.Operation, streamed back by Execute until done
+#[derive(Clone, PartialEq, Message)]
+pub struct Operation {
+	#[prost(string, tag = "1")]
+	pub name: String,
+	#[prost(bool, tag = "3")]
+	pub done: bool,
+	#[prost(message, optional, tag = "4")]
+	pub error: Option<Status>,
+	/// An ExecuteResponse once done
+	#[prost(message, optional, tag = "5")]
+	pub response: Option<Any>,
+}
+
+/// google.protobuf.Any
+#[derive(Clone, PartialEq, Message)]
+pub struct Any {
+	#[prost(string, tag = "1")]
+	pub type_url: String,
+	#[prost(bytes = "vec", tag = "2")]
+	pub value: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ExecuteResponse {
+	#[prost(message, optional, tag = "1")]
+	pub result: Option<ActionResult>,
+	#[prost(bool, tag = "2")]
+	pub cached_result: bool,
+	#[prost(message, optional, tag = "3")]
+	pub status: Option<Status>,
+	#[prost(string, tag = "5")]
+	pub message: String,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct ActionResult {
+	#[prost(message, repeated, tag = "2")]
+	pub output_files: Vec<OutputFile>,
+	#[prost(int32, tag = "4")]
+	pub exit_code: i32,
+	#[prost(bytes = "vec", tag = "5")]
+	pub stdout_raw: Vec<u8>,
+	#[prost(message, optional, tag = "6")]
+	pub stdout_digest: Option<Digest>,
+	#[prost(bytes = "vec", tag = "7")]
+	pub stderr_raw: Vec<u8>,
+	#[prost(message, optional, tag = "8")]
+	pub stderr_digest: Option<Digest>,
+}
+
+#[derive(Clone, PartialEq, Message)]
+pub struct OutputFile {
+	/// Relative to the command's working directory
+	#[prost(string, tag = "1")]
+	pub path: String,
+	#[prost(message, optional, tag = "2")]
+	pub digest: Option<Digest>,
+	#[prost(bool, tag = "4")]
+	pub is_executable: bool,
+	/// Inlined by some servers for small files
+	#[prost(bytes = "vec", tag = "5")]
+	pub contents: Vec<u8>,
+}
+
+/// google.rpc.Status, where code 0 is OK
+#[derive(Clone, PartialEq, Message)]
+pub struct Status {
+	#[prost(int32, tag = "1")]
+	pub code: i32,
+	#[prost(string, tag = "2")]
+	pub message: String,
+}
@@ -0,0 +1,619 @@
+use super::{
+	Execution, Executor,
+	reapi::{self, ActionResult, Digest},
+	relative_path,
+};
+use crate::{error::ForgeError, project::Rule};
+use prost::Message;
+use std::{
+	collections::{BTreeMap, HashMap},
+	path::{Path, PathBuf},
+};
+use tonic::{
+	client::Grpc,
+	codec::ProstCodec,
+	codegen::{http::uri::PathAndQuery, tokio_stream},
+	transport::{Channel, ClientTlsConfig, Endpoint},
+};
+use walkdir::WalkDir;
+
+/// Most blob data sent or asked for in one batch call, under the 4 MiB message limit most servers keep; larger
+/// blobs go through ByteStream
+const MAX_BATCH_BYTES: i64 = 3 * 1024 * 1024;
+
+/// Size of each message of a ByteStream write
+const STREAM_CHUNK_BYTES: usize = 1024 * 1024;
+
+type Blobs = HashMap<Digest, Vec<u8>>;
+
+/// Runs commands on a Remote Execution API server: the rule's inputs and command go to the server's CAS, the
+/// action runs there (or comes from its action cache), and the declared outputs are downloaded into the project
+pub struct RemoteExecutor {
+	endpoint: String,
+	instance_name: String,
+	project_root: PathBuf,
+	channel: Channel,
+	runtime: tokio::runtime::Handle,
+}
+
+impl RemoteExecutor {
+	/// endpoint is grpc://host:port, or grpcs://host:port for TLS
+	pub fn new(
+		endpoint: &str,
+		instance_name: &str,
+		project_root: &Path,
+		runtime: tokio::runtime::Handle,
+	) -> Result<Self, ForgeError> {
+		let (url, tls) = match endpoint.split_once("://") {
+			Some(("grpc" | "http", address)) => (format!("http://{}", address), false),
+			Some(("grpcs" | "https", address)) => (format!("https://{}", address), true),
+			_ => {
+				return Err(ForgeError::RemoteExecution(format!(
+					"endpoint '{}' must start with grpc:// or grpcs://",
+					endpoint
+				)));
+			}
+		};
+		let mut channel = Endpoint::from_shared(url).map_err(remote_error)?;
+		if tls {
+			channel = channel
+				.tls_config(ClientTlsConfig::new().with_native_roots())
+				.map_err(remote_error)?;
+		}
+		// Connecting on the first remote rule keeps builds that run nothing remotely from needing the server
+		let channel = {
+			let _runtime = runtime.enter();
+			channel.connect_lazy()
+		};
+
+		Ok(Self {
+			endpoint: endpoint.to_string(),
+			instance_name: instance_name.to_string(),
+			project_root: project_root.to_path_buf(),
+			channel,
+			runtime,
+		})
+	}
+
+	async fn upload(&self, client: &mut Grpc<Channel>, mut blobs: Blobs) -> Result<(), ForgeError> {
+		let missing: reapi::FindMissingBlobsResponse = call(
+			client,
+			reapi::FIND_MISSING_BLOBS,
+			reapi::FindMissingBlobsRequest {
+				instance_name: self.instance_name.clone(),
+				blob_digests: blobs.keys().cloned().collect(),
+			},
+		)
+		.await?;
+
+		let mut batch = Vec::new();
+		let mut batch_bytes = 0;
+		for digest in missing.missing_blob_digests {
+			let Some(data) = blobs.remove(&digest) else {
+				continue;
+			};
+			if digest.size_bytes > MAX_BATCH_BYTES {
+				self.write_blob(client, &digest, &data).await?;
+				continue;
+			}
+			if batch_bytes + digest.size_bytes > MAX_BATCH_BYTES {
+				self.upload_batch(client, std::mem::take(&mut batch)).await?;
+				batch_bytes = 0;
+			}
+			batch_bytes += digest.size_bytes;
+			batch.push(reapi::UpdateBlob {
+				digest: Some(digest),
+				data,
+			});
+		}
+		if !batch.is_empty() {
+			self.upload_batch(client, batch).await?;
+		}
+		Ok(())
+	}
+
+	async fn upload_batch(&self, client: &mut Grpc<Channel>, requests: Vec<reapi::UpdateBlob>) -> Result<(), ForgeError> {
+		let response: reapi::BatchUpdateBlobsResponse = call(
+			client,
+			reapi::BATCH_UPDATE_BLOBS,
+			reapi::BatchUpdateBlobsRequest {
+				instance_name: self.instance_name.clone(),
+				requests,
+			},
+		)
+		.await?;
+		for blob in response.responses {
+			if let Some(status) = blob.status.filter(|status| status.code != 0) {
+				return Err(ForgeError::RemoteExecution(format!(
+					"uploading {} failed: {}",
+					blob.digest.map_or_else(String::new, |digest| digest.hash),
+					status.message
+				)));
+			}
+		}
+		Ok(())
+	}
+
+	/// Upload one blob through ByteStream Write, in chunks
+	async fn write_blob(&self, client: &mut Grpc<Channel>, digest: &Digest, data: &[u8]) -> Result<(), ForgeError> {
+		let resource_name = self.resource_name(&format!(
+			"uploads/{}/blobs/{}/{}",
+			uuid::Uuid::new_v4(),
+			digest.hash,
+			digest.size_bytes
+		));
+		let requests = write_requests(&resource_name, data);
+
+		client.ready().await.map_err(remote_error)?;
+		let response: reapi::WriteResponse = client
+			.client_streaming(
+				tonic::Request::new(tokio_stream::iter(requests)),
+				PathAndQuery::from_static(reapi::BYTESTREAM_WRITE),
+				ProstCodec::default(),
+			)
+			.await
+			.map_err(remote_error)?
+			.into_inner();
+		// A server that already has the blob may stop the write early and report its full size
+		if response.committed_size != digest.size_bytes {
+			return Err(ForgeError::RemoteExecution(format!(
+				"uploading {} stopped after {} of {} bytes",
+				digest.hash, response.committed_size, digest.size_bytes
+			)));
+		}
+		Ok(())
+	}
+
+	/// Download one blob through ByteStream Read
+	async fn read_blob(&self, client: &mut Grpc<Channel>, digest: &Digest) -> Result<Vec<u8>, ForgeError> {
+		let request = reapi::ReadRequest {
+			resource_name: self.resource_name(&format!("blobs/{}/{}", digest.hash, digest.size_bytes)),
+			read_offset: 0,
+			read_limit: 0,
+		};
+		client.ready().await.map_err(remote_error)?;
+		let mut responses: tonic::Streaming<reapi::ReadResponse> = client
+			.server_streaming(
+				tonic::Request::new(request),
+				PathAndQuery::from_static(reapi::BYTESTREAM_READ),
+				ProstCodec::default(),
+			)
+			.await
+			.map_err(remote_error)?
+			.into_inner();
+
+		let mut data = Vec::with_capacity(digest.size_bytes as usize);
+		while let Some(response) = responses.message().await.map_err(remote_error)? {
+			data.extend_from_slice(&response.data);
+		}
+		if data.len() as i64 != digest.size_bytes {
+			return Err(ForgeError::RemoteExecution(format!(
+				"downloading {} ended after {} of {} bytes",
+				digest.hash,
+				data.len(),
+				digest.size_bytes
+			)));
+		}
+		Ok(data)
+	}
+
+	/// ByteStream resource names start with the instance name, when there is one
+	fn resource_name(&self, path: &str) -> String {
+		if self.instance_name.is_empty() {
+			path.to_string()
+		} else {
+			format!("{}/{}", self.instance_name, path)
+		}
+	}
+
+	async fn read_blobs(&self, client: &mut Grpc<Channel>, digests: Vec<Digest>) -> Result<Blobs, ForgeError> {
+		let mut blobs = Blobs::new();
+		let mut batches: Vec<Vec<Digest>> = vec![Vec::new()];
+		let mut batch_bytes = 0;
+		for digest in digests {
+			if digest.size_bytes > MAX_BATCH_BYTES {
+				let data = self.read_blob(client, &digest).await?;
+				blobs.insert(digest, data);
+				continue;
+			}
+			if batch_bytes + digest.size_bytes > MAX_BATCH_BYTES {
+				batches.push(Vec::new());
+				batch_bytes = 0;
+			}
+			batch_bytes += digest.size_bytes;
+			batches.last_mut().unwrap().push(digest);
+		}
+
+		for digests in batches.into_iter().filter(|batch| !batch.is_empty()) {
+			let response: reapi::BatchReadBlobsResponse = call(
+				client,
+				reapi::BATCH_READ_BLOBS,
+				reapi::BatchReadBlobsRequest {
+					instance_name: self.instance_name.clone(),
+					digests,
+				},
+			)
+			.await?;
+			for blob in response.responses {
+				let digest = blob.digest.unwrap_or_default();
+				if let Some(status) = blob.status.filter(|status| status.code != 0) {
+					return Err(ForgeError::RemoteExecution(format!(
+						"downloading {} failed: {}",
+						digest.hash, status.message
+					)));
+				}
+				blobs.insert(digest, blob.data);
+			}
+		}
+		Ok(blobs)
+	}
+
+	async fn run_action(&self, client: &mut Grpc<Channel>, action_digest: Digest) -> Result<ActionResult, ForgeError> {
+		client.ready().await.map_err(remote_error)?;
+		let request = reapi::ExecuteRequest {
+			instance_name: self.instance_name.clone(),
+			skip_cache_lookup: false,
+			action_digest: Some(action_digest),
+		};
+		let mut operations: tonic::Streaming<reapi::Operation> = client
+			.server_streaming(
+				tonic::Request::new(request),
+				PathAndQuery::from_static(reapi::EXECUTE),
+				ProstCodec::default(),
+			)
+			.await
+			.map_err(remote_error)?
+			.into_inner();
+
+		while let Some(operation) = operations.message().await.map_err(remote_error)? {
+			log::debug!(
+				"Remote operation {} on {}: done = {}",
+				operation.name,
+				self.endpoint,
+				operation.done
+			);
+			if !operation.done {
+				continue;
+			}
+			if let Some(error) = operation.error.filter(|error| error.code != 0) {
+				return Err(ForgeError::RemoteExecution(error.message));
+			}
+			let response = operation
+				.response
+				.filter(|response| response.type_url.ends_with("ExecuteResponse"))
+				.ok_or_else(|| ForgeError::RemoteExecution("the server finished without an ExecuteResponse".into()))?;
+			let response = reapi::ExecuteResponse::decode(response.value.as_slice()).map_err(remote_error)?;
+			if let Some(status) = response.status.filter(|status| status.code != 0) {
+				return Err(ForgeError::RemoteExecution(format!(
+					"{} {}",
+					status.message, response.message
+				)));
+			}
+			if response.cached_result {
+				log::debug!("Remote action cache hit on {}", self.endpoint);
+			}
+			return response
+				.result
+				.ok_or_else(|| ForgeError::RemoteExecution("the server finished without an action result".into()));
+		}
+		Err(ForgeError::RemoteExecution(
+			"the server closed the Execute stream before the action finished".into(),
+		))
+	}
+
+	/// Write the output files of result under the working directory, returning how the command ran
+	async fn download(
+		&self,
+		client: &mut Grpc<Channel>,
+		result: ActionResult,
+		working_directory: &str,
+		output_paths: &[String],
+	) -> Result<Execution, ForgeError> {
+		let output_dir = self.project_root.join(working_directory);
+		let paths = result
+			.output_files
+			.iter()
+			.map(|file| output_file_path(&output_dir, &file.path, output_paths))
+			.collect::<Result<Vec<_>, _>>()?;
+
+		let inline_or_digest = |inline: &[u8], digest: &Option<Digest>| {
+			digest.clone().filter(|digest| inline.is_empty() && digest.size_bytes > 0)
+		};
+		let mut wanted: Vec<Digest> = result
+			.output_files
+			.iter()
+			.filter_map(|file| inline_or_digest(&file.contents, &file.digest))
+			.collect();
+		wanted.extend(inline_or_digest(&result.stdout_raw, &result.stdout_digest));
+		wanted.extend(inline_or_digest(&result.stderr_raw, &result.stderr_digest));
+		let fetched = self.read_blobs(client, wanted).await?;
+		let content = |inline: &[u8], digest: &Option<Digest>| match inline_or_digest(inline, digest) {
+			Some(digest) => fetched
+				.get(&digest)
+				.cloned()
+				.ok_or_else(|| ForgeError::RemoteExecution(format!("the server did not return {}", digest.hash))),
+			None => Ok(inline.to_vec()),
+		};
+
+		for (file, path) in result.output_files.iter().zip(paths) {
+			if let Some(parent) = path.parent() {
+				std::fs::create_dir_all(parent)?;
+			}
+			std::fs::write(&path, content(&file.contents, &file.digest)?)?;
+
+			#[cfg(unix)]
+			{
+				use std::os::unix::fs::PermissionsExt;
+				if file.is_executable {
+					std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+				}
+			}
+		}
+
+		Ok(Execution {
+			exit_code: Some(result.exit_code),
+			status: format!("exit code {} on {}", result.exit_code, self.endpoint),
+			stdout: content(&result.stdout_raw, &result.stdout_digest)?,
+			stderr: content(&result.stderr_raw, &result.stderr_digest)?,
+			accesses: None,
+		})
+	}
+}
+
+impl Executor for RemoteExecutor {
+	fn name(&self) -> &str {
+		&self.endpoint
+	}
+
+	fn execute(&self, rule: &Rule, args: &[String]) -> Result<Execution, ForgeError> {
+		let working_directory = relative_path(&self.project_root, &rule.workdir).ok_or_else(|| {
+			ForgeError::RemoteExecution(format!(
+				"rule '{}' runs in {}, outside the project",
+				rule.name,
+				rule.workdir.display()
+			))
+		})?;
+
+		let mut blobs = Blobs::new();
+		let mut tree = Tree::default();
+		tree.directory(&working_directory);
+		for input in &rule.inputs {
+			add_input(&mut tree, &mut blobs, &self.project_root, Path::new(input))?;
+		}
+		let input_root_digest = tree.digest(&mut blobs);
+
+		let mut output_paths = rule
+			.outputs
+			.iter()
+			.map(|output| {
+				relative_path(&self.project_root.join(&working_directory), &self.project_root.join(output)).ok_or_else(
+					|| {
+						ForgeError::RemoteExecution(format!(
+							"output '{}' of rule '{}' is outside its working directory",
+							output, rule.name
+						))
+					},
+				)
+			})
+			.collect::<Result<Vec<_>, _>>()?;
+		output_paths.sort();
+
+		let command = reapi::Command {
+			arguments: std::iter::once(rule.command.clone()).chain(args.iter().cloned()).collect(),
+			environment_variables: rule
+				.env
+				.iter()
+				.collect::<BTreeMap<_, _>>()
+				.into_iter()
+				.map(|(name, value)| reapi::EnvironmentVariable {
+					name: name.clone(),
+					value: value.clone(),
+				})
+				.collect(),
+			working_directory: working_directory.clone(),
+			output_paths: output_paths.clone(),
+		};
+		let action = reapi::Action {
+			command_digest: Some(add_blob(&mut blobs, command.encode_to_vec())),
+			input_root_digest: Some(input_root_digest),
+			do_not_cache: false,
+		};
+		let action_digest = add_blob(&mut blobs, action.encode_to_vec());
+
+		self.runtime.block_on(async {
+			let mut client = Grpc::new(self.channel.clone());
+			self.upload(&mut client, blobs).await?;
+			let result = self.run_action(&mut client, action_digest).await?;
+			self.download(&mut client, result, &working_directory, &output_paths).await
+		})
+	}
+}
+
+/// A directory of the input root, before it is encoded as Directory messages
+#[derive(Default)]
+struct Tree {
+	files: BTreeMap<String, (Digest, bool)>,
+	directories: BTreeMap<String, Tree>,
+}
+
+impl Tree {
+	/// The directory at path ("a/b", "" for this one), created with its parents when missing
+	fn directory(&mut self, path: &str) -> &mut Tree {
+		path.split('/')
+			.filter(|name| !name.is_empty())
+			.fold(self, |tree, name| tree.directories.entry(name.to_string()).or_default())
+	}
+
+	fn add_file(&mut self, path: &str, digest: Digest, is_executable: bool) {
+		let (directory, name) = path.rsplit_once('/').unwrap_or(("", path));
+		self.directory(directory)
+			.files
+			.insert(name.to_string(), (digest, is_executable));
+	}
+
+	/// Encode this directory and those under it, adding each to blobs, returning the digest of this one
+	fn digest(&self, blobs: &mut Blobs) -> Digest {
+		let directory = reapi::Directory {
+			files: self
+				.files
+				.iter()
+				.map(|(name, (digest, is_executable))| reapi::FileNode {
+					name: name.clone(),
+					digest: Some(digest.clone()),
+					is_executable: *is_executable,
+				})
+				.collect(),
+			directories: self
+				.directories
+				.iter()
+				.map(|(name, tree)| reapi::DirectoryNode {
+					name: name.clone(),
+					digest: Some(tree.digest(blobs)),
+				})
+				.collect(),
+		};
+		add_blob(blobs, directory.encode_to_vec())
+	}
+}
+
+/// The messages of a ByteStream write of data, STREAM_CHUNK_BYTES each, the last one finishing it
+fn write_requests(resource_name: &str, data: &[u8]) -> Vec<reapi::WriteRequest> {
+	let chunks = data.chunks(STREAM_CHUNK_BYTES).count();
+	data.chunks(STREAM_CHUNK_BYTES)
+		.enumerate()
+		.map(|(index, chunk)| reapi::WriteRequest {
+			// Only the first message has to name the resource
+			resource_name: if index == 0 {
+				resource_name.to_string()
+			} else {
+				String::new()
+			},
+			write_offset: (index * STREAM_CHUNK_BYTES) as i64,
+			finish_write: index + 1 == chunks,
+			data: chunk.to_vec(),
+		})
+		.collect()
+}
+
+fn add_blob(blobs: &mut Blobs, data: Vec<u8>) -> Digest {
+	let digest = Digest::of(&data);
+	blobs.insert(digest.clone(), data);
+	digest
+}
+
+/// Add the file at input, or every file under it when it is a directory; inputs that do not exist are left out
+fn add_input(tree: &mut Tree, blobs: &mut Blobs, project_root: &Path, input: &Path) -> Result<(), ForgeError> {
+	let input = project_root.join(input);
+	for entry in WalkDir::new(&input).follow_links(true).into_iter().filter_map(Result::ok) {
+		if !entry.file_type().is_file() {
+			continue;
+		}
+		let Some(path) = relative_path(project_root, entry.path()) else {
+			continue;
+		};
+
+		#[cfg(unix)]
+		let is_executable = {
+			use std::os::unix::fs::PermissionsExt;
+			entry
+				.metadata()
+				.is_ok_and(|metadata| metadata.permissions().mode() & 0o111 != 0)
+		};
+		#[cfg(not(unix))]
+		let is_executable = false;
+
+		let digest = add_blob(blobs, std::fs::read(entry.path())?);
+		tree.add_file(&path, digest, is_executable);
+	}
+	Ok(())
+}
+
+async fn call<Request, Response>(
+	client: &mut Grpc<Channel>,
+	path: &'static str,
+	request: Request,
+) -> Result<Response, ForgeError>
+where
+	Request: Message + Send + Sync + 'static,
+	Response: Message + Default + Send + Sync + 'static,
+{
+	client.ready().await.map_err(remote_error)?;
+	client
+		.unary(
+			tonic::Request::new(request),
+			PathAndQuery::from_static(path),
+			ProstCodec::default(),
+		)
+		.await
+		.map(tonic::Response::into_inner)
+		.map_err(remote_error)
+}
+
+/// Where an output file the server returned goes, refusing any it was not asked for so it cannot write elsewhere
+fn output_file_path(output_dir: &Path, path: &str, output_paths: &[String]) -> Result<PathBuf, ForgeError> {
+	relative_path(output_dir, Path::new(path))
+		.filter(|relative| output_paths.contains(relative))
+		.map(|relative| output_dir.join(relative))
+		.ok_or_else(|| {
+			ForgeError::RemoteExecution(format!(
+				"the server returned '{}', which is not an output of the action",
+				path
+			))
+		})
+}
+
+fn remote_error(error: impl std::fmt::Display) -> ForgeError {
+	ForgeError::RemoteExecution(error.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_input_tree_digest() {
+		let digest_of = |paths: &[&str]| {
+			let mut tree = Tree::default();
+			for path in paths {
+				tree.add_file(path, Digest::of(path.as_bytes()), false);
+			}
+			tree.digest(&mut Blobs::new())
+		};
+		assert_eq!(
+			digest_of(&["src/b.c", "src/a.c", "include/a.h"]),
+			digest_of(&["include/a.h", "src/a.c", "src/b.c"])
+		);
+		assert_ne!(digest_of(&["src/a.c"]), digest_of(&["src/b.c"]));
+	}
+
+	#[test]
+	fn test_output_file_path() {
+		let output_dir = Path::new("/project/app");
+		let outputs = ["bin/app".to_string()];
+		assert_eq!(
+			output_file_path(output_dir, "bin/app", &outputs).unwrap(),
+			Path::new("/project/app/bin/app")
+		);
+		for path in ["../bin/app", "/etc/passwd", "bin/../../app", "bin/other"] {
+			assert!(output_file_path(output_dir, path, &outputs).is_err());
+		}
+	}
+
+	#[test]
+	fn test_write_requests() {
+		let data = vec![7u8; STREAM_CHUNK_BYTES * 2 + 10];
+		let requests = write_requests("uploads/id/blobs/abc/2097162", &data);
+		assert_eq!(requests.len(), 3);
+		assert_eq!(requests[0].resource_name, "uploads/id/blobs/abc/2097162");
+		assert!(requests[1..].iter().all(|request| request.resource_name.is_empty()));
+		assert_eq!(
+			requests.iter().map(|request| request.write_offset).collect::<Vec<_>>(),
+			[0, STREAM_CHUNK_BYTES as i64, 2 * STREAM_CHUNK_BYTES as i64]
+		);
+		assert_eq!(
+			requests.iter().map(|request| request.finish_write).collect::<Vec<_>>(),
+			[false, false, true]
+		);
+		assert_eq!(requests.iter().map(|request| request.data.len()).sum::<usize>(), data.len());
+	}
+}
@@ -0,0 +1,218 @@
+use super::{Execution, Executor, relative_path};
+use crate::{error::ForgeError, export::shell_quote, forge_root_config::RemoteHost, project::Rule};
+use std::{
+	collections::HashMap,
+	io::Write,
+	path::{Path, PathBuf},
+	process::{Command, Stdio},
+	sync::{Condvar, Mutex},
+};
+
+/// Runs commands on [build.remote_hosts] over ssh: the rule's inputs are rsynced into a mirror of the project on the
+/// least loaded host, the command runs there, and its outputs are rsynced back
+pub struct SshExecutor {
+	/// Sorted by name
+	hosts: Vec<(String, RemoteHost)>,
+	/// Rules running on each host, by index in hosts
+	running: Mutex<Vec<usize>>,
+	freed: Condvar,
+	project_root: PathBuf,
+	project_name: String,
+}
+
+/// A running rule's place on a host, given back when dropped
+struct HostSlot<'a> {
+	executor: &'a SshExecutor,
+	index: usize,
+}
+
+impl SshExecutor {
+	pub fn new(hosts: &HashMap<String, RemoteHost>, project_root: &Path, project_name: &str) -> Self {
+		let mut hosts: Vec<(String, RemoteHost)> = hosts.iter().map(|(name, host)| (name.clone(), host.clone())).collect();
+		hosts.sort_by(|a, b| a.0.cmp(&b.0));
+		Self {
+			running: Mutex::new(vec![0; hosts.len()]),
+			hosts,
+			freed: Condvar::new(),
+			project_root: project_root.to_path_buf(),
+			project_name: project_name.to_string(),
+		}
+	}
+
+	/// Wait for a host with a free job, taking the one with the fewest running rules for its jobs
+	fn acquire(&self) -> HostSlot<'_> {
+		let mut running = self.running.lock().unwrap();
+		loop {
+			let load = |index: usize| running[index] as f64 / self.hosts[index].1.jobs as f64;
+			let free = (0..self.hosts.len())
+				.filter(|&index| running[index] < self.hosts[index].1.jobs)
+				.min_by(|&a, &b| load(a).total_cmp(&load(b)));
+			if let Some(index) = free {
+				running[index] += 1;
+				return HostSlot { executor: self, index };
+			}
+			running = self.freed.wait(running).unwrap();
+		}
+	}
+
+	/// Where the project is mirrored on host, relative to the remote home unless absolute
+	fn remote_dir(&self, host: &RemoteHost) -> String {
+		host.dir
+			.clone()
+			.unwrap_or_else(|| format!(".forge/remote/{}", self.project_name))
+	}
+
+	/// path relative to the project, failing for paths outside it, which cannot be mirrored
+	fn project_path(&self, rule: &Rule, path: &Path) -> Result<String, ForgeError> {
+		relative_path(&self.project_root, path).ok_or_else(|| {
+			ForgeError::RemoteExecution(format!(
+				"rule '{}' uses {}, which is outside the project",
+				rule.name,
+				path.display()
+			))
+		})
+	}
+}
+
+impl Drop for HostSlot<'_> {
+	fn drop(&mut self) {
+		self.executor.running.lock().unwrap()[self.index] -= 1;
+		self.executor.freed.notify_all();
+	}
+}
+
+impl Executor for SshExecutor {
+	fn name(&self) -> &str {
+		"ssh"
+	}
+
+	fn execute(&self, rule: &Rule, args: &[String]) -> Result<Execution, ForgeError> {
+		let working_directory = self.project_path(rule, &rule.workdir)?;
+		let inputs: Vec<String> = rule
+			.inputs
+			.iter()
+			.filter(|input| self.project_root.join(input).exists())
+			.map(|input| self.project_path(rule, Path::new(input)))
+			.collect::<Result<_, _>>()?;
+		let outputs: Vec<String> = rule
+			.outputs
+			.iter()
+			.map(|output| self.project_path(rule, Path::new(output)))
+			.collect::<Result<_, _>>()?;
+
+		let slot = self.acquire();
+		let (name, host) = &self.hosts[slot.index];
+		let remote_dir = self.remote_dir(host);
+		log::debug!("Running rule '{}' on remote host {} ({})", rule.name, name, host.ssh);
+
+		let local_root = format!("{}/", self.project_root.display());
+		let remote_root = format!("{}:{}/", host.ssh, remote_dir);
+		let mut directories: Vec<String> = outputs
+			.iter()
+			.filter_map(|output| Path::new(output).parent())
+			.map(|parent| join_remote(&remote_dir, &parent.to_string_lossy()))
+			.collect();
+		directories.push(join_remote(&remote_dir, &working_directory));
+		ssh(
+			host,
+			&format!(
+				"mkdir -p {}",
+				directories.iter().map(|dir| shell_quote(dir)).collect::<Vec<_>>().join(" ")
+			),
+		)?;
+		rsync(&inputs, &local_root, &remote_root)?;
+
+		let mut words = vec!["env".to_string()];
+		let mut env: Vec<_> = rule.env.iter().collect();
+		env.sort();
+		words.extend(env.into_iter().map(|(name, value)| format!("{}={}", name, value)));
+		words.push(rule.command.clone());
+		words.extend(args.iter().cloned());
+		let script = format!(
+			"cd {} && {}",
+			shell_quote(&join_remote(&remote_dir, &working_directory)),
+			words.iter().map(|word| shell_quote(word)).collect::<Vec<_>>().join(" ")
+		);
+		let output = Command::new("ssh").arg(&host.ssh).arg(script).output()?;
+		if output.status.success() {
+			rsync(&outputs, &remote_root, &local_root)?;
+		}
+
+		let mut execution = Execution::from(output);
+		execution.status = format!("{} on {}", execution.status, name);
+		Ok(execution)
+	}
+}
+
+/// dir/path in the remote mirror, dir for ""
+fn join_remote(dir: &str, path: &str) -> String {
+	if path.is_empty() {
+		dir.to_string()
+	} else {
+		format!("{}/{}", dir, path)
+	}
+}
+
+fn ssh(host: &RemoteHost, script: &str) -> Result<(), ForgeError> {
+	let output = Command::new("ssh").arg(&host.ssh).arg(script).output()?;
+	if !output.status.success() {
+		return Err(ForgeError::RemoteExecution(format!(
+			"ssh {} '{}' failed: {}",
+			host.ssh,
+			script,
+			String::from_utf8_lossy(&output.stderr).trim()
+		)));
+	}
+	Ok(())
+}
+
+/// Copy files, relative to source, into destination, keeping their paths
+fn rsync(files: &[String], source: &str, destination: &str) -> Result<(), ForgeError> {
+	if files.is_empty() {
+		return Ok(());
+	}
+
+	let mut child = Command::new("rsync")
+		.args(["-a", "-r", "--files-from=-", source, destination])
+		.stdin(Stdio::piped())
+		.stdout(Stdio::null())
+		.stderr(Stdio::piped())
+		.spawn()?;
+	if let Some(mut stdin) = child.stdin.take() {
+		stdin.write_all(files.join("\n").as_bytes())?;
+	}
+	let output = child.wait_with_output()?;
+	if !output.status.success() {
+		return Err(ForgeError::RemoteExecution(format!(
+			"rsync from {} to {} failed: {}",
+			source,
+			destination,
+			String::from_utf8_lossy(&output.stderr).trim()
+		)));
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_least_loaded_host() {
+		let host = |jobs| RemoteHost {
+			ssh: "localhost".to_string(),
+			dir: None,
+			jobs,
+		};
+		let hosts = HashMap::from([("big".to_string(), host(4)), ("small".to_string(), host(1))]);
+		let executor = SshExecutor::new(&hosts, Path::new("/project"), "project");
+
+		let first = executor.acquire();
+		let second = executor.acquire();
+		let third = executor.acquire();
+		assert_eq!((first.index, second.index, third.index), (0, 1, 0));
+		drop(second);
+		assert_eq!(executor.acquire().index, 1);
+		assert_eq!(executor.remote_dir(&hosts["big"]), ".forge/remote/project");
+	}
+}
@@ -0,0 +1,161 @@
+use super::{Execution, Executor, script_command};
+use crate::{error::ForgeError, interrupt, lua_api, project::Rule};
+use regex::Regex;
+use std::{
+	collections::BTreeSet,
+	path::{Path, PathBuf},
+	sync::{
+		LazyLock,
+		atomic::{AtomicU64, Ordering},
+	},
+};
+
+/// Syscalls that open, create or move files; stat-like calls are left out since compilers probe many paths they
+/// never read
+const TRACED_SYSCALLS: &str = "open,openat,openat2,creat,rename,renameat,renameat2";
+
+/// The path strace -y prints after a file descriptor, as in 3</src/main.c> or AT_FDCWD</src>
+static FD_PATH: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"<([^<>]*)>").unwrap());
+static QUOTED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#""((?:[^"\\]|\\.)*)""#).unwrap());
+
+/// The files a command and every process it started read and wrote, as absolute paths
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FileAccesses {
+	pub reads: BTreeSet<PathBuf>,
+	pub writes: BTreeSet<PathBuf>,
+}
+
+/// Runs commands locally like LocalExecutor, under strace, recording the files they touch
+pub struct TracingExecutor {
+	/// Each run writes one trace file per process in a directory of its own under here
+	dir: PathBuf,
+	next: AtomicU64,
+}
+
+impl TracingExecutor {
+	pub fn new(dir: &Path) -> Self {
+		Self {
+			dir: dir.to_path_buf(),
+			next: AtomicU64::new(0),
+		}
+	}
+
+	/// Whether commands can be traced here: only on Linux, with strace installed
+	pub fn available() -> bool {
+		cfg!(target_os = "linux") && lua_api::exec::find_executable("strace").is_some()
+	}
+}
+
+impl Executor for TracingExecutor {
+	fn name(&self) -> &str {
+		"local"
+	}
+
+	fn execute(&self, rule: &Rule, args: &[String]) -> Result<Execution, ForgeError> {
+		let (program, args) = script_command(&rule.command, args);
+		let dir = self.dir.join(self.next.fetch_add(1, Ordering::Relaxed).to_string());
+		if dir.exists() {
+			std::fs::remove_dir_all(&dir)?;
+		}
+		std::fs::create_dir_all(&dir)?;
+
+		let output = interrupt::output(
+			std::process::Command::new("strace")
+				.args(["-ff", "-qq", "-y", "-s", "4096", "-e"])
+				.arg(format!("trace={}", TRACED_SYSCALLS))
+				.arg("-o")
+				.arg(dir.join("trace"))
+				.arg("--")
+				.arg(program)
+				.args(args)
+				.envs(&rule.env)
+				.current_dir(&rule.workdir),
+		)?;
+
+		let mut accesses = FileAccesses::default();
+		for entry in std::fs::read_dir(&dir)? {
+			let trace = std::fs::read_to_string(entry?.path())?;
+			for line in trace.lines() {
+				record_access(line, &rule.workdir, &mut accesses);
+			}
+		}
+		let _ = std::fs::remove_dir_all(&dir);
+
+		let mut execution: Execution = output.into();
+		execution.accesses = Some(accesses);
+		Ok(execution)
+	}
+}
+
+/// Add the file a successful syscall of one strace line opened or renamed to, relative paths of rename being taken
+/// from workdir since its calling process's directory is not printed
+fn record_access(line: &str, workdir: &Path, accesses: &mut FileAccesses) {
+	let Some((call, result)) = line.rsplit_once(" = ") else {
+		return;
+	};
+	let Some((syscall, args)) = call.split_once('(') else {
+		return;
+	};
+	if result.starts_with('-') {
+		return;
+	}
+
+	match syscall {
+		"open" | "openat" | "openat2" | "creat" => {
+			let Some(path) = FD_PATH.captures(result).map(|captures| PathBuf::from(&captures[1])) else {
+				return;
+			};
+			let writes = syscall == "creat" || ["O_WRONLY", "O_RDWR", "O_CREAT"].iter().any(|flag| args.contains(flag));
+			if writes {
+				accesses.writes.insert(path);
+			} else if !args.contains("O_DIRECTORY") {
+				accesses.reads.insert(path);
+			}
+		}
+		"rename" | "renameat" | "renameat2" => {
+			let Some(target) = QUOTED.captures_iter(args).nth(1).map(|captures| captures[1].to_string()) else {
+				return;
+			};
+			let base = match syscall {
+				"rename" => None,
+				_ => FD_PATH.captures_iter(args).nth(1).map(|captures| PathBuf::from(&captures[1])),
+			};
+			accesses
+				.writes
+				.insert(base.unwrap_or_else(|| workdir.to_path_buf()).join(target));
+		}
+		_ => {}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_record_access() {
+		let mut accesses = FileAccesses::default();
+		let workdir = Path::new("/p");
+		for line in [
+			r#"openat(AT_FDCWD</p>, "src/main.c", O_RDONLY) = 3</p/src/main.c>"#,
+			r#"openat(AT_FDCWD</p>, "include", O_RDONLY|O_NONBLOCK|O_CLOEXEC|O_DIRECTORY) = 4</p/include>"#,
+			r#"openat(AT_FDCWD</p>, "missing.h", O_RDONLY) = -1 ENOENT (No such file or directory)"#,
+			r#"openat(AT_FDCWD</p>, "out/main.o", O_WRONLY|O_CREAT|O_TRUNC, 0666) = 3</p/out/main.o>"#,
+			r#"renameat2(AT_FDCWD</p>, "out/.tmp1", AT_FDCWD</p>, "out/lib.a", 0) = 0"#,
+			r#"rename("a.tmp", "a.txt") = 0"#,
+			"+++ exited with 0 +++",
+		] {
+			record_access(line, workdir, &mut accesses);
+		}
+
+		assert_eq!(accesses.reads, BTreeSet::from([PathBuf::from("/p/src/main.c")]));
+		assert_eq!(
+			accesses.writes,
+			BTreeSet::from([
+				PathBuf::from("/p/a.txt"),
+				PathBuf::from("/p/out/lib.a"),
+				PathBuf::from("/p/out/main.o")
+			])
+		);
+	}
+}
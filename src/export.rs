@@ -0,0 +1,190 @@
+use crate::project::Rule;
+use std::{
+	collections::{HashMap, HashSet},
+	fmt::Write,
+	path::Path,
+};
+
+/// Build systems the evaluated graph can be written for
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum ExportFormat {
+	Ninja,
+}
+
+impl ExportFormat {
+	pub fn default_file_name(self) -> &'static str {
+		match self {
+			ExportFormat::Ninja => "build.ninja",
+		}
+	}
+
+	pub fn render(self, root: &Path, rules: &[Rule]) -> String {
+		match self {
+			ExportFormat::Ninja => to_ninja(root, rules),
+		}
+	}
+}
+
+/// Lower rules to a build.ninja meant to live in root
+/// Every rule becomes a build edge running its command through one generic ninja rule; inputs are explicit
+/// dependencies, forge dependencies become order-only edges and each rule gets a phony alias named after it
+pub fn to_ninja(root: &Path, rules: &[Rule]) -> String {
+	let mut rules: Vec<&Rule> = rules.iter().collect();
+	rules.sort_by(|a, b| a.name.cmp(&b.name));
+
+	let all_outputs: HashSet<&str> = rules
+		.iter()
+		.flat_map(|rule| rule.outputs.iter().map(String::as_str))
+		.collect();
+	let by_name: HashMap<&str, &Rule> = rules.iter().map(|rule| (rule.name.as_str(), *rule)).collect();
+
+	let mut out = String::new();
+	out.push_str("# Generated by `forge export --format ninja`, edits are overwritten on the next export\n");
+	out.push_str("ninja_required_version = 1.3\n\n");
+	out.push_str("rule forge\n  command = $command\n  description = $description\n");
+
+	for rule in &rules {
+		// Ninja needs at least one output; rules without any build their alias, which never exists and so always runs
+		let targets: Vec<String> = if rule.outputs.is_empty() {
+			vec![escape_path(&rule.name)]
+		} else {
+			rule.outputs.iter().map(|output| escape_path(output)).collect()
+		};
+		let inputs: Vec<String> = rule.inputs.iter().map(|input| escape_path(input)).collect();
+		let order_only: Vec<String> = rule
+			.dependencies
+			.iter()
+			.filter_map(|dependency| by_name.get(dependency.as_str()))
+			.flat_map(|dependency| {
+				if dependency.outputs.is_empty() {
+					vec![escape_path(&dependency.name)]
+				} else {
+					dependency.outputs.iter().map(|output| escape_path(output)).collect()
+				}
+			})
+			.collect();
+
+		let _ = write!(out, "\nbuild {}: forge", targets.join(" "));
+		for input in &inputs {
+			let _ = write!(out, " {}", input);
+		}
+		if !order_only.is_empty() {
+			let _ = write!(out, " || {}", order_only.join(" "));
+		}
+		let _ = writeln!(out, "\n  command = {}", escape_value(&shell_command(root, rule)));
+		let _ = writeln!(out, "  description = {}", escape_value(&rule.name));
+
+		if !rule.outputs.is_empty() && !all_outputs.contains(rule.name.as_str()) {
+			let _ = writeln!(out, "build {}: phony {}", escape_path(&rule.name), targets.join(" "));
+		}
+	}
+
+	out
+}
+
+/// The rule's command as a single POSIX shell line: cd into its workdir, set its env, run it
+fn shell_command(root: &Path, rule: &Rule) -> String {
+	if rule.action.is_some() {
+		log::warn!(
+			"Rule '{}' runs a forge.action callback, which only forge can run; ninja will fail to run it",
+			rule.name
+		);
+	}
+	let mut parts = Vec::new();
+	if rule.workdir != root {
+		parts.push(format!("cd {} &&", shell_quote(&rule.workdir.to_string_lossy())));
+	}
+
+	let mut env: Vec<(&String, &String)> = rule.env.iter().collect();
+	env.sort();
+	if !env.is_empty() {
+		parts.push("env".to_string());
+		parts.extend(env.iter().map(|(key, value)| shell_quote(&format!("{}={}", key, value))));
+	}
+
+	parts.push(shell_quote(&rule.command));
+	for arg in &rule.args {
+		if arg.starts_with('@') {
+			log::warn!(
+				"Rule '{}' reads arguments from {} when it runs, which ninja cannot do; it is passed through as is",
+				rule.name,
+				arg
+			);
+		}
+		parts.push(shell_quote(arg));
+	}
+	parts.join(" ")
+}
+
+pub fn shell_quote(word: &str) -> String {
+	let safe = !word.is_empty() && word.bytes().all(|b| b.is_ascii_alphanumeric() || b"-_./=:,+@%".contains(&b));
+	if safe {
+		word.to_string()
+	} else {
+		format!("'{}'", word.replace('\'', "'\\''"))
+	}
+}
+
+/// Escape a path in a build line, where spaces and colons separate paths
+fn escape_path(path: &str) -> String {
+	let mut escaped = String::with_capacity(path.len());
+	for c in path.chars() {
+		match c {
+			'$' | ' ' | ':' => {
+				escaped.push('$');
+				escaped.push(c);
+			}
+			'\n' => escaped.push_str("$\n"),
+			c => escaped.push(c),
+		}
+	}
+	escaped
+}
+
+/// Escape a variable value, where only "$" and newlines are special
+fn escape_value(value: &str) -> String {
+	value.replace('$', "$$").replace('\n', "$\n")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::path::PathBuf;
+
+	fn rule(name: &str, inputs: &[&str], outputs: &[&str], dependencies: &[&str]) -> Rule {
+		Rule {
+			name: name.to_string(),
+			command: "cc".to_string(),
+			args: vec!["-c".to_string(), "it's.c".to_string()],
+			inputs: inputs.iter().map(|s| s.to_string()).collect(),
+			outputs: outputs.iter().map(|s| s.to_string()).collect(),
+			dependencies: dependencies.iter().map(|s| s.to_string()).collect(),
+			workdir: PathBuf::from("/project"),
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn test_escape() {
+		assert_eq!(escape_path("cc:src/a b.c"), "cc$:src/a$ b.c");
+		assert_eq!(escape_value("echo $HOME"), "echo $$HOME");
+		assert_eq!(shell_quote("it's"), "'it'\\''s'");
+		assert_eq!(shell_quote("-Iinclude"), "-Iinclude");
+	}
+
+	#[test]
+	fn test_to_ninja() {
+		let rules = vec![
+			rule("link", &["a.o"], &["app"], &["gen"]),
+			rule("compile", &["a.c"], &["a.o"], &[]),
+			rule("gen", &[], &[], &[]),
+		];
+		let ninja = to_ninja(Path::new("/project"), &rules);
+
+		assert!(ninja.contains("\nbuild a.o: forge a.c\n  command = cc -c 'it'\\''s.c'\n"));
+		assert!(ninja.contains("build compile: phony a.o\n"));
+		assert!(ninja.contains("\nbuild app: forge a.o || gen\n"));
+		assert!(ninja.contains("\nbuild gen: forge\n"));
+		assert!(!ninja.contains("build gen: phony"));
+	}
+}
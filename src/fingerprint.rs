@@ -0,0 +1,204 @@
+use crate::{forge_root_config::ForgeRootConfig, lua_api};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, path::Path, process::Command};
+
+/// Name of the fingerprint stored next to the outputs of every artifact in the CAS
+pub const FINGERPRINT_NAME: &str = ".forge-fingerprint.json";
+
+/// Compilers whose version is recorded when they are on PATH
+const COMPILERS: &[&str] = &["cc", "c++", "rustc", "go", "zig"];
+
+/// Variables that change what compilers produce, recorded when set; [build] fingerprint_env adds to them
+const ENV_VARS: &[&str] = &[
+	"CC",
+	"CXX",
+	"CFLAGS",
+	"CXXFLAGS",
+	"CPPFLAGS",
+	"LDFLAGS",
+	"RUSTFLAGS",
+	"MACOSX_DEPLOYMENT_TARGET",
+	"SDKROOT",
+];
+
+/// What artifacts built on this machine depend on besides their inputs, so a cache shared between machines does not
+/// serve outputs built for another ABI
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fingerprint {
+	pub os: String,
+	pub arch: String,
+	/// The C library builds link against, e.g. "glibc 2.39" or "musl 1.2.4"; None off Linux or when not found
+	pub libc: Option<String>,
+	/// First line of `<compiler> --version` by compiler, and "toolchain:<name>" with the version of each
+	/// FORGE_ROOT toolchain
+	pub compilers: BTreeMap<String, String>,
+	pub env: BTreeMap<String, String>,
+}
+
+impl Fingerprint {
+	/// The fingerprint of this machine for the project configured by config
+	pub fn capture(config: &ForgeRootConfig) -> Self {
+		let mut compilers: BTreeMap<String, String> = COMPILERS
+			.iter()
+			.filter_map(|compiler| Some((compiler.to_string(), compiler_version(compiler)?)))
+			.collect();
+		for (name, toolchain) in &config.toolchains {
+			compilers.insert(format!("toolchain:{}", name), toolchain.version.clone());
+		}
+
+		let env = ENV_VARS
+			.iter()
+			.map(|name| name.to_string())
+			.chain(config.build.fingerprint_env.iter().cloned())
+			.filter_map(|name| Some((name.clone(), std::env::var(&name).ok()?)))
+			.collect();
+
+		Self {
+			os: std::env::consts::OS.to_string(),
+			arch: std::env::consts::ARCH.to_string(),
+			libc: libc_version(),
+			compilers,
+			env,
+		}
+	}
+
+	/// Read the fingerprint stored in an artifact directory of the CAS, None for artifacts stored before fingerprints
+	pub fn load(artifact_dir: &Path) -> Option<Self> {
+		let content = std::fs::read(artifact_dir.join(FINGERPRINT_NAME)).ok()?;
+		serde_json::from_slice(&content).ok()
+	}
+
+	pub fn save(&self, artifact_dir: &Path) -> anyhow::Result<()> {
+		std::fs::write(artifact_dir.join(FINGERPRINT_NAME), serde_json::to_vec_pretty(self)?)?;
+		Ok(())
+	}
+
+	/// What differs between self and the fingerprint of an artifact, one "<field>: <ours> != <theirs>" each, empty when
+	/// its outputs can be used here
+	pub fn incompatibilities(&self, artifact: &Fingerprint) -> Vec<String> {
+		let mut differences = Vec::new();
+		let mut compare = |field: &str, ours: Option<&str>, theirs: Option<&str>| {
+			if ours != theirs {
+				differences.push(format!(
+					"{}: {} != {}",
+					field,
+					ours.unwrap_or("unset"),
+					theirs.unwrap_or("unset")
+				));
+			}
+		};
+		compare("os", Some(self.os.as_str()), Some(artifact.os.as_str()));
+		compare("arch", Some(self.arch.as_str()), Some(artifact.arch.as_str()));
+		compare("libc", self.libc.as_deref(), artifact.libc.as_deref());
+		for (section, ours, theirs) in [
+			("compilers", &self.compilers, &artifact.compilers),
+			("env", &self.env, &artifact.env),
+		] {
+			let mut keys: Vec<&String> = ours.keys().chain(theirs.keys()).collect();
+			keys.sort();
+			keys.dedup();
+			for key in keys {
+				compare(
+					&format!("{}.{}", section, key),
+					ours.get(key).map(String::as_str),
+					theirs.get(key).map(String::as_str),
+				);
+			}
+		}
+		differences
+	}
+}
+
+impl fmt::Display for Fingerprint {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "os        {}", self.os)?;
+		writeln!(f, "arch      {}", self.arch)?;
+		write!(f, "libc      {}", self.libc.as_deref().unwrap_or("unknown"))?;
+		for (section, values) in [("compilers", &self.compilers), ("env", &self.env)] {
+			if values.is_empty() {
+				continue;
+			}
+			write!(f, "\n{}", section)?;
+			for (key, value) in values {
+				write!(f, "\n  {:<24} {}", key, value)?;
+			}
+		}
+		Ok(())
+	}
+}
+
+fn compiler_version(compiler: &str) -> Option<String> {
+	let path = lua_api::exec::find_executable(compiler)?;
+	let output = Command::new(&path).arg("--version").output().ok()?;
+	// go only knows `go version`, and prints nothing useful for --version
+	let output = if output.status.success() {
+		output
+	} else {
+		Command::new(&path).arg("version").output().ok()?
+	};
+	first_line(&output.stdout)
+}
+
+/// The libc of the system, from `ldd --version`: glibc prints its version on stdout, musl on stderr with a failure
+fn libc_version() -> Option<String> {
+	if !cfg!(target_os = "linux") {
+		return None;
+	}
+	let output = Command::new("ldd").arg("--version").output().ok()?;
+	parse_libc(
+		&String::from_utf8_lossy(&output.stdout),
+		&String::from_utf8_lossy(&output.stderr),
+	)
+}
+
+fn parse_libc(stdout: &str, stderr: &str) -> Option<String> {
+	if let Some(line) = stdout.lines().next()
+		&& (line.contains("GNU libc") || line.contains("GLIBC"))
+	{
+		return Some(format!("glibc {}", line.rsplit(' ').next()?));
+	}
+	if stderr.starts_with("musl libc") {
+		let version = stderr.lines().find_map(|line| line.strip_prefix("Version "))?;
+		return Some(format!("musl {}", version.trim()));
+	}
+	None
+}
+
+fn first_line(output: &[u8]) -> Option<String> {
+	let line = String::from_utf8_lossy(output).lines().next()?.trim().to_string();
+	(!line.is_empty()).then_some(line)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_fingerprint_compatibility() {
+		assert_eq!(
+			parse_libc("ldd (Ubuntu GLIBC 2.39-0ubuntu8.4) 2.39\nCopyright (C) 2024\n", ""),
+			Some("glibc 2.39".to_string())
+		);
+		assert_eq!(
+			parse_libc("", "musl libc (x86_64)\nVersion 1.2.4\nDynamic Program Loader\n"),
+			Some("musl 1.2.4".to_string())
+		);
+
+		let ours = Fingerprint {
+			os: "linux".to_string(),
+			arch: "x86_64".to_string(),
+			libc: Some("glibc 2.39".to_string()),
+			compilers: BTreeMap::from([("cc".to_string(), "cc (GCC) 14.2.0".to_string())]),
+			env: BTreeMap::new(),
+		};
+		assert!(ours.incompatibilities(&ours.clone()).is_empty());
+
+		let mut theirs = ours.clone();
+		theirs.libc = Some("musl 1.2.4".to_string());
+		theirs.env.insert("CFLAGS".to_string(), "-O3".to_string());
+		assert_eq!(
+			ours.incompatibilities(&theirs),
+			["libc: glibc 2.39 != musl 1.2.4", "env.CFLAGS: unset != -O3"]
+		);
+	}
+}
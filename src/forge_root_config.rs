@@ -21,6 +21,14 @@ pub struct ForgeRootConfig {
 	pub discovery: DiscoveryConfig,
 	#[serde(default)]
 	pub build: BuildConfig,
+	#[serde(default)]
+	pub lua: LuaConfig,
+	/// Prebuilt toolchains forge downloads into ~/.forge/toolchains before evaluating FORGE files, by name
+	#[serde(default)]
+	pub toolchains: std::collections::HashMap<String, ToolchainConfig>,
+	/// Settings of the targets cross builds are made for, by triple like "aarch64-unknown-linux-gnu"
+	#[serde(default)]
+	pub targets: std::collections::HashMap<String, TargetConfig>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone)]
@@ -49,6 +57,178 @@ pub struct BuildConfig {
 	pub cache_dir: String,
 	#[serde(default)]
 	pub global_env: std::collections::HashMap<String, String>,
+	/// Seed forge.random and forge.uuid deterministically so repeated builds produce identical outputs
+	#[serde(default)]
+	pub reproducible: bool,
+	/// Reject path arguments (forge.fs and other modules) outside the project root, build cache, download cache and temp dir
+	#[serde(default)]
+	pub restrict_fs: bool,
+	/// Which Lua standard library functions and forge APIs FORGE files and preludes may use
+	#[serde(default)]
+	pub lua_sandbox: LuaSandbox,
+	/// Run every FORGE file in its own global environment so globals one file defines are not visible to the next;
+	/// set to false for projects that share globals between FORGE files
+	#[serde(default = "default_true")]
+	pub isolate_forge_files: bool,
+	/// Reuse the rules of FORGE files whose content, required modules, configuration and observed files and
+	/// environment variables are unchanged instead of evaluating them again; needs isolate_forge_files
+	#[serde(default = "default_true")]
+	pub cache_evaluation: bool,
+	/// How many rules of each pool may run at once, for rules that set pool = "<name>"; limits steps like LTO links
+	/// independently of the overall parallelism
+	#[serde(default)]
+	pub pools: std::collections::HashMap<String, usize>,
+	/// Remote Execution API server rules can run on
+	#[serde(default)]
+	pub remote: RemoteConfig,
+	/// Machines reachable over ssh that rules with remote = true run on when [build.remote] has no endpoint, a
+	/// lighter alternative needing only ssh and rsync on both ends
+	#[serde(default)]
+	pub remote_hosts: std::collections::HashMap<String, RemoteHost>,
+	/// Trace local rule commands with strace (Linux only) and report files they read but do not list in inputs
+	/// or write but do not list in outputs
+	#[serde(default)]
+	pub enforce_deps: EnforceDeps,
+	/// Move relative rule outputs under <cache_dir>/<target>/<profile>, so builds for different targets and profiles
+	/// do not overwrite each other's artifacts; a rule's inputs and outputs follow, as do its args, env values and
+	/// script words naming one of them, while anything else must build its paths from forge.out_dir
+	#[serde(default)]
+	pub isolate_outputs: bool,
+	/// Environment variables recorded in the fingerprint of every artifact besides CC, CFLAGS and the like, so
+	/// artifacts built with another value are not restored from the cache
+	#[serde(default)]
+	pub fingerprint_env: Vec<String>,
+	/// Program and leading arguments that run the script of rules giving one instead of a command
+	#[serde(default = "default_shell")]
+	pub shell: Vec<String>,
+	/// Seconds a persistent worker may take to answer one request before it is killed and the rule fails
+	#[serde(default = "default_worker_timeout")]
+	pub worker_timeout: u64,
+	/// Patterns (globs, case-insensitive) of environment variables whose values the audit log records as
+	/// "<redacted>"
+	#[serde(default = "default_audit_redact")]
+	pub audit_redact: Vec<String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct RemoteConfig {
+	/// grpc://host:port, or grpcs://host:port for TLS; without it every rule runs locally
+	pub endpoint: Option<String>,
+	/// Instance name the server expects in every request, often empty
+	#[serde(default)]
+	pub instance_name: String,
+	/// Run every rule remotely unless it sets remote = false, rather than only the rules setting remote = true
+	#[serde(default)]
+	pub all_rules: bool,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct RemoteHost {
+	/// What ssh connects to, user@host or a Host from ~/.ssh/config
+	pub ssh: String,
+	/// Where the project is mirrored on the host, relative to its home unless absolute; .forge/remote/<project name>
+	/// by default
+	pub dir: Option<String>,
+	/// How many rules may run on the host at once
+	#[serde(default = "default_host_jobs")]
+	pub jobs: usize,
+}
+
+/// A toolchain archive, by one url for every platform or one per platform; url, root and bin may use {version},
+/// {os}, {arch} and {platform}, and env values also {root}, the directory the toolchain was unpacked to
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ToolchainConfig {
+	#[serde(default)]
+	pub version: String,
+	pub url: Option<String>,
+	pub sha256: Option<String>,
+	pub blake3: Option<String>,
+	/// Archives by "<os>-<arch>", like "linux-x86_64" or "macos-aarch64", taking precedence over url
+	#[serde(default)]
+	pub platforms: std::collections::HashMap<String, ToolchainArchive>,
+	/// Directory of the archive that is the toolchain's root, for archives holding everything in one directory
+	pub root: Option<String>,
+	/// Directories of the toolchain, relative to its root, put in front of PATH for every rule
+	#[serde(default = "default_toolchain_bin")]
+	pub bin: Vec<String>,
+	/// Variables set for every rule that does not set them itself
+	#[serde(default)]
+	pub env: std::collections::HashMap<String, String>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+pub struct ToolchainArchive {
+	pub url: String,
+	pub sha256: Option<String>,
+	pub blake3: Option<String>,
+}
+
+impl ToolchainConfig {
+	/// The archive to download on platform, "<os>-<arch>"
+	pub fn archive(&self, platform: &str) -> Option<ToolchainArchive> {
+		self.platforms.get(platform).cloned().or_else(|| {
+			self.url.as_ref().map(|url| ToolchainArchive {
+				url: url.clone(),
+				sha256: self.sha256.clone(),
+				blake3: self.blake3.clone(),
+			})
+		})
+	}
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct TargetConfig {
+	/// Root filesystem the target's headers and libraries are found in, passed to compilers as --sysroot
+	pub sysroot: Option<SysrootConfig>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum SysrootConfig {
+	/// A directory, relative to the project root unless absolute
+	Path(String),
+	/// An archive forge downloads into ~/.forge/toolchains; root is the directory of the archive that is the sysroot
+	Archive {
+		url: String,
+		sha256: Option<String>,
+		blake3: Option<String>,
+		root: Option<String>,
+	},
+}
+
+/// How much of the host FORGE files can reach from Lua
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LuaSandbox {
+	/// No io library, no dynamic code loading, no os.execute, no network access and no forge API that runs host programs
+	/// (exec, docker, cargo, cmake, pkg_config, rust)
+	Strict,
+	/// Dynamic code loading, os.execute, io.popen and os.exit are removed; every forge API stays available
+	Standard,
+	/// The whole safe standard library and every forge API
+	#[default]
+	Full,
+}
+
+/// What to do about the undeclared reads and writes of a traced rule
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EnforceDeps {
+	/// Rules are not traced
+	#[default]
+	Off,
+	/// Undeclared accesses are logged as warnings
+	Warn,
+	/// Undeclared accesses fail the rule
+	Error,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct LuaConfig {
+	/// Directories, relative to the project root, that require searches after the root for names without an "@"
+	/// prefix, so shared in-repo Lua libraries can be required by their path inside them
+	#[serde(default)]
+	pub package_paths: Vec<String>,
 }
 
 impl Default for DiscoveryConfig {
@@ -67,10 +247,47 @@ impl Default for BuildConfig {
 		Self {
 			cache_dir: default_cache_dir(),
 			global_env: std::collections::HashMap::new(),
+			reproducible: false,
+			restrict_fs: false,
+			lua_sandbox: LuaSandbox::Full,
+			isolate_forge_files: true,
+			cache_evaluation: true,
+			pools: std::collections::HashMap::new(),
+			remote: RemoteConfig::default(),
+			remote_hosts: std::collections::HashMap::new(),
+			enforce_deps: EnforceDeps::Off,
+			isolate_outputs: false,
+			fingerprint_env: Vec::new(),
+			shell: default_shell(),
+			worker_timeout: default_worker_timeout(),
+			audit_redact: default_audit_redact(),
 		}
 	}
 }
 
+fn default_shell() -> Vec<String> {
+	vec!["sh".to_string(), "-e".to_string()]
+}
+
+fn default_audit_redact() -> Vec<String> {
+	["*TOKEN*", "*SECRET*", "*KEY*", "*PASSWORD*"]
+		.into_iter()
+		.map(String::from)
+		.collect()
+}
+
+fn default_worker_timeout() -> u64 {
+	600
+}
+
+fn default_toolchain_bin() -> Vec<String> {
+	vec!["bin".to_string()]
+}
+
+fn default_host_jobs() -> usize {
+	1
+}
+
 fn default_version() -> String {
 	"0.1.0".to_string()
 }
@@ -117,6 +334,37 @@ impl ForgeRootConfig {
 			));
 		}
 
+		if let Some((name, _)) = self.build.pools.iter().find(|(_, depth)| **depth == 0) {
+			return Err(ForgeRootConfigError::Invalid(format!(
+				"Pool '{}' must allow at least one rule at a time",
+				name
+			)));
+		}
+
+		if let Some((name, _)) = self.build.remote_hosts.iter().find(|(_, host)| host.jobs == 0) {
+			return Err(ForgeRootConfigError::Invalid(format!(
+				"Remote host '{}' must allow at least one job",
+				name
+			)));
+		}
+
+		if self.build.remote.all_rules && self.build.remote.endpoint.is_none() && self.build.remote_hosts.is_empty() {
+			return Err(ForgeRootConfigError::Invalid(
+				"[build.remote] all_rules needs an endpoint or [build.remote_hosts] to run the rules on".to_string(),
+			));
+		}
+
+		if let Some((name, _)) = self
+			.toolchains
+			.iter()
+			.find(|(_, toolchain)| toolchain.url.is_none() && toolchain.platforms.is_empty())
+		{
+			return Err(ForgeRootConfigError::Invalid(format!(
+				"Toolchain '{}' needs a url or [toolchains.{}.platforms] to download it from",
+				name, name
+			)));
+		}
+
 		Ok(())
 	}
 
@@ -129,6 +377,9 @@ impl ForgeRootConfig {
 			},
 			discovery: DiscoveryConfig::default(),
 			build: BuildConfig::default(),
+			lua: LuaConfig::default(),
+			toolchains: std::collections::HashMap::new(),
+			targets: std::collections::HashMap::new(),
 		}
 	}
 
@@ -176,4 +427,66 @@ mod tests {
 		assert_eq!(config.project.name, parsed.project.name);
 		assert_eq!(config.discovery.use_gitignore, parsed.discovery.use_gitignore);
 	}
+
+	#[test]
+	fn test_build_options() {
+		let config: ForgeRootConfig = toml::from_str("[project]\nname = \"test\"\n").unwrap();
+		assert_eq!(config.build.lua_sandbox, LuaSandbox::Full);
+		assert!(config.build.isolate_forge_files);
+		assert!(config.build.cache_evaluation);
+		assert_eq!(config.build.enforce_deps, EnforceDeps::Off);
+		assert!(!config.build.isolate_outputs);
+		assert_eq!(config.build.shell, ["sh", "-e"]);
+
+		let config: ForgeRootConfig =
+			toml::from_str("[project]\nname = \"test\"\n\n[build]\nlua_sandbox = \"strict\"\nenforce_deps = \"error\"\n")
+				.unwrap();
+		assert_eq!(config.build.lua_sandbox, LuaSandbox::Strict);
+		assert_eq!(config.build.enforce_deps, EnforceDeps::Error);
+
+		let config: ForgeRootConfig =
+			toml::from_str("[project]\nname = \"test\"\n\n[lua]\npackage_paths = [\"tools/lua\"]\n").unwrap();
+		assert_eq!(config.lua.package_paths, vec!["tools/lua".to_string()]);
+		assert!(
+			toml::from_str::<ForgeRootConfig>("[project]\nname = \"test\"\n\n[build]\nlua_sandbox = \"none\"\n").is_err()
+		);
+
+		let config: ForgeRootConfig = toml::from_str("[project]\nname = \"test\"\n\n[build.pools]\nlinkers = 0\n").unwrap();
+		assert!(config.validate().is_err());
+	}
+
+	#[test]
+	fn test_toolchains() {
+		let config: ForgeRootConfig = toml::from_str(
+			"[project]\nname = \"test\"\n\n[toolchains.zig]\nversion = \"0.13.0\"\n\
+			 url = \"https://ziglang.org/download/{version}/zig-{os}-{arch}-{version}.tar.xz\"\n\n\
+			 [toolchains.zig.platforms.windows-x86_64]\nurl = \"https://ziglang.org/zig-windows.zip\"\nsha256 = \"ab\"\n",
+		)
+		.unwrap();
+		assert!(config.validate().is_ok());
+		let zig = &config.toolchains["zig"];
+		assert_eq!(zig.bin, vec!["bin".to_string()]);
+		assert_eq!(zig.archive("windows-x86_64").unwrap().sha256.as_deref(), Some("ab"));
+		assert!(zig.archive("linux-x86_64").unwrap().url.contains("{os}"));
+
+		let config: ForgeRootConfig = toml::from_str("[project]\nname = \"test\"\n\n[toolchains.zig]\n").unwrap();
+		assert!(config.validate().is_err());
+	}
+
+	#[test]
+	fn test_target_sysroots() {
+		let config: ForgeRootConfig = toml::from_str(
+			"[project]\nname = \"test\"\n\n[targets.aarch64-unknown-linux-gnu]\nsysroot = \"sysroots/aarch64\"\n\n\
+			 [targets.arm-unknown-linux-gnueabihf]\nsysroot = { url = \"https://example.com/armhf.tar.gz\", sha256 = \"ab\" }\n",
+		)
+		.unwrap();
+		assert!(matches!(
+			&config.targets["aarch64-unknown-linux-gnu"].sysroot,
+			Some(SysrootConfig::Path(path)) if path == "sysroots/aarch64"
+		));
+		assert!(matches!(
+			&config.targets["arm-unknown-linux-gnueabihf"].sysroot,
+			Some(SysrootConfig::Archive { sha256: Some(sha256), .. }) if sha256 == "ab"
+		));
+	}
 }
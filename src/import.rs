@@ -0,0 +1,1360 @@
+use std::{
+	collections::{HashMap, HashSet},
+	path::{Component, Path, PathBuf},
+};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ImportError {
+	#[error("Failed to read {path}: {source}")]
+	Io {
+		path: String,
+		source: std::io::Error,
+	},
+
+	#[error("{file}:{line}: {message}")]
+	Parse {
+		file: String,
+		line: usize,
+		message: String,
+	},
+}
+
+/// One build step of an imported Makefile or Ninja file
+/// Paths are relative to the directory of the build file, the command runs there through sh -c
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedRule {
+	/// Target (make) or first output (ninja), used to name the forge rule
+	pub name: String,
+	pub command: String,
+	pub inputs: Vec<String>,
+	/// Empty for phony make targets with a recipe, which run every time
+	pub outputs: Vec<String>,
+	/// Names of other imported rules that must run first
+	pub dependencies: Vec<String>,
+}
+
+/// Rewrite the paths of imported rules from the build file directory to dir (usually the build file directory
+/// relative to the project root), leaving absolute paths alone
+pub fn rebase(rules: &mut [ImportedRule], dir: &Path) {
+	let rebase_path = |path: &mut String| {
+		if !Path::new(path.as_str()).is_absolute() {
+			*path = normalize(&dir.join(path.as_str())).to_string_lossy().to_string();
+		}
+	};
+	for rule in rules {
+		rebase_path(&mut rule.name);
+		rule.inputs.iter_mut().for_each(rebase_path);
+		rule.outputs.iter_mut().for_each(rebase_path);
+		rule.dependencies.iter_mut().for_each(rebase_path);
+	}
+}
+
+/// Remove "." and inner ".." components without touching the file system
+fn normalize(path: &Path) -> PathBuf {
+	let mut normalized = PathBuf::new();
+	for component in path.components() {
+		match component {
+			Component::CurDir => {}
+			Component::ParentDir if matches!(normalized.components().next_back(), Some(Component::Normal(_))) => {
+				normalized.pop();
+			}
+			component => normalized.push(component),
+		}
+	}
+	normalized
+}
+
+fn read(path: &Path) -> Result<String, ImportError> {
+	std::fs::read_to_string(path).map_err(|source| ImportError::Io {
+		path: path.display().to_string(),
+		source,
+	})
+}
+
+/// Fail when file, included by path at line, is one of the files being parsed, so it would include itself forever
+fn check_include(including: &[PathBuf], path: &Path, line: usize, file: &Path) -> Result<(), ImportError> {
+	if let Ok(canonical) = file.canonicalize()
+		&& including.contains(&canonical)
+	{
+		return Err(parse_error(path, line, format!("{} includes itself", file.display())));
+	}
+	Ok(())
+}
+
+fn parse_error(file: &Path, line: usize, message: impl Into<String>) -> ImportError {
+	ImportError::Parse {
+		file: file.display().to_string(),
+		line,
+		message: message.into(),
+	}
+}
+
+/// Logical lines of a build file with their 1-based starting line number, joining lines that end with the
+/// continuation marker with separator
+fn logical_lines(content: &str, separator: &str, continues: impl Fn(&str) -> Option<&str>) -> Vec<(usize, String)> {
+	let mut lines = Vec::new();
+	let mut current: Option<(usize, String)> = None;
+	for (index, line) in content.lines().enumerate() {
+		let (number, mut text) = match current.take() {
+			Some((number, text)) => (number, text + separator + line.trim_start()),
+			None => (index + 1, line.to_string()),
+		};
+		if let Some(stripped) = continues(&text) {
+			text = stripped.to_string();
+			current = Some((number, text));
+		} else {
+			lines.push((number, text));
+		}
+	}
+	lines.extend(current);
+	lines
+}
+
+// ---------------------------------------------------------------------------------------------------------------
+// Ninja
+
+#[derive(Default)]
+struct NinjaFile {
+	vars: HashMap<String, String>,
+	/// Unevaluated bindings of each rule, expanded per build edge
+	rules: HashMap<String, HashMap<String, String>>,
+	edges: Vec<NinjaEdge>,
+	phony: HashMap<String, Vec<String>>,
+	/// The files being parsed, outermost first, to catch include cycles
+	including: Vec<PathBuf>,
+}
+
+struct NinjaEdge {
+	rule: String,
+	outputs: Vec<String>,
+	implicit_outputs: Vec<String>,
+	inputs: Vec<String>,
+	implicit_inputs: Vec<String>,
+	order_only: Vec<String>,
+	vars: HashMap<String, String>,
+}
+
+enum NinjaBlock {
+	None,
+	Rule(String),
+	Build(usize),
+	Pool,
+}
+
+#[derive(Debug, PartialEq)]
+enum NinjaToken {
+	Path(String),
+	Colon,
+	Pipe,
+	DoublePipe,
+	Validation,
+}
+
+/// Parse a build.ninja (following include and subninja) into rules
+/// Phony edges become aliases resolved into the inputs of the edges that use them; depfiles are not read
+pub fn parse_ninja(path: &Path) -> Result<Vec<ImportedRule>, ImportError> {
+	let mut ninja = NinjaFile::default();
+	parse_ninja_file(path, path.parent().unwrap_or(Path::new(".")), &mut ninja)?;
+
+	let producers: HashMap<&str, &str> = ninja
+		.edges
+		.iter()
+		.filter(|edge| edge.rule != "phony")
+		.flat_map(|edge| {
+			let name = edge.outputs[0].as_str();
+			edge.outputs
+				.iter()
+				.chain(&edge.implicit_outputs)
+				.map(move |output| (output.as_str(), name))
+		})
+		.collect();
+
+	let mut rules = Vec::new();
+	for edge in ninja.edges.iter().filter(|edge| edge.rule != "phony") {
+		let bindings = &ninja.rules[&edge.rule];
+		let command = ninja_edge_var(&ninja, edge, bindings, "command", 0).unwrap_or_default();
+
+		let mut inputs = Vec::new();
+		for input in edge.inputs.iter().chain(&edge.implicit_inputs) {
+			resolve_phony(&ninja.phony, input, &mut inputs, &mut HashSet::new());
+		}
+		let mut order_only = Vec::new();
+		for input in &edge.order_only {
+			resolve_phony(&ninja.phony, input, &mut order_only, &mut HashSet::new());
+		}
+
+		let mut dependencies: Vec<String> = Vec::new();
+		for input in &order_only {
+			if let Some(producer) = producers.get(input.as_str())
+				&& !dependencies.iter().any(|dependency| dependency == producer)
+			{
+				dependencies.push(producer.to_string());
+			}
+		}
+
+		rules.push(ImportedRule {
+			name: edge.outputs[0].clone(),
+			command,
+			inputs,
+			outputs: edge.outputs.iter().chain(&edge.implicit_outputs).cloned().collect(),
+			dependencies,
+		});
+	}
+	Ok(rules)
+}
+
+fn parse_ninja_file(path: &Path, build_dir: &Path, ninja: &mut NinjaFile) -> Result<(), ImportError> {
+	let content = read(path)?;
+	ninja
+		.including
+		.push(path.canonicalize().unwrap_or_else(|_| path.to_path_buf()));
+	// A line ending in an unescaped "$" continues on the next one
+	let lines = logical_lines(&content, "", |line| {
+		let dollars = line.len() - line.trim_end_matches('$').len();
+		(dollars % 2 == 1).then(|| &line[..line.len() - 1])
+	});
+
+	let mut block = NinjaBlock::None;
+	for (number, line) in lines {
+		let trimmed = line.trim();
+		if trimmed.is_empty() || trimmed.starts_with('#') {
+			continue;
+		}
+
+		if line.starts_with([' ', '\t']) {
+			let (key, value) = split_binding(trimmed).ok_or_else(|| parse_error(path, number, "expected 'name = value'"))?;
+			match &block {
+				NinjaBlock::Rule(rule) => {
+					ninja.rules.get_mut(rule).unwrap().insert(key.to_string(), value.to_string());
+				}
+				NinjaBlock::Build(index) => {
+					let value = ninja_expand(value, &|name| ninja.vars.get(name).cloned());
+					ninja.edges[*index].vars.insert(key.to_string(), value);
+				}
+				NinjaBlock::Pool => {}
+				NinjaBlock::None => return Err(parse_error(path, number, "indented binding outside of a rule or build")),
+			}
+			continue;
+		}
+
+		let (keyword, rest) = trimmed.split_once([' ', '\t']).unwrap_or((trimmed, ""));
+		let rest = rest.trim();
+		block = match keyword {
+			"rule" => {
+				ninja.rules.insert(rest.to_string(), HashMap::new());
+				NinjaBlock::Rule(rest.to_string())
+			}
+			"build" => {
+				let edge = parse_ninja_build(rest, &ninja.vars).map_err(|message| parse_error(path, number, message))?;
+				if edge.rule != "phony" && !ninja.rules.contains_key(&edge.rule) {
+					return Err(parse_error(path, number, format!("unknown rule '{}'", edge.rule)));
+				}
+				if edge.rule == "phony" {
+					for output in &edge.outputs {
+						let mut inputs = edge.inputs.clone();
+						inputs.extend(edge.implicit_inputs.iter().cloned());
+						ninja.phony.insert(output.clone(), inputs);
+					}
+				}
+				ninja.edges.push(edge);
+				NinjaBlock::Build(ninja.edges.len() - 1)
+			}
+			"include" | "subninja" => {
+				let file = build_dir.join(ninja_expand(rest, &|name| ninja.vars.get(name).cloned()));
+				check_include(&ninja.including, path, number, &file)?;
+				parse_ninja_file(&file, build_dir, ninja)?;
+				NinjaBlock::None
+			}
+			"pool" => NinjaBlock::Pool,
+			"default" => NinjaBlock::None,
+			_ => {
+				let (key, value) =
+					split_binding(trimmed).ok_or_else(|| parse_error(path, number, format!("unexpected '{}'", keyword)))?;
+				let value = ninja_expand(value, &|name| ninja.vars.get(name).cloned());
+				ninja.vars.insert(key.to_string(), value);
+				NinjaBlock::None
+			}
+		};
+	}
+	ninja.including.pop();
+	Ok(())
+}
+
+fn split_binding(line: &str) -> Option<(&str, &str)> {
+	let (key, value) = line.split_once('=')?;
+	let key = key.trim();
+	(!key.is_empty() && !key.contains(char::is_whitespace)).then(|| (key, value.trim_start()))
+}
+
+/// "outs [| implicit]: rule ins [| implicit] [|| order-only] [|@ validations]"
+fn parse_ninja_build(line: &str, vars: &HashMap<String, String>) -> Result<NinjaEdge, String> {
+	let tokens = ninja_tokens(line, vars)?;
+	let colon = tokens
+		.iter()
+		.position(|token| *token == NinjaToken::Colon)
+		.ok_or("expected ':' in build statement")?;
+
+	let mut edge = NinjaEdge {
+		rule: String::new(),
+		outputs: Vec::new(),
+		implicit_outputs: Vec::new(),
+		inputs: Vec::new(),
+		implicit_inputs: Vec::new(),
+		order_only: Vec::new(),
+		vars: HashMap::new(),
+	};
+
+	let mut implicit = false;
+	for token in &tokens[..colon] {
+		match token {
+			NinjaToken::Path(path) if implicit => edge.implicit_outputs.push(path.clone()),
+			NinjaToken::Path(path) => edge.outputs.push(path.clone()),
+			NinjaToken::Pipe => implicit = true,
+			_ => return Err("unexpected token in build outputs".to_string()),
+		}
+	}
+	if edge.outputs.is_empty() {
+		return Err("build statement has no outputs".to_string());
+	}
+
+	let mut rest = tokens[colon + 1..].iter();
+	match rest.next() {
+		Some(NinjaToken::Path(rule)) => edge.rule = rule.clone(),
+		_ => return Err("expected a rule name after ':'".to_string()),
+	}
+
+	let mut target = &mut edge.inputs;
+	let mut skip = false;
+	for token in rest {
+		match token {
+			NinjaToken::Path(_) if skip => {}
+			NinjaToken::Path(path) => target.push(path.clone()),
+			NinjaToken::Pipe => target = &mut edge.implicit_inputs,
+			NinjaToken::DoublePipe => target = &mut edge.order_only,
+			NinjaToken::Validation => skip = true,
+			NinjaToken::Colon => return Err("unexpected ':' in build inputs".to_string()),
+		}
+	}
+	Ok(edge)
+}
+
+fn ninja_tokens(line: &str, vars: &HashMap<String, String>) -> Result<Vec<NinjaToken>, String> {
+	let mut tokens = Vec::new();
+	let mut raw = String::new();
+	let mut chars = line.chars().peekable();
+
+	let flush = |raw: &mut String, tokens: &mut Vec<NinjaToken>| {
+		if !raw.is_empty() {
+			tokens.push(NinjaToken::Path(ninja_expand(raw, &|name| vars.get(name).cloned())));
+			raw.clear();
+		}
+	};
+
+	while let Some(c) = chars.next() {
+		match c {
+			'$' => {
+				let Some(next) = chars.next() else {
+					return Err("line ends with '$'".to_string());
+				};
+				raw.push('$');
+				raw.push(next);
+				if next == '{' {
+					for c in chars.by_ref() {
+						raw.push(c);
+						if c == '}' {
+							break;
+						}
+					}
+				}
+			}
+			' ' | '\t' => flush(&mut raw, &mut tokens),
+			':' => {
+				flush(&mut raw, &mut tokens);
+				tokens.push(NinjaToken::Colon);
+			}
+			'|' if raw.is_empty() => {
+				let token = match chars.peek() {
+					Some('|') => {
+						chars.next();
+						NinjaToken::DoublePipe
+					}
+					Some('@') => {
+						chars.next();
+						NinjaToken::Validation
+					}
+					_ => NinjaToken::Pipe,
+				};
+				tokens.push(token);
+			}
+			c => raw.push(c),
+		}
+	}
+	flush(&mut raw, &mut tokens);
+	Ok(tokens)
+}
+
+/// Expand "$var", "${var}" and the "$$", "$ ", "$:" escapes
+fn ninja_expand(value: &str, lookup: &dyn Fn(&str) -> Option<String>) -> String {
+	let mut expanded = String::with_capacity(value.len());
+	let mut chars = value.chars().peekable();
+	while let Some(c) = chars.next() {
+		if c != '$' {
+			expanded.push(c);
+			continue;
+		}
+		match chars.next() {
+			Some('{') => {
+				let name: String = chars.by_ref().take_while(|&c| c != '}').collect();
+				expanded.push_str(&lookup(&name).unwrap_or_default());
+			}
+			Some(c) if c.is_ascii_alphanumeric() || c == '_' || c == '-' => {
+				let mut name = c.to_string();
+				while let Some(&c) = chars.peek()
+					&& (c.is_ascii_alphanumeric() || c == '_' || c == '-')
+				{
+					name.push(c);
+					chars.next();
+				}
+				expanded.push_str(&lookup(&name).unwrap_or_default());
+			}
+			Some(c) => expanded.push(c),
+			None => {}
+		}
+	}
+	expanded
+}
+
+/// Ninja scoping for rule bindings: $in / $out, then the edge's bindings, the rule's own, then file variables
+fn ninja_edge_var(
+	ninja: &NinjaFile,
+	edge: &NinjaEdge,
+	bindings: &HashMap<String, String>,
+	name: &str,
+	depth: usize,
+) -> Option<String> {
+	match name {
+		"in" => return Some(edge.inputs.join(" ")),
+		"in_newline" => return Some(edge.inputs.join("\n")),
+		"out" => return Some(edge.outputs.join(" ")),
+		_ => {}
+	}
+	if let Some(value) = edge.vars.get(name) {
+		return Some(value.clone());
+	}
+	// A binding referring to itself would recurse forever
+	if depth < 16
+		&& let Some(raw) = bindings.get(name)
+	{
+		return Some(ninja_expand(raw, &|inner| {
+			ninja_edge_var(ninja, edge, bindings, inner, depth + 1)
+		}));
+	}
+	ninja.vars.get(name).cloned()
+}
+
+fn resolve_phony(phony: &HashMap<String, Vec<String>>, name: &str, out: &mut Vec<String>, seen: &mut HashSet<String>) {
+	match phony.get(name) {
+		Some(inputs) if seen.insert(name.to_string()) => {
+			for input in inputs {
+				resolve_phony(phony, input, out, seen);
+			}
+		}
+		Some(_) => {}
+		None if !out.iter().any(|existing| existing == name) => out.push(name.to_string()),
+		None => {}
+	}
+}
+
+// ---------------------------------------------------------------------------------------------------------------
+// Make
+
+#[derive(Default)]
+struct Makefile {
+	/// Variable values; those set with "=" are expanded when used, the others when set
+	vars: HashMap<String, MakeVar>,
+	targets: HashMap<String, MakeTarget>,
+	/// Targets in the order they were first seen
+	order: Vec<String>,
+	patterns: Vec<MakePattern>,
+	phony: HashSet<String>,
+	/// What variables the Makefile does not set expand to, in place of make's process environment
+	env: HashMap<String, String>,
+	/// The files being parsed, outermost first, to catch include cycles
+	including: Vec<PathBuf>,
+}
+
+#[derive(Clone)]
+struct MakeVar {
+	value: String,
+	recursive: bool,
+}
+
+#[derive(Default, Clone)]
+struct MakeTarget {
+	prerequisites: Vec<String>,
+	order_only: Vec<String>,
+	/// Recipe lines with their line numbers
+	recipe: Option<Vec<(usize, String)>>,
+	stem: Option<String>,
+}
+
+struct MakePattern {
+	target: String,
+	prerequisites: Vec<String>,
+	order_only: Vec<String>,
+	recipe: Vec<(usize, String)>,
+}
+
+/// Parse a Makefile (following include) into rules
+/// Supports variables, substitution references, common text functions, pattern rules and .PHONY; conditionals,
+/// $(shell) and make's built-in implicit rules are not supported. Variables the Makefile does not set are looked up
+/// in env, never in forge's own environment, so the import does not change with the shell it runs from
+pub fn parse_make(path: &Path, env: &HashMap<String, String>) -> Result<Vec<ImportedRule>, ImportError> {
+	let mut make = Makefile {
+		env: env.clone(),
+		..Default::default()
+	};
+	let dir = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+	parse_make_file(path, &dir, &mut make)?;
+
+	// Apply pattern rules to every file the Makefile mentions that has no recipe of its own
+	let mut pending: Vec<String> = make.order.clone();
+	for target in make.targets.values() {
+		pending.extend(target.prerequisites.iter().cloned());
+	}
+	let mut seen = HashSet::new();
+	while let Some(name) = pending.pop() {
+		if !seen.insert(name.clone())
+			|| make.phony.contains(&name)
+			|| make.targets.get(&name).is_some_and(|target| target.recipe.is_some())
+		{
+			continue;
+		}
+		if let Some(target) = instantiate_pattern(&make, &dir, &name) {
+			pending.extend(target.prerequisites.iter().cloned());
+			let entry = make.targets.entry(name.clone()).or_default();
+			// The pattern's prerequisites come first, so $< is the source the pattern matched
+			let mut prerequisites = target.prerequisites;
+			prerequisites.append(&mut entry.prerequisites);
+			entry.prerequisites = prerequisites;
+			entry.order_only.extend(target.order_only);
+			entry.recipe = target.recipe;
+			entry.stem = target.stem;
+			if !make.order.contains(&name) {
+				make.order.push(name);
+			}
+		}
+	}
+
+	let has_recipe = |name: &str| make.targets.get(name).is_some_and(|target| target.recipe.is_some());
+	let mut rules = Vec::new();
+	for name in &make.order {
+		let target = &make.targets[name];
+		let Some(recipe) = &target.recipe else {
+			continue;
+		};
+
+		let mut inputs = Vec::new();
+		let mut dependencies = Vec::new();
+		for prerequisite in &target.prerequisites {
+			resolve_make_prerequisite(&make, prerequisite, &mut inputs, &mut dependencies, &mut HashSet::new());
+		}
+		for prerequisite in &target.order_only {
+			if has_recipe(prerequisite) && !dependencies.contains(prerequisite) {
+				dependencies.push(prerequisite.clone());
+			}
+		}
+
+		let mut auto = HashMap::new();
+		auto.insert("@".to_string(), name.clone());
+		auto.insert("<".to_string(), target.prerequisites.first().cloned().unwrap_or_default());
+		let mut unique = Vec::new();
+		for prerequisite in &target.prerequisites {
+			if !unique.contains(prerequisite) {
+				unique.push(prerequisite.clone());
+			}
+		}
+		auto.insert("^".to_string(), unique.join(" "));
+		auto.insert("?".to_string(), unique.join(" "));
+		auto.insert("+".to_string(), target.prerequisites.join(" "));
+		auto.insert("|".to_string(), target.order_only.join(" "));
+		auto.insert("*".to_string(), target.stem.clone().unwrap_or_default());
+
+		let mut lines = Vec::new();
+		for (number, line) in recipe {
+			let expanded = make_expand(&make, line, &auto, &dir, 0)
+				.map_err(|message| parse_error(path, *number, format!("recipe of '{}': {}", name, message)))?;
+			// "@" (silent), "+" and "-" (ignore errors) prefix recipe lines
+			let expanded = expanded.trim();
+			let flags_end = expanded.find(|c| !matches!(c, '@' | '+' | '-')).unwrap_or(expanded.len());
+			let (flags, command) = expanded.split_at(flags_end);
+			let command = command.trim();
+			if command.is_empty() {
+				continue;
+			}
+			if flags.contains('-') {
+				lines.push(format!("{{ {}; }} || true", command));
+			} else {
+				lines.push(command.to_string());
+			}
+		}
+
+		rules.push(ImportedRule {
+			name: name.clone(),
+			command: lines.join(" && "),
+			inputs,
+			outputs: if make.phony.contains(name) {
+				Vec::new()
+			} else {
+				vec![name.clone()]
+			},
+			dependencies,
+		});
+	}
+	Ok(rules)
+}
+
+/// Phony targets and targets without a recipe stand for their prerequisites; targets with a recipe that produce
+/// no file (phony) are ordering dependencies instead of inputs
+fn resolve_make_prerequisite(
+	make: &Makefile,
+	name: &str,
+	inputs: &mut Vec<String>,
+	dependencies: &mut Vec<String>,
+	seen: &mut HashSet<String>,
+) {
+	if !seen.insert(name.to_string()) {
+		return;
+	}
+	let target = make.targets.get(name);
+	let phony = make.phony.contains(name);
+	match target {
+		Some(target) if target.recipe.is_some() && phony => {
+			if !dependencies.iter().any(|dependency| dependency == name) {
+				dependencies.push(name.to_string());
+			}
+		}
+		Some(target) if target.recipe.is_none() && (phony || !target.prerequisites.is_empty()) => {
+			for prerequisite in &target.prerequisites {
+				resolve_make_prerequisite(make, prerequisite, inputs, dependencies, seen);
+			}
+		}
+		_ if phony => {}
+		_ => {
+			if !inputs.iter().any(|input| input == name) {
+				inputs.push(name.to_string());
+			}
+		}
+	}
+}
+
+fn instantiate_pattern(make: &Makefile, dir: &Path, name: &str) -> Option<MakeTarget> {
+	for pattern in &make.patterns {
+		let Some(stem) = match_pattern(&pattern.target, name) else {
+			continue;
+		};
+		let prerequisites: Vec<String> = pattern
+			.prerequisites
+			.iter()
+			.map(|prerequisite| prerequisite.replacen('%', stem, 1))
+			.collect();
+		// Like make, a pattern only applies when its prerequisites exist or can be made
+		let available = prerequisites.iter().all(|prerequisite| {
+			dir.join(prerequisite).exists()
+				|| make.targets.contains_key(prerequisite)
+				|| make
+					.patterns
+					.iter()
+					.any(|other| match_pattern(&other.target, prerequisite).is_some())
+		});
+		if !available {
+			continue;
+		}
+		return Some(MakeTarget {
+			prerequisites,
+			order_only: pattern
+				.order_only
+				.iter()
+				.map(|prerequisite| prerequisite.replacen('%', stem, 1))
+				.collect(),
+			recipe: Some(pattern.recipe.clone()),
+			stem: Some(stem.to_string()),
+		});
+	}
+	None
+}
+
+/// The part of name matched by "%" in pattern
+fn match_pattern<'a>(pattern: &str, name: &'a str) -> Option<&'a str> {
+	let (prefix, suffix) = pattern.split_once('%')?;
+	(name.len() >= prefix.len() + suffix.len() && name.starts_with(prefix) && name.ends_with(suffix))
+		.then(|| &name[prefix.len()..name.len() - suffix.len()])
+}
+
+fn parse_make_file(path: &Path, dir: &Path, make: &mut Makefile) -> Result<(), ImportError> {
+	let content = read(path)?;
+	make.including
+		.push(path.canonicalize().unwrap_or_else(|_| path.to_path_buf()));
+	let lines = logical_lines(&content, " ", |line| {
+		let backslashes = line.len() - line.trim_end_matches('\\').len();
+		(backslashes % 2 == 1).then(|| line[..line.len() - 1].trim_end())
+	});
+	let no_auto = HashMap::new();
+	let expand = |make: &Makefile, text: &str, number: usize| {
+		make_expand(make, text, &no_auto, dir, 0).map_err(|message| parse_error(path, number, message))
+	};
+
+	// Targets whose recipe the following tab-indented lines belong to
+	let mut recipe_targets: Vec<String> = Vec::new();
+	let mut recipe_pattern: Option<usize> = None;
+
+	for (number, line) in lines {
+		if let Some(command) = line.strip_prefix('\t')
+			&& (!recipe_targets.is_empty() || recipe_pattern.is_some())
+		{
+			if let Some(index) = recipe_pattern {
+				make.patterns[index].recipe.push((number, command.to_string()));
+			}
+			for target in &recipe_targets {
+				make.targets
+					.get_mut(target)
+					.unwrap()
+					.recipe
+					.get_or_insert_with(Vec::new)
+					.push((number, command.to_string()));
+			}
+			continue;
+		}
+
+		let line = strip_make_comment(&line);
+		let trimmed = line.trim();
+		if trimmed.is_empty() {
+			continue;
+		}
+		recipe_targets.clear();
+		recipe_pattern = None;
+
+		let (keyword, rest) = trimmed.split_once([' ', '\t']).unwrap_or((trimmed, ""));
+		match keyword {
+			"include" | "-include" | "sinclude" => {
+				for file in expand(make, rest, number)?.split_whitespace() {
+					let file = dir.join(file);
+					if keyword == "include" || file.exists() {
+						check_include(&make.including, path, number, &file)?;
+						parse_make_file(&file, dir, make)?;
+					}
+				}
+				continue;
+			}
+			"ifeq" | "ifneq" | "ifdef" | "ifndef" | "else" | "endif" | "define" | "endef" => {
+				return Err(parse_error(path, number, format!("'{}' is not supported", keyword)));
+			}
+			"vpath" | "unexport" => continue,
+			_ => {}
+		}
+		let statement = trimmed.strip_prefix("export ").unwrap_or(trimmed).trim();
+
+		if let Some((name, op, value)) = split_make_assignment(statement) {
+			let name = expand(make, name, number)?;
+			let value = value.trim().to_string();
+			match op {
+				"=" => {
+					make.vars.insert(name, MakeVar { value, recursive: true });
+				}
+				":=" | "::=" => {
+					let value = expand(make, &value, number)?;
+					make.vars.insert(name, MakeVar { value, recursive: false });
+				}
+				"?=" => {
+					make.vars.entry(name).or_insert(MakeVar { value, recursive: true });
+				}
+				"+=" => {
+					// Appending keeps the flavor of the variable: simple ones expand the new text right away
+					let value = match make.vars.get(&name) {
+						Some(var) if !var.recursive => expand(make, &value, number)?,
+						_ => value,
+					};
+					let var = make.vars.entry(name).or_insert(MakeVar {
+						value: String::new(),
+						recursive: true,
+					});
+					if !var.value.is_empty() {
+						var.value.push(' ');
+					}
+					var.value.push_str(&value);
+				}
+				_ => return Err(parse_error(path, number, format!("'{}' assignments are not supported", op))),
+			}
+			continue;
+		}
+
+		let Some((targets, prerequisites)) = split_make_rule(statement) else {
+			return Err(parse_error(path, number, "expected a rule or variable assignment"));
+		};
+		let (prerequisites, inline_recipe) = match prerequisites.split_once(';') {
+			Some((prerequisites, recipe)) => (prerequisites, Some((number, recipe.trim().to_string()))),
+			None => (prerequisites, None),
+		};
+		if split_make_assignment(prerequisites.trim()).is_some() {
+			log::warn!("{}:{}: ignoring target-specific variable", path.display(), number);
+			continue;
+		}
+
+		let targets = expand(make, targets, number)?;
+		let prerequisites = expand(make, prerequisites, number)?;
+		let (normal, order_only) = prerequisites.split_once('|').unwrap_or((prerequisites.as_str(), ""));
+		let normal: Vec<String> = normal.split_whitespace().map(str::to_string).collect();
+		let order_only: Vec<String> = order_only.split_whitespace().map(str::to_string).collect();
+
+		for target in targets.split_whitespace() {
+			if target == ".PHONY" {
+				make.phony.extend(normal.iter().cloned());
+				continue;
+			}
+			// Other special targets (.SUFFIXES, .DEFAULT_GOAL, ...) do not describe build steps
+			if target.starts_with('.') && target.chars().nth(1).is_some_and(|c| c.is_ascii_uppercase()) {
+				continue;
+			}
+
+			if target.contains('%') {
+				make.patterns.push(MakePattern {
+					target: target.to_string(),
+					prerequisites: normal.clone(),
+					order_only: order_only.clone(),
+					recipe: inline_recipe.iter().cloned().collect(),
+				});
+				recipe_pattern = Some(make.patterns.len() - 1);
+				continue;
+			}
+
+			if !make.targets.contains_key(target) {
+				make.order.push(target.to_string());
+			}
+			let entry = make.targets.entry(target.to_string()).or_default();
+			entry.prerequisites.extend(normal.iter().cloned());
+			entry.order_only.extend(order_only.iter().cloned());
+			if let Some(recipe) = &inline_recipe {
+				entry.recipe = Some(vec![recipe.clone()]);
+			} else if entry.recipe.is_some() {
+				// A later rule for the same target only adds prerequisites, unless it brings its own recipe
+				continue;
+			}
+			recipe_targets.push(target.to_string());
+		}
+	}
+	make.including.pop();
+	Ok(())
+}
+
+/// Drop an unescaped "#" comment (recipes are handled before this, "#" there belongs to the shell)
+fn strip_make_comment(line: &str) -> String {
+	let mut out = String::with_capacity(line.len());
+	let mut chars = line.chars();
+	while let Some(c) = chars.next() {
+		match c {
+			'\\' => match chars.next() {
+				Some('#') => out.push('#'),
+				Some(next) => {
+					out.push('\\');
+					out.push(next);
+				}
+				None => out.push('\\'),
+			},
+			'#' => break,
+			c => out.push(c),
+		}
+	}
+	out
+}
+
+/// Split "NAME op value" for the assignment operators, if the line is an assignment rather than a rule
+fn split_make_assignment(line: &str) -> Option<(&str, &'static str, &str)> {
+	let mut depth = 0usize;
+	for (index, c) in line.char_indices() {
+		match c {
+			'(' | '{' => depth += 1,
+			')' | '}' => depth = depth.saturating_sub(1),
+			'=' if depth == 0 => {
+				let before = &line[..index];
+				for op in ["::", ":", "?", "+", "!"] {
+					if let Some(name) = before.strip_suffix(op) {
+						let op = match op {
+							"::" => "::=",
+							":" => ":=",
+							"?" => "?=",
+							"+" => "+=",
+							_ => "!=",
+						};
+						return Some((name.trim(), op, &line[index + 1..]));
+					}
+				}
+				return Some((before.trim(), "=", &line[index + 1..]));
+			}
+			// A ":" outside of a reference before any "=" makes this a rule (possibly with a target-specific variable)
+			':' if depth == 0 && !line[index..].starts_with(":=") && !line[index..].starts_with("::=") => return None,
+			_ => {}
+		}
+	}
+	None
+}
+
+/// Split "targets: prerequisites" (also "::"), ignoring colons inside variable references
+fn split_make_rule(line: &str) -> Option<(&str, &str)> {
+	let mut depth = 0usize;
+	for (index, c) in line.char_indices() {
+		match c {
+			'(' | '{' => depth += 1,
+			')' | '}' => depth = depth.saturating_sub(1),
+			':' if depth == 0 => {
+				let rest = &line[index + 1..];
+				return Some((&line[..index], rest.strip_prefix(':').unwrap_or(rest)));
+			}
+			_ => {}
+		}
+	}
+	None
+}
+
+/// Expand variable references, substitution references and functions; auto holds the automatic variables
+fn make_expand(
+	make: &Makefile,
+	text: &str,
+	auto: &HashMap<String, String>,
+	dir: &Path,
+	depth: usize,
+) -> Result<String, String> {
+	if depth > 32 {
+		return Err("variable expansion is too deep (recursive variable?)".to_string());
+	}
+
+	let mut out = String::with_capacity(text.len());
+	let mut chars = text.char_indices().peekable();
+	while let Some((index, c)) = chars.next() {
+		if c != '$' {
+			out.push(c);
+			continue;
+		}
+		let Some((_, next)) = chars.next() else {
+			break;
+		};
+		let reference = match next {
+			'$' => {
+				out.push('$');
+				continue;
+			}
+			'(' | '{' => {
+				let close = if next == '(' { ')' } else { '}' };
+				let start = index + 2;
+				let mut level = 1;
+				let mut end = None;
+				for (position, c) in chars.by_ref() {
+					if c == next {
+						level += 1;
+					} else if c == close {
+						level -= 1;
+						if level == 0 {
+							end = Some(position);
+							break;
+						}
+					}
+				}
+				let end = end.ok_or_else(|| format!("unterminated reference in '{}'", text))?;
+				&text[start..end]
+			}
+			c => &text[index + 1..index + 1 + c.len_utf8()],
+		};
+		out.push_str(&make_reference(make, reference, auto, dir, depth)?);
+	}
+	Ok(out)
+}
+
+fn make_reference(
+	make: &Makefile,
+	reference: &str,
+	auto: &HashMap<String, String>,
+	dir: &Path,
+	depth: usize,
+) -> Result<String, String> {
+	let expand = |text: &str| make_expand(make, text, auto, dir, depth + 1);
+
+	if let Some((function, args)) = reference.split_once([' ', '\t']) {
+		let args = split_make_args(args);
+		let arg = |index: usize| -> Result<String, String> {
+			args.get(index)
+				.map(|arg| expand(arg))
+				.unwrap_or_else(|| Err(format!("missing argument {} to '{}'", index + 1, function)))
+		};
+		let words = |text: String| text.split_whitespace().map(str::to_string).collect::<Vec<_>>();
+		return Ok(match function {
+			"subst" => arg(2)?.replace(&arg(0)?, &arg(1)?),
+			"patsubst" => {
+				let (from, to) = (arg(0)?, arg(1)?);
+				words(arg(2)?)
+					.iter()
+					.map(|word| substitute_pattern(word, &from, &to))
+					.collect::<Vec<_>>()
+					.join(" ")
+			}
+			"addprefix" => {
+				let prefix = arg(0)?;
+				words(arg(1)?)
+					.iter()
+					.map(|word| format!("{}{}", prefix, word))
+					.collect::<Vec<_>>()
+					.join(" ")
+			}
+			"addsuffix" => {
+				let suffix = arg(0)?;
+				words(arg(1)?)
+					.iter()
+					.map(|word| format!("{}{}", word, suffix))
+					.collect::<Vec<_>>()
+					.join(" ")
+			}
+			"notdir" => words(arg(0)?)
+				.iter()
+				.map(|word| word.rsplit_once('/').map_or(word.as_str(), |(_, name)| name).to_string())
+				.collect::<Vec<_>>()
+				.join(" "),
+			"dir" => words(arg(0)?)
+				.iter()
+				.map(|word| word.rsplit_once('/').map_or("./".to_string(), |(dir, _)| format!("{}/", dir)))
+				.collect::<Vec<_>>()
+				.join(" "),
+			"basename" => words(arg(0)?)
+				.iter()
+				.map(|word| match word.rfind('.') {
+					Some(dot) if !word[dot..].contains('/') => word[..dot].to_string(),
+					_ => word.clone(),
+				})
+				.collect::<Vec<_>>()
+				.join(" "),
+			"filter" | "filter-out" => {
+				let patterns = words(arg(0)?);
+				let keep = function == "filter";
+				words(arg(1)?)
+					.into_iter()
+					.filter(|word| {
+						patterns
+							.iter()
+							.any(|pattern| pattern == word || match_pattern(pattern, word).is_some())
+							== keep
+					})
+					.collect::<Vec<_>>()
+					.join(" ")
+			}
+			"strip" => words(arg(0)?).join(" "),
+			"wildcard" => {
+				let mut matches = Vec::new();
+				for pattern in words(arg(0)?) {
+					let full = dir.join(&pattern).to_string_lossy().to_string();
+					let Ok(paths) = glob::glob(&full) else {
+						return Err(format!("invalid wildcard pattern '{}'", pattern));
+					};
+					for path in paths.flatten() {
+						let relative = path.strip_prefix(dir).unwrap_or(&path);
+						matches.push(relative.to_string_lossy().to_string());
+					}
+				}
+				matches.join(" ")
+			}
+			function => return Err(format!("make function '{}' is not supported", function)),
+		});
+	}
+
+	// Substitution reference: $(VAR:from=to)
+	if let Some((name, substitution)) = reference.split_once(':')
+		&& let Some((from, to)) = substitution.split_once('=')
+	{
+		let value = make_reference(make, name, auto, dir, depth)?;
+		let (from, to) = (expand(from)?, expand(to)?);
+		let (from, to) = if from.contains('%') {
+			(from, to)
+		} else {
+			(format!("%{}", from), format!("%{}", to))
+		};
+		return Ok(value
+			.split_whitespace()
+			.map(|word| substitute_pattern(word, &from, &to))
+			.collect::<Vec<_>>()
+			.join(" "));
+	}
+
+	let name = expand(reference)?;
+	// $(@D) / $(@F) and friends: directory or file part of an automatic variable
+	if let Some(base) = name.strip_suffix(['D', 'F'])
+		&& let Some(value) = auto.get(base)
+	{
+		let directory = name.ends_with('D');
+		let part = |word: &str| match (word.rsplit_once('/'), directory) {
+			(Some((dir, _)), true) => dir.to_string(),
+			(Some((_, file)), false) => file.to_string(),
+			(None, true) => ".".to_string(),
+			(None, false) => word.to_string(),
+		};
+		return Ok(value.split_whitespace().map(part).collect::<Vec<_>>().join(" "));
+	}
+	if let Some(value) = auto.get(&name) {
+		return Ok(value.clone());
+	}
+	match make.vars.get(&name) {
+		Some(var) if var.recursive => expand(&var.value),
+		Some(var) => Ok(var.value.clone()),
+		None => Ok(make.env.get(&name).cloned().unwrap_or_default()),
+	}
+}
+
+/// Split function arguments on top-level commas
+fn split_make_args(args: &str) -> Vec<&str> {
+	let mut parts = Vec::new();
+	let mut depth = 0usize;
+	let mut start = 0;
+	for (index, c) in args.char_indices() {
+		match c {
+			'(' | '{' => depth += 1,
+			')' | '}' => depth = depth.saturating_sub(1),
+			',' if depth == 0 => {
+				parts.push(&args[start..index]);
+				start = index + 1;
+			}
+			_ => {}
+		}
+	}
+	parts.push(&args[start..]);
+	parts
+}
+
+fn substitute_pattern(word: &str, from: &str, to: &str) -> String {
+	match match_pattern(from, word) {
+		Some(stem) => to.replacen('%', stem, 1),
+		None if !from.contains('%') && word == from => to.to_string(),
+		None => word.to_string(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn write_temp(name: &str, content: &str) -> PathBuf {
+		let dir = std::env::temp_dir().join(format!("forge-import-test-{}-{}", name, std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+		std::fs::create_dir_all(&dir).unwrap();
+		let path = dir.join(name);
+		std::fs::write(&path, content).unwrap();
+		path
+	}
+
+	#[test]
+	fn test_parse_ninja() {
+		let path = write_temp(
+			"build.ninja",
+			"cflags = -O2\n\
+			rule cc\n  command = cc $cflags -c $in -o $out\n\
+			rule link\n  command = cc $in -o $out $libs\n\
+			build gen.h: cc gen.in\n\
+			build main.o: cc main.c | common.h || gen.h\n  cflags = -O0\n\
+			build app: link main.o\n  libs = -lm\n\
+			build all: phony app\n",
+		);
+		let rules = parse_ninja(&path).unwrap();
+		std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+		assert_eq!(rules.len(), 3);
+		assert_eq!(rules[1].command, "cc -O0 -c main.c -o main.o");
+		assert_eq!(rules[1].inputs, vec!["main.c", "common.h"]);
+		assert_eq!(rules[1].dependencies, vec!["gen.h"]);
+		assert_eq!(rules[2].command, "cc main.o -o app -lm");
+	}
+
+	#[test]
+	fn test_parse_make() {
+		let path = write_temp(
+			"Makefile",
+			"CC = cc\n\
+			OBJS := main.o util.o\n\
+			CFLAGS = -Wall\n\
+			CFLAGS += -O2\n\
+			.PHONY: all clean\n\
+			all: app\n\
+			app: $(OBJS)\n\t$(CC) $^ -o $@\n\
+			%.o: %.c\n\t@$(CC) $(CFLAGS) -c $< -o $@\n\
+			clean:\n\t-rm -f app $(OBJS:.o=.d)\n",
+		);
+		let dir = path.parent().unwrap().to_path_buf();
+		std::fs::write(dir.join("main.c"), "").unwrap();
+		std::fs::write(dir.join("util.c"), "").unwrap();
+		let rules = parse_make(&path, &HashMap::new()).unwrap();
+		std::fs::remove_dir_all(&dir).unwrap();
+
+		let rule = |name: &str| rules.iter().find(|rule| rule.name == name).unwrap();
+		assert_eq!(rule("app").command, "cc main.o util.o -o app");
+		assert_eq!(rule("app").inputs, vec!["main.o", "util.o"]);
+		assert_eq!(rule("main.o").command, "cc -Wall -O2 -c main.c -o main.o");
+		assert_eq!(rule("clean").outputs, Vec::<String>::new());
+		assert_eq!(rule("clean").command, "{ rm -f app main.d util.d; } || true");
+		assert!(rules.iter().all(|rule| rule.name != "all"));
+	}
+
+	#[test]
+	fn test_ninja_edge_cases() {
+		let path = write_temp(
+			"build.ninja",
+			"extra = -Wall\n\
+			include rules.ninja\n\
+			subninja sub.ninja\n\
+			build out/a.o | out/a.d: cc src/a.c $\n    src/b.c || stamp |@ lint\n  flags = -g\n\
+			build b.o: cc b.c || out/a.d\n\
+			build stamp: touch\n\
+			build lint: touch\n\
+			build dollar$ name$:x: cc weird$$.c\n\
+			build gen: phony stamp\n\
+			build all: phony gen out/a.o\n\
+			build app: link out/a.o all\n\
+			build looped: loop\n",
+		);
+		let dir = path.parent().unwrap().to_path_buf();
+		std::fs::write(
+			dir.join("rules.ninja"),
+			"rule cc\n  command = cc $flags -c $in -o $out\n  flags = -O2 $extra\n\
+			rule touch\n  command = touch $out\n\
+			rule link\n  command = ld $in_newline > $out\n\
+			rule loop\n  command = $command x\n",
+		)
+		.unwrap();
+		std::fs::write(dir.join("sub.ninja"), "build sub.txt: touch\n").unwrap();
+		let rules = parse_ninja(&path).unwrap();
+
+		let rule = |name: &str| rules.iter().find(|rule| rule.name == name).unwrap();
+		assert_eq!(rules.len(), 8);
+		assert_eq!(rule("sub.txt").command, "touch sub.txt");
+		assert_eq!(rule("out/a.o").command, "cc -g -c src/a.c src/b.c -o out/a.o");
+		assert_eq!(rule("out/a.o").inputs, vec!["src/a.c", "src/b.c"]);
+		assert_eq!(rule("out/a.o").outputs, vec!["out/a.o", "out/a.d"]);
+		assert_eq!(rule("out/a.o").dependencies, vec!["stamp"]);
+		assert_eq!(rule("b.o").dependencies, vec!["out/a.o"]);
+		assert_eq!(rule("dollar name:x").command, "cc -O2 -Wall -c weird$.c -o dollar name:x");
+		assert_eq!(rule("app").command, "ld out/a.o\nall > app");
+		assert_eq!(rule("app").inputs, vec!["out/a.o", "stamp"]);
+		assert!(rule("looped").command.ends_with(" x x"));
+
+		std::fs::write(&path, "build x: nope\n").unwrap();
+		assert!(matches!(
+			parse_ninja(&path),
+			Err(ImportError::Parse { line: 1, message, .. }) if message == "unknown rule 'nope'"
+		));
+		std::fs::write(&path, "rule cc\n  command = cc\nbuild x cc\n").unwrap();
+		assert!(matches!(parse_ninja(&path), Err(ImportError::Parse { line: 3, .. })));
+		std::fs::write(&path, "subninja build.ninja\n").unwrap();
+		assert!(matches!(
+			parse_ninja(&path),
+			Err(ImportError::Parse { line: 1, message, .. }) if message.ends_with("build.ninja includes itself")
+		));
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn test_make_variables() {
+		let path = write_temp(
+			"Makefile",
+			"OUT = build\n\
+			SRCS := a.c b.c\n\
+			OBJS = $(SRCS:%.c=$(OUT)/%.o)\n\
+			FLAGS ?= -O1\n\
+			FLAGS ?= -O3\n\
+			LATE = $(VALUE)\n\
+			NOW := $(VALUE)\n\
+			VALUE = set\n\
+			WORDS := $(patsubst %.c,%.s,$(SRCS)) $(addprefix -I,inc src) $(notdir src/x.c) $(dir src/x.c y.c) \
+			$(basename a.tar.gz d.x/y)\n\
+			KEPT := $(filter %.c,$(SRCS) x.h) $(filter-out a.c,$(SRCS))\n\
+			.PHONY: show\n\
+			show: ; echo $(OBJS) $(FLAGS) [$(LATE)] [$(NOW)] $(WORDS) $(KEPT) $(subst a,A,abc) [$(FROM_ENV)] [$(HOME)]\n\
+			$(OUT)/app: $(OBJS)\n\tcp $< $(@D)/$(@F).tmp && echo $$HOME\n",
+		);
+		let env = HashMap::from([("FROM_ENV".to_string(), "from-env".to_string())]);
+		let rules = parse_make(&path, &env).unwrap();
+		std::fs::remove_dir_all(path.parent().unwrap()).unwrap();
+
+		let rule = |name: &str| rules.iter().find(|rule| rule.name == name).unwrap();
+		// HOME is set for forge but not passed in env, so it expands to nothing
+		assert_eq!(
+			rule("show").command,
+			"echo build/a.o build/b.o -O1 [set] [] a.s b.s -Iinc -Isrc x.c src/ ./ a.tar d.x/y a.c b.c b.c Abc \
+			[from-env] []"
+		);
+		assert_eq!(rule("build/app").command, "cp build/a.o build/app.tmp && echo $HOME");
+		assert_eq!(rule("build/app").inputs, vec!["build/a.o", "build/b.o"]);
+	}
+
+	#[test]
+	fn test_make_pattern_rules_and_includes() {
+		let path = write_temp(
+			"Makefile",
+			"include rules.mk\n\
+			-include missing.mk\n\
+			all: app\n\
+			app: main.o gen.o | out\n\tld -o $@ $^\n\
+			main.o: config.h\n\
+			out:\n\tmkdir -p out\n",
+		);
+		let dir = path.parent().unwrap().to_path_buf();
+		std::fs::write(
+			dir.join("rules.mk"),
+			"%.o: %.c\n\tcc -c $< -o $@ -DSTEM=$*\n%.c: %.y\n\tyacc $< -o $@\n",
+		)
+		.unwrap();
+		std::fs::write(dir.join("main.c"), "").unwrap();
+		std::fs::write(dir.join("gen.y"), "").unwrap();
+		let rules = parse_make(&path, &HashMap::new()).unwrap();
+
+		let rule = |name: &str| rules.iter().find(|rule| rule.name == name).unwrap();
+		assert_eq!(rules.len(), 5);
+		assert_eq!(rule("app").command, "ld -o app main.o gen.o");
+		assert_eq!(rule("app").inputs, vec!["main.o", "gen.o"]);
+		assert_eq!(rule("app").dependencies, vec!["out"]);
+		assert_eq!(rule("main.o").command, "cc -c main.c -o main.o -DSTEM=main");
+		assert_eq!(rule("main.o").inputs, vec!["main.c", "config.h"]);
+		// gen.c does not exist, but the second pattern makes it from gen.y; main.c exists and has no main.y
+		assert_eq!(rule("gen.o").inputs, vec!["gen.c"]);
+		assert_eq!(rule("gen.c").command, "yacc gen.y -o gen.c");
+		assert_eq!(rule("out").outputs, vec!["out"]);
+
+		std::fs::write(dir.join("loop.mk"), "include Makefile\n").unwrap();
+		std::fs::write(&path, "include loop.mk\n").unwrap();
+		assert!(matches!(
+			parse_make(&path, &HashMap::new()),
+			Err(ImportError::Parse { line: 1, message, .. }) if message.ends_with("Makefile includes itself")
+		));
+		std::fs::write(&path, "include nowhere.mk\n").unwrap();
+		assert!(matches!(parse_make(&path, &HashMap::new()), Err(ImportError::Io { .. })));
+		std::fs::write(&path, "A = 1\nifeq ($(A),1)\nendif\n").unwrap();
+		assert!(matches!(
+			parse_make(&path, &HashMap::new()),
+			Err(ImportError::Parse { line: 2, .. })
+		));
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+
+	#[test]
+	fn test_rebase() {
+		let mut rules = vec![ImportedRule {
+			name: "app".to_string(),
+			command: "true".to_string(),
+			inputs: vec![
+				"./main.o".to_string(),
+				"../shared/lib.a".to_string(),
+				"/usr/lib/libm.a".to_string(),
+			],
+			outputs: vec!["app".to_string()],
+			dependencies: Vec::new(),
+		}];
+		rebase(&mut rules, Path::new("legacy/tool"));
+		assert_eq!(rules[0].name, "legacy/tool/app");
+		assert_eq!(
+			rules[0].inputs,
+			vec!["legacy/tool/main.o", "legacy/shared/lib.a", "/usr/lib/libm.a"]
+		);
+	}
+}
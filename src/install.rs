@@ -0,0 +1,165 @@
+use crate::{project::Rule, provenance};
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path};
+use walkdir::WalkDir;
+
+/// Written at the root of every forge package, next to the installed files
+pub const PACKAGE_MANIFEST_NAME: &str = "forge-package.json";
+
+/// Archive formats forge package writes
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum PackageFormat {
+	#[value(name = "tar.gz")]
+	TarGz,
+	Zip,
+}
+
+impl PackageFormat {
+	/// Extension of the archive, also its format for archive::write_archive
+	pub fn extension(self) -> &'static str {
+		match self {
+			PackageFormat::TarGz => "tar.gz",
+			PackageFormat::Zip => "zip",
+		}
+	}
+}
+
+/// What a forge package holds
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PackageManifest {
+	pub name: String,
+	pub version: String,
+	/// Sorted by path
+	pub files: Vec<InstalledFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InstalledFile {
+	/// Relative to the prefix, with "/" separators
+	pub path: String,
+	/// Relative to the project root
+	pub source: String,
+	pub blake3: String,
+	pub size: u64,
+}
+
+/// Whether dest stays inside the prefix it is installed under
+pub fn is_valid_dest(dest: &str) -> bool {
+	!dest.is_empty()
+		&& Path::new(dest)
+			.components()
+			.all(|component| matches!(component, Component::Normal(_)))
+}
+
+/// Copy the inputs of every forge.install rule under prefix
+/// A single file installed to a dest not ending in "/" is copied to dest itself, like cp; several sources, or a dest
+/// ending in "/", go into the dest directory under their own names, and directories are copied with their contents
+pub fn install(project_root: &Path, installs: &[Rule], prefix: &Path) -> anyhow::Result<Vec<InstalledFile>> {
+	let mut installed = Vec::new();
+	for rule in installs {
+		let Some(dest) = rule.install.as_deref() else {
+			continue;
+		};
+		let into_directory = dest.ends_with('/') || rule.inputs.len() > 1;
+
+		for src in &rule.inputs {
+			let source = project_root.join(src);
+			if !source.exists() {
+				return Err(anyhow::anyhow!(
+					"'{}' installed by '{}' does not exist, was it built?",
+					src,
+					rule.name
+				));
+			}
+			let target = if into_directory {
+				let name = source
+					.file_name()
+					.ok_or_else(|| anyhow::anyhow!("cannot install '{}' into a directory", src))?;
+				prefix.join(dest).join(name)
+			} else {
+				prefix.join(dest)
+			};
+
+			for entry in WalkDir::new(&source).follow_links(true) {
+				let entry = entry?;
+				if !entry.file_type().is_file() {
+					continue;
+				}
+				let relative = entry.path().strip_prefix(&source)?;
+				let to = if relative.as_os_str().is_empty() {
+					target.clone()
+				} else {
+					target.join(relative)
+				};
+				if let Some(parent) = to.parent() {
+					std::fs::create_dir_all(parent)?;
+				}
+				std::fs::copy(entry.path(), &to).map_err(|e| {
+					anyhow::anyhow!("Failed to install {} to {}: {}", entry.path().display(), to.display(), e)
+				})?;
+
+				let (blake3, size) = provenance::hash_file(&to)?;
+				installed.push(InstalledFile {
+					path: slash_path(to.strip_prefix(prefix)?),
+					source: slash_path(entry.path().strip_prefix(project_root).unwrap_or(entry.path())),
+					blake3,
+					size,
+				});
+			}
+		}
+	}
+	installed.sort_by(|a, b| a.path.cmp(&b.path));
+	Ok(installed)
+}
+
+fn slash_path(path: &Path) -> String {
+	path.components()
+		.map(|component| component.as_os_str().to_string_lossy())
+		.collect::<Vec<_>>()
+		.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::path::PathBuf;
+
+	fn install_rule(dest: &str, inputs: &[&str]) -> Rule {
+		Rule {
+			name: format!("install:{}", dest),
+			inputs: inputs.iter().map(|s| s.to_string()).collect(),
+			workdir: PathBuf::from("/project"),
+			install: Some(dest.to_string()),
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn test_install_layout() {
+		let dir = std::env::temp_dir().join(format!("forge-install-test-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+		let project = dir.join("project");
+		std::fs::create_dir_all(project.join("build/include/math")).unwrap();
+		std::fs::write(project.join("build/app"), "app").unwrap();
+		std::fs::write(project.join("build/libmath.a"), "lib").unwrap();
+		std::fs::write(project.join("build/include/math/math.h"), "h").unwrap();
+
+		let installs = [
+			install_rule("bin/demo", &["build/app"]),
+			install_rule("lib/", &["build/libmath.a"]),
+			install_rule("include", &["build/include/math", "build/app"]),
+		];
+		let installed = install(&project, &installs, &dir.join("prefix")).unwrap();
+
+		let paths: Vec<&str> = installed.iter().map(|file| file.path.as_str()).collect();
+		assert_eq!(paths, ["bin/demo", "include/app", "include/math/math.h", "lib/libmath.a"]);
+		assert_eq!(installed[2].source, "build/include/math/math.h");
+		assert_eq!(std::fs::read_to_string(dir.join("prefix/bin/demo")).unwrap(), "app");
+
+		assert!(install(&project, &[install_rule("bin/", &["build/missing"])], &dir.join("prefix")).is_err());
+		assert!(is_valid_dest("share/doc/"));
+		assert!(!is_valid_dest("../etc"));
+		assert!(!is_valid_dest("/usr/bin"));
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
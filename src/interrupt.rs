@@ -0,0 +1,133 @@
+use std::{
+	collections::HashSet,
+	process::{Command, Output},
+	sync::{
+		Mutex,
+		atomic::{AtomicBool, Ordering},
+	},
+};
+
+/// Exit code of a build stopped by Ctrl-C, 128 + SIGINT as shells report it
+pub const EXIT_CODE: i32 = 130;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Set while a child owns the terminal and handles Ctrl-C itself, such as the shell of forge replay --shell
+static DEFERRED: AtomicBool = AtomicBool::new(false);
+
+/// Saves what the current build finished, run before a second Ctrl-C exits
+static EXIT_HOOK: Mutex<Option<Box<dyn Fn() + Send>>> = Mutex::new(None);
+
+/// Rule commands and persistent workers running now, each leading its own process group on Unix, by pid
+static RUNNING: Mutex<Option<HashSet<u32>>> = Mutex::new(None);
+
+/// Listen for Ctrl-C: the first stops dispatching rules and kills the running ones so the build can end cleanly,
+/// a second exits without waiting for them, after running the exit hook
+pub fn install() {
+	std::thread::spawn(|| {
+		let runtime = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+			Ok(runtime) => runtime,
+			Err(e) => {
+				log::warn!("Ctrl-C will not stop builds cleanly: {}", e);
+				return;
+			}
+		};
+		runtime.block_on(async {
+			while tokio::signal::ctrl_c().await.is_ok() {
+				if DEFERRED.load(Ordering::SeqCst) {
+					continue;
+				}
+				if INTERRUPTED.swap(true, Ordering::SeqCst) {
+					eprintln!("Interrupted again, exiting without waiting for running rules");
+					if let Some(hook) = EXIT_HOOK.lock().unwrap().as_ref() {
+						hook();
+					}
+					std::process::exit(EXIT_CODE);
+				}
+				eprintln!("\nInterrupted, stopping running rules (press Ctrl-C again to exit immediately)");
+				let running: Vec<u32> = RUNNING.lock().unwrap().iter().flatten().copied().collect();
+				for pid in running {
+					kill(pid);
+				}
+			}
+		});
+	});
+}
+
+/// Replace what runs before a second Ctrl-C exits, None to run nothing
+pub fn set_exit_hook(hook: Option<Box<dyn Fn() + Send>>) {
+	*EXIT_HOOK.lock().unwrap() = hook;
+}
+
+/// Leave Ctrl-C to the child forge waits on in the foreground, which gets it from the terminal as well
+pub fn defer_to_child() {
+	DEFERRED.store(true, Ordering::SeqCst);
+}
+
+/// Whether Ctrl-C was pressed
+pub fn interrupted() -> bool {
+	INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Run command to completion like Command::output, in its own process group so Ctrl-C can kill it along with
+/// everything it started
+pub fn output(command: &mut Command) -> std::io::Result<Output> {
+	#[cfg(unix)]
+	{
+		use std::os::unix::process::CommandExt;
+		command.process_group(0);
+	}
+	let child = command
+		.stdin(std::process::Stdio::null())
+		.stdout(std::process::Stdio::piped())
+		.stderr(std::process::Stdio::piped())
+		.spawn()?;
+	let pid = child.id();
+	track(pid);
+	let output = child.wait_with_output();
+	untrack(pid);
+	output
+}
+
+/// Have Ctrl-C kill the process group led by pid, for children forge keeps running instead of waiting on
+pub fn track(pid: u32) {
+	RUNNING.lock().unwrap().get_or_insert_default().insert(pid);
+	// Ctrl-C may have come between spawning and registering the child
+	if interrupted() {
+		kill(pid);
+	}
+}
+
+pub fn untrack(pid: u32) {
+	if let Some(running) = RUNNING.lock().unwrap().as_mut() {
+		running.remove(&pid);
+	}
+}
+
+fn kill(pid: u32) {
+	// SAFETY: kill has no memory safety requirements, a negative pid addresses the process group
+	#[cfg(unix)]
+	unsafe {
+		libc::kill(-(pid as i32), libc::SIGTERM);
+	}
+
+	#[cfg(not(unix))]
+	{
+		let _ = Command::new("taskkill").args(["/T", "/F", "/PID", &pid.to_string()]).output();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[cfg(unix)]
+	#[test]
+	fn test_output_tracks_running_commands() {
+		// Other tests start tracked processes too, so only the pid of this command, which sh prints, is checked
+		let output = output(Command::new("sh").args(["-c", "echo $$"])).unwrap();
+		assert!(output.status.success());
+		let pid: u32 = String::from_utf8(output.stdout).unwrap().trim().parse().unwrap();
+		assert!(!RUNNING.lock().unwrap().iter().flatten().any(|running| *running == pid));
+	}
+}
@@ -0,0 +1,139 @@
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::BTreeMap,
+	path::{Path, PathBuf},
+	sync::{
+		Mutex,
+		atomic::{AtomicBool, Ordering},
+	},
+};
+
+/// Name of the lockfile, next to FORGE_ROOT
+pub const LOCKFILE_NAME: &str = "FORGE.lock";
+
+const HEADER: &str = "# Checksums of the http.download calls that do not give one, written by forge.\n\
+	# Run `forge lock update` after a download is expected to change.\n\n";
+
+/// Checksums of the downloads whose FORGE files give none, recorded on their first fetch and enforced afterwards
+#[derive(Debug)]
+pub struct Lockfile {
+	path: PathBuf,
+	/// Fetching every download again and recording what it is now, rather than enforcing what was recorded
+	updating: bool,
+	downloads: Mutex<BTreeMap<String, LockedDownload>>,
+	changed: AtomicBool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedDownload {
+	pub blake3: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct LockfileContents {
+	#[serde(default)]
+	downloads: BTreeMap<String, LockedDownload>,
+}
+
+impl Lockfile {
+	/// The lockfile at path, empty when it does not exist yet
+	pub fn load(path: &Path) -> anyhow::Result<Self> {
+		let contents = match std::fs::read_to_string(path) {
+			Ok(content) => toml::from_str::<LockfileContents>(&content)
+				.map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", path.display(), e))?,
+			Err(e) if e.kind() == std::io::ErrorKind::NotFound => LockfileContents::default(),
+			Err(e) => return Err(e.into()),
+		};
+		Ok(Self {
+			path: path.to_path_buf(),
+			updating: false,
+			downloads: Mutex::new(contents.downloads),
+			changed: AtomicBool::new(false),
+		})
+	}
+
+	/// An empty lockfile replacing the one at path on save, holding only the downloads fetched until then
+	pub fn update(path: &Path) -> Self {
+		Self {
+			path: path.to_path_buf(),
+			updating: true,
+			downloads: Mutex::new(BTreeMap::new()),
+			changed: AtomicBool::new(true),
+		}
+	}
+
+	pub fn is_updating(&self) -> bool {
+		self.updating
+	}
+
+	/// The blake3 recorded for url
+	pub fn locked(&self, url: &str) -> Option<String> {
+		self.downloads
+			.lock()
+			.unwrap()
+			.get(url)
+			.map(|download| download.blake3.clone())
+	}
+
+	pub fn record(&self, url: &str, blake3: String) {
+		let download = LockedDownload { blake3 };
+		let previous = self.downloads.lock().unwrap().insert(url.to_string(), download.clone());
+		if previous.as_ref() != Some(&download) {
+			self.changed.store(true, Ordering::Relaxed);
+		}
+	}
+
+	/// Every locked (url, blake3), sorted by url
+	pub fn downloads(&self) -> Vec<(String, String)> {
+		self.downloads
+			.lock()
+			.unwrap()
+			.iter()
+			.map(|(url, download)| (url.clone(), download.blake3.clone()))
+			.collect()
+	}
+
+	pub fn download_count(&self) -> usize {
+		self.downloads.lock().unwrap().len()
+	}
+
+	/// Write the lockfile if a download was recorded since it was loaded
+	pub fn save(&self) -> anyhow::Result<()> {
+		if !self.changed.load(Ordering::Relaxed) {
+			return Ok(());
+		}
+		let contents = LockfileContents {
+			downloads: self.downloads.lock().unwrap().clone(),
+		};
+		std::fs::write(&self.path, format!("{}{}", HEADER, toml::to_string(&contents)?))?;
+		self.changed.store(false, Ordering::Relaxed);
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_lockfile_round_trip() {
+		let path = std::env::temp_dir().join(format!("forge-lockfile-{}.lock", std::process::id()));
+		let url = "https://example.com/archive.tar.gz?version=1";
+
+		let lockfile = Lockfile::load(&path).unwrap();
+		assert_eq!(lockfile.locked(url), None);
+		lockfile.record(url, "abc".to_string());
+		lockfile.save().unwrap();
+
+		let lockfile = Lockfile::load(&path).unwrap();
+		assert_eq!(lockfile.locked(url).as_deref(), Some("abc"));
+		assert!(!lockfile.is_updating());
+
+		let updated = Lockfile::update(&path);
+		assert_eq!(updated.locked(url), None);
+		updated.save().unwrap();
+		assert_eq!(Lockfile::load(&path).unwrap().download_count(), 0);
+
+		std::fs::remove_file(&path).unwrap();
+	}
+}
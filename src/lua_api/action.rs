@@ -0,0 +1,197 @@
+use crate::lua_api::observations;
+use crate::project::Rule;
+use mlua::{Function, Lua, Result, Table, UserData, Value, ffi};
+use std::{
+	ffi::CStr,
+	path::PathBuf,
+	sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// forge modules an action can use while it runs; anything that registers rules, runs programs or reaches the
+/// network is left out
+const ACTION_MODULES: &[&str] = &[
+	"fs", "hash", "log", "parse", "path", "regex", "semver", "string", "table", "template",
+];
+
+/// Lua globals an action can use besides forge
+const ACTION_GLOBALS: &[&str] = &[
+	"assert", "error", "ipairs", "math", "next", "pairs", "pcall", "print", "select", "string", "table", "tonumber",
+	"tostring", "type", "utf8", "xpcall",
+];
+
+/// A Lua callback that runs as a rule's command, kept with the state it was defined in
+pub struct Action {
+	pub lua: Lua,
+	pub function: Function,
+}
+
+/// Returned by forge.action, used as the command of a rule
+pub struct ActionHandle {
+	/// Unique within the process, to find the callback again at build time
+	pub id: String,
+	/// Where the callback is defined, "path:line"; stable across builds, unlike id
+	pub location: String,
+	/// The file the callback was defined in, whose content the rule's hash follows
+	pub source: Option<PathBuf>,
+}
+
+impl UserData for ActionHandle {}
+
+/// Actions forge.action created in a Lua state that the project has not taken yet
+#[derive(Default)]
+struct RegisteredActions(Vec<(String, Function)>);
+
+static NEXT_ACTION_ID: AtomicUsize = AtomicUsize::new(0);
+
+pub fn install(lua: &Lua, forge_table: &Table) -> Result<()> {
+	lua.set_app_data(RegisteredActions::default());
+
+	let action_fn = lua.create_function(|lua, function: Function| {
+		// Only the state that evaluated the FORGE file can run the callback, so its rules cannot come from the cache
+		observations::mark_volatile(lua);
+
+		let info = function.info();
+		let source = info
+			.source
+			.as_deref()
+			.and_then(|source| source.strip_prefix('@'))
+			.map(PathBuf::from);
+		let location = format!(
+			"{}:{}",
+			source.as_ref().map_or("?".into(), |source| source.display().to_string()),
+			info.line_defined.unwrap_or(0)
+		);
+		check_upvalues(lua, &function, &location)?;
+		let id = format!("action-{}", NEXT_ACTION_ID.fetch_add(1, Ordering::Relaxed));
+		let mut registered = lua
+			.app_data_mut::<RegisteredActions>()
+			.ok_or_else(|| mlua::Error::RuntimeError("forge.action is not available here".to_string()))?;
+		registered.0.push((id.clone(), function));
+		Ok(ActionHandle { id, location, source })
+	})?;
+	forge_table.set("action", action_fn)
+}
+
+/// Take the actions created in lua since the last call
+pub fn take_registered_actions(lua: &Lua) -> Vec<(String, Action)> {
+	lua.app_data_mut::<RegisteredActions>()
+		.map(|mut registered| std::mem::take(&mut registered.0))
+		.unwrap_or_default()
+		.into_iter()
+		.map(|(id, function)| {
+			(
+				id,
+				Action {
+					lua: lua.clone(),
+					function,
+				},
+			)
+		})
+		.collect()
+}
+
+/// Replacing _ENV only restricts the globals; a captured local like `local exec = forge.exec`, or a helper function
+/// reading the FORGE file's globals, would still reach everything, so only plain values can be captured
+fn check_upvalues(lua: &Lua, function: &Function, location: &str) -> Result<()> {
+	// SAFETY: reads the upvalues of the function passed in and leaves the name of the first offending one, or nil
+	let escaping: Option<String> = unsafe {
+		lua.exec_raw(function, |state| {
+			for index in 1.. {
+				let name = ffi::lua_getupvalue(state, 1, index);
+				if name.is_null() {
+					break;
+				}
+				let kind = ffi::lua_type(state, -1);
+				ffi::lua_pop(state, 1);
+				if CStr::from_ptr(name) != c"_ENV"
+					&& !matches!(kind, ffi::LUA_TNIL | ffi::LUA_TBOOLEAN | ffi::LUA_TNUMBER | ffi::LUA_TSTRING)
+				{
+					ffi::lua_settop(state, 0);
+					ffi::lua_pushstring(state, name);
+					return;
+				}
+			}
+			ffi::lua_settop(state, 0);
+			ffi::lua_pushnil(state);
+		})?
+	};
+	match escaping {
+		Some(name) => Err(mlua::Error::RuntimeError(format!(
+			"forge.action callback at {} captures '{}', which is not a string, number or boolean; reach forge from \
+			 inside the callback instead",
+			location, name
+		))),
+		None => Ok(()),
+	}
+}
+
+/// Run the action of rule with a restricted environment in place of its globals, passing it
+/// { name, inputs, outputs, workdir, env }
+pub fn run(action: &Action, rule: &Rule) -> Result<()> {
+	let lua = &action.lua;
+	let globals = lua.globals();
+	// Checked again, captured locals can be reassigned after forge.action
+	let location = rule.command.strip_prefix("forge.action ").unwrap_or(&rule.command);
+	check_upvalues(lua, &action.function, location)?;
+
+	let environment = lua.create_table()?;
+	for name in ACTION_GLOBALS {
+		environment.set(*name, globals.get::<Value>(*name)?)?;
+	}
+	let forge: Table = globals.get("forge")?;
+	let restricted = lua.create_table()?;
+	for name in ACTION_MODULES {
+		restricted.set(*name, forge.get::<Value>(*name)?)?;
+	}
+	environment.set("forge", restricted)?;
+	action.function.set_environment(environment)?;
+
+	let context = lua.create_table()?;
+	context.set("name", rule.name.as_str())?;
+	context.set("inputs", rule.inputs.clone())?;
+	context.set("outputs", rule.outputs.clone())?;
+	context.set("workdir", rule.workdir.to_string_lossy().to_string())?;
+	context.set("env", rule.env.clone())?;
+	action.function.call::<()>(context)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_actions_cannot_capture_host_access() {
+		let lua = Lua::new();
+		let forge = lua.create_table().unwrap();
+		install(&lua, &forge).unwrap();
+		lua.globals().set("forge", forge).unwrap();
+
+		lua.load("local prefix = 'out/'\nforge.action(function(rule) return prefix .. rule.name end)")
+			.exec()
+			.unwrap();
+		let error = lua
+			.load("local run = os.execute\nforge.action(function(rule) run('true') end)")
+			.exec()
+			.unwrap_err();
+		assert!(error.to_string().contains("captures 'run'"));
+
+		// A local assigned after forge.action is caught when the action runs
+		lua.load("local later\nforge.action(function(rule) later() end)\nlater = os.execute")
+			.exec()
+			.unwrap();
+		let actions = take_registered_actions(&lua);
+		assert_eq!(actions.len(), 2);
+		let rule = Rule {
+			name: "generate".to_string(),
+			command: "forge.action FORGE:3".to_string(),
+			..Default::default()
+		};
+		assert!(run(&actions[0].1, &rule).is_ok());
+		assert!(
+			run(&actions[1].1, &rule)
+				.unwrap_err()
+				.to_string()
+				.contains("captures 'later'")
+		);
+	}
+}
@@ -0,0 +1,662 @@
+use crate::lua_api::project_path::{self, ProjectPath};
+use forge_macros::{LuaClass, forge_lua_module, lua_api};
+use mlua::{FromLua, Lua, LuaSerdeExt, Result, Table, UserData, UserDataMethods, Value};
+use serde::{Deserialize, Serialize};
+use std::{
+	fs::File,
+	io::{BufReader, BufWriter, Read, Write},
+	path::{Component, Path, PathBuf},
+};
+use thiserror::Error;
+use walkdir::WalkDir;
+
+#[derive(Error, Debug)]
+pub enum ArchiveError {
+	#[error("Unsupported archive format: {format}")]
+	UnsupportedFormat {
+		format: String,
+	},
+
+	#[error("Invalid archive entry: {path} - {reason}")]
+	InvalidEntry {
+		path: String,
+		reason: String,
+	},
+
+	#[error("Archive creation failed: {archive} - {reason}")]
+	CreationFailed {
+		archive: String,
+		reason: String,
+	},
+
+	#[error("Archive extraction failed: {archive} - {reason}")]
+	ExtractionFailed {
+		archive: String,
+		reason: String,
+	},
+}
+
+#[derive(Debug, Deserialize, Serialize, LuaClass)]
+pub struct ArchiveCreateRequest {
+	pub format: Option<String>,
+	pub root: String,
+	pub files: Option<Vec<String>>,
+	pub dest: String,
+	pub deterministic: Option<bool>,
+}
+
+impl FromLua for ArchiveCreateRequest {
+	fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+		lua.from_value(value)
+	}
+}
+
+/// Selects which entries of an archive are listed or extracted, and under which name
+#[derive(Debug, Default, Deserialize, Serialize, LuaClass)]
+pub struct ArchiveFilter {
+	pub strip_components: Option<usize>,
+	pub include: Option<Vec<String>>,
+	pub exclude: Option<Vec<String>>,
+}
+
+impl FromLua for ArchiveFilter {
+	fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+		lua.from_value(value)
+	}
+}
+
+#[derive(Debug, Deserialize, Serialize, LuaClass)]
+pub struct ArchiveExtractRequest {
+	pub archive: String,
+	pub dest: String,
+	#[serde(flatten)]
+	pub filter: ArchiveFilter,
+	pub preserve_permissions: Option<bool>,
+	pub symlinks: Option<bool>,
+}
+
+impl FromLua for ArchiveExtractRequest {
+	fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+		lua.from_value(value)
+	}
+}
+
+struct ExtractOptions<'a> {
+	dest: Option<&'a Path>,
+	preserve_permissions: bool,
+	symlinks: bool,
+}
+
+#[derive(Clone)]
+pub struct ArchiveApi;
+
+impl UserData for ArchiveApi {
+	fn add_methods<M: UserDataMethods<Self>>(_methods: &mut M) {}
+}
+
+#[lua_api(name = "archive")]
+impl ArchiveApi {
+	pub fn new() -> Self {
+		Self
+	}
+
+	/// Create a tar, tar.gz or zip archive from files under root (files default to everything under root)
+	/// root and dest may be absolute or relative to the project root
+	fn create(lua: &Lua, request: ArchiveCreateRequest) -> Result<String> {
+		let root = project_path::resolve(lua, &request.root)?;
+		let dest = project_path::resolve(lua, &request.dest)?;
+
+		let format = match &request.format {
+			Some(format) => format.clone(),
+			None => format_from_path(&dest).ok_or_else(|| {
+				mlua::Error::external(ArchiveError::UnsupportedFormat {
+					format: dest.to_string_lossy().to_string(),
+				})
+			})?,
+		};
+
+		write_archive(
+			&root,
+			request.files.as_deref(),
+			&dest,
+			&format,
+			request.deterministic.unwrap_or(true),
+		)
+		.map_err(mlua::Error::external)?;
+
+		Ok(dest.to_string_lossy().to_string())
+	}
+
+	/// Extract a tar, tar.gz, crate or zip archive (archive and dest may be relative to the project root)
+	/// Supports strip_components, include/exclude globs (matched after stripping), preserve_permissions and symlinks
+	/// @return Extracted files, relative to dest
+	fn extract(lua: &Lua, request: ArchiveExtractRequest) -> Result<Vec<String>> {
+		let archive = project_path::resolve(lua, &request.archive)?;
+		let dest = project_path::resolve(lua, &request.dest)?;
+
+		let options = ExtractOptions {
+			dest: Some(&dest),
+			preserve_permissions: request.preserve_permissions.unwrap_or(true),
+			symlinks: request.symlinks.unwrap_or(true),
+		};
+		extract_with(&archive, &request.filter, &options).map_err(mlua::Error::external)
+	}
+
+	/// List the files an extraction with the same filter would produce, without writing anything
+	fn list(path: ProjectPath, filter: Option<ArchiveFilter>) -> Result<Vec<String>> {
+		let archive = path.into_path_buf();
+
+		let options = ExtractOptions {
+			dest: None,
+			preserve_permissions: true,
+			symlinks: true,
+		};
+		process_archive(&archive, &filter.unwrap_or_default(), &options).map_err(mlua::Error::external)
+	}
+}
+
+/// Extract the entries of archive selected by filter to dest, keeping permissions and symlinks
+/// @return Extracted files, relative to dest
+pub fn extract_archive(
+	archive: &Path,
+	dest: &Path,
+	filter: &ArchiveFilter,
+) -> std::result::Result<Vec<String>, ArchiveError> {
+	let options = ExtractOptions {
+		dest: Some(dest),
+		preserve_permissions: true,
+		symlinks: true,
+	};
+	extract_with(archive, filter, &options)
+}
+
+fn extract_with(
+	archive: &Path,
+	filter: &ArchiveFilter,
+	options: &ExtractOptions,
+) -> std::result::Result<Vec<String>, ArchiveError> {
+	if let Some(dest) = options.dest {
+		std::fs::create_dir_all(dest).map_err(|e| ArchiveError::ExtractionFailed {
+			archive: archive.to_string_lossy().to_string(),
+			reason: e.to_string(),
+		})?;
+	}
+	process_archive(archive, filter, options)
+}
+
+/// Write files under root (everything under it by default) to a tar, tar.gz or zip archive at dest
+pub fn write_archive(
+	root: &Path,
+	files: Option<&[String]>,
+	dest: &Path,
+	format: &str,
+	deterministic: bool,
+) -> std::result::Result<(), ArchiveError> {
+	let entries = collect_entries(root, files)?;
+
+	if let Some(parent) = dest.parent() {
+		std::fs::create_dir_all(parent).map_err(|e| ArchiveError::CreationFailed {
+			archive: dest.to_string_lossy().to_string(),
+			reason: e.to_string(),
+		})?;
+	}
+
+	if !matches!(format, "tar" | "tar.gz" | "tgz" | "zip") {
+		return Err(ArchiveError::UnsupportedFormat {
+			format: format.to_string(),
+		});
+	}
+
+	let result = File::create(dest).and_then(|file| match format {
+		"tar" => write_tar(file, root, &entries, deterministic).map(|_| ()),
+		"zip" => write_zip(file, root, &entries, deterministic),
+		_ => {
+			let encoder = flate2::GzBuilder::new().write(file, flate2::Compression::default());
+			write_tar(encoder, root, &entries, deterministic).and_then(|encoder| encoder.finish().map(|_| ()))
+		}
+	});
+
+	result.map_err(|e| ArchiveError::CreationFailed {
+		archive: dest.to_string_lossy().to_string(),
+		reason: e.to_string(),
+	})
+}
+
+fn format_from_path(path: &Path) -> Option<String> {
+	let name = path.file_name()?.to_string_lossy();
+	if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+		Some("tar.gz".to_string())
+	} else if name.ends_with(".tar") {
+		Some("tar".to_string())
+	} else if name.ends_with(".zip") {
+		Some("zip".to_string())
+	} else if name.ends_with(".crate") {
+		Some("tar.gz".to_string())
+	} else {
+		None
+	}
+}
+
+fn collect_entries(root: &Path, files: Option<&[String]>) -> std::result::Result<Vec<String>, ArchiveError> {
+	let requested: Vec<PathBuf> = match files {
+		Some(files) => files.iter().map(|f| root.join(f)).collect(),
+		None => vec![root.to_path_buf()],
+	};
+
+	let mut entries = Vec::new();
+	for path in requested {
+		if !path.exists() {
+			return Err(ArchiveError::InvalidEntry {
+				path: path.to_string_lossy().to_string(),
+				reason: "file does not exist".to_string(),
+			});
+		}
+
+		for entry in WalkDir::new(&path) {
+			let entry = entry.map_err(|e| ArchiveError::InvalidEntry {
+				path: e.path().unwrap_or(&path).to_string_lossy().to_string(),
+				reason: e.to_string(),
+			})?;
+			if entry.file_type().is_dir() {
+				continue;
+			}
+
+			let relative = entry.path().strip_prefix(root).map_err(|_| ArchiveError::InvalidEntry {
+				path: entry.path().to_string_lossy().to_string(),
+				reason: format!("not inside archive root {}", root.display()),
+			})?;
+
+			let name = relative
+				.components()
+				.map(|c| c.as_os_str().to_string_lossy())
+				.collect::<Vec<_>>()
+				.join("/");
+			entries.push(name);
+		}
+	}
+
+	entries.sort();
+	entries.dedup();
+	Ok(entries)
+}
+
+fn write_tar<W: Write>(writer: W, root: &Path, entries: &[String], deterministic: bool) -> std::io::Result<W> {
+	let mut builder = tar::Builder::new(writer);
+	builder.mode(if deterministic {
+		tar::HeaderMode::Deterministic
+	} else {
+		tar::HeaderMode::Complete
+	});
+
+	for name in entries {
+		builder.append_path_with_name(root.join(name), name)?;
+	}
+
+	builder.into_inner()
+}
+
+fn write_zip(file: File, root: &Path, entries: &[String], deterministic: bool) -> std::io::Result<()> {
+	use zip::write::SimpleFileOptions;
+
+	let mut zip = zip::ZipWriter::new(BufWriter::new(file));
+
+	for name in entries {
+		let path = root.join(name);
+		let mut options = SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+		if deterministic {
+			options = options.last_modified_time(zip::DateTime::default());
+		}
+
+		#[cfg(unix)]
+		{
+			use std::os::unix::fs::PermissionsExt;
+			let mode = std::fs::metadata(&path)?.permissions().mode();
+			options = options.unix_permissions(if mode & 0o111 != 0 { 0o755 } else { 0o644 });
+		}
+
+		zip.start_file(name.as_str(), options).map_err(std::io::Error::other)?;
+		let mut source = File::open(&path)?;
+		std::io::copy(&mut source, &mut zip)?;
+	}
+
+	zip.finish().map_err(std::io::Error::other)?;
+	Ok(())
+}
+
+struct CompiledFilter {
+	strip_components: usize,
+	include: Vec<glob::Pattern>,
+	exclude: Vec<glob::Pattern>,
+}
+
+impl CompiledFilter {
+	fn new(filter: &ArchiveFilter) -> std::result::Result<Self, ArchiveError> {
+		let compile = |patterns: &Option<Vec<String>>| {
+			patterns
+				.iter()
+				.flatten()
+				.map(|pattern| {
+					glob::Pattern::new(pattern).map_err(|e| ArchiveError::InvalidEntry {
+						path: pattern.clone(),
+						reason: format!("invalid glob: {}", e),
+					})
+				})
+				.collect::<std::result::Result<Vec<_>, _>>()
+		};
+
+		Ok(Self {
+			strip_components: filter.strip_components.unwrap_or(0),
+			include: compile(&filter.include)?,
+			exclude: compile(&filter.exclude)?,
+		})
+	}
+
+	/// Map an entry path to its output name, or None if it is stripped away or filtered out
+	fn select(&self, entry: &Path) -> std::result::Result<Option<String>, ArchiveError> {
+		let mut parts = Vec::new();
+		for component in entry.components() {
+			match component {
+				Component::Normal(part) => parts.push(part.to_string_lossy().to_string()),
+				Component::CurDir => {}
+				_ => {
+					return Err(ArchiveError::InvalidEntry {
+						path: entry.to_string_lossy().to_string(),
+						reason: "entry escapes the destination directory".to_string(),
+					});
+				}
+			}
+		}
+
+		if parts.len() <= self.strip_components {
+			return Ok(None);
+		}
+		let name = parts[self.strip_components..].join("/");
+
+		if !self.include.is_empty() && !self.include.iter().any(|p| p.matches(&name)) {
+			return Ok(None);
+		}
+		if self.exclude.iter().any(|p| p.matches(&name)) {
+			return Ok(None);
+		}
+
+		Ok(Some(name))
+	}
+}
+
+fn process_archive(
+	archive: &Path,
+	filter: &ArchiveFilter,
+	options: &ExtractOptions,
+) -> std::result::Result<Vec<String>, ArchiveError> {
+	let filter = CompiledFilter::new(filter)?;
+	let format = format_from_path(archive).ok_or_else(|| ArchiveError::UnsupportedFormat {
+		format: archive.to_string_lossy().to_string(),
+	})?;
+
+	let failed = |e: std::io::Error| ArchiveError::ExtractionFailed {
+		archive: archive.to_string_lossy().to_string(),
+		reason: e.to_string(),
+	};
+
+	let file = BufReader::new(File::open(archive).map_err(failed)?);
+	let mut names = match format.as_str() {
+		"tar" => process_tar(file, &filter, options),
+		"tar.gz" => process_tar(flate2::read::GzDecoder::new(file), &filter, options),
+		_ => process_zip(file, &filter, options),
+	}
+	.map_err(|e| match e.downcast::<ArchiveError>() {
+		Ok(err) => err,
+		Err(e) => failed(e),
+	})?;
+
+	names.sort();
+	Ok(names)
+}
+
+fn process_tar<R: Read>(reader: R, filter: &CompiledFilter, options: &ExtractOptions) -> std::io::Result<Vec<String>> {
+	let mut archive = tar::Archive::new(reader);
+	archive.set_preserve_permissions(options.preserve_permissions);
+
+	let mut names = Vec::new();
+	for entry in archive.entries()? {
+		let mut entry = entry?;
+		let entry_type = entry.header().entry_type();
+		if entry_type.is_dir() || (entry_type.is_symlink() && !options.symlinks) {
+			continue;
+		}
+
+		let Some(name) = filter.select(&entry.path()?).map_err(std::io::Error::other)? else {
+			continue;
+		};
+
+		if let Some(dest) = options.dest {
+			let target = prepare_target(dest, &name)?;
+
+			if entry_type.is_symlink() {
+				let link = entry.link_name()?.unwrap_or_default();
+				check_link_target(dest, &name, &link)?;
+				#[cfg(unix)]
+				std::os::unix::fs::symlink(link, &target)?;
+				#[cfg(not(unix))]
+				std::fs::write(&target, link.to_string_lossy().as_bytes())?;
+			} else if entry_type.is_hard_link() {
+				// Hard links name another entry of the archive, which was stripped and extracted like any other
+				let link = entry.link_name()?.unwrap_or_default();
+				let Some(source) = filter.select(&link).map_err(std::io::Error::other)? else {
+					return Err(std::io::Error::other(ArchiveError::InvalidEntry {
+						path: name,
+						reason: format!("hard link to {} which is not extracted", link.display()),
+					}));
+				};
+				std::fs::hard_link(dest.join(source), &target)?;
+			} else if options.preserve_permissions || !entry_type.is_file() {
+				entry.unpack(&target)?;
+			} else {
+				std::io::copy(&mut entry, &mut File::create(&target)?)?;
+			}
+		}
+
+		names.push(name);
+	}
+
+	Ok(names)
+}
+
+fn process_zip<R: Read + std::io::Seek>(
+	reader: R,
+	filter: &CompiledFilter,
+	options: &ExtractOptions,
+) -> std::io::Result<Vec<String>> {
+	let mut archive = zip::ZipArchive::new(reader).map_err(std::io::Error::other)?;
+
+	let mut names = Vec::new();
+	for index in 0..archive.len() {
+		let mut file = archive.by_index(index).map_err(std::io::Error::other)?;
+		if file.is_dir() || (file.is_symlink() && !options.symlinks) {
+			continue;
+		}
+
+		let Some(name) = filter.select(Path::new(file.name())).map_err(std::io::Error::other)? else {
+			continue;
+		};
+
+		if let Some(dest) = options.dest {
+			let target = prepare_target(dest, &name)?;
+
+			if file.is_symlink() {
+				let mut link = String::new();
+				file.read_to_string(&mut link)?;
+				check_link_target(dest, &name, Path::new(&link))?;
+				#[cfg(unix)]
+				std::os::unix::fs::symlink(link, &target)?;
+				#[cfg(not(unix))]
+				std::fs::write(&target, link)?;
+			} else {
+				std::io::copy(&mut file, &mut File::create(&target)?)?;
+
+				#[cfg(unix)]
+				if options.preserve_permissions
+					&& let Some(mode) = file.unix_mode()
+				{
+					use std::os::unix::fs::PermissionsExt;
+					std::fs::set_permissions(&target, std::fs::Permissions::from_mode(mode & 0o7777))?;
+				}
+			}
+		}
+
+		names.push(name);
+	}
+
+	Ok(names)
+}
+
+/// Create the directories of the entry extracted as name and make sure it lands inside dest, the check tar's
+/// unpack_in does, which can't be used as names may be stripped: a symlink extracted earlier must not redirect a later
+/// entry outside of dest
+fn prepare_target(dest: &Path, name: &str) -> std::io::Result<PathBuf> {
+	let target = dest.join(name);
+	let parent = target.parent().unwrap_or(dest);
+	std::fs::create_dir_all(parent)?;
+	if !parent.canonicalize()?.starts_with(dest.canonicalize()?) {
+		return Err(std::io::Error::other(ArchiveError::InvalidEntry {
+			path: name.to_string(),
+			reason: "entry escapes the destination directory".to_string(),
+		}));
+	}
+
+	// Writing would otherwise follow a symlink already at target
+	if target.symlink_metadata().is_ok_and(|metadata| metadata.is_symlink()) {
+		std::fs::remove_file(&target)?;
+	}
+	Ok(target)
+}
+
+/// Fail for a symlink extracted at name under dest whose target resolves outside dest, following the symlinks
+/// already extracted the way opening it will, so a chain of links that each look harmless cannot climb out
+fn check_link_target(dest: &Path, name: &str, link: &Path) -> std::io::Result<()> {
+	let escapes = || {
+		std::io::Error::other(ArchiveError::InvalidEntry {
+			path: name.to_string(),
+			reason: format!("symlink to {} escapes the destination directory", link.display()),
+		})
+	};
+
+	let dest = dest.canonicalize()?;
+	let parent = dest.join(name).parent().unwrap_or(&dest).canonicalize()?;
+	match resolve_link(parent, link, &mut 0) {
+		Some(resolved) if resolved.starts_with(&dest) => Ok(()),
+		_ => Err(escapes()),
+	}
+}
+
+/// Where link resolves from the directory base, following the symlinks on the way; None for an absolute link,
+/// too many links, or a ".." after a name that does not exist yet, as a later entry could make it a symlink
+fn resolve_link(base: PathBuf, link: &Path, follows: &mut usize) -> Option<PathBuf> {
+	let mut resolved = base;
+	let mut missing = false;
+	for component in link.components() {
+		match component {
+			Component::CurDir => {}
+			Component::ParentDir if missing => return None,
+			Component::ParentDir => {
+				resolved.pop();
+			}
+			Component::Normal(name) => {
+				resolved.push(name);
+				if let Ok(target) = std::fs::read_link(&resolved) {
+					*follows += 1;
+					if *follows > 40 {
+						return None;
+					}
+					resolved.pop();
+					resolved = resolve_link(resolved, &target, follows)?;
+				} else {
+					missing |= resolved.symlink_metadata().is_err();
+				}
+			}
+			Component::RootDir | Component::Prefix(_) => return None,
+		}
+	}
+	Some(resolved)
+}
+
+forge_lua_module!(archive, ArchiveApi, "Archive creation");
+
+pub fn create_archive_table(lua: &Lua) -> Result<Table> {
+	ArchiveApi::create_archive_table(lua)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_extract_rejects_escaping_symlinks() {
+		let dir = std::env::temp_dir().join(format!("forge-archive-test-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+		std::fs::create_dir_all(&dir).unwrap();
+
+		let tar_path = dir.join("links.tar");
+		let mut builder = tar::Builder::new(File::create(&tar_path).unwrap());
+		for (name, target) in [("pkg/lib/current", "../../VERSION"), ("pkg/escape", "/etc/passwd")] {
+			let mut header = tar::Header::new_gnu();
+			header.set_entry_type(tar::EntryType::Symlink);
+			header.set_size(0);
+			builder.append_link(&mut header, name, target).unwrap();
+		}
+		builder.into_inner().unwrap();
+
+		let error = extract_archive(&tar_path, &dir.join("tar"), &ArchiveFilter::default()).unwrap_err();
+		assert!(error.to_string().contains("pkg/escape - symlink to /etc/passwd escapes"));
+		assert!(dir.join("tar/pkg/lib/current").symlink_metadata().is_ok());
+
+		let zip_path = dir.join("links.zip");
+		let mut zip = zip::ZipWriter::new(File::create(&zip_path).unwrap());
+		zip.add_symlink("pkg/lib/current", "../../VERSION", zip::write::SimpleFileOptions::default())
+			.unwrap();
+		zip.finish().unwrap();
+
+		let names = extract_archive(&zip_path, &dir.join("zip"), &ArchiveFilter::default()).unwrap();
+		assert_eq!(names, ["pkg/lib/current"]);
+
+		// Stripping the top-level directory leaves the link one level too shallow for its target
+		let stripped = ArchiveFilter {
+			strip_components: Some(1),
+			..Default::default()
+		};
+		let error = extract_archive(&zip_path, &dir.join("stripped"), &stripped).unwrap_err();
+		assert!(error.to_string().contains("lib/current - symlink to ../../VERSION escapes"));
+
+		let _ = std::fs::remove_dir_all(&dir);
+	}
+
+	#[test]
+	fn test_extract_rejects_chained_symlinks() {
+		let dir = std::env::temp_dir().join(format!("forge-archive-chain-test-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+		std::fs::create_dir_all(&dir).unwrap();
+
+		let extract = |links: &[(&str, &str)]| {
+			let tar_path = dir.join("links.tar");
+			let mut builder = tar::Builder::new(File::create(&tar_path).unwrap());
+			for (name, target) in links {
+				let mut header = tar::Header::new_gnu();
+				header.set_entry_type(tar::EntryType::Symlink);
+				header.set_size(0);
+				builder.append_link(&mut header, name, target).unwrap();
+			}
+			builder.into_inner().unwrap();
+			let _ = std::fs::remove_dir_all(dir.join("out"));
+			extract_archive(&tar_path, &dir.join("out"), &ArchiveFilter::default())
+		};
+
+		// Each stays inside on its own, but a/b goes through a/x, which already points at the destination
+		let error = extract(&[("a/x", ".."), ("a/b", "x/../..")]).unwrap_err();
+		assert!(error.to_string().contains("a/b - symlink to x/../.. escapes"));
+		// a/x could become a symlink once a/l is extracted
+		assert!(extract(&[("a/l", "x/../.."), ("a/x", ".")]).is_err());
+		assert!(extract(&[("a/x", ".."), ("a/b", "x/lib")]).is_ok());
+
+		let _ = std::fs::remove_dir_all(&dir);
+	}
+}
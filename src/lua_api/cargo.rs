@@ -0,0 +1,335 @@
+use crate::lua_api::{
+	cc::register_rule,
+	project_path,
+	rust::{cargo_program, tool_command},
+};
+use forge_macros::{LuaClass, forge_lua_module, lua_api};
+use mlua::{FromLua, Lua, LuaSerdeExt, Result, Table, UserData, UserDataMethods, Value};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use walkdir::WalkDir;
+
+const LIBRARY_KINDS: &[&str] = &["lib", "rlib", "dylib", "cdylib", "staticlib"];
+
+#[derive(Error, Debug)]
+pub enum CargoError {
+	#[error("cargo metadata failed for {manifest}: {reason}")]
+	MetadataFailed {
+		manifest: String,
+		reason: String,
+	},
+
+	#[error("{manifest} has no {kind} targets")]
+	NoArtifacts {
+		kind: String,
+		manifest: String,
+	},
+}
+
+#[derive(Debug, Deserialize, Serialize, LuaClass)]
+pub struct CargoBuildRequest {
+	pub manifest: Option<String>,
+	pub profile: Option<String>,
+	pub features: Option<Vec<String>>,
+	pub target: Option<String>,
+	pub name: Option<String>,
+	pub bin: Option<String>,
+	pub dependencies: Option<Vec<String>>,
+}
+
+impl FromLua for CargoBuildRequest {
+	fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+		lua.from_value(value)
+	}
+}
+
+/// What `cargo metadata --no-deps` says about the workspace of a manifest
+#[derive(Debug, Deserialize)]
+struct CargoMetadata {
+	packages: Vec<CargoPackage>,
+	target_directory: PathBuf,
+	workspace_root: PathBuf,
+}
+
+#[derive(Debug, Deserialize)]
+struct CargoPackage {
+	manifest_path: PathBuf,
+	targets: Vec<CargoTarget>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CargoTarget {
+	name: String,
+	kind: Vec<String>,
+	#[serde(default)]
+	crate_types: Vec<String>,
+}
+
+#[derive(Clone, Copy)]
+enum ArtifactKind {
+	Binary,
+	Library,
+}
+
+impl ArtifactKind {
+	fn as_str(self) -> &'static str {
+		match self {
+			ArtifactKind::Binary => "binary",
+			ArtifactKind::Library => "library",
+		}
+	}
+}
+
+#[derive(Clone)]
+pub struct CargoApi;
+
+impl UserData for CargoApi {
+	fn add_methods<M: UserDataMethods<Self>>(_methods: &mut M) {}
+}
+
+#[lua_api(name = "cargo")]
+impl CargoApi {
+	pub fn new() -> Self {
+		Self
+	}
+
+	/// Register a rule that builds the binaries of a Cargo package and rebuilds them when their sources change
+	/// @return Paths the executables are built at
+	#[lua_table(request: CargoBinaryOptions {
+		/// Path to Cargo.toml (default "Cargo.toml")
+		manifest: Option<String>,
+		/// Cargo profile (default "dev")
+		profile: Option<String>,
+		/// Features to enable
+		features: Option<Vec<String>>,
+		/// Target triple to build for
+		target: Option<String>,
+		/// Rule name (default "cargo:<manifest>")
+		name: Option<String>,
+		/// Only build this binary target
+		bin: Option<String>,
+		/// Rules that must run first
+		dependencies: Option<Vec<String>>,
+	})]
+	fn binary(lua: &Lua, request: CargoBuildRequest) -> Result<Vec<String>> {
+		register_cargo_rule(lua, request, ArtifactKind::Binary)
+	}
+
+	/// Register a rule that builds the library of a Cargo package and rebuilds it when its sources change
+	/// @return Paths the library files are built at (rlib, staticlib, cdylib, ...)
+	#[lua_table(request: CargoLibraryOptions {
+		/// Path to Cargo.toml (default "Cargo.toml")
+		manifest: Option<String>,
+		/// Cargo profile (default "dev")
+		profile: Option<String>,
+		/// Features to enable
+		features: Option<Vec<String>>,
+		/// Target triple to build for
+		target: Option<String>,
+		/// Rule name (default "cargo:<manifest>")
+		name: Option<String>,
+		/// Rules that must run first
+		dependencies: Option<Vec<String>>,
+	})]
+	fn library(lua: &Lua, request: CargoBuildRequest) -> Result<Vec<String>> {
+		register_cargo_rule(lua, request, ArtifactKind::Library)
+	}
+}
+
+/// Find the artifacts of the package with cargo metadata and register the rule that builds them, so evaluating the
+/// FORGE file does not have to wait for cargo build
+fn register_cargo_rule(lua: &Lua, request: CargoBuildRequest, kind: ArtifactKind) -> Result<Vec<String>> {
+	let manifest_arg = request.manifest.as_deref().unwrap_or("Cargo.toml");
+	let manifest = project_path::resolve(lua, manifest_arg)?;
+	let manifest = manifest.canonicalize().unwrap_or(manifest);
+	let package_root = manifest.parent().unwrap_or(Path::new(".")).to_path_buf();
+	let profile = request.profile.clone().unwrap_or_else(|| "dev".to_string());
+
+	let mut args = vec![
+		"build".to_string(),
+		"--manifest-path".to_string(),
+		manifest.to_string_lossy().to_string(),
+	];
+	args.extend(["--profile".to_string(), profile.clone()]);
+	if let Some(features) = &request.features
+		&& !features.is_empty()
+	{
+		args.extend(["--features".to_string(), features.join(",")]);
+	}
+	if let Some(target) = &request.target {
+		args.extend(["--target".to_string(), target.clone()]);
+	}
+	match (kind, &request.bin) {
+		(ArtifactKind::Binary, Some(bin)) => args.extend(["--bin".to_string(), bin.clone()]),
+		(ArtifactKind::Binary, None) => args.push("--bins".to_string()),
+		(ArtifactKind::Library, _) => args.push("--lib".to_string()),
+	}
+
+	let program = cargo_program();
+	let metadata = cargo_metadata(lua, &program, &manifest)?;
+	let package = metadata
+		.packages
+		.iter()
+		.find(|package| package.manifest_path.canonicalize().unwrap_or(package.manifest_path.clone()) == manifest);
+
+	let mut artifact_dir = metadata.target_directory.clone();
+	if let Some(target) = &request.target {
+		artifact_dir.push(target);
+	}
+	artifact_dir.push(profile_dir(&profile));
+
+	let outputs: Vec<String> = package
+		.into_iter()
+		.flat_map(|package| &package.targets)
+		.filter(|target| match (kind, &request.bin) {
+			(ArtifactKind::Binary, bin) => {
+				target.kind.iter().any(|k| k == "bin") && bin.as_ref().is_none_or(|bin| *bin == target.name)
+			}
+			(ArtifactKind::Library, _) => target.kind.iter().any(|k| LIBRARY_KINDS.contains(&k.as_str())),
+		})
+		.flat_map(|target| artifact_names(target, kind, request.target.as_deref()))
+		.map(|name| artifact_dir.join(name).to_string_lossy().to_string())
+		.collect();
+
+	if outputs.is_empty() {
+		return Err(mlua::Error::external(CargoError::NoArtifacts {
+			kind: kind.as_str().to_string(),
+			manifest: manifest.display().to_string(),
+		}));
+	}
+
+	let name = request.name.clone().unwrap_or_else(|| format!("cargo:{}", manifest_arg));
+	register_rule(
+		lua,
+		&name,
+		&program,
+		args,
+		package_inputs(&manifest, &package_root, &metadata),
+		outputs.clone(),
+		request.dependencies.clone().unwrap_or_default(),
+	)?;
+
+	Ok(outputs)
+}
+
+fn cargo_metadata(lua: &Lua, program: &str, manifest: &Path) -> Result<CargoMetadata> {
+	let failed = |reason: String| {
+		mlua::Error::external(CargoError::MetadataFailed {
+			manifest: manifest.display().to_string(),
+			reason,
+		})
+	};
+
+	let output = tool_command(lua, program)?
+		.args(["metadata", "--format-version", "1", "--no-deps", "--manifest-path"])
+		.arg(manifest)
+		.output()
+		.map_err(|e| failed(e.to_string()))?;
+	if !output.status.success() {
+		return Err(failed(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+	}
+	serde_json::from_slice(&output.stdout).map_err(|e| failed(e.to_string()))
+}
+
+/// Directory cargo puts the artifacts of a profile in, below the target directory
+fn profile_dir(profile: &str) -> &str {
+	match profile {
+		"dev" | "test" => "debug",
+		"bench" => "release",
+		profile => profile,
+	}
+}
+
+/// File names cargo gives the artifacts of target when building for triple, the host when None
+fn artifact_names(target: &CargoTarget, kind: ArtifactKind, triple: Option<&str>) -> Vec<String> {
+	let windows = triple.map_or(cfg!(windows), |triple| triple.contains("windows"));
+	let apple = triple.map_or(cfg!(target_vendor = "apple"), |triple| triple.contains("apple"));
+	let msvc = triple.map_or(cfg!(target_env = "msvc"), |triple| triple.contains("msvc"));
+
+	match kind {
+		ArtifactKind::Binary => vec![format!("{}{}", target.name, if windows { ".exe" } else { "" })],
+		ArtifactKind::Library => {
+			let name = target.name.replace('-', "_");
+			let crate_types = if target.crate_types.is_empty() {
+				&target.kind
+			} else {
+				&target.crate_types
+			};
+			crate_types
+				.iter()
+				.filter_map(|crate_type| match crate_type.as_str() {
+					"lib" | "rlib" => Some(format!("lib{}.rlib", name)),
+					"staticlib" if msvc => Some(format!("{}.lib", name)),
+					"staticlib" => Some(format!("lib{}.a", name)),
+					"cdylib" | "dylib" if windows => Some(format!("{}.dll", name)),
+					"cdylib" | "dylib" if apple => Some(format!("lib{}.dylib", name)),
+					"cdylib" | "dylib" => Some(format!("lib{}.so", name)),
+					_ => None,
+				})
+				.collect()
+		}
+	}
+}
+
+/// What the artifacts of a package are built from: its manifest, the lockfile of its workspace and the Rust sources
+/// below it
+fn package_inputs(manifest: &Path, package_root: &Path, metadata: &CargoMetadata) -> Vec<String> {
+	let mut inputs = vec![manifest.to_string_lossy().to_string()];
+	let lockfile = metadata.workspace_root.join("Cargo.lock");
+	if lockfile.exists() {
+		inputs.push(lockfile.to_string_lossy().to_string());
+	}
+
+	let sources = WalkDir::new(package_root)
+		.sort_by_file_name()
+		.into_iter()
+		.filter_entry(|entry| {
+			entry.depth() == 0
+				|| (entry.path() != metadata.target_directory && !entry.file_name().to_string_lossy().starts_with('.'))
+		})
+		.filter_map(|entry| entry.ok())
+		.filter(|entry| entry.file_type().is_file() && entry.path().extension().is_some_and(|ext| ext == "rs"));
+	inputs.extend(sources.map(|entry| entry.path().to_string_lossy().to_string()));
+	inputs
+}
+
+forge_lua_module!(cargo, CargoApi, "Rules that build Cargo binaries and libraries");
+
+pub fn create_cargo_table(lua: &Lua) -> Result<Table> {
+	CargoApi::create_cargo_table(lua)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_artifact_names() {
+		let library = CargoTarget {
+			name: "my-lib".to_string(),
+			kind: vec!["cdylib".to_string(), "rlib".to_string()],
+			crate_types: vec!["cdylib".to_string(), "rlib".to_string()],
+		};
+		assert_eq!(
+			artifact_names(&library, ArtifactKind::Library, Some("x86_64-unknown-linux-gnu")),
+			["libmy_lib.so", "libmy_lib.rlib"]
+		);
+		assert_eq!(
+			artifact_names(&library, ArtifactKind::Library, Some("x86_64-pc-windows-msvc")),
+			["my_lib.dll", "libmy_lib.rlib"]
+		);
+
+		let binary = CargoTarget {
+			name: "my-tool".to_string(),
+			kind: vec!["bin".to_string()],
+			crate_types: vec!["bin".to_string()],
+		};
+		assert_eq!(
+			artifact_names(&binary, ArtifactKind::Binary, Some("x86_64-pc-windows-gnu")),
+			["my-tool.exe"]
+		);
+		assert_eq!(profile_dir("dev"), "debug");
+		assert_eq!(profile_dir("release-lto"), "release-lto");
+	}
+}
@@ -0,0 +1,218 @@
+use forge_macros::{LuaClass, forge_lua_module, lua_api};
+use mlua::{FromLua, Function, Lua, LuaSerdeExt, Result, Table, UserData, UserDataMethods, Value};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const CXX_EXTENSIONS: &[&str] = &["cc", "cpp", "cxx", "c++", "C"];
+
+#[derive(Debug, Deserialize, Serialize, LuaClass)]
+pub struct CcCompileRequest {
+	pub srcs: Vec<String>,
+	pub out_dir: String,
+	pub name: Option<String>,
+	pub compiler: Option<String>,
+	pub flags: Option<Vec<String>>,
+	pub include_dirs: Option<Vec<String>>,
+	pub defines: Option<Vec<String>>,
+	pub headers: Option<Vec<String>>,
+	pub dependencies: Option<Vec<String>>,
+}
+
+impl FromLua for CcCompileRequest {
+	fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+		lua.from_value(value)
+	}
+}
+
+#[derive(Debug, Deserialize, Serialize, LuaClass)]
+pub struct CcLinkRequest {
+	pub objs: Vec<String>,
+	pub kind: String,
+	pub out: String,
+	pub name: Option<String>,
+	pub linker: Option<String>,
+	pub flags: Option<Vec<String>>,
+	pub libs: Option<Vec<String>>,
+	pub dependencies: Option<Vec<String>>,
+}
+
+impl FromLua for CcLinkRequest {
+	fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+		lua.from_value(value)
+	}
+}
+
+#[derive(Clone)]
+pub struct CcApi;
+
+impl UserData for CcApi {
+	fn add_methods<M: UserDataMethods<Self>>(_methods: &mut M) {}
+}
+
+#[lua_api(name = "cc")]
+impl CcApi {
+	pub fn new() -> Self {
+		Self
+	}
+
+	/// Register one compile rule per source file, named "<name>:<src>"
+	/// Compiler defaults to $CC (or $CXX for C++ sources), falling back to cc / c++
+	/// @return Object file paths, in the order of srcs
+	#[lua_table(request: CcCompileOptions {
+		/// Source files to compile
+		srcs: Vec<String>,
+		/// Directory for object files
+		out_dir: String,
+		/// Rule name prefix (default "cc")
+		name: Option<String>,
+		/// Compiler program
+		compiler: Option<String>,
+		/// Extra compiler flags
+		flags: Option<Vec<String>>,
+		/// Include directories, passed as -I
+		include_dirs: Option<Vec<String>>,
+		/// Preprocessor definitions, passed as -D
+		defines: Option<Vec<String>>,
+		/// Headers every object depends on, so editing them triggers a rebuild
+		headers: Option<Vec<String>>,
+		/// Rules that must run first
+		dependencies: Option<Vec<String>>,
+	})]
+	fn compile(lua: &Lua, request: CcCompileRequest) -> Result<Vec<String>> {
+		let prefix = request.name.as_deref().unwrap_or("cc");
+		let mut objects = Vec::with_capacity(request.srcs.len());
+
+		for src in &request.srcs {
+			let is_cxx = Path::new(src)
+				.extension()
+				.is_some_and(|ext| CXX_EXTENSIONS.contains(&&*ext.to_string_lossy()));
+			let compiler = match &request.compiler {
+				Some(compiler) => compiler.clone(),
+				None if is_cxx => tool_from_env("CXX", "c++"),
+				None => tool_from_env("CC", "cc"),
+			};
+
+			let object = Path::new(&request.out_dir)
+				.join(format!("{}.o", src.trim_start_matches("./")))
+				.to_string_lossy()
+				.to_string();
+
+			let mut args = request.flags.clone().unwrap_or_default();
+			args.extend(request.include_dirs.iter().flatten().map(|dir| format!("-I{}", dir)));
+			args.extend(request.defines.iter().flatten().map(|define| format!("-D{}", define)));
+			args.extend(["-c".to_string(), src.clone(), "-o".to_string(), object.clone()]);
+
+			let mut inputs = vec![src.clone()];
+			inputs.extend(request.headers.iter().flatten().cloned());
+
+			register_rule(
+				lua,
+				&format!("{}:{}", prefix, src),
+				&compiler,
+				args,
+				inputs,
+				vec![object.clone()],
+				request.dependencies.clone().unwrap_or_default(),
+			)?;
+			objects.push(object);
+		}
+
+		Ok(objects)
+	}
+
+	/// Register a rule linking objects into a static library, shared library or executable
+	/// Static libraries use $AR (default ar), the others $CC (default cc) unless linker is given
+	/// @return The output path
+	#[lua_table(request: CcLinkOptions {
+		/// Object files, usually the result of cc.compile
+		objs: Vec<String>,
+		/// "static", "shared" or "exe"
+		kind: String,
+		/// Output path
+		out: String,
+		/// Rule name (defaults to the output path)
+		name: Option<String>,
+		/// Linker program
+		linker: Option<String>,
+		/// Extra linker flags
+		flags: Option<Vec<String>>,
+		/// Libraries to link, passed as -l
+		libs: Option<Vec<String>>,
+		/// Rules that must run first
+		dependencies: Option<Vec<String>>,
+	})]
+	fn link(lua: &Lua, request: CcLinkRequest) -> Result<String> {
+		let (program, args) = match request.kind.as_str() {
+			"static" => {
+				let mut args = vec!["rcs".to_string(), request.out.clone()];
+				args.extend(request.objs.iter().cloned());
+				let archiver = request.linker.clone().unwrap_or_else(|| tool_from_env("AR", "ar"));
+				(archiver, args)
+			}
+			"shared" | "exe" => {
+				let mut args = request.flags.clone().unwrap_or_default();
+				if request.kind == "shared" {
+					args.push("-shared".to_string());
+				}
+				args.extend(["-o".to_string(), request.out.clone()]);
+				args.extend(request.objs.iter().cloned());
+				args.extend(request.libs.iter().flatten().map(|lib| format!("-l{}", lib)));
+				let linker = request.linker.clone().unwrap_or_else(|| tool_from_env("CC", "cc"));
+				(linker, args)
+			}
+			other => {
+				return Err(mlua::Error::RuntimeError(format!(
+					"Unknown link kind '{}', expected static, shared or exe",
+					other
+				)));
+			}
+		};
+
+		let name = request.name.clone().unwrap_or_else(|| request.out.clone());
+		register_rule(
+			lua,
+			&name,
+			&program,
+			args,
+			request.objs.clone(),
+			vec![request.out.clone()],
+			request.dependencies.clone().unwrap_or_default(),
+		)?;
+
+		Ok(request.out)
+	}
+}
+
+fn tool_from_env(var: &str, default: &str) -> String {
+	std::env::var(var).unwrap_or_else(|_| default.to_string())
+}
+
+/// Register a rule through forge.rule so it goes through the same validation as rules written in Lua
+pub fn register_rule(
+	lua: &Lua,
+	name: &str,
+	command: &str,
+	args: Vec<String>,
+	inputs: Vec<String>,
+	outputs: Vec<String>,
+	dependencies: Vec<String>,
+) -> Result<()> {
+	let forge: Table = lua.globals().get("forge")?;
+	let rule: Function = forge.get("rule")?;
+
+	let spec = lua.create_table()?;
+	spec.set("name", name)?;
+	spec.set("command", command)?;
+	spec.set("args", args)?;
+	spec.set("inputs", inputs)?;
+	spec.set("outputs", outputs)?;
+	spec.set("dependencies", dependencies)?;
+
+	rule.call::<()>(spec)
+}
+
+forge_lua_module!(cc, CcApi, "C/C++ compile and link rule helpers");
+
+pub fn create_cc_table(lua: &Lua) -> Result<Table> {
+	CcApi::create_cc_table(lua)
+}
@@ -0,0 +1,424 @@
+use crate::lua_api::{cc::register_rule, project_path, sandbox};
+use forge_macros::{LuaClass, forge_lua_module, lua_api};
+use mlua::{FromLua, Lua, LuaSerdeExt, Result, Table, UserData, UserDataMethods, Value};
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::{BTreeMap, HashSet},
+	path::{Path, PathBuf},
+};
+use thiserror::Error;
+use walkdir::WalkDir;
+
+/// Written into the build directory after a successful configure, holding the hash of what it depended on
+const CONFIGURE_STAMP: &str = ".forge-cmake-configure";
+/// Stateless query asking cmake to describe the configured targets through the file API
+const CODEMODEL_QUERY: &str = ".cmake/api/v1/query/codemodel-v2";
+
+#[derive(Error, Debug)]
+pub enum CmakeError {
+	#[error("cmake configure failed for {source_dir}: {reason}")]
+	ConfigureFailed {
+		source_dir: String,
+		reason: String,
+	},
+
+	#[error("Failed to read the cmake file API reply in {build_dir}: {reason}")]
+	CodemodelUnavailable {
+		build_dir: String,
+		reason: String,
+	},
+
+	#[error("cmake target '{target}' not found in {build_dir}")]
+	UnknownTarget {
+		target: String,
+		build_dir: String,
+	},
+}
+
+#[derive(Debug, Deserialize, Serialize, LuaClass)]
+pub struct CmakeConfigureRequest {
+	pub source_dir: Option<String>,
+	pub build_dir: String,
+	pub defines: Option<BTreeMap<String, CmakeDefine>>,
+	pub generator: Option<String>,
+	pub build_type: Option<String>,
+}
+
+impl FromLua for CmakeConfigureRequest {
+	fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+		lua.from_value(value)
+	}
+}
+
+/// Value of a cache entry passed as -D
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum CmakeDefine {
+	Bool(bool),
+	Number(serde_json::Number),
+	String(String),
+}
+
+impl FromLua for CmakeDefine {
+	fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+		lua.from_value(value)
+	}
+}
+
+#[derive(Debug, Deserialize, Serialize, LuaClass)]
+pub struct CmakeBuildRequest {
+	pub build_dir: Option<String>,
+	pub targets: Option<Vec<String>>,
+	pub config: Option<String>,
+	pub name: Option<String>,
+	pub dependencies: Option<Vec<String>>,
+}
+
+impl FromLua for CmakeBuildRequest {
+	fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+		lua.from_value(value)
+	}
+}
+
+/// Build directory of the last cmake.configure, used by cmake.build when no build_dir is given
+struct ConfiguredBuildDir(PathBuf);
+
+#[derive(Deserialize)]
+struct ReplyIndex {
+	objects: Vec<ReplyObject>,
+}
+
+#[derive(Deserialize)]
+struct ReplyObject {
+	kind: String,
+	#[serde(rename = "jsonFile")]
+	json_file: String,
+}
+
+#[derive(Deserialize)]
+struct Codemodel {
+	paths: CodemodelPaths,
+	configurations: Vec<CodemodelConfiguration>,
+}
+
+#[derive(Deserialize)]
+struct CodemodelPaths {
+	source: PathBuf,
+	build: PathBuf,
+}
+
+#[derive(Deserialize)]
+struct CodemodelConfiguration {
+	name: String,
+	targets: Vec<CodemodelTarget>,
+}
+
+#[derive(Deserialize)]
+struct CodemodelTarget {
+	name: String,
+	#[serde(rename = "jsonFile")]
+	json_file: String,
+}
+
+#[derive(Deserialize)]
+struct TargetReply {
+	#[serde(default)]
+	artifacts: Vec<ReplyPath>,
+	#[serde(default)]
+	sources: Vec<ReplyPath>,
+}
+
+#[derive(Deserialize)]
+struct ReplyPath {
+	path: PathBuf,
+}
+
+#[derive(Clone)]
+pub struct CmakeApi;
+
+impl UserData for CmakeApi {
+	fn add_methods<M: UserDataMethods<Self>>(_methods: &mut M) {}
+}
+
+#[lua_api(name = "cmake")]
+impl CmakeApi {
+	pub fn new() -> Self {
+		Self
+	}
+
+	/// Configure a CMake project while the FORGE files are evaluated
+	/// The configure step is skipped when the CMakeLists / *.cmake files, defines, generator and build type are
+	/// unchanged since the last successful run
+	/// @return Absolute path of the build directory
+	#[lua_table(request: CmakeConfigureOptions {
+		/// Directory with the top-level CMakeLists.txt (default ".")
+		source_dir: Option<String>,
+		/// Build directory
+		build_dir: String,
+		/// Cache entries passed as -D; booleans become ON / OFF
+		defines: Option<BTreeMap<String, CmakeDefine>>,
+		/// Generator passed as -G
+		generator: Option<String>,
+		/// CMAKE_BUILD_TYPE
+		build_type: Option<String>,
+	})]
+	fn configure(lua: &Lua, request: CmakeConfigureRequest) -> Result<String> {
+		let source_dir = project_path::resolve(lua, request.source_dir.as_deref().unwrap_or("."))?;
+		let source_dir = source_dir.canonicalize().unwrap_or(source_dir);
+		let build_dir = project_path::resolve(lua, &request.build_dir)?;
+		std::fs::create_dir_all(build_dir.join(CODEMODEL_QUERY).parent().unwrap()).map_err(mlua::Error::external)?;
+		let build_dir = build_dir.canonicalize().unwrap_or(build_dir);
+
+		let mut args = vec![
+			"-S".to_string(),
+			source_dir.to_string_lossy().to_string(),
+			"-B".to_string(),
+			build_dir.to_string_lossy().to_string(),
+		];
+		if let Some(generator) = &request.generator {
+			args.extend(["-G".to_string(), generator.clone()]);
+		}
+		if let Some(build_type) = &request.build_type {
+			args.push(format!("-DCMAKE_BUILD_TYPE={}", build_type));
+		}
+		for (name, value) in request.defines.iter().flatten() {
+			let value = match value {
+				CmakeDefine::Bool(true) => "ON".to_string(),
+				CmakeDefine::Bool(false) => "OFF".to_string(),
+				CmakeDefine::Number(value) => value.to_string(),
+				CmakeDefine::String(value) => value.clone(),
+			};
+			args.push(format!("-D{}={}", name, value));
+		}
+
+		let program = cmake_program();
+		let hash = configure_hash(&program, &args, &source_dir, &build_dir);
+		let stamp = build_dir.join(CONFIGURE_STAMP);
+		let up_to_date = std::fs::read_to_string(&stamp).is_ok_and(|previous| previous.trim() == hash)
+			&& build_dir.join("CMakeCache.txt").exists()
+			&& latest_reply_index(&build_dir).is_some();
+
+		if up_to_date {
+			log::debug!("cmake configure of {} is up to date", source_dir.display());
+		} else {
+			std::fs::write(build_dir.join(CODEMODEL_QUERY), "").map_err(mlua::Error::external)?;
+			log::info!("Configuring cmake project {}", source_dir.display());
+			let configure_error = |reason: String| {
+				mlua::Error::external(CmakeError::ConfigureFailed {
+					source_dir: source_dir.display().to_string(),
+					reason,
+				})
+			};
+			let output = sandbox::command(lua, &program)?
+				.args(&args)
+				.output()
+				.map_err(|e| configure_error(e.to_string()))?;
+			if !output.status.success() {
+				return Err(configure_error(format!(
+					"exited with {}\n{}",
+					output.status,
+					String::from_utf8_lossy(&output.stderr).trim_end()
+				)));
+			}
+			std::fs::write(&stamp, &hash).map_err(mlua::Error::external)?;
+		}
+
+		lua.set_app_data(ConfiguredBuildDir(build_dir.clone()));
+		Ok(build_dir.to_string_lossy().to_string())
+	}
+
+	/// Register a rule running `cmake --build` for targets of a configured project
+	/// Outputs are the artifacts cmake reports for those targets, inputs their sources and CMakeCache.txt
+	/// @return Paths of the produced artifacts
+	#[lua_table(request: CmakeBuildOptions {
+		/// Build directory (defaults to the one of the last cmake.configure)
+		build_dir: Option<String>,
+		/// Targets to build (default: every target that produces an artifact)
+		targets: Option<Vec<String>>,
+		/// Configuration for multi-config generators, passed as --config
+		config: Option<String>,
+		/// Rule name (default "cmake:<build_dir>")
+		name: Option<String>,
+		/// Rules that must run first
+		dependencies: Option<Vec<String>>,
+	})]
+	fn build(lua: &Lua, request: CmakeBuildRequest) -> Result<Vec<String>> {
+		let build_dir = match &request.build_dir {
+			Some(build_dir) => {
+				let build_dir = project_path::resolve(lua, build_dir)?;
+				build_dir.canonicalize().unwrap_or(build_dir)
+			}
+			None => match lua.app_data_ref::<ConfiguredBuildDir>() {
+				Some(configured) => configured.0.clone(),
+				None => {
+					return Err(mlua::Error::RuntimeError(
+						"cmake.build needs a build_dir or a previous cmake.configure".to_string(),
+					));
+				}
+			},
+		};
+
+		let targets = read_targets(&build_dir, request.config.as_deref()).map_err(|reason| {
+			mlua::Error::external(CmakeError::CodemodelUnavailable {
+				build_dir: build_dir.display().to_string(),
+				reason,
+			})
+		})?;
+
+		let selected: Vec<&(String, TargetReply)> = match &request.targets {
+			Some(names) => names
+				.iter()
+				.map(|name| {
+					targets.iter().find(|(target, _)| target == name).ok_or_else(|| {
+						mlua::Error::external(CmakeError::UnknownTarget {
+							target: name.clone(),
+							build_dir: build_dir.display().to_string(),
+						})
+					})
+				})
+				.collect::<Result<_>>()?,
+			None => targets.iter().filter(|(_, reply)| !reply.artifacts.is_empty()).collect(),
+		};
+
+		let mut args = vec![
+			"--build".to_string(),
+			build_dir.to_string_lossy().to_string(),
+			"--target".to_string(),
+		];
+		args.extend(selected.iter().map(|(name, _)| name.clone()));
+		if let Some(config) = &request.config {
+			args.extend(["--config".to_string(), config.clone()]);
+		}
+
+		let mut outputs = Vec::new();
+		let mut inputs = vec![build_dir.join("CMakeCache.txt").to_string_lossy().to_string()];
+		let mut seen = HashSet::new();
+		for (_, reply) in &selected {
+			outputs.extend(
+				reply
+					.artifacts
+					.iter()
+					.map(|artifact| artifact.path.to_string_lossy().to_string()),
+			);
+			for source in &reply.sources {
+				let source = source.path.to_string_lossy().to_string();
+				if seen.insert(source.clone()) {
+					inputs.push(source);
+				}
+			}
+		}
+
+		let name = request
+			.name
+			.clone()
+			.unwrap_or_else(|| format!("cmake:{}", build_dir.display()));
+		register_rule(
+			lua,
+			&name,
+			&cmake_program(),
+			args,
+			inputs,
+			outputs.clone(),
+			request.dependencies.clone().unwrap_or_default(),
+		)?;
+
+		Ok(outputs)
+	}
+}
+
+fn cmake_program() -> String {
+	std::env::var("CMAKE").unwrap_or_else(|_| "cmake".to_string())
+}
+
+/// Hash of the configure command line and every CMakeLists.txt / *.cmake file of the source tree
+fn configure_hash(program: &str, args: &[String], source_dir: &Path, build_dir: &Path) -> String {
+	let mut hasher = blake3::Hasher::new();
+	hasher.update(program.as_bytes());
+	for arg in args {
+		hasher.update(arg.as_bytes());
+		hasher.update(b"\0");
+	}
+
+	let mut files: Vec<PathBuf> = WalkDir::new(source_dir)
+		.into_iter()
+		.filter_entry(|entry| {
+			entry.depth() == 0 || (entry.path() != build_dir && !entry.file_name().to_string_lossy().starts_with('.'))
+		})
+		.filter_map(|entry| entry.ok())
+		.filter(|entry| {
+			let name = entry.file_name().to_string_lossy();
+			entry.file_type().is_file() && (name == "CMakeLists.txt" || name.ends_with(".cmake"))
+		})
+		.map(|entry| entry.into_path())
+		.collect();
+	files.sort();
+
+	for file in files {
+		hasher.update(file.to_string_lossy().as_bytes());
+		if let Ok(content) = std::fs::read(&file) {
+			hasher.update(&content);
+		}
+	}
+	hasher.finalize().to_hex().to_string()
+}
+
+/// The newest index file cmake wrote into the file API reply directory
+fn latest_reply_index(build_dir: &Path) -> Option<PathBuf> {
+	let reply_dir = build_dir.join(".cmake/api/v1/reply");
+	let mut indexes: Vec<PathBuf> = std::fs::read_dir(reply_dir)
+		.ok()?
+		.filter_map(|entry| entry.ok().map(|entry| entry.path()))
+		.filter(|path| {
+			path.file_name()
+				.and_then(|name| name.to_str())
+				.is_some_and(|name| name.starts_with("index-") && name.ends_with(".json"))
+		})
+		.collect();
+	indexes.sort();
+	indexes.pop()
+}
+
+/// Targets of a configuration (the first one when config is not given) with artifacts and sources made absolute
+fn read_targets(build_dir: &Path, config: Option<&str>) -> std::result::Result<Vec<(String, TargetReply)>, String> {
+	let read_json = |path: &Path| -> std::result::Result<serde_json::Value, String> {
+		let content = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+		serde_json::from_str(&content).map_err(|e| format!("{}: {}", path.display(), e))
+	};
+	let reply_dir = build_dir.join(".cmake/api/v1/reply");
+
+	let index_path = latest_reply_index(build_dir).ok_or("no reply index, run cmake.configure first")?;
+	let index: ReplyIndex = serde_json::from_value(read_json(&index_path)?).map_err(|e| e.to_string())?;
+	let codemodel_file = index
+		.objects
+		.iter()
+		.find(|object| object.kind == "codemodel")
+		.ok_or("the reply has no codemodel")?;
+	let codemodel: Codemodel =
+		serde_json::from_value(read_json(&reply_dir.join(&codemodel_file.json_file))?).map_err(|e| e.to_string())?;
+
+	let configuration = match config {
+		Some(config) => codemodel.configurations.iter().find(|c| c.name == config),
+		None => codemodel.configurations.first(),
+	}
+	.ok_or_else(|| format!("configuration '{}' not found", config.unwrap_or_default()))?;
+
+	let mut targets = Vec::new();
+	for target in &configuration.targets {
+		let mut reply: TargetReply =
+			serde_json::from_value(read_json(&reply_dir.join(&target.json_file))?).map_err(|e| e.to_string())?;
+		for artifact in &mut reply.artifacts {
+			artifact.path = codemodel.paths.build.join(&artifact.path);
+		}
+		for source in &mut reply.sources {
+			source.path = codemodel.paths.source.join(&source.path);
+		}
+		targets.push((target.name.clone(), reply));
+	}
+	Ok(targets)
+}
+
+forge_lua_module!(cmake, CmakeApi, "Cached CMake configure and rules that build CMake targets");
+
+pub fn create_cmake_table(lua: &Lua) -> Result<Table> {
+	CmakeApi::create_cmake_table(lua)
+}
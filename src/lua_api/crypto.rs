@@ -0,0 +1,132 @@
+use crate::{lua_api::project_path::ProjectPath, provenance};
+use forge_macros::{forge_lua_module, lua_api};
+use hmac::{Hmac, Mac};
+use mlua::{Lua, Result, Table, UserData, UserDataMethods};
+use sha2::{Digest, Sha256, Sha512};
+use std::{fs::File, io::BufReader, path::Path};
+
+const DEFAULT_PBKDF2_ITERATIONS: u32 = 100_000;
+
+#[derive(Clone)]
+pub struct CryptoApi;
+
+impl UserData for CryptoApi {
+	fn add_methods<M: UserDataMethods<Self>>(_methods: &mut M) {}
+}
+
+#[lua_api(name = "crypto")]
+impl CryptoApi {
+	pub fn new() -> Self {
+		Self
+	}
+
+	/// SHA-256 of a string, as lowercase hex
+	fn sha256(data: mlua::String) -> Result<String> {
+		Ok(to_hex(&Sha256::digest(&*data.as_bytes())))
+	}
+
+	/// SHA-512 of a string, as lowercase hex
+	fn sha512(data: mlua::String) -> Result<String> {
+		Ok(to_hex(&Sha512::digest(&*data.as_bytes())))
+	}
+
+	/// BLAKE3 of a string, as lowercase hex
+	fn blake3(data: mlua::String) -> Result<String> {
+		Ok(blake3::hash(&data.as_bytes()).to_hex().to_string())
+	}
+
+	/// SHA-256 of a file, streamed from disk
+	fn sha256_file(path: ProjectPath) -> Result<String> {
+		digest_file::<Sha256>(&path)
+	}
+
+	/// SHA-512 of a file, streamed from disk
+	fn sha512_file(path: ProjectPath) -> Result<String> {
+		digest_file::<Sha512>(&path)
+	}
+
+	/// BLAKE3 of a file, streamed from disk
+	fn blake3_file(path: ProjectPath) -> Result<String> {
+		let mut hasher = blake3::Hasher::new();
+		let mut reader = BufReader::new(File::open(&*path).map_err(mlua::Error::external)?);
+		std::io::copy(&mut reader, &mut hasher).map_err(mlua::Error::external)?;
+		Ok(hasher.finalize().to_hex().to_string())
+	}
+
+	/// HMAC of message with key, algorithm is "sha256" or "sha512"
+	fn hmac(algorithm: String, key: mlua::String, message: mlua::String) -> Result<String> {
+		match algorithm.as_str() {
+			"sha256" => {
+				let mut mac = Hmac::<Sha256>::new_from_slice(&key.as_bytes()).map_err(mlua::Error::external)?;
+				mac.update(&message.as_bytes());
+				Ok(to_hex(&mac.finalize().into_bytes()))
+			}
+			"sha512" => {
+				let mut mac = Hmac::<Sha512>::new_from_slice(&key.as_bytes()).map_err(mlua::Error::external)?;
+				mac.update(&message.as_bytes());
+				Ok(to_hex(&mac.finalize().into_bytes()))
+			}
+			other => Err(mlua::Error::RuntimeError(format!(
+				"Unsupported HMAC algorithm '{}', expected sha256 or sha512",
+				other
+			))),
+		}
+	}
+
+	/// Derive a key of len bytes from a password with PBKDF2-HMAC-SHA256, as lowercase hex
+	/// @param iterations Number of PBKDF2 rounds (default 100000)
+	fn derive_key(password: mlua::String, salt: mlua::String, len: usize, iterations: Option<u32>) -> Result<String> {
+		if len == 0 {
+			return Err(mlua::Error::RuntimeError("Derived key length must be at least 1".to_string()));
+		}
+
+		let mut key = vec![0u8; len];
+		pbkdf2::pbkdf2_hmac::<Sha256>(
+			&password.as_bytes(),
+			&salt.as_bytes(),
+			iterations.unwrap_or(DEFAULT_PBKDF2_ITERATIONS),
+			&mut key,
+		);
+		Ok(to_hex(&key))
+	}
+
+	/// Sign a file with an ed25519 key given as its hex-encoded 32-byte seed; returns the base64 signature
+	fn sign_file(path: ProjectPath, key: String) -> Result<String> {
+		let key = provenance::signing_key(key.trim()).map_err(mlua::Error::RuntimeError)?;
+		let data = std::fs::read(&*path).map_err(mlua::Error::external)?;
+		Ok(provenance::sign_with(&data, &key))
+	}
+
+	/// Check a base64 signature from sign_file against a file and the hex ed25519 public key of the signer
+	fn verify_file(path: ProjectPath, signature: String, public_key: String) -> Result<bool> {
+		let data = std::fs::read(&*path).map_err(mlua::Error::external)?;
+		provenance::verify_signature(&data, &signature, public_key.trim()).map_err(mlua::Error::RuntimeError)
+	}
+
+	/// Hex public key of an ed25519 key given as its hex-encoded 32-byte seed, to hand to verify_file
+	fn public_key(key: String) -> Result<String> {
+		let key = provenance::signing_key(key.trim()).map_err(mlua::Error::RuntimeError)?;
+		Ok(provenance::public_key(&key))
+	}
+}
+
+fn digest_file<D: Digest + std::io::Write>(path: &Path) -> Result<String> {
+	let mut hasher = D::new();
+	let mut reader = BufReader::new(File::open(path).map_err(mlua::Error::external)?);
+	std::io::copy(&mut reader, &mut hasher).map_err(mlua::Error::external)?;
+	Ok(to_hex(&hasher.finalize()))
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+	bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+forge_lua_module!(
+	crypto,
+	CryptoApi,
+	"SHA-2/BLAKE3 digests, HMAC, key derivation and ed25519 signatures"
+);
+
+pub fn create_crypto_table(lua: &Lua) -> Result<Table> {
+	CryptoApi::create_crypto_table(lua)
+}
@@ -0,0 +1,205 @@
+use crate::lua_api::{project_path, sandbox};
+use forge_macros::{LuaClass, forge_lua_module, lua_api};
+use mlua::{FromLua, Lua, LuaSerdeExt, Result, Table, UserData, UserDataMethods, Value};
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::BTreeMap,
+	process::{Command, Output},
+};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum DockerError {
+	#[error("Container engine not available: {program} - {reason}")]
+	NotAvailable {
+		program: String,
+		reason: String,
+	},
+}
+
+#[derive(Debug, Deserialize, Serialize, LuaClass)]
+pub struct DockerBuildRequest {
+	pub context: String,
+	pub dockerfile: Option<String>,
+	pub tags: Option<Vec<String>>,
+	pub build_args: Option<BTreeMap<String, String>>,
+	pub target: Option<String>,
+	pub platform: Option<String>,
+}
+
+impl FromLua for DockerBuildRequest {
+	fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+		lua.from_value(value)
+	}
+}
+
+#[derive(Debug, Deserialize, Serialize, LuaClass)]
+pub struct DockerMount {
+	pub source: String,
+	pub target: String,
+	pub readonly: Option<bool>,
+}
+
+#[derive(Debug, Deserialize, Serialize, LuaClass)]
+pub struct DockerRunRequest {
+	pub image: String,
+	pub cmd: Option<Vec<String>>,
+	pub mounts: Option<Vec<DockerMount>>,
+	pub env: Option<BTreeMap<String, String>>,
+	pub workdir: Option<String>,
+	pub network: Option<String>,
+	pub remove: Option<bool>,
+}
+
+impl FromLua for DockerRunRequest {
+	fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+		lua.from_value(value)
+	}
+}
+
+#[derive(Clone)]
+pub struct DockerApi;
+
+impl UserData for DockerApi {
+	fn add_methods<M: UserDataMethods<Self>>(_methods: &mut M) {}
+}
+
+#[lua_api(name = "docker")]
+impl DockerApi {
+	pub fn new() -> Self {
+		Self
+	}
+
+	/// Check if a container engine is installed and its daemon is reachable ($DOCKER overrides the program)
+	fn available(lua: &Lua) -> Result<bool> {
+		Ok(sandbox::command(lua, docker_program())?
+			.args(["version", "--format", "{{.Server.Version}}"])
+			.output()
+			.is_ok_and(|output| output.status.success()))
+	}
+
+	/// Build an image; context and dockerfile may be relative to the project root
+	/// @return { success, exit_code, stdout, stderr, image_id }
+	#[lua_table(request: DockerBuildOptions {
+		/// Build context directory
+		context: String,
+		/// Dockerfile path (defaults to context/Dockerfile)
+		dockerfile: Option<String>,
+		/// Tags to apply to the image
+		tags: Option<Vec<String>>,
+		/// Values for ARG instructions
+		build_args: Option<Table>,
+		/// Stage to build in a multi-stage Dockerfile
+		target: Option<String>,
+		/// Target platform, e.g. linux/amd64
+		platform: Option<String>,
+	})]
+	fn build(lua: &Lua, request: DockerBuildRequest) -> Result<Table> {
+		let context = project_path::resolve(lua, &request.context)?;
+		let iid_file = std::env::temp_dir().join(format!("forge-docker-iid-{}", uuid::Uuid::new_v4()));
+
+		let mut cmd = sandbox::command(lua, docker_program())?;
+		cmd.arg("build").arg("--iidfile").arg(&iid_file);
+		if let Some(dockerfile) = &request.dockerfile {
+			cmd.arg("--file").arg(project_path::resolve(lua, dockerfile)?);
+		}
+		for tag in request.tags.iter().flatten() {
+			cmd.args(["--tag", tag.as_str()]);
+		}
+		for (key, value) in request.build_args.iter().flatten() {
+			cmd.arg("--build-arg").arg(format!("{}={}", key, value));
+		}
+		if let Some(target) = &request.target {
+			cmd.args(["--target", target.as_str()]);
+		}
+		if let Some(platform) = &request.platform {
+			cmd.args(["--platform", platform.as_str()]);
+		}
+		cmd.arg(&context);
+
+		let output = run_docker(&mut cmd)?;
+		let result = result_table(lua, &output)?;
+
+		let image_id = std::fs::read_to_string(&iid_file).ok().map(|id| id.trim().to_string());
+		let _ = std::fs::remove_file(&iid_file);
+		result.set("image_id", image_id)?;
+
+		Ok(result)
+	}
+
+	/// Run a command in a container; mount sources may be relative to the project root
+	/// @return { success, exit_code, stdout, stderr }
+	#[lua_table(request: DockerRunOptions {
+		/// Image to run
+		image: String,
+		/// Command and arguments, defaults to the image entrypoint
+		cmd: Option<Vec<String>>,
+		/// Bind mounts as { source, target, readonly }
+		mounts: Option<Vec<Table>>,
+		/// Environment variables
+		env: Option<Table>,
+		/// Working directory inside the container
+		workdir: Option<String>,
+		/// Network to attach to, e.g. "none" or "host"
+		network: Option<String>,
+		/// Remove the container when it exits (default true)
+		remove: Option<bool>,
+	})]
+	fn run(lua: &Lua, request: DockerRunRequest) -> Result<Table> {
+		let mut cmd = sandbox::command(lua, docker_program())?;
+		cmd.arg("run");
+		if request.remove.unwrap_or(true) {
+			cmd.arg("--rm");
+		}
+		for mount in request.mounts.iter().flatten() {
+			let source = project_path::resolve(lua, &mount.source)?;
+			let mut spec = format!("type=bind,source={},target={}", source.display(), mount.target);
+			if mount.readonly.unwrap_or(false) {
+				spec.push_str(",readonly");
+			}
+			cmd.arg("--mount").arg(spec);
+		}
+		for (key, value) in request.env.iter().flatten() {
+			cmd.arg("--env").arg(format!("{}={}", key, value));
+		}
+		if let Some(workdir) = &request.workdir {
+			cmd.args(["--workdir", workdir.as_str()]);
+		}
+		if let Some(network) = &request.network {
+			cmd.args(["--network", network.as_str()]);
+		}
+		cmd.arg(&request.image);
+		cmd.args(request.cmd.iter().flatten());
+
+		let output = run_docker(&mut cmd)?;
+		result_table(lua, &output)
+	}
+}
+
+fn docker_program() -> String {
+	std::env::var("DOCKER").unwrap_or_else(|_| "docker".to_string())
+}
+
+fn run_docker(cmd: &mut Command) -> Result<Output> {
+	cmd.output().map_err(|e| {
+		mlua::Error::external(DockerError::NotAvailable {
+			program: cmd.get_program().to_string_lossy().to_string(),
+			reason: e.to_string(),
+		})
+	})
+}
+
+fn result_table(lua: &Lua, output: &Output) -> Result<Table> {
+	let result = lua.create_table()?;
+	result.set("success", output.status.success())?;
+	result.set("exit_code", output.status.code())?;
+	result.set("stdout", String::from_utf8_lossy(&output.stdout).to_string())?;
+	result.set("stderr", String::from_utf8_lossy(&output.stderr).to_string())?;
+	Ok(result)
+}
+
+forge_lua_module!(docker, DockerApi, "Container image builds and runs");
+
+pub fn create_docker_table(lua: &Lua) -> Result<Table> {
+	DockerApi::create_docker_table(lua)
+}
@@ -1,6 +1,19 @@
-use forge_macros::lua_api;
-use mlua::{Lua, Table, UserData, UserDataMethods};
-use std::process::Command;
+use crate::lua_api::{project_path::ProjectPath, sandbox};
+use forge_macros::{forge_lua_module, lua_api};
+use mlua::{Function, Lua, Table, UserData, UserDataMethods};
+use std::{
+	collections::HashMap,
+	io::{BufRead, BufReader, Read, Write},
+	path::{Path, PathBuf},
+	process::{Child, Command, ExitStatus, Stdio},
+	sync::{
+		Arc, LazyLock, Mutex,
+		atomic::{AtomicBool, Ordering},
+		mpsc,
+	},
+	thread,
+	time::{Duration, Instant},
+};
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -25,8 +38,30 @@ pub enum ExecError {
 	_Timeout {
 		command: String,
 	},
+
+	#[error("Output of {command} exceeded max_output_bytes ({limit})")]
+	OutputLimitExceeded {
+		command: String,
+		limit: usize,
+	},
+
+	#[error("Invalid exec option '{option}': {reason}")]
+	InvalidOption {
+		option: String,
+		reason: String,
+	},
 }
 
+const WAIT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// How long a command gets to exit after kill_signal before it is sent KILL, unless kill_grace says otherwise
+const DEFAULT_KILL_GRACE: Duration = Duration::from_secs(5);
+
+#[cfg(unix)]
+const KILL_SIGNAL: i32 = libc::SIGKILL;
+#[cfg(not(unix))]
+const KILL_SIGNAL: i32 = 0;
+
 #[derive(Clone)]
 pub struct ExecApi;
 
@@ -43,7 +78,7 @@ impl ExecApi {
 	/// Execute command with optional arguments (simple version)
 	fn exec(lua: &Lua, command: String, args: Option<Vec<String>>) -> mlua::Result<Table> {
 		let args = args.unwrap_or_default();
-		let mut cmd = Command::new(&command);
+		let mut cmd = sandbox::command(lua, &command)?;
 		cmd.args(&args);
 
 		let output = cmd.output().map_err(|_| {
@@ -61,58 +96,653 @@ impl ExecApi {
 		Ok(result)
 	}
 
+	/// Find an executable on PATH (trying PATHEXT extensions on Windows)
+	/// Names containing a path separator are checked as given instead of searched
+	/// @return Absolute path of the executable, or nil if it isn't found
+	fn which(name: String) -> mlua::Result<Option<String>> {
+		Ok(find_executable(&name).map(|path| path.to_string_lossy().to_string()))
+	}
+
+	/// Check whether an executable can be found on PATH
+	fn exists(name: String) -> mlua::Result<bool> {
+		Ok(find_executable(&name).is_some())
+	}
+
 	/// Execute command with full configuration table
+	#[lua_table(options: ExecRunOptions {
+		/// Program to run
+		command: String,
+		/// Arguments passed to the program
+		args: Option<Vec<String>>,
+		/// Extra environment variables
+		env: Option<Table>,
+		/// Directory to run the command in
+		working_dir: Option<String>,
+		/// Timeout in seconds, the command's process group is killed when it expires
+		timeout: Option<f64>,
+		/// Signal sent on timeout: "KILL" (default), "TERM", "INT", "HUP" or "QUIT"; Windows always terminates
+		kill_signal: Option<String>,
+		/// Seconds the command gets to exit after kill_signal before it is killed (default 5)
+		kill_grace: Option<f64>,
+		/// Text written to the command's stdin, which is otherwise empty
+		stdin: Option<String>,
+		/// Run command as a shell line through sh -c (cmd /C on Windows), args become $1, $2, ...
+		shell: Option<bool>,
+		/// Capture stdout and stderr into the result (default true); false lets them through to the terminal
+		capture: Option<bool>,
+		/// Kill the command and fail with an error once stdout or stderr grows past this many bytes
+		max_output_bytes: Option<usize>,
+	})]
 	fn run(lua: &Lua, options: Table) -> mlua::Result<Table> {
 		let command: String = options.get("command")?;
-		let args: Vec<String> = options.get("args").unwrap_or_default();
-		let env: Option<Table> = options.get("env").ok();
-		let working_dir: Option<String> = options.get("working_dir").ok();
-		let _timeout: Option<f64> = options.get("timeout").ok();
+		let timeout = parse_timeout(options.get("timeout")?)?;
+		let kill_signal = parse_signal(options.get::<Option<String>>("kill_signal")?.as_deref().unwrap_or("KILL"))?;
+		let kill_grace = parse_timeout(options.get("kill_grace")?)?.unwrap_or(DEFAULT_KILL_GRACE);
+		let mut cmd = build_command(lua, &options)?;
 
-		let mut cmd = Command::new(&command);
-		cmd.args(&args);
+		let stdin: Option<String> = options.get("stdin")?;
+		let capture = options.get::<Option<bool>>("capture")?.unwrap_or(true);
+		let max_output_bytes: Option<usize> = options.get("max_output_bytes")?;
 
-		if let Some(env_table) = env {
-			for pair in env_table.pairs::<String, String>() {
-				let (key, value) = pair?;
-				cmd.env(key, value);
+		// A separate process group lets the timeout and output limit kill everything the command started, not only
+		// the command
+		#[cfg(unix)]
+		{
+			use std::os::unix::process::CommandExt;
+			if timeout.is_some() || max_output_bytes.is_some() {
+				cmd.process_group(0);
 			}
 		}
 
-		if let Some(dir) = working_dir {
-			let dir_path = std::path::Path::new(&dir);
-			if !dir_path.exists() {
-				return Err(mlua::Error::external(ExecError::InvalidWorkingDir { dir: dir.clone() }));
-			}
-			cmd.current_dir(dir);
+		cmd.stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() });
+		if capture {
+			cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
 		}
-
-		// TODO: Implement timeout handling in future
-		// For now, execute without timeout
-		let output = cmd.output().map_err(|e| {
+		let mut child = cmd.spawn().map_err(|e| {
 			mlua::Error::external(ExecError::CommandFailed {
 				command: command.clone(),
 				reason: e.to_string(),
 			})
 		})?;
 
+		// Written from a thread so a command that produces output before reading its input can't deadlock
+		let stdin_writer = child.stdin.take().zip(stdin).map(|(mut pipe, input)| {
+			thread::spawn(move || {
+				let _ = pipe.write_all(input.as_bytes());
+			})
+		});
+		let exceeded = max_output_bytes.map(|_| Arc::new(AtomicBool::new(false)));
+		let stdout = collect_output(child.stdout.take(), max_output_bytes, exceeded.clone());
+		let stderr = collect_output(child.stderr.take(), max_output_bytes, exceeded.clone());
+		let (status, timed_out) = wait_with_timeout(&mut child, timeout, kill_signal, kill_grace, exceeded.as_deref())
+			.map_err(|e| {
+				mlua::Error::external(ExecError::CommandFailed {
+					command: command.clone(),
+					reason: e.to_string(),
+				})
+			})?;
+		if let Some(writer) = stdin_writer {
+			let _ = writer.join();
+		}
+		let stdout = stdout.join().unwrap_or_default();
+		let stderr = stderr.join().unwrap_or_default();
+
+		if let Some(limit) = max_output_bytes
+			&& (stdout.truncated || stderr.truncated)
+		{
+			return Err(mlua::Error::external(ExecError::OutputLimitExceeded { command, limit }));
+		}
+
 		let result = lua.create_table()?;
-		result.set("success", output.status.success())?;
-		result.set("exit_code", output.status.code())?;
-		result.set("stdout", String::from_utf8_lossy(&output.stdout).to_string())?;
-		result.set("stderr", String::from_utf8_lossy(&output.stderr).to_string())?;
+		result.set("success", status.success() && !timed_out)?;
+		result.set("exit_code", status.code())?;
+		result.set("timed_out", timed_out)?;
+		if capture {
+			result.set("stdout", String::from_utf8_lossy(&stdout.bytes).to_string())?;
+			result.set("stderr", String::from_utf8_lossy(&stderr.bytes).to_string())?;
+		}
 
-		if !output.status.success() {
+		if timed_out {
+			result.set("error", format!("Command '{}' timed out", command))?;
+		} else if !status.success() {
 			result.set(
 				"error",
-				format!("Command '{}' failed with exit code {:?}", command, output.status.code()),
+				format!("Command '{}' failed with exit code {:?}", command, status.code()),
 			)?;
 		}
 
 		Ok(result)
 	}
+
+	/// Start a command in the background and return a handle with :pid(), :wait(timeout) and :kill()
+	/// Processes still running when the build ends are killed along with their children
+	#[lua_table(options: ExecSpawnOptions {
+		/// Program to run
+		command: String,
+		/// Arguments passed to the program
+		args: Option<Vec<String>>,
+		/// Extra environment variables
+		env: Option<Table>,
+		/// Directory to run the command in
+		working_dir: Option<String>,
+		/// Run command as a shell line through sh -c (cmd /C on Windows), args become $1, $2, ...
+		shell: Option<bool>,
+		/// File that receives stdout and stderr, output is discarded otherwise
+		log_file: Option<ProjectPath>,
+	})]
+	fn spawn(lua: &Lua, options: Table) -> mlua::Result<ProcessHandle> {
+		let command: String = options.get("command")?;
+		let log_file: Option<ProjectPath> = options.get("log_file")?;
+		let mut cmd = build_command(lua, &options)?;
+
+		// Its own process group, so kill() and the end-of-build cleanup also reach grandchildren
+		#[cfg(unix)]
+		{
+			use std::os::unix::process::CommandExt;
+			cmd.process_group(0);
+		}
+
+		cmd.stdin(Stdio::null());
+		match log_file {
+			Some(path) => {
+				let file = std::fs::OpenOptions::new()
+					.create(true)
+					.append(true)
+					.open(&*path)
+					.map_err(|e| {
+						mlua::Error::external(ExecError::InvalidOption {
+							option: "log_file".to_string(),
+							reason: format!("{}: {}", path.display(), e),
+						})
+					})?;
+				let file_clone = file.try_clone().map_err(mlua::Error::external)?;
+				cmd.stdout(file).stderr(file_clone);
+			}
+			None => {
+				cmd.stdout(Stdio::null()).stderr(Stdio::null());
+			}
+		}
+
+		let child = cmd.spawn().map_err(|e| {
+			mlua::Error::external(ExecError::CommandFailed {
+				command: command.clone(),
+				reason: e.to_string(),
+			})
+		})?;
+
+		let pid = child.id();
+		let child = Arc::new(Mutex::new(child));
+		SPAWNED.lock().unwrap().insert(pid, child.clone());
+
+		Ok(ProcessHandle { pid, command, child })
+	}
+
+	/// Run a command and call on_stdout / on_stderr with each line of output while it runs
+	/// @return { success, exit_code }
+	#[lua_table(options: ExecStreamOptions {
+		/// Program to run
+		command: String,
+		/// Arguments passed to the program
+		args: Option<Vec<String>>,
+		/// Extra environment variables
+		env: Option<Table>,
+		/// Directory to run the command in
+		working_dir: Option<String>,
+		/// Called with each stdout line, without the trailing newline
+		on_stdout: Option<Function>,
+		/// Called with each stderr line, without the trailing newline
+		on_stderr: Option<Function>,
+	})]
+	fn stream(lua: &Lua, options: Table) -> mlua::Result<Table> {
+		let command: String = options.get("command")?;
+		let on_stdout: Option<Function> = options.get("on_stdout")?;
+		let on_stderr: Option<Function> = options.get("on_stderr")?;
+
+		let mut cmd = build_command(lua, &options)?;
+		cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+		let mut child = cmd.spawn().map_err(|e| {
+			mlua::Error::external(ExecError::CommandFailed {
+				command: command.clone(),
+				reason: e.to_string(),
+			})
+		})?;
+
+		// Lines are read on helper threads and handed back here, Lua callbacks must run on this thread
+		let (sender, receiver) = mpsc::channel();
+		let readers = [
+			child
+				.stdout
+				.take()
+				.map(|stdout| forward_lines(stdout, OutputStream::Stdout, sender.clone())),
+			child
+				.stderr
+				.take()
+				.map(|stderr| forward_lines(stderr, OutputStream::Stderr, sender.clone())),
+		];
+		drop(sender);
+
+		let mut callback_error = None;
+		for (stream, line) in receiver {
+			let callback = match stream {
+				OutputStream::Stdout => &on_stdout,
+				OutputStream::Stderr => &on_stderr,
+			};
+			if let Some(callback) = callback
+				&& callback_error.is_none()
+				&& let Err(e) = callback.call::<()>(line)
+			{
+				// Keep draining so the child can't block on a full pipe, then report the error
+				callback_error = Some(e);
+			}
+		}
+
+		for reader in readers.into_iter().flatten() {
+			let _ = reader.join();
+		}
+		let status = child.wait().map_err(|e| {
+			mlua::Error::external(ExecError::CommandFailed {
+				command: command.clone(),
+				reason: e.to_string(),
+			})
+		})?;
+
+		if let Some(e) = callback_error {
+			return Err(e);
+		}
+
+		let result = lua.create_table()?;
+		result.set("success", status.success())?;
+		result.set("exit_code", status.code())?;
+		Ok(result)
+	}
 }
 
+/// Background processes started by exec.spawn that haven't been waited for yet
+static SPAWNED: LazyLock<Mutex<HashMap<u32, Arc<Mutex<Child>>>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Handle returned by exec.spawn
+pub struct ProcessHandle {
+	pid: u32,
+	command: String,
+	child: Arc<Mutex<Child>>,
+}
+
+impl UserData for ProcessHandle {
+	fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+		methods.add_method("pid", |_, this, ()| Ok(this.pid));
+
+		// Returns { success, exit_code } once the process exits, or nil if timeout (seconds) expires first
+		methods.add_method("wait", |lua, this, timeout: Option<f64>| {
+			let deadline = parse_timeout(timeout)?.map(|timeout| Instant::now() + timeout);
+			loop {
+				let status = this.child.lock().unwrap().try_wait().map_err(|e| {
+					mlua::Error::external(ExecError::CommandFailed {
+						command: this.command.clone(),
+						reason: e.to_string(),
+					})
+				})?;
+
+				if let Some(status) = status {
+					SPAWNED.lock().unwrap().remove(&this.pid);
+					let result = lua.create_table()?;
+					result.set("success", status.success())?;
+					result.set("exit_code", status.code())?;
+					return Ok(Some(result));
+				}
+
+				if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+					return Ok(None);
+				}
+				thread::sleep(WAIT_POLL_INTERVAL);
+			}
+		});
+
+		methods.add_method("kill", |_, this, ()| {
+			let mut child = this.child.lock().unwrap();
+			if matches!(child.try_wait(), Ok(None)) {
+				kill_process_group(&mut child, KILL_SIGNAL);
+				let _ = child.wait();
+			}
+			SPAWNED.lock().unwrap().remove(&this.pid);
+			Ok(())
+		});
+	}
+}
+
+/// Kill every process started by exec.spawn that is still running, called when the build ends
+pub fn kill_spawned_processes() {
+	let spawned: Vec<_> = SPAWNED.lock().unwrap().drain().collect();
+	for (pid, child) in spawned {
+		let mut child = child.lock().unwrap();
+		if matches!(child.try_wait(), Ok(None)) {
+			log::debug!("Killing background process {} left running by exec.spawn", pid);
+			kill_process_group(&mut child, KILL_SIGNAL);
+			let _ = child.wait();
+		}
+	}
+}
+
+#[derive(Clone, Copy)]
+enum OutputStream {
+	Stdout,
+	Stderr,
+}
+
+fn forward_lines<R: Read + Send + 'static>(
+	reader: R,
+	stream: OutputStream,
+	sender: mpsc::Sender<(OutputStream, String)>,
+) -> thread::JoinHandle<()> {
+	thread::spawn(move || {
+		let mut reader = BufReader::new(reader);
+		let mut line = Vec::new();
+		while reader.read_until(b'\n', &mut line).is_ok_and(|read| read > 0) {
+			let text = String::from_utf8_lossy(&line);
+			let text = text.trim_end_matches(['\n', '\r']).to_string();
+			if sender.send((stream, text)).is_err() {
+				break;
+			}
+			line.clear();
+		}
+	})
+}
+
+#[derive(Default)]
+struct CollectedOutput {
+	bytes: Vec<u8>,
+	/// More output arrived than the limit allowed, reading stopped there
+	truncated: bool,
+}
+
+/// Read a pipe to the end on a separate thread, keeping at most limit bytes
+/// Reading stops once the limit is passed, raising exceeded so the command is killed instead of running on
+fn collect_output<R: Read + Send + 'static>(
+	reader: Option<R>,
+	limit: Option<usize>,
+	exceeded: Option<Arc<AtomicBool>>,
+) -> thread::JoinHandle<CollectedOutput> {
+	thread::spawn(move || {
+		let mut output = CollectedOutput::default();
+		let Some(mut reader) = reader else {
+			return output;
+		};
+
+		let mut chunk = [0u8; 8192];
+		while let Ok(read) = reader.read(&mut chunk) {
+			if read == 0 {
+				break;
+			}
+			let keep = limit.map_or(read, |limit| read.min(limit.saturating_sub(output.bytes.len())));
+			output.bytes.extend_from_slice(&chunk[..keep]);
+			if keep < read {
+				output.truncated = true;
+				if let Some(exceeded) = &exceeded {
+					exceeded.store(true, Ordering::SeqCst);
+				}
+				break;
+			}
+		}
+		output
+	})
+}
+
+fn parse_timeout(timeout: Option<f64>) -> mlua::Result<Option<Duration>> {
+	match timeout {
+		Some(timeout) if !timeout.is_finite() || timeout < 0.0 => Err(mlua::Error::external(ExecError::InvalidOption {
+			option: "timeout".to_string(),
+			reason: format!("expected a non-negative number of seconds, got {}", timeout),
+		})),
+		Some(timeout) => Ok(Some(Duration::from_secs_f64(timeout))),
+		None => Ok(None),
+	}
+}
+
+/// Map a signal name ("TERM" or "SIGTERM") to its number, 0 on platforms without signals
+fn parse_signal(name: &str) -> mlua::Result<i32> {
+	let upper = name.to_ascii_uppercase();
+	let short = upper.strip_prefix("SIG").unwrap_or(&upper);
+
+	#[cfg(unix)]
+	let signal = match short {
+		"KILL" => Some(libc::SIGKILL),
+		"TERM" => Some(libc::SIGTERM),
+		"INT" => Some(libc::SIGINT),
+		"HUP" => Some(libc::SIGHUP),
+		"QUIT" => Some(libc::SIGQUIT),
+		_ => None,
+	};
+
+	#[cfg(not(unix))]
+	let signal = matches!(short, "KILL" | "TERM" | "INT" | "HUP" | "QUIT").then_some(0);
+
+	signal.ok_or_else(|| {
+		mlua::Error::external(ExecError::InvalidOption {
+			option: "kill_signal".to_string(),
+			reason: format!("unknown signal '{}'", name),
+		})
+	})
+}
+
+/// Wait for child, sending signal to its process group once timeout expires, and KILL when the group is still running
+/// grace later, so a command that traps or ignores signal can't outlive its timeout. The group is killed right away
+/// once output_exceeded is raised
+/// @return The exit status and whether the timeout expired
+fn wait_with_timeout(
+	child: &mut Child,
+	timeout: Option<Duration>,
+	signal: i32,
+	grace: Duration,
+	output_exceeded: Option<&AtomicBool>,
+) -> std::io::Result<(ExitStatus, bool)> {
+	if timeout.is_none() && output_exceeded.is_none() {
+		return child.wait().map(|status| (status, false));
+	}
+
+	if let Some(status) = wait_until(child, timeout.map(|timeout| Instant::now() + timeout), output_exceeded)? {
+		return Ok((status, false));
+	}
+	if output_exceeded.is_some_and(|exceeded| exceeded.load(Ordering::SeqCst)) {
+		kill_process_group(child, KILL_SIGNAL);
+		return child.wait().map(|status| (status, false));
+	}
+
+	kill_process_group(child, signal);
+	if signal != KILL_SIGNAL && wait_until(child, Some(Instant::now() + grace), None)?.is_none() {
+		kill_process_group(child, KILL_SIGNAL);
+	}
+	child.wait().map(|status| (status, true))
+}
+
+/// Poll child until it exits, deadline passes or stop is raised, None when it is still running
+fn wait_until(
+	child: &mut Child,
+	deadline: Option<Instant>,
+	stop: Option<&AtomicBool>,
+) -> std::io::Result<Option<ExitStatus>> {
+	loop {
+		if let Some(status) = child.try_wait()? {
+			return Ok(Some(status));
+		}
+		if stop.is_some_and(|stop| stop.load(Ordering::SeqCst)) {
+			return Ok(None);
+		}
+
+		let now = Instant::now();
+		if deadline.is_some_and(|deadline| now >= deadline) {
+			return Ok(None);
+		}
+		thread::sleep(deadline.map_or(WAIT_POLL_INTERVAL, |deadline| WAIT_POLL_INTERVAL.min(deadline - now)));
+	}
+}
+
+fn kill_process_group(child: &mut Child, signal: i32) {
+	// SAFETY: kill has no memory safety requirements, a negative pid addresses the process group
+	#[cfg(unix)]
+	unsafe {
+		libc::kill(-(child.id() as i32), signal);
+	}
+
+	#[cfg(not(unix))]
+	{
+		let _ = signal;
+		let _ = child.kill();
+	}
+}
+
+pub fn find_executable(name: &str) -> Option<PathBuf> {
+	if name.is_empty() {
+		return None;
+	}
+
+	if name.contains(['/', std::path::MAIN_SEPARATOR]) {
+		return executable_candidates(Path::new(name))
+			.find(|candidate| is_executable(candidate))
+			.and_then(|candidate| std::path::absolute(candidate).ok());
+	}
+
+	let path = std::env::var_os("PATH")?;
+	std::env::split_paths(&path)
+		.filter(|dir| !dir.as_os_str().is_empty())
+		.flat_map(|dir| executable_candidates(&dir.join(name)).collect::<Vec<_>>())
+		.find(|candidate| is_executable(candidate))
+		.and_then(|candidate| std::path::absolute(candidate).ok())
+}
+
+/// The path itself, plus on Windows the path with each PATHEXT extension appended
+fn executable_candidates(path: &Path) -> impl Iterator<Item = PathBuf> {
+	let mut candidates = vec![path.to_path_buf()];
+
+	if cfg!(windows) && path.extension().is_none() {
+		let extensions = std::env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string());
+		candidates.extend(
+			extensions
+				.split(';')
+				.filter(|extension| !extension.is_empty())
+				.map(|extension| {
+					let mut candidate = path.as_os_str().to_os_string();
+					candidate.push(extension);
+					PathBuf::from(candidate)
+				}),
+		);
+	}
+
+	candidates.into_iter()
+}
+
+fn is_executable(path: &Path) -> bool {
+	let Ok(metadata) = std::fs::metadata(path) else {
+		return false;
+	};
+
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::PermissionsExt;
+		metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+	}
+
+	#[cfg(not(unix))]
+	{
+		metadata.is_file()
+	}
+}
+
+/// Build a Command from the command, args, env and working_dir fields shared by run and stream
+fn build_command(lua: &Lua, options: &Table) -> mlua::Result<Command> {
+	let command: String = options.get("command")?;
+	let args: Vec<String> = options.get("args").unwrap_or_default();
+	let env: Option<Table> = options.get("env").ok();
+	let working_dir: Option<String> = options.get("working_dir").ok();
+
+	let shell = options.get::<Option<bool>>("shell")?.unwrap_or(false);
+
+	let mut cmd = if shell {
+		shell_command(lua, &command, &args)?
+	} else {
+		let mut cmd = sandbox::command(lua, &command)?;
+		cmd.args(&args);
+		cmd
+	};
+
+	if let Some(env_table) = env {
+		for pair in env_table.pairs::<String, String>() {
+			let (key, value) = pair?;
+			cmd.env(key, value);
+		}
+	}
+
+	if let Some(dir) = working_dir {
+		let dir_path = std::path::Path::new(&dir);
+		if !dir_path.exists() {
+			return Err(mlua::Error::external(ExecError::InvalidWorkingDir { dir: dir.clone() }));
+		}
+		cmd.current_dir(dir);
+	}
+
+	Ok(cmd)
+}
+
+#[cfg(unix)]
+fn shell_command(lua: &Lua, line: &str, args: &[String]) -> mlua::Result<Command> {
+	let mut cmd = sandbox::command(lua, "sh")?;
+	// "sh" fills $0 so args land in $1, $2, ...
+	cmd.arg("-c").arg(line).arg("sh").args(args);
+	Ok(cmd)
+}
+
+#[cfg(windows)]
+fn shell_command(lua: &Lua, line: &str, args: &[String]) -> mlua::Result<Command> {
+	let mut cmd = sandbox::command(lua, "cmd")?;
+	cmd.arg("/C").arg(line).args(args);
+	Ok(cmd)
+}
+
+forge_lua_module!(exec, ExecApi, "Command execution operations");
+
 pub fn create_exec_table(lua: &Lua) -> mlua::Result<Table> {
 	ExecApi::create_exec_table(lua)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[cfg(unix)]
+	#[test]
+	fn test_timeout_escalates_to_kill() {
+		use std::os::unix::process::{CommandExt, ExitStatusExt};
+
+		// An ignored TERM is inherited by sleep, so only the KILL after the grace period stops the group
+		let mut child = Command::new("sh")
+			.args(["-c", "trap '' TERM; sleep 30"])
+			.process_group(0)
+			.spawn()
+			.unwrap();
+		let start = Instant::now();
+		let (status, timed_out) = wait_with_timeout(
+			&mut child,
+			Some(Duration::from_millis(100)),
+			libc::SIGTERM,
+			Duration::from_millis(200),
+			None,
+		)
+		.unwrap();
+
+		assert!(timed_out);
+		assert_eq!(status.signal(), Some(libc::SIGKILL));
+		assert!(start.elapsed() < Duration::from_secs(10));
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn test_output_limit_kills_command() {
+		let lua = Lua::new();
+		lua.globals().set("exec", create_exec_table(&lua).unwrap()).unwrap();
+
+		// yes never exits on its own, only the limit stops it
+		let error = lua
+			.load(r#"return exec.run({ command = "yes", max_output_bytes = 4096 })"#)
+			.exec()
+			.unwrap_err();
+		assert!(error.to_string().contains("exceeded max_output_bytes (4096)"));
+	}
+}
@@ -1,8 +1,15 @@
+use crate::eval_cache::Observation;
+use crate::lua_api::{
+	archive::{self, ArchiveFilter},
+	observations,
+	project_path::{ProjectPath, ProjectRoot},
+};
 use anyhow::Result;
-use forge_macros::lua_api;
+use forge_macros::{forge_lua_module, lua_api};
 use mlua::{Lua, Table, UserData, UserDataMethods};
 use std::{
 	fs,
+	io::{Read, Seek, SeekFrom, Write},
 	path::{Path, PathBuf},
 	time::SystemTime,
 };
@@ -52,34 +59,15 @@ impl UserData for FsApi {
 	fn add_methods<M: UserDataMethods<Self>>(_methods: &mut M) {}
 }
 
-fn validate_path(path: &str) -> Result<PathBuf, FsError> {
-	if path.is_empty() {
-		return Err(FsError::InvalidPath {
-			path: path.to_string(),
-			reason: "Path cannot be empty".to_string(),
-		});
-	}
-
-	let path_buf = PathBuf::from(path);
-	if !path_buf.is_absolute() {
-		return Err(FsError::InvalidPath {
-			path: path.to_string(),
-			reason: "Path must be absolute. Use forge.path.join() to build absolute paths".to_string(),
-		});
-	}
-
-	Ok(path_buf)
-}
-
 #[lua_api(name = "fs")]
 impl FsApi {
 	pub fn new() -> Self {
 		Self
 	}
 
-	/// Read file contents as string (path must be absolute)
-	fn read(path: String) -> mlua::Result<String> {
-		let path = validate_path(&path).map_err(mlua::Error::external)?;
+	/// Read file contents as string (absolute or relative to the project root)
+	fn read(path: ProjectPath) -> mlua::Result<String> {
+		let path = path.into_path_buf();
 
 		if !path.exists() {
 			return Err(mlua::Error::external(FsError::PathNotFound {
@@ -101,9 +89,9 @@ impl FsApi {
 		})
 	}
 
-	/// Write string content to file (path must be absolute)
-	fn write(path: String, content: String) -> mlua::Result<()> {
-		let path = validate_path(&path).map_err(mlua::Error::external)?;
+	/// Write string content to file (absolute or relative to the project root)
+	fn write(path: ProjectPath, content: String) -> mlua::Result<()> {
+		let path = path.into_path_buf();
 
 		if let Some(parent) = path.parent() {
 			fs::create_dir_all(parent).map_err(|_| {
@@ -120,9 +108,143 @@ impl FsApi {
 		})
 	}
 
-	/// Create directory and all parent directories (path must be absolute)
-	fn mkdir(path: String) -> mlua::Result<()> {
-		let path = validate_path(&path).map_err(mlua::Error::external)?;
+	/// Read file contents as raw bytes, returned as a Lua string (absolute or relative to the project root)
+	fn read_bytes(lua: &Lua, path: ProjectPath) -> mlua::Result<mlua::String> {
+		let path = path.into_path_buf();
+
+		if !path.is_file() {
+			return Err(mlua::Error::external(FsError::PathNotFound {
+				path: path.to_string_lossy().to_string(),
+			}));
+		}
+
+		let bytes = fs::read(&path).map_err(|_| {
+			mlua::Error::external(FsError::PermissionDenied {
+				path: path.to_string_lossy().to_string(),
+			})
+		})?;
+		lua.create_string(bytes)
+	}
+
+	/// Write a Lua string to file byte for byte (absolute or relative to the project root)
+	fn write_bytes(path: ProjectPath, data: mlua::String) -> mlua::Result<()> {
+		let path = path.into_path_buf();
+		create_parent_dirs(&path)?;
+
+		fs::write(&path, &*data.as_bytes()).map_err(|_| {
+			mlua::Error::external(FsError::PermissionDenied {
+				path: path.to_string_lossy().to_string(),
+			})
+		})
+	}
+
+	/// Write to a temporary file next to path and rename it into place
+	/// Readers see either the old or the new content, never a partial write
+	fn write_atomic(path: ProjectPath, content: mlua::String) -> mlua::Result<()> {
+		let path = path.into_path_buf();
+		create_parent_dirs(&path)?;
+
+		let file_name = path
+			.file_name()
+			.map(|name| name.to_string_lossy().to_string())
+			.ok_or_else(|| {
+				mlua::Error::external(FsError::InvalidPath {
+					path: path.to_string_lossy().to_string(),
+					reason: "Path has no file name".to_string(),
+				})
+			})?;
+		let temp_path = path.with_file_name(format!(".{}.{}.tmp", file_name, uuid::Uuid::new_v4()));
+
+		let result = fs::File::create(&temp_path)
+			.and_then(|mut file| {
+				file.write_all(&content.as_bytes())?;
+				file.sync_all()
+			})
+			.and_then(|_| fs::rename(&temp_path, &path));
+
+		if result.is_err() {
+			let _ = fs::remove_file(&temp_path);
+		}
+		result.map_err(|_| {
+			mlua::Error::external(FsError::PermissionDenied {
+				path: path.to_string_lossy().to_string(),
+			})
+		})
+	}
+
+	/// Run callback while holding an exclusive advisory lock on "<path>.lock", waiting for other holders
+	/// Other forge rules and processes that use with_lock on the same path are serialized
+	/// @return Whatever callback returns
+	fn with_lock(path: ProjectPath, callback: mlua::Function) -> mlua::Result<mlua::MultiValue> {
+		let path = path.into_path_buf();
+		create_parent_dirs(&path)?;
+
+		let mut lock_path = path.into_os_string();
+		lock_path.push(".lock");
+		let lock_path = PathBuf::from(lock_path);
+
+		let lock_file = fs::OpenOptions::new()
+			.create(true)
+			.truncate(false)
+			.write(true)
+			.open(&lock_path)
+			.map_err(|_| {
+				mlua::Error::external(FsError::PermissionDenied {
+					path: lock_path.to_string_lossy().to_string(),
+				})
+			})?;
+		lock_file.lock().map_err(|e| {
+			mlua::Error::external(FsError::InvalidPath {
+				path: lock_path.to_string_lossy().to_string(),
+				reason: format!("Failed to acquire lock: {}", e),
+			})
+		})?;
+
+		// The lock is released when lock_file is dropped, also when callback errors
+		callback.call::<mlua::MultiValue>(())
+	}
+
+	/// Append to a file, creating it if missing (absolute or relative to the project root)
+	fn append(path: ProjectPath, data: mlua::String) -> mlua::Result<()> {
+		let path = path.into_path_buf();
+		create_parent_dirs(&path)?;
+
+		fs::OpenOptions::new()
+			.create(true)
+			.append(true)
+			.open(&path)
+			.and_then(|mut file| file.write_all(&data.as_bytes()))
+			.map_err(|_| {
+				mlua::Error::external(FsError::PermissionDenied {
+					path: path.to_string_lossy().to_string(),
+				})
+			})
+	}
+
+	/// Read up to len bytes starting at offset, shorter at the end of the file
+	fn read_range(lua: &Lua, path: ProjectPath, offset: u64, len: u64) -> mlua::Result<mlua::String> {
+		let path = path.into_path_buf();
+
+		let mut file = fs::File::open(&path).map_err(|_| {
+			mlua::Error::external(FsError::PathNotFound {
+				path: path.to_string_lossy().to_string(),
+			})
+		})?;
+
+		let mut bytes = Vec::new();
+		file.seek(SeekFrom::Start(offset))
+			.and_then(|_| file.take(len).read_to_end(&mut bytes))
+			.map_err(|_| {
+				mlua::Error::external(FsError::PermissionDenied {
+					path: path.to_string_lossy().to_string(),
+				})
+			})?;
+		lua.create_string(bytes)
+	}
+
+	/// Create directory and all parent directories (absolute or relative to the project root)
+	fn mkdir(path: ProjectPath) -> mlua::Result<()> {
+		let path = path.into_path_buf();
 
 		fs::create_dir_all(&path).map_err(|_| {
 			mlua::Error::external(FsError::PermissionDenied {
@@ -131,9 +253,18 @@ impl FsApi {
 		})
 	}
 
-	/// Find files matching glob pattern (pattern must be absolute)
-	fn glob(pattern: String) -> mlua::Result<Vec<String>> {
-		let paths: Vec<String> = glob::glob(&pattern)
+	/// Find files matching glob pattern (relative patterns are matched from the project root)
+	#[lua_table(options: FsGlobOptions {
+		/// Return paths relative to the project root, as rule inputs expect (default false)
+		relative: Option<bool>,
+	})]
+	fn glob(lua: &Lua, pattern: ProjectPath, options: Option<Table>) -> mlua::Result<Vec<String>> {
+		let relative = options
+			.as_ref()
+			.and_then(|opts| opts.get::<Option<bool>>("relative").ok().flatten())
+			.unwrap_or(false);
+		let pattern = pattern.to_string_lossy().to_string();
+		let paths: Vec<PathBuf> = glob::glob(&pattern)
 			.map_err(|e| {
 				mlua::Error::external(FsError::InvalidGlobPattern {
 					pattern: pattern.clone(),
@@ -141,20 +272,22 @@ impl FsApi {
 				})
 			})?
 			.filter_map(|res| res.ok())
-			.map(|p| p.to_string_lossy().to_string())
 			.collect();
-		Ok(paths)
+		observations::record(lua, Observation::glob(&pattern, &paths));
+		Ok(paths.iter().map(|p| output_path(lua, p, relative)).collect())
 	}
 
-	/// Check if file or directory exists (path must be absolute)
-	fn exists(path: String) -> mlua::Result<bool> {
-		let path = validate_path(&path).map_err(mlua::Error::external)?;
-		Ok(path.exists())
+	/// Check if file or directory exists (absolute or relative to the project root)
+	fn exists(lua: &Lua, path: ProjectPath) -> mlua::Result<bool> {
+		let path = path.into_path_buf();
+		let exists = path.exists();
+		observations::record(lua, Observation::Exists { path, exists });
+		Ok(exists)
 	}
 
-	/// Get modification time as Unix timestamp (path must be absolute)
-	fn mtime(path: String) -> mlua::Result<Option<u64>> {
-		let path = validate_path(&path).map_err(mlua::Error::external)?;
+	/// Get modification time as Unix timestamp (absolute or relative to the project root)
+	fn mtime(path: ProjectPath) -> mlua::Result<Option<u64>> {
+		let path = path.into_path_buf();
 
 		if !path.exists() {
 			return Ok(None);
@@ -175,10 +308,42 @@ impl FsApi {
 		Ok(None)
 	}
 
-	/// Copy file from source to destination (both paths must be absolute)
-	fn copy(src: String, dest: String) -> mlua::Result<()> {
-		let src_path = validate_path(&src).map_err(mlua::Error::external)?;
-		let dest_path = validate_path(&dest).map_err(mlua::Error::external)?;
+	/// Copy file from source to destination (absolute or relative to the project root)
+	#[lua_table(options: FsCopyOptions {
+		/// Copy the file a symlink points to rather than the link itself (default true)
+		follow_symlinks: Option<bool>,
+	})]
+	fn copy(src: ProjectPath, dest: ProjectPath, options: Option<Table>) -> mlua::Result<()> {
+		let src_path = src.into_path_buf();
+		let dest_path = dest.into_path_buf();
+
+		let follow_symlinks = options
+			.as_ref()
+			.and_then(|opts| opts.get::<Option<bool>>("follow_symlinks").ok().flatten())
+			.unwrap_or(true);
+
+		if !follow_symlinks && src_path.is_symlink() {
+			let target = fs::read_link(&src_path).map_err(|_| {
+				mlua::Error::external(FsError::PermissionDenied {
+					path: src_path.to_string_lossy().to_string(),
+				})
+			})?;
+			if let Some(parent) = dest_path.parent() {
+				fs::create_dir_all(parent).map_err(|_| {
+					mlua::Error::external(FsError::PermissionDenied {
+						path: parent.to_string_lossy().to_string(),
+					})
+				})?;
+			}
+			if dest_path.symlink_metadata().is_ok() {
+				fs::remove_file(&dest_path).map_err(|_| {
+					mlua::Error::external(FsError::PermissionDenied {
+						path: dest_path.to_string_lossy().to_string(),
+					})
+				})?;
+			}
+			return create_symlink(&target, &dest_path);
+		}
 
 		if !src_path.exists() {
 			return Err(mlua::Error::external(FsError::PathNotFound {
@@ -202,10 +367,104 @@ impl FsApi {
 		Ok(())
 	}
 
-	/// Move/rename file from source to destination (both paths must be absolute)
-	fn move_file(src: String, dest: String) -> mlua::Result<()> {
-		let src_path = validate_path(&src).map_err(mlua::Error::external)?;
-		let dest_path = validate_path(&dest).map_err(mlua::Error::external)?;
+	/// Recursively copy a directory, preserving permissions and recreating symlinks
+	/// include/exclude globs are matched against paths relative to src, an excluded directory is skipped entirely
+	/// @return Number of files copied
+	#[lua_table(options: FsCopyDirOptions {
+		/// Directory to copy
+		src: ProjectPath,
+		/// Destination directory, created if missing
+		dest: ProjectPath,
+		/// Only copy files matching one of these globs
+		include: Option<Vec<String>>,
+		/// Skip files and directories matching one of these globs
+		exclude: Option<Vec<String>>,
+		/// Replace files that already exist in dest (default true)
+		overwrite: Option<bool>,
+	})]
+	fn copy_dir(options: Table) -> mlua::Result<u64> {
+		let src_path = options.get::<ProjectPath>("src")?.into_path_buf();
+		let dest_path = options.get::<ProjectPath>("dest")?.into_path_buf();
+		let include = compile_globs(options.get::<Option<Vec<String>>>("include")?)?;
+		let exclude = compile_globs(options.get::<Option<Vec<String>>>("exclude")?)?;
+		let overwrite = options.get::<Option<bool>>("overwrite")?.unwrap_or(true);
+
+		if !src_path.is_dir() {
+			return Err(mlua::Error::external(FsError::InvalidPath {
+				path: src_path.to_string_lossy().to_string(),
+				reason: "Path is not a directory".to_string(),
+			}));
+		}
+
+		let mut copied = 0;
+		let mut walker = WalkDir::new(&src_path).min_depth(1).into_iter();
+		while let Some(entry) = walker.next() {
+			let entry = entry.map_err(|e| {
+				mlua::Error::external(FsError::PermissionDenied {
+					path: e.path().unwrap_or(&src_path).to_string_lossy().to_string(),
+				})
+			})?;
+			let relative = entry.path().strip_prefix(&src_path).unwrap_or(entry.path());
+			let relative_str = relative.to_string_lossy().replace('\\', "/");
+
+			if exclude.iter().any(|pattern| pattern.matches(&relative_str)) {
+				if entry.file_type().is_dir() {
+					walker.skip_current_dir();
+				}
+				continue;
+			}
+
+			let target = dest_path.join(relative);
+			if entry.file_type().is_dir() {
+				continue;
+			}
+			if !include.is_empty() && !include.iter().any(|pattern| pattern.matches(&relative_str)) {
+				continue;
+			}
+			if !overwrite && target.symlink_metadata().is_ok() {
+				continue;
+			}
+
+			if let Some(parent) = target.parent() {
+				fs::create_dir_all(parent).map_err(|_| {
+					mlua::Error::external(FsError::PermissionDenied {
+						path: parent.to_string_lossy().to_string(),
+					})
+				})?;
+			}
+
+			if entry.file_type().is_symlink() {
+				let link = fs::read_link(entry.path()).map_err(|_| {
+					mlua::Error::external(FsError::PermissionDenied {
+						path: entry.path().to_string_lossy().to_string(),
+					})
+				})?;
+				if target.symlink_metadata().is_ok() {
+					fs::remove_file(&target).map_err(|_| {
+						mlua::Error::external(FsError::PermissionDenied {
+							path: target.to_string_lossy().to_string(),
+						})
+					})?;
+				}
+				create_symlink(&link, &target)?;
+			} else {
+				fs::copy(entry.path(), &target).map_err(|_| {
+					mlua::Error::external(FsError::PermissionDenied {
+						path: entry.path().to_string_lossy().to_string(),
+					})
+				})?;
+			}
+			copied += 1;
+		}
+
+		Ok(copied)
+	}
+
+	/// Move/rename file from source to destination (absolute or relative to the project root)
+	#[lua(name = "move")]
+	fn move_path(src: ProjectPath, dest: ProjectPath) -> mlua::Result<()> {
+		let src_path = src.into_path_buf();
+		let dest_path = dest.into_path_buf();
 
 		if !src_path.exists() {
 			return Err(mlua::Error::external(FsError::PathNotFound {
@@ -229,9 +488,15 @@ impl FsApi {
 		Ok(())
 	}
 
-	/// Remove file or empty directory (path must be absolute)
-	fn remove(path: String) -> mlua::Result<()> {
-		let path = validate_path(&path).map_err(mlua::Error::external)?;
+	/// Move/rename file from source to destination, the former name of fs.move
+	#[lua(deprecated = "use fs.move")]
+	fn move_file(src: ProjectPath, dest: ProjectPath) -> mlua::Result<()> {
+		Self::move_path(src, dest)
+	}
+
+	/// Remove file or empty directory (absolute or relative to the project root)
+	fn remove(path: ProjectPath) -> mlua::Result<()> {
+		let path = path.into_path_buf();
 
 		if !path.exists() {
 			return Err(mlua::Error::external(FsError::PathNotFound {
@@ -261,9 +526,9 @@ impl FsApi {
 		Ok(())
 	}
 
-	/// Remove directory and all its contents (path must be absolute)
-	fn remove_dir(path: String) -> mlua::Result<()> {
-		let path = validate_path(&path).map_err(mlua::Error::external)?;
+	/// Remove directory and all its contents (absolute or relative to the project root)
+	fn remove_dir(path: ProjectPath) -> mlua::Result<()> {
+		let path = path.into_path_buf();
 
 		if !path.exists() {
 			return Err(mlua::Error::external(FsError::PathNotFound {
@@ -278,21 +543,136 @@ impl FsApi {
 		})
 	}
 
-	/// Check if path is a file (path must be absolute)
-	fn is_file(path: String) -> mlua::Result<bool> {
-		let path = validate_path(&path).map_err(mlua::Error::external)?;
+	/// Check if path is a file (absolute or relative to the project root)
+	fn is_file(path: ProjectPath) -> mlua::Result<bool> {
+		let path = path.into_path_buf();
 		Ok(path.is_file())
 	}
 
-	/// Check if path is a directory (path must be absolute)
-	fn is_dir(path: String) -> mlua::Result<bool> {
-		let path = validate_path(&path).map_err(mlua::Error::external)?;
+	/// Check if path is a directory (absolute or relative to the project root)
+	fn is_dir(path: ProjectPath) -> mlua::Result<bool> {
+		let path = path.into_path_buf();
 		Ok(path.is_dir())
 	}
 
-	/// Walk directory tree (path must be absolute)
-	fn walk(path: String, options: Option<Table>) -> mlua::Result<Vec<String>> {
-		let path = validate_path(&path).map_err(mlua::Error::external)?;
+	/// Create a symlink at dest pointing to src
+	/// src is stored as given, so a relative src is resolved from the link's directory
+	fn symlink(src: String, dest: ProjectPath) -> mlua::Result<()> {
+		let dest_path = dest.into_path_buf();
+
+		if let Some(parent) = dest_path.parent() {
+			fs::create_dir_all(parent).map_err(|_| {
+				mlua::Error::external(FsError::PermissionDenied {
+					path: parent.to_string_lossy().to_string(),
+				})
+			})?;
+		}
+
+		create_symlink(Path::new(&src), &dest_path)
+	}
+
+	/// Read the target of a symlink (absolute or relative to the project root)
+	fn read_link(path: ProjectPath) -> mlua::Result<String> {
+		let path = path.into_path_buf();
+
+		if !path.is_symlink() {
+			return Err(mlua::Error::external(FsError::InvalidPath {
+				path: path.to_string_lossy().to_string(),
+				reason: "Path is not a symlink".to_string(),
+			}));
+		}
+
+		let target = fs::read_link(&path).map_err(|_| {
+			mlua::Error::external(FsError::PermissionDenied {
+				path: path.to_string_lossy().to_string(),
+			})
+		})?;
+		Ok(target.to_string_lossy().to_string())
+	}
+
+	/// Check if path is a symlink, without following it (absolute or relative to the project root)
+	fn is_symlink(path: ProjectPath) -> mlua::Result<bool> {
+		let path = path.into_path_buf();
+		Ok(path.is_symlink())
+	}
+
+	/// Set permission bits, mode is octal like chmod takes it: a string such as "755" or the number 755
+	/// On Windows only the read-only flag is derived from the owner write bit
+	fn chmod(path: ProjectPath, mode: mlua::Value) -> mlua::Result<()> {
+		let path = path.into_path_buf();
+		let mode = parse_mode(&mode)?;
+		set_mode(&path, mode)
+	}
+
+	/// Mark a file executable for everyone who can read it (no-op on Windows)
+	fn set_executable(path: ProjectPath) -> mlua::Result<()> {
+		let path = path.into_path_buf();
+		let metadata = read_metadata(&path)?;
+
+		#[cfg(unix)]
+		{
+			use std::os::unix::fs::PermissionsExt;
+			let mode = metadata.permissions().mode() & 0o7777;
+			set_mode(&path, mode | ((mode & 0o444) >> 2))
+		}
+
+		#[cfg(not(unix))]
+		{
+			let _ = metadata;
+			Ok(())
+		}
+	}
+
+	/// Get file size in bytes (absolute or relative to the project root)
+	fn size(path: ProjectPath) -> mlua::Result<u64> {
+		let path = path.into_path_buf();
+		Ok(read_metadata(&path)?.len())
+	}
+
+	/// Get file metadata (absolute or relative to the project root)
+	/// @return { size, mtime, mode, is_file, is_dir, is_symlink }, mode is nil on Windows
+	fn metadata(lua: &Lua, path: ProjectPath) -> mlua::Result<Table> {
+		let path = path.into_path_buf();
+		let metadata = read_metadata(&path)?;
+
+		let table = lua.create_table()?;
+		table.set("size", metadata.len())?;
+		table.set(
+			"mtime",
+			metadata
+				.modified()
+				.ok()
+				.and_then(|modified| modified.duration_since(SystemTime::UNIX_EPOCH).ok())
+				.map(|duration| duration.as_secs()),
+		)?;
+		table.set("mode", file_mode(&metadata))?;
+		table.set("is_file", metadata.is_file())?;
+		table.set("is_dir", metadata.is_dir())?;
+		table.set("is_symlink", path.is_symlink())?;
+		Ok(table)
+	}
+
+	/// Walk directory tree (absolute or relative to the project root)
+	/// include/exclude globs are matched against paths relative to the walked directory
+	/// @return Entries as { path, is_dir, size, mtime }, not including the directory itself
+	#[lua_table(options: FsWalkOptions {
+		/// Descend into subdirectories (default true)
+		recursive: Option<bool>,
+		/// Maximum depth to descend, 1 lists only direct children
+		max_depth: Option<usize>,
+		/// Only return entries matching one of these globs (directories are still descended)
+		include: Option<Vec<String>>,
+		/// Skip entries matching one of these globs, an excluded directory is not descended
+		exclude: Option<Vec<String>>,
+		/// Descend into symlinked directories (default false)
+		follow_symlinks: Option<bool>,
+		/// Leave directories out of the results (default false)
+		files_only: Option<bool>,
+		/// Return paths relative to the project root, as rule inputs expect (default false)
+		relative: Option<bool>,
+	})]
+	fn walk(lua: &Lua, path: ProjectPath, options: Option<Table>) -> mlua::Result<Vec<Table>> {
+		let path = path.into_path_buf();
 
 		if !path.exists() {
 			return Err(mlua::Error::external(FsError::PathNotFound {
@@ -311,19 +691,75 @@ impl FsApi {
 			.as_ref()
 			.and_then(|opts| opts.get::<Option<bool>>("recursive").ok().flatten())
 			.unwrap_or(true);
-
-		let mut walker = WalkDir::new(&path);
+		let max_depth = options
+			.as_ref()
+			.and_then(|opts| opts.get::<Option<usize>>("max_depth").ok().flatten());
+		let follow_symlinks = options
+			.as_ref()
+			.and_then(|opts| opts.get::<Option<bool>>("follow_symlinks").ok().flatten())
+			.unwrap_or(false);
+		let files_only = options
+			.as_ref()
+			.and_then(|opts| opts.get::<Option<bool>>("files_only").ok().flatten())
+			.unwrap_or(false);
+		let relative_paths = options
+			.as_ref()
+			.and_then(|opts| opts.get::<Option<bool>>("relative").ok().flatten())
+			.unwrap_or(false);
+		let include = compile_globs(
+			options
+				.as_ref()
+				.and_then(|opts| opts.get::<Option<Vec<String>>>("include").ok().flatten()),
+		)?;
+		let exclude = compile_globs(
+			options
+				.as_ref()
+				.and_then(|opts| opts.get::<Option<Vec<String>>>("exclude").ok().flatten()),
+		)?;
+
+		let mut walker = WalkDir::new(&path).min_depth(1).follow_links(follow_symlinks);
 		if !recursive {
 			walker = walker.max_depth(1);
+		} else if let Some(max_depth) = max_depth {
+			walker = walker.max_depth(max_depth);
 		}
 
-		let paths: Vec<String> = walker
-			.into_iter()
-			.filter_map(|entry| entry.ok())
-			.map(|entry| entry.path().to_string_lossy().to_string())
-			.collect();
+		let mut entries = Vec::new();
+		let mut walker = walker.into_iter();
+		while let Some(entry) = walker.next() {
+			let Ok(entry) = entry else {
+				continue;
+			};
+			let relative = entry.path().strip_prefix(&path).unwrap_or(entry.path());
+			let relative = relative.to_string_lossy().replace('\\', "/");
+			let is_dir = entry.file_type().is_dir();
+
+			if exclude.iter().any(|pattern| pattern.matches(&relative)) {
+				if is_dir {
+					walker.skip_current_dir();
+				}
+				continue;
+			}
+			if (files_only && is_dir) || (!include.is_empty() && !include.iter().any(|pattern| pattern.matches(&relative))) {
+				continue;
+			}
 
-		Ok(paths)
+			let metadata = entry.metadata().ok();
+			let table = lua.create_table()?;
+			table.set("path", output_path(lua, entry.path(), relative_paths))?;
+			table.set("is_dir", is_dir)?;
+			table.set("size", metadata.as_ref().map(|metadata| metadata.len()))?;
+			table.set(
+				"mtime",
+				metadata
+					.and_then(|metadata| metadata.modified().ok())
+					.and_then(|modified| modified.duration_since(SystemTime::UNIX_EPOCH).ok())
+					.map(|duration| duration.as_secs()),
+			)?;
+			entries.push(table);
+		}
+
+		Ok(entries)
 	}
 
 	/// Get system temporary directory
@@ -346,13 +782,17 @@ impl FsApi {
 		Ok(temp_file.to_string_lossy().to_string())
 	}
 
-	/// Extract archive to destination (both paths must be absolute)
+	/// Extract archive to destination (absolute or relative to the project root), the former archive.extract
+	#[lua(deprecated = "use archive.extract")]
+	#[lua_table(options: FsExtractOptions {
+		/// Path of the archive
+		archive: ProjectPath,
+		/// Destination directory
+		dest: ProjectPath,
+	})]
 	fn extract(options: Table) -> mlua::Result<String> {
-		let archive_path: String = options.get("archive")?;
-		let dest_path: String = options.get("dest")?;
-
-		let archive_path = validate_path(&archive_path).map_err(mlua::Error::external)?;
-		let dest_path = validate_path(&dest_path).map_err(mlua::Error::external)?;
+		let archive_path = options.get::<ProjectPath>("archive")?.into_path_buf();
+		let dest_path = options.get::<ProjectPath>("dest")?.into_path_buf();
 
 		if !archive_path.exists() {
 			return Err(mlua::Error::external(FsError::PathNotFound {
@@ -366,99 +806,176 @@ impl FsApi {
 	}
 }
 
-pub fn extract_archive(archive_path: &Path, dest_path: &Path) -> Result<(), FsError> {
-	use flate2::read::GzDecoder;
-	use std::fs::File;
-	use tar::Archive;
-	use zip::ZipArchive;
+fn create_parent_dirs(path: &Path) -> mlua::Result<()> {
+	if let Some(parent) = path.parent() {
+		fs::create_dir_all(parent).map_err(|_| {
+			mlua::Error::external(FsError::PermissionDenied {
+				path: parent.to_string_lossy().to_string(),
+			})
+		})?;
+	}
+	Ok(())
+}
 
-	std::fs::create_dir_all(dest_path).map_err(|_| FsError::PermissionDenied {
-		path: dest_path.to_string_lossy().to_string(),
-	})?;
+/// Path as returned to Lua, relative to the project root when requested and the path lies inside it
+fn output_path(lua: &Lua, path: &Path, relative: bool) -> String {
+	if relative
+		&& let Some(root) = lua.app_data_ref::<ProjectRoot>()
+		&& let Ok(stripped) = path.strip_prefix(&root.0)
+	{
+		return stripped.to_string_lossy().to_string();
+	}
+	path.to_string_lossy().to_string()
+}
 
-	let file = File::open(archive_path).map_err(|_| FsError::PathNotFound {
-		path: archive_path.to_string_lossy().to_string(),
-	})?;
+fn compile_globs(patterns: Option<Vec<String>>) -> mlua::Result<Vec<glob::Pattern>> {
+	patterns
+		.into_iter()
+		.flatten()
+		.map(|pattern| {
+			glob::Pattern::new(&pattern).map_err(|e| {
+				mlua::Error::external(FsError::InvalidGlobPattern {
+					pattern: pattern.clone(),
+					reason: e.to_string(),
+				})
+			})
+		})
+		.collect()
+}
 
-	let extension = archive_path.extension().and_then(|s| s.to_str());
+/// Metadata of path, following symlinks unless the link is dangling
+fn read_metadata(path: &Path) -> mlua::Result<fs::Metadata> {
+	if path.symlink_metadata().is_err() {
+		return Err(mlua::Error::external(FsError::PathNotFound {
+			path: path.to_string_lossy().to_string(),
+		}));
+	}
 
-	match extension {
-		Some("zip") => {
-			let mut archive = ZipArchive::new(file).map_err(|e| FsError::ExtractionFailed {
-				archive: archive_path.to_string_lossy().to_string(),
-				reason: format!("Invalid zip file: {}", e),
-			})?;
-			archive.extract(dest_path).map_err(|e| FsError::ExtractionFailed {
-				archive: archive_path.to_string_lossy().to_string(),
-				reason: format!("Extraction error: {}", e),
-			})?;
-		}
-		Some("gz")
-			if archive_path
-				.file_name()
-				.and_then(|s| s.to_str())
-				.is_some_and(|s| s.contains(".tar.")) =>
-		{
-			let tar = GzDecoder::new(file);
-			let mut archive = Archive::new(tar);
-			archive.unpack(dest_path).map_err(|e| FsError::ExtractionFailed {
-				archive: archive_path.to_string_lossy().to_string(),
-				reason: format!("Extraction error: {}", e),
-			})?;
-		}
-		Some("tar") => {
-			let mut archive = Archive::new(file);
-			archive.unpack(dest_path).map_err(|e| FsError::ExtractionFailed {
-				archive: archive_path.to_string_lossy().to_string(),
-				reason: format!("Extraction error: {}", e),
-			})?;
-		}
-		Some("crate") => {
-			let tar = GzDecoder::new(file);
-			let mut archive = Archive::new(tar);
-
-			for entry in archive.entries().map_err(|e| FsError::ExtractionFailed {
-				archive: archive_path.to_string_lossy().to_string(),
-				reason: format!("Invalid crate file: {}", e),
-			})? {
-				let mut entry = entry.map_err(|e| FsError::ExtractionFailed {
-					archive: archive_path.to_string_lossy().to_string(),
-					reason: format!("Extraction error: {}", e),
-				})?;
-				let path = entry.path().map_err(|e| FsError::ExtractionFailed {
-					archive: archive_path.to_string_lossy().to_string(),
-					reason: format!("Invalid path in archive: {}", e),
-				})?;
-				let components: Vec<_> = path.components().collect();
+	fs::metadata(path).or_else(|_| fs::symlink_metadata(path)).map_err(|_| {
+		mlua::Error::external(FsError::PermissionDenied {
+			path: path.to_string_lossy().to_string(),
+		})
+	})
+}
 
-				if components.len() > 1 {
-					let new_path: PathBuf = components[1..].iter().collect();
-					let dest_file_path = dest_path.join(&new_path);
+/// Mode bits from an octal string or a number whose decimal digits are the octal ones, so 755 means 0o755
+fn parse_mode(mode: &mlua::Value) -> mlua::Result<u32> {
+	let parsed = match mode {
+		mlua::Value::Integer(mode) => u32::from_str_radix(&mode.to_string(), 8).ok(),
+		mlua::Value::String(mode) => u32::from_str_radix(mode.to_str()?.trim_start_matches("0o"), 8).ok(),
+		_ => None,
+	};
+
+	parsed.filter(|mode| *mode <= 0o7777).ok_or_else(|| {
+		mlua::Error::RuntimeError(format!(
+			"Invalid file mode {:?}, expected octal digits such as \"755\" or 755",
+			mode
+		))
+	})
+}
 
-					if let Some(parent) = dest_file_path.parent() {
-						std::fs::create_dir_all(parent).map_err(|_| FsError::PermissionDenied {
-							path: parent.to_string_lossy().to_string(),
-						})?;
-					}
+fn set_mode(path: &Path, mode: u32) -> mlua::Result<()> {
+	let mut permissions = read_metadata(path)?.permissions();
 
-					entry.unpack(&dest_file_path).map_err(|e| FsError::ExtractionFailed {
-						archive: archive_path.to_string_lossy().to_string(),
-						reason: format!("Extraction error: {}", e),
-					})?;
-				}
-			}
-		}
-		_ => {
-			return Err(FsError::ExtractionFailed {
-				archive: archive_path.to_string_lossy().to_string(),
-				reason: format!("Unsupported archive format: {:?}", archive_path),
-			});
-		}
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::PermissionsExt;
+		permissions.set_mode(mode);
+	}
+
+	#[cfg(not(unix))]
+	permissions.set_readonly(mode & 0o200 == 0);
+
+	fs::set_permissions(path, permissions).map_err(|_| {
+		mlua::Error::external(FsError::PermissionDenied {
+			path: path.to_string_lossy().to_string(),
+		})
+	})
+}
+
+fn file_mode(metadata: &fs::Metadata) -> Option<u32> {
+	#[cfg(unix)]
+	{
+		use std::os::unix::fs::PermissionsExt;
+		Some(metadata.permissions().mode() & 0o7777)
+	}
+
+	#[cfg(not(unix))]
+	{
+		let _ = metadata;
+		None
 	}
+}
+
+fn create_symlink(target: &Path, link: &Path) -> mlua::Result<()> {
+	#[cfg(unix)]
+	let result = std::os::unix::fs::symlink(target, link);
+
+	#[cfg(windows)]
+	let result = {
+		let resolved = link
+			.parent()
+			.map(|parent| parent.join(target))
+			.unwrap_or_else(|| target.to_path_buf());
+		if resolved.is_dir() {
+			std::os::windows::fs::symlink_dir(target, link)
+		} else {
+			std::os::windows::fs::symlink_file(target, link)
+		}
+	};
 
+	result.map_err(|e| {
+		mlua::Error::external(FsError::InvalidPath {
+			path: link.to_string_lossy().to_string(),
+			reason: format!("Failed to create symlink: {}", e),
+		})
+	})
+}
+
+/// Extract a tar, tar.gz, zip or crate archive to dest_path, dropping the top-level directory of crates
+pub fn extract_archive(archive_path: &Path, dest_path: &Path) -> Result<(), FsError> {
+	let is_crate = archive_path.extension().is_some_and(|extension| extension == "crate");
+	let filter = ArchiveFilter {
+		strip_components: is_crate.then_some(1),
+		..Default::default()
+	};
+
+	archive::extract_archive(archive_path, dest_path, &filter).map_err(|e| FsError::ExtractionFailed {
+		archive: archive_path.to_string_lossy().to_string(),
+		reason: e.to_string(),
+	})?;
 	Ok(())
 }
 
+forge_lua_module!(
+	fs,
+	FsApi,
+	"File system operations (paths may be relative to the project root)"
+);
+
 pub fn create_fs_table(lua: &Lua) -> mlua::Result<Table> {
 	FsApi::create_fs_table(lua)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_mode() {
+		let lua = Lua::new();
+		let string = |mode: &str| mlua::Value::String(lua.create_string(mode).unwrap());
+		assert_eq!(parse_mode(&mlua::Value::Integer(755)).unwrap(), 0o755);
+		assert_eq!(parse_mode(&mlua::Value::Integer(4755)).unwrap(), 0o4755);
+		assert_eq!(parse_mode(&string("644")).unwrap(), 0o644);
+		assert_eq!(parse_mode(&string("0o600")).unwrap(), 0o600);
+		for invalid in [
+			mlua::Value::Integer(789),
+			mlua::Value::Integer(-1),
+			mlua::Value::Integer(17777),
+			string("rwx"),
+		] {
+			assert!(parse_mode(&invalid).is_err());
+		}
+	}
+}
@@ -1,5 +1,5 @@
 use blake3::Hasher;
-use forge_macros::lua_api;
+use forge_macros::{forge_lua_module, lua_api};
 use mlua::{Lua, Result, Table, UserData, UserDataMethods};
 use std::{fs, path::Path};
 
@@ -72,6 +72,8 @@ impl HashApi {
 	}
 }
 
+forge_lua_module!(hash, HashApi, "Hashing operations");
+
 pub fn create_hash_table(lua: &Lua) -> Result<Table> {
 	HashApi::create_hash_table(lua)
 }
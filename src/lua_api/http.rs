@@ -1,8 +1,12 @@
 use crate::error::ForgeError;
-use crate::lua_api::fs::extract_archive;
+use crate::lockfile::Lockfile;
+use crate::lua_api::{fs::extract_archive, log::render_progress, project_path};
+use crate::user_config::{Credential, UserConfig};
+use base64::{Engine, prelude::BASE64_STANDARD};
 use blake3::Hasher as Blake3Hasher;
-use forge_macros::lua_api;
+use forge_macros::{LuaClass, forge_lua_module, lua_api};
 use mlua::{FromLua, Lua, LuaSerdeExt, Result, Table, UserData, UserDataMethods, Value};
+use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 use std::collections::HashMap;
@@ -10,27 +14,100 @@ use std::fs::File;
 use std::io::{Read, Write};
 use std::{
 	fs::{self},
-	path::PathBuf,
+	path::{Path, PathBuf},
+	sync::{
+		Arc, Mutex,
+		atomic::{AtomicBool, AtomicU64, Ordering},
+	},
+	time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
+use ureq::{ResponseExt, SendBody, http};
 
-#[derive(Debug, Deserialize, Serialize)]
+/// How often download progress is redrawn
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Sidecar file in each download cache entry holding its CacheMeta
+const CACHE_META_FILE: &str = ".forge-meta.json";
+
+const DEFAULT_DOWNLOAD_CONCURRENCY: usize = 8;
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Downloads fetched over the network and served from the download cache by this process, for build metrics
+static DOWNLOADED: AtomicU64 = AtomicU64::new(0);
+static DOWNLOADS_CACHED: AtomicU64 = AtomicU64::new(0);
+
+/// Retry and proxy settings accepted by every http request
+#[derive(Debug, Default, Deserialize, Serialize, LuaClass)]
+pub struct HttpTransportOptions {
+	/// Extra attempts after a connection error, 429 or 5xx response; POST and PATCH only retry with retry_unsafe
+	pub retries: Option<u32>,
+	/// Seconds to wait before the first retry, doubled for each following one
+	pub retry_backoff: Option<f64>,
+	/// Retry POST and PATCH requests too, which may apply twice when a response is lost (default false)
+	pub retry_unsafe: Option<bool>,
+	/// Proxy URL, overriding HTTP_PROXY / HTTPS_PROXY / NO_PROXY
+	pub proxy: Option<String>,
+}
+
+/// Credentials for a request; without them the user config's credential helpers and netrc are consulted for https URLs
+#[derive(Debug, Deserialize, Serialize, LuaClass)]
+pub struct HttpAuth {
+	pub bearer: Option<String>,
+	pub basic: Option<HttpBasicAuth>,
+}
+
+#[derive(Debug, Deserialize, Serialize, LuaClass)]
+pub struct HttpBasicAuth {
+	pub user: String,
+	pub pass: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, LuaClass)]
 pub struct HttpGetRequest {
 	pub url: String,
 	pub timeout: Option<u64>,
 	pub follow_redirects: Option<bool>,
 	pub headers: Option<HashMap<String, String>>,
+	pub auth: Option<HttpAuth>,
+	#[serde(flatten)]
+	pub transport: HttpTransportOptions,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// Request with a body, shared by post, put, patch and delete
+#[derive(Debug, Deserialize, Serialize, LuaClass)]
 pub struct HttpPostRequest {
 	pub url: String,
 	pub timeout: Option<u64>,
 	pub follow_redirects: Option<bool>,
 	pub headers: Option<HashMap<String, String>>,
 	pub body: Option<String>,
+	pub body_file: Option<String>,
+	pub multipart: Option<Vec<HttpMultipartPart>>,
+	pub auth: Option<HttpAuth>,
+	#[serde(flatten)]
+	pub transport: HttpTransportOptions,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+/// One field of a multipart/form-data body: either a value or the contents of a file
+#[derive(Debug, Deserialize, Serialize, LuaClass)]
+pub struct HttpMultipartPart {
+	pub name: String,
+	pub value: Option<String>,
+	pub file: Option<String>,
+	/// Filename sent for file parts, defaults to the file's name
+	pub filename: Option<String>,
+	pub content_type: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, LuaClass)]
+pub struct HttpDownloadManyEntry {
+	#[serde(flatten)]
+	pub download: HttpDownloadRequest,
+	pub dest: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, LuaClass)]
 pub struct HttpDownloadRequest {
 	pub url: String,
 	pub cache_key: Option<String>,
@@ -38,6 +115,10 @@ pub struct HttpDownloadRequest {
 	pub sha256: Option<String>,
 	pub extract: Option<bool>,
 	pub extract_dir: Option<String>,
+	pub ttl: Option<u64>,
+	pub auth: Option<HttpAuth>,
+	#[serde(flatten)]
+	pub transport: HttpTransportOptions,
 }
 
 impl FromLua for HttpGetRequest {
@@ -58,6 +139,12 @@ impl FromLua for HttpDownloadRequest {
 	}
 }
 
+impl FromLua for HttpDownloadManyEntry {
+	fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+		lua.from_value(value)
+	}
+}
+
 #[derive(Clone)]
 pub struct HttpApi;
 
@@ -67,176 +154,1057 @@ impl UserData for HttpApi {
 
 #[lua_api(name = "http")]
 impl HttpApi {
+	/// Redirects followed by a request unless follow_redirects is false
+	#[lua_const]
+	const MAX_REDIRECTS: u32 = 10;
+
 	pub fn new() -> Self {
 		Self
 	}
 
 	/// Perform HTTP GET request
+	/// @return { status, body, headers, url }, url is the final URL after redirects; non-2xx responses are returned too
 	fn get(lua: &Lua, request: HttpGetRequest) -> Result<Value> {
-		let agent_config = ureq::Agent::config_builder()
-			.timeout_global(request.timeout.map(std::time::Duration::from_secs))
-			.max_redirects(if request.follow_redirects.unwrap_or(true) { 10 } else { 0 })
-			.build();
+		let response = send_get(&request)?;
+		response_table(lua, response, true).map(Value::Table)
+	}
 
-		let agent: ureq::Agent = agent_config.into();
-		let mut req = agent.get(&request.url);
-		req = req.header("User-Agent", format!("forge/{}", env!("CARGO_PKG_VERSION")));
+	/// GET a JSON document and decode it into a Lua value
+	/// Non-2xx responses and invalid JSON raise errors naming the URL
+	fn get_json(lua: &Lua, request: HttpGetRequest) -> Result<Value> {
+		let mut request = request;
+		set_default_header(&mut request.headers, "Accept", "application/json");
+		let response = send_get(&request)?;
+		json_response(lua, response, &request.url)
+	}
 
-		if let Some(headers) = request.headers {
-			for (key, value) in headers {
+	/// Perform HTTP HEAD request
+	/// @return { status, headers, url }
+	fn head(lua: &Lua, request: HttpGetRequest) -> Result<Value> {
+		ensure_online(&request.url)?;
+		let agent = build_agent(&request.url, request.timeout, request.follow_redirects, &request.transport)?;
+		let authorization = authorization_header(&request.url, request.auth.as_ref())?;
+		let response = with_retries(&request.transport, "HEAD", || {
+			let mut req = agent.head(&request.url);
+			req = req.header("User-Agent", format!("forge/{}", env!("CARGO_PKG_VERSION")));
+			if let Some(authorization) = &authorization {
+				req = req.header("Authorization", authorization);
+			}
+
+			for (key, value) in request.headers.iter().flatten() {
 				req = req.header(key, value);
 			}
+
+			req.call()
+		})
+		.map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+
+		response_table(lua, response, false).map(Value::Table)
+	}
+
+	/// Perform HTTP POST request
+	/// The body is one of body (a string), body_file (streamed from disk) or multipart (a list of { name, value | file, filename, content_type })
+	/// @return { status, body, headers, url }
+	fn post(lua: &Lua, request: HttpPostRequest) -> Result<Value> {
+		let response = send_with_body(lua, "POST", &request)?;
+		response_table(lua, response, true).map(Value::Table)
+	}
+
+	/// POST value encoded as JSON and decode the JSON response, raising errors for non-2xx responses
+	/// Takes the options of http.post; body, body_file and multipart are replaced by the encoded value
+	fn post_json(lua: &Lua, request: HttpPostRequest, value: Value) -> Result<Value> {
+		let mut request = request;
+		let json: serde_json::Value = lua.from_value(value)?;
+		request.body = Some(serde_json::to_string(&json).map_err(mlua::Error::external)?);
+		request.body_file = None;
+		request.multipart = None;
+		set_default_header(&mut request.headers, "Content-Type", "application/json");
+		set_default_header(&mut request.headers, "Accept", "application/json");
+
+		let response = send_with_body(lua, "POST", &request)?;
+		json_response(lua, response, &request.url)
+	}
+
+	/// Perform HTTP PUT request, taking the same options as http.post
+	/// @return { status, body, headers, url }
+	fn put(lua: &Lua, request: HttpPostRequest) -> Result<Value> {
+		let response = send_with_body(lua, "PUT", &request)?;
+		response_table(lua, response, true).map(Value::Table)
+	}
+
+	/// Perform HTTP PATCH request, taking the same options as http.post
+	/// @return { status, body, headers, url }
+	fn patch(lua: &Lua, request: HttpPostRequest) -> Result<Value> {
+		let response = send_with_body(lua, "PATCH", &request)?;
+		response_table(lua, response, true).map(Value::Table)
+	}
+
+	/// Perform HTTP DELETE request, taking the same options as http.post; the body is optional
+	/// @return { status, body, headers, url }
+	fn delete(lua: &Lua, request: HttpPostRequest) -> Result<Value> {
+		let response = send_with_body(lua, "DELETE", &request)?;
+		response_table(lua, response, true).map(Value::Table)
+	}
+
+	/// Download and cache a file under ~/.forge/downloads, streaming it to disk
+	/// Entries are keyed by URL and checksum; without a checksum, ttl (seconds) makes stale entries revalidate via ETag / Last-Modified
+	/// Interrupted downloads resume where they stopped, and concurrent downloads of the same entry wait for each other
+	/// With --offline only cache hits succeed, stale entries are used without revalidation
+	/// Without a checksum, the file must match the blake3 FORGE.lock recorded on its first fetch; ttl is then ignored
+	fn download(lua: &Lua, request: HttpDownloadRequest) -> Result<String> {
+		let lockfile = lockfile(lua);
+		let progress = DownloadProgress::new(download_filename(&request));
+		let cached = fetch_cached(&request, lockfile.as_deref(), &progress)?;
+		progress.finish();
+
+		let path = if request.extract.unwrap_or(false) {
+			extract_in_cache(&request, &cached)?
+		} else {
+			cached.path
+		};
+		Ok(path.to_string_lossy().to_string())
+	}
+
+	/// Download several files concurrently through the same cache as http.download, with one combined progress bar
+	/// Each entry takes the options of http.download plus dest, where the file is copied (or extracted, with extract = true)
+	/// @param concurrency Maximum simultaneous downloads (default 8)
+	/// @return Paths in the order of entries: dest when given, otherwise the cached file or extracted directory
+	fn download_many(lua: &Lua, entries: Vec<HttpDownloadManyEntry>, concurrency: Option<usize>) -> Result<Vec<String>> {
+		let dests = entries
+			.iter()
+			.map(|entry| entry.dest.as_deref().map(|dest| project_path::resolve(lua, dest)).transpose())
+			.collect::<Result<Vec<_>>>()?;
+
+		let pool = rayon::ThreadPoolBuilder::new()
+			.num_threads(concurrency.unwrap_or(DEFAULT_DOWNLOAD_CONCURRENCY).max(1))
+			.build()
+			.map_err(mlua::Error::external)?;
+		let lockfile = lockfile(lua);
+		let progress = DownloadProgress::new(format!("Downloading {} files", entries.len()));
+		let results: Vec<Result<PathBuf>> = pool.install(|| {
+			entries
+				.par_iter()
+				.zip(dests.par_iter())
+				.map(|(entry, dest)| download_to(&entry.download, dest.as_deref(), lockfile.as_deref(), &progress))
+				.collect()
+		});
+		progress.finish();
+
+		let failures: Vec<String> = entries
+			.iter()
+			.zip(&results)
+			.filter_map(|(entry, result)| result.as_ref().err().map(|e| format!("  {}: {}", entry.download.url, e)))
+			.collect();
+		if !failures.is_empty() {
+			return Err(mlua::Error::RuntimeError(format!(
+				"{} of {} downloads failed:\n{}",
+				failures.len(),
+				entries.len(),
+				failures.join("\n")
+			)));
 		}
 
-		let mut response = req.call().map_err(|e| mlua::Error::RuntimeError(e.to_string()))?;
+		Ok(results
+			.into_iter()
+			.flatten()
+			.map(|path| path.to_string_lossy().to_string())
+			.collect())
+	}
+}
+
+/// Body of a post/put/patch/delete request, with paths already resolved against the project root
+enum RequestBody {
+	Text(String),
+	File(PathBuf),
+	Multipart {
+		boundary: String,
+		parts: Vec<MultipartPart>,
+	},
+}
+
+enum MultipartPart {
+	Value {
+		name: String,
+		value: String,
+	},
+	File {
+		name: String,
+		path: PathBuf,
+		filename: String,
+		content_type: String,
+	},
+}
+
+fn send_get(request: &HttpGetRequest) -> Result<http::Response<ureq::Body>> {
+	ensure_online(&request.url)?;
+	let agent = build_agent(&request.url, request.timeout, request.follow_redirects, &request.transport)?;
+	let authorization = authorization_header(&request.url, request.auth.as_ref())?;
+	with_retries(&request.transport, "GET", || {
+		let mut req = agent.get(&request.url);
+		req = req.header("User-Agent", format!("forge/{}", env!("CARGO_PKG_VERSION")));
+		if let Some(authorization) = &authorization {
+			req = req.header("Authorization", authorization);
+		}
+
+		for (key, value) in request.headers.iter().flatten() {
+			req = req.header(key, value);
+		}
+
+		req.call()
+	})
+	.map_err(|e| mlua::Error::RuntimeError(e.to_string()))
+}
+
+fn send_with_body(lua: &Lua, method: &str, request: &HttpPostRequest) -> Result<http::Response<ureq::Body>> {
+	ensure_online(&request.url)?;
+	let body = request_body(lua, request)?;
+	let agent = build_agent(&request.url, request.timeout, request.follow_redirects, &request.transport)?;
+	let authorization = authorization_header(&request.url, request.auth.as_ref())?;
+	with_retries(&request.transport, method, || {
+		let mut req = match method {
+			"PUT" => agent.put(&request.url),
+			"PATCH" => agent.patch(&request.url),
+			"DELETE" => agent.delete(&request.url).force_send_body(),
+			_ => agent.post(&request.url),
+		};
+		if let Some(authorization) = &authorization {
+			req = req.header("Authorization", authorization);
+		}
 
-		let table = lua.create_table()?;
-		table.set("status", response.status().as_u16())?;
+		for (key, value) in request.headers.iter().flatten() {
+			req = req.header(key, value);
+		}
 
-		if response.status() == 200 {
-			let body = response.body_mut().read_to_string().map_err(mlua::Error::external)?;
-			table.set("body", body)?;
+		req = req.header("User-Agent", format!("forge/{}", env!("CARGO_PKG_VERSION")));
+		// Files are reopened on every attempt, so retries resend the whole body
+		match &body {
+			RequestBody::Text(text) => req.send(text.as_str()),
+			RequestBody::File(path) => req.send(File::open(path)?),
+			RequestBody::Multipart { boundary, parts } => {
+				let (reader, length) = multipart_reader(boundary, parts)?;
+				req.header("Content-Type", format!("multipart/form-data; boundary={}", boundary))
+					.header("Content-Length", length.to_string())
+					.send(SendBody::from_owned_reader(reader))
+			}
 		}
+	})
+	.map_err(|e| mlua::Error::RuntimeError(format!("HTTP {} {} failed: {}", method, request.url, e)))
+}
 
-		Ok(Value::Table(table))
+/// Add a header unless the caller already set it (header names are case-insensitive)
+fn set_default_header(headers: &mut Option<HashMap<String, String>>, name: &str, value: &str) {
+	let headers = headers.get_or_insert_with(HashMap::new);
+	if !headers.keys().any(|key| key.eq_ignore_ascii_case(name)) {
+		headers.insert(name.to_string(), value.to_string());
 	}
+}
 
-	/// Perform HTTP POST request
-	fn post(lua: &Lua, request: HttpPostRequest) -> Result<Value> {
-		let agent_config = ureq::Agent::config_builder()
-			.timeout_global(request.timeout.map(std::time::Duration::from_secs))
-			.max_redirects(if request.follow_redirects.unwrap_or(true) { 10 } else { 0 })
-			.build();
+/// Decode a JSON response body; an empty body decodes to nil
+fn json_response(lua: &Lua, mut response: http::Response<ureq::Body>, url: &str) -> Result<Value> {
+	let status = response.status();
+	let body = response
+		.body_mut()
+		.read_to_string()
+		.map_err(|e| mlua::Error::RuntimeError(format!("Failed to read response from {}: {}", url, e)))?;
 
-		let agent: ureq::Agent = agent_config.into();
-		let mut req = agent.post(&request.url);
+	if !status.is_success() {
+		let excerpt: String = body.chars().take(200).collect();
+		return Err(mlua::Error::RuntimeError(format!(
+			"HTTP {} for {}: {}",
+			status.as_u16(),
+			url,
+			excerpt
+		)));
+	}
+	if body.trim().is_empty() {
+		return Ok(Value::Nil);
+	}
 
-		if let Some(headers) = request.headers {
-			for (key, value) in headers {
-				req = req.header(key, value);
+	let value: serde_json::Value =
+		serde_json::from_str(&body).map_err(|e| mlua::Error::RuntimeError(format!("Invalid JSON from {}: {}", url, e)))?;
+	lua.to_value(&value)
+}
+
+fn request_body(lua: &Lua, request: &HttpPostRequest) -> Result<RequestBody> {
+	match (&request.body, &request.body_file, &request.multipart) {
+		(body, None, None) => Ok(RequestBody::Text(body.clone().unwrap_or_default())),
+		(None, Some(path), None) => Ok(RequestBody::File(project_path::resolve(lua, path)?)),
+		(None, None, Some(parts)) => {
+			let parts = parts
+				.iter()
+				.map(|part| match (&part.value, &part.file) {
+					(Some(value), None) => Ok(MultipartPart::Value {
+						name: part.name.clone(),
+						value: value.clone(),
+					}),
+					(None, Some(file)) => {
+						let path = project_path::resolve(lua, file)?;
+						let filename = part.filename.clone().unwrap_or_else(|| {
+							path.file_name()
+								.map(|name| name.to_string_lossy().to_string())
+								.unwrap_or_else(|| part.name.clone())
+						});
+						Ok(MultipartPart::File {
+							name: part.name.clone(),
+							path,
+							filename,
+							content_type: part
+								.content_type
+								.clone()
+								.unwrap_or_else(|| "application/octet-stream".to_string()),
+						})
+					}
+					_ => Err(mlua::Error::RuntimeError(format!(
+						"Multipart part '{}' needs exactly one of value or file",
+						part.name
+					))),
+				})
+				.collect::<Result<Vec<_>>>()?;
+			Ok(RequestBody::Multipart {
+				boundary: format!("forge-{}", uuid::Uuid::new_v4().simple()),
+				parts,
+			})
+		}
+		_ => Err(mlua::Error::RuntimeError(
+			"Only one of body, body_file or multipart can be given".to_string(),
+		)),
+	}
+}
+
+/// Stream a multipart/form-data body, reading file parts from disk as it goes
+/// @return The reader and the total body length
+fn multipart_reader(boundary: &str, parts: &[MultipartPart]) -> std::io::Result<(Box<dyn Read + Send>, u64)> {
+	let mut reader: Box<dyn Read + Send> = Box::new(std::io::empty());
+	let mut length = 0;
+
+	for part in parts {
+		let header = match part {
+			MultipartPart::Value { name, .. } => format!(
+				"--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n",
+				boundary,
+				escape_quoted(name)
+			),
+			MultipartPart::File {
+				name,
+				filename,
+				content_type,
+				..
+			} => format!(
+				"--{}\r\nContent-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\nContent-Type: {}\r\n\r\n",
+				boundary,
+				escape_quoted(name),
+				escape_quoted(filename),
+				content_type
+			),
+		};
+		length += header.len() as u64;
+		reader = Box::new(reader.chain(std::io::Cursor::new(header.into_bytes())));
+
+		match part {
+			MultipartPart::Value { value, .. } => {
+				length += value.len() as u64;
+				reader = Box::new(reader.chain(std::io::Cursor::new(value.clone().into_bytes())));
+			}
+			MultipartPart::File { path, .. } => {
+				let file = File::open(path)?;
+				length += file.metadata()?.len();
+				reader = Box::new(reader.chain(file));
 			}
 		}
 
-		req = req.header("User-Agent", format!("forge/{}", env!("CARGO_PKG_VERSION")));
+		length += 2;
+		reader = Box::new(reader.chain(&b"\r\n"[..]));
+	}
+
+	let closing = format!("--{}--\r\n", boundary);
+	length += closing.len() as u64;
+	Ok((Box::new(reader.chain(std::io::Cursor::new(closing.into_bytes()))), length))
+}
 
-		let response = req
-			.send(request.body.unwrap_or("".into()))
-			.map_err(|e| mlua::Error::RuntimeError(format!("HTTP request failed: {}", e)))?;
+fn escape_quoted(value: &str) -> String {
+	value.replace('\\', "\\\\").replace('"', "\\\"")
+}
 
-		let status = response.status();
-		let result = lua.create_table()?;
-		result.set("status", status.as_u16())?;
+/// A file present in the download cache; the entry stays locked while this is alive
+struct CachedFile {
+	path: PathBuf,
+	/// Whether the content was (re)downloaded rather than served from the cache
+	changed: bool,
+	_lock: File,
+}
+
+/// The project's FORGE.lock, when lua evaluates FORGE files
+fn lockfile(lua: &Lua) -> Option<Arc<Lockfile>> {
+	lua.app_data_ref::<Arc<Lockfile>>().map(|lockfile| Arc::clone(&lockfile))
+}
 
-		let response_headers = lua.create_table()?;
-		for (name, value) in response.headers() {
-			response_headers.set(name.as_str(), value.to_str().map_err(mlua::Error::external)?)?;
+/// Make sure the download described by request is in the cache, fetching or revalidating it as needed
+/// Without a checksum, its content is checked against lockfile, or recorded there on its first fetch
+fn fetch_cached(
+	request: &HttpDownloadRequest,
+	lockfile: Option<&Lockfile>,
+	progress: &DownloadProgress,
+) -> Result<CachedFile> {
+	let entry_dir = get_cache_dir()?.join(cache_entry_name(request));
+	fs::create_dir_all(&entry_dir).map_err(mlua::Error::external)?;
+	let cache_path = entry_dir.join(download_filename(request));
+	let meta_path = entry_dir.join(CACHE_META_FILE);
+
+	// Held by the CachedFile, so a second rule downloading the same entry waits and then finds it cached
+	let lock = lock_cache_entry(&cache_path)?;
+
+	let meta = fs::read(&meta_path)
+		.ok()
+		.and_then(|data| serde_json::from_slice::<CacheMeta>(&data).ok());
+	let has_checksum = request.blake3.is_some() || request.sha256.is_some();
+	let lockfile = lockfile.filter(|_| !has_checksum);
+	let locked = lockfile.and_then(|lockfile| lockfile.locked(&request.url));
+	// forge lock update fetches each unpinned download again, once
+	let refresh = lockfile.is_some_and(Lockfile::is_updating) && locked.is_none();
+	let cached = cache_path.exists()
+		&& verify_file_hash(
+			&cache_path,
+			request.blake3.as_deref().or(locked.as_deref()),
+			request.sha256.as_deref(),
+			&request.url,
+		)
+		.is_ok();
+	let stale = match (request.ttl, &meta) {
+		_ if has_checksum || locked.is_some() => false,
+		_ if refresh => true,
+		(None, _) => false,
+		(Some(ttl), Some(meta)) => unix_now().saturating_sub(meta.fetched_at) >= ttl,
+		(Some(_), None) => true,
+	};
+
+	let outcome = if !cached {
+		ensure_online(&request.url)?;
+		Some(fetch_to_cache(request, &cache_path, None, progress)?)
+	} else if stale && !is_offline() {
+		match fetch_to_cache(request, &cache_path, meta.as_ref(), progress) {
+			Ok(outcome) => Some(outcome),
+			Err(e) => {
+				log::warn!("Using cached {} after failed revalidation: {}", request.url, e);
+				None
+			}
 		}
-		result.set("headers", response_headers)?;
+	} else {
+		None
+	};
 
-		Ok(Value::Table(result))
+	let changed = matches!(outcome, Some(FetchOutcome::Downloaded(_)));
+	let fetched = outcome.is_some();
+	if changed {
+		DOWNLOADED.fetch_add(1, Ordering::Relaxed);
+	} else {
+		DOWNLOADS_CACHED.fetch_add(1, Ordering::Relaxed);
+	}
+	if let Some(FetchOutcome::Downloaded(meta) | FetchOutcome::NotModified(meta)) = outcome {
+		let data = serde_json::to_vec_pretty(&meta).map_err(mlua::Error::external)?;
+		fs::write(&meta_path, data).map_err(mlua::Error::external)?;
 	}
 
-	/// Download and cache a file
-	fn download(request: HttpDownloadRequest) -> Result<String> {
-		let cache_dir = get_cache_dir()?;
-		let filename = request
-			.cache_key
-			.unwrap_or_else(|| request.url.split('/').next_back().unwrap_or("download").to_string());
-		let cache_path = cache_dir.join(&filename);
+	// A locked entry served from the cache was already verified above
+	if let Some(lockfile) = lockfile
+		&& (locked.is_none() || fetched)
+	{
+		let actual = file_blake3(&cache_path)?;
+		match locked {
+			Some(expected) if expected != actual => {
+				let _ = fs::remove_file(&cache_path);
+				return Err(mlua::Error::external(ForgeError::LockfileMismatch {
+					url: request.url.clone(),
+					expected,
+					actual,
+				}));
+			}
+			Some(_) => {}
+			None => lockfile.record(&request.url, actual),
+		}
+	}
 
-		if cache_path.exists()
-			&& let Ok(cached_data) = std::fs::read(&cache_path)
-			&& verify_hash(&cached_data, request.blake3.clone(), request.sha256.clone(), &request.url).is_ok()
-		{
-			if request.extract.unwrap_or(false) {
-				let extract_path = cache_dir.join(request.extract_dir.unwrap_or_else(|| format!("{}_extracted", filename)));
-				if !extract_path.exists() {
-					fs::create_dir_all(&extract_path).map_err(mlua::Error::external)?;
-					extract_archive(&cache_path, &extract_path).map_err(mlua::Error::external)?;
-				}
-				return Ok(extract_path.to_string_lossy().to_string());
+	Ok(CachedFile {
+		path: cache_path,
+		changed,
+		_lock: lock,
+	})
+}
+
+/// How many downloads were fetched over the network and how many were served from the download cache so far
+pub fn download_counts() -> (u64, u64) {
+	(DOWNLOADED.load(Ordering::Relaxed), DOWNLOADS_CACHED.load(Ordering::Relaxed))
+}
+
+/// Fetch url into the download cache as http.download does, for downloads forge makes itself rather than a FORGE file
+/// Without a checksum, the file is checked against lockfile or recorded there
+pub fn download_file(url: &str, blake3: Option<&str>, sha256: Option<&str>, lockfile: Option<&Lockfile>) -> Result<PathBuf> {
+	let request = HttpDownloadRequest {
+		url: url.to_string(),
+		cache_key: None,
+		blake3: blake3.map(str::to_string),
+		sha256: sha256.map(str::to_string),
+		extract: None,
+		extract_dir: None,
+		ttl: None,
+		auth: None,
+		transport: HttpTransportOptions::default(),
+	};
+	let progress = DownloadProgress::new(download_filename(&request));
+	let cached = fetch_cached(&request, lockfile, &progress)?;
+	progress.finish();
+	Ok(cached.path)
+}
+
+/// Extract a cached archive next to it, reusing a previous extraction when the archive did not change
+fn extract_in_cache(request: &HttpDownloadRequest, cached: &CachedFile) -> Result<PathBuf> {
+	let entry_dir = cached.path.parent().unwrap_or(Path::new("."));
+	let extract_path = entry_dir.join(
+		request
+			.extract_dir
+			.clone()
+			.unwrap_or_else(|| format!("{}_extracted", download_filename(request))),
+	);
+	if cached.changed || !extract_path.exists() {
+		if extract_path.exists() {
+			fs::remove_dir_all(&extract_path).map_err(mlua::Error::external)?;
+		}
+		fs::create_dir_all(&extract_path).map_err(mlua::Error::external)?;
+		extract_archive(&cached.path, &extract_path).map_err(mlua::Error::external)?;
+	}
+	Ok(extract_path)
+}
+
+/// One download_many entry: fetch into the cache, then copy or extract to dest if given
+fn download_to(
+	request: &HttpDownloadRequest,
+	dest: Option<&Path>,
+	lockfile: Option<&Lockfile>,
+	progress: &DownloadProgress,
+) -> Result<PathBuf> {
+	let cached = fetch_cached(request, lockfile, progress)?;
+	let extract = request.extract.unwrap_or(false);
+
+	match dest {
+		None if extract => extract_in_cache(request, &cached),
+		None => Ok(cached.path),
+		Some(dest) => {
+			if extract {
+				extract_archive(&cached.path, dest).map_err(mlua::Error::external)?;
 			} else {
-				return Ok(cache_path.to_string_lossy().to_string());
+				if let Some(parent) = dest.parent() {
+					fs::create_dir_all(parent).map_err(mlua::Error::external)?;
+				}
+				fs::copy(&cached.path, dest).map_err(|e| {
+					mlua::Error::RuntimeError(format!("Failed to copy {} to {}: {}", request.url, dest.display(), e))
+				})?;
 			}
+			Ok(dest.to_path_buf())
+		}
+	}
+}
+
+/// Name of the cached file: cache_key if given, which matters for archives without an extension in the URL, else the URL's last segment
+fn download_filename(request: &HttpDownloadRequest) -> String {
+	request.cache_key.clone().unwrap_or_else(|| {
+		let path = request.url.split(['?', '#']).next().unwrap_or(&request.url);
+		path.split('/')
+			.next_back()
+			.filter(|name| !name.is_empty())
+			.unwrap_or("download")
+			.to_string()
+	})
+}
+
+/// Progress of one download, or of every download in a download_many batch
+struct DownloadProgress {
+	label: String,
+	downloaded: AtomicU64,
+	total: AtomicU64,
+	last_report: Mutex<Instant>,
+}
+
+impl DownloadProgress {
+	fn new(label: String) -> Self {
+		Self {
+			label,
+			downloaded: AtomicU64::new(0),
+			total: AtomicU64::new(0),
+			last_report: Mutex::new(Instant::now()),
+		}
+	}
+
+	/// Account for a response whose body is length bytes, resuming after resumed bytes already on disk
+	fn start(&self, resumed: u64, length: u64) {
+		self.downloaded.fetch_add(resumed, Ordering::Relaxed);
+		self.total.fetch_add(resumed + length, Ordering::Relaxed);
+	}
+
+	fn advance(&self, bytes: u64) {
+		let downloaded = self.downloaded.fetch_add(bytes, Ordering::Relaxed) + bytes;
+		let mut last_report = self.last_report.lock().unwrap();
+		if last_report.elapsed() >= PROGRESS_INTERVAL {
+			render_progress(downloaded, self.total.load(Ordering::Relaxed), &self.label);
+			*last_report = Instant::now();
 		}
+	}
 
-		let config = ureq::Agent::config_builder().max_redirects(10).build();
-		let agent: ureq::Agent = config.into();
+	fn finish(&self) {
+		let total = self.total.load(Ordering::Relaxed);
+		if total > 0 {
+			render_progress(self.downloaded.load(Ordering::Relaxed).max(total), total, &self.label);
+		}
+	}
+}
 
-		let mut response = agent
+/// Metadata stored next to a download, used to revalidate entries that have no checksum
+#[derive(Debug, Deserialize, Serialize)]
+struct CacheMeta {
+	url: String,
+	etag: Option<String>,
+	last_modified: Option<String>,
+	/// Seconds since the Unix epoch of the last fetch or revalidation
+	fetched_at: u64,
+}
+
+enum FetchOutcome {
+	Downloaded(CacheMeta),
+	/// The server confirmed the cached copy is current
+	NotModified(CacheMeta),
+}
+
+/// Directory name of a download's cache entry, derived from the URL and the expected checksum
+fn cache_entry_name(request: &HttpDownloadRequest) -> String {
+	let checksum = match (&request.blake3, &request.sha256) {
+		(Some(blake3), _) => format!("blake3:{}", blake3.to_ascii_lowercase()),
+		(None, Some(sha256)) => format!("sha256:{}", sha256.to_ascii_lowercase()),
+		(None, None) => String::new(),
+	};
+	let key = blake3::hash(format!("{}\n{}", request.url, checksum).as_bytes());
+	key.to_hex()[..32].to_string()
+}
+
+/// Fetch request.url into cache_path through a ".part" file, resuming a previous partial download
+/// A partial download is only resumed with an If-Range naming the version it holds, so a file that changed on the
+/// server in between is downloaded again from the start instead of being spliced
+/// With validators from a previous fetch the request is conditional and may be answered with 304 Not Modified
+fn fetch_to_cache(
+	request: &HttpDownloadRequest,
+	cache_path: &Path,
+	validators: Option<&CacheMeta>,
+	progress: &DownloadProgress,
+) -> Result<FetchOutcome> {
+	let part_path = with_suffix(cache_path, ".part");
+	let part_validator_path = with_suffix(cache_path, ".part.validator");
+	let part_validator = fs::read_to_string(&part_validator_path)
+		.ok()
+		.filter(|validator| !validator.is_empty());
+	let resume_from = match &part_validator {
+		Some(_) => fs::metadata(&part_path).map(|metadata| metadata.len()).unwrap_or(0),
+		None => 0,
+	};
+
+	let agent = build_agent(&request.url, None, Some(true), &request.transport)?;
+	let authorization = authorization_header(&request.url, request.auth.as_ref())?;
+	let mut response = with_retries(&request.transport, "GET", || {
+		let mut req = agent
 			.get(&request.url)
 			.header("User-Agent", format!("forge/{}", env!("CARGO_PKG_VERSION")))
-			.header("Accept", "application/octet-stream")
-			.call()
-			.map_err(|e| mlua::Error::RuntimeError(format!("Failed to download {}: {}", request.url, e)))?;
-
-		if response.status() != 200 {
-			return Err(mlua::Error::RuntimeError(format!(
-				"HTTP {} for {}",
-				response.status(),
-				request.url
-			)));
+			.header("Accept", "application/octet-stream");
+		if let Some(authorization) = &authorization {
+			req = req.header("Authorization", authorization);
 		}
+		if let Some(validators) = validators {
+			if let Some(etag) = &validators.etag {
+				req = req.header("If-None-Match", etag);
+			}
+			if let Some(last_modified) = &validators.last_modified {
+				req = req.header("If-Modified-Since", last_modified);
+			}
+		} else if resume_from > 0
+			&& let Some(part_validator) = &part_validator
+		{
+			req = req
+				.header("Range", format!("bytes={}-", resume_from))
+				.header("If-Range", part_validator);
+		}
+		req.call()
+	})
+	.map_err(|e| mlua::Error::RuntimeError(format!("Failed to download {}: {}", request.url, e)))?;
 
-		let mut data = Vec::new();
+	let header = |name: &str| {
 		response
-			.body_mut()
-			.as_reader()
-			.read_to_end(&mut data)
-			.map_err(|e| mlua::Error::RuntimeError(format!("Failed to read response: {}", e)))?;
-
-		verify_hash(&data, request.blake3, request.sha256, &request.url)?;
+			.headers()
+			.get(name)
+			.and_then(|value| value.to_str().ok())
+			.map(str::to_string)
+	};
+	let mut meta = CacheMeta {
+		url: request.url.clone(),
+		etag: header("etag"),
+		last_modified: header("last-modified"),
+		fetched_at: unix_now(),
+	};
 
-		let mut file = File::create(&cache_path)
+	let (file, downloaded) = match (response.status().as_u16(), validators) {
+		(304, Some(validators)) => {
+			// Keep the old validators if the 304 did not repeat them
+			meta.etag = meta.etag.or_else(|| validators.etag.clone());
+			meta.last_modified = meta.last_modified.or_else(|| validators.last_modified.clone());
+			return Ok(FetchOutcome::NotModified(meta));
+		}
+		(200, _) => {
+			// If-Range only accepts strong ETags, Last-Modified stands in for the others
+			let validator = meta
+				.etag
+				.clone()
+				.filter(|etag| !etag.starts_with("W/"))
+				.or_else(|| meta.last_modified.clone());
+			match validator {
+				Some(validator) => fs::write(&part_validator_path, validator),
+				None => fs::remove_file(&part_validator_path).or(Ok(())),
+			}
 			.map_err(|e| mlua::Error::RuntimeError(format!("Failed to create cache file: {}", e)))?;
-		file.write_all(&data)
-			.map_err(|e| mlua::Error::RuntimeError(format!("Failed to write cache file: {}", e)))?;
-
-		if request.extract.unwrap_or(false) {
-			let extract_path = cache_dir.join(request.extract_dir.unwrap_or_else(|| format!("{}_extracted", filename)));
-			fs::create_dir_all(&extract_path).map_err(mlua::Error::external)?;
-			extract_archive(&cache_path, &extract_path).map_err(mlua::Error::external)?;
-			Ok(extract_path.to_string_lossy().to_string())
+			(File::create(&part_path), 0)
+		}
+		(206, None) if resume_from > 0 => (fs::OpenOptions::new().append(true).open(&part_path), resume_from),
+		// The partial file is longer than the current version, start over
+		(416, None) if resume_from > 0 => {
+			let _ = fs::remove_file(&part_path);
+			let _ = fs::remove_file(&part_validator_path);
+			return fetch_to_cache(request, cache_path, validators, progress);
+		}
+		(status, _) => {
+			return Err(mlua::Error::RuntimeError(format!("HTTP {} for {}", status, request.url)));
+		}
+	};
+	let mut file = file.map_err(|e| mlua::Error::RuntimeError(format!("Failed to create cache file: {}", e)))?;
+
+	// Bodies of unknown length are not counted, so the bar never runs past 100%
+	let progress = response.body().content_length().map(|length| {
+		progress.start(downloaded, length);
+		progress
+	});
+	stream_body(response.body_mut().as_reader(), &mut file, progress)
+		.map_err(|e| mlua::Error::RuntimeError(format!("Failed to download {}: {}", request.url, e)))?;
+	drop(file);
+
+	let verified = verify_file_hash(&part_path, request.blake3.as_deref(), request.sha256.as_deref(), &request.url);
+	if verified.is_err() {
+		// A corrupt partial file would otherwise be resumed forever
+		let _ = fs::remove_file(&part_path);
+	}
+	let _ = fs::remove_file(&part_validator_path);
+	verified?;
+
+	fs::rename(&part_path, cache_path)
+		.map_err(|e| mlua::Error::RuntimeError(format!("Failed to write cache file: {}", e)))?;
+	Ok(FetchOutcome::Downloaded(meta))
+}
+
+fn stream_body(mut body: impl Read, file: &mut File, progress: Option<&DownloadProgress>) -> std::io::Result<()> {
+	let mut buffer = vec![0u8; 64 * 1024];
+	loop {
+		let read = body.read(&mut buffer)?;
+		if read == 0 {
+			return Ok(());
+		}
+		file.write_all(&buffer[..read])?;
+		if let Some(progress) = progress {
+			progress.advance(read as u64);
+		}
+	}
+}
+
+fn lock_cache_entry(cache_path: &Path) -> Result<File> {
+	let lock_path = with_suffix(cache_path, ".lock");
+	let lock_file = fs::OpenOptions::new()
+		.create(true)
+		.truncate(false)
+		.write(true)
+		.open(&lock_path)
+		.map_err(|e| mlua::Error::RuntimeError(format!("Failed to open {}: {}", lock_path.display(), e)))?;
+	lock_file
+		.lock()
+		.map_err(|e| mlua::Error::RuntimeError(format!("Failed to lock {}: {}", lock_path.display(), e)))?;
+	Ok(lock_file)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+	let mut path = path.as_os_str().to_os_string();
+	path.push(suffix);
+	PathBuf::from(path)
+}
+
+/// Agent for the request functions; error statuses are returned to Lua rather than raised
+fn build_agent(
+	url: &str,
+	timeout: Option<u64>,
+	follow_redirects: Option<bool>,
+	transport: &HttpTransportOptions,
+) -> Result<ureq::Agent> {
+	let proxy = match &transport.proxy {
+		Some(proxy) => Some(
+			ureq::Proxy::new(proxy).map_err(|e| mlua::Error::RuntimeError(format!("Invalid proxy '{}': {}", proxy, e)))?,
+		),
+		None => proxy_from_env(url),
+	};
+
+	Ok(ureq::Agent::config_builder()
+		.timeout_global(timeout.map(Duration::from_secs))
+		.max_redirects(if follow_redirects.unwrap_or(true) {
+			HttpApi::MAX_REDIRECTS
 		} else {
-			Ok(cache_path.to_string_lossy().to_string())
+			0
+		})
+		.http_status_as_error(false)
+		.proxy(proxy)
+		.build()
+		.into())
+}
+
+/// Proxy for url from HTTPS_PROXY / HTTP_PROXY / ALL_PROXY (either case), unless NO_PROXY matches its host
+fn proxy_from_env(url: &str) -> Option<ureq::Proxy> {
+	let uri: http::Uri = url.parse().ok()?;
+	let host = uri.host()?.trim_start_matches('[').trim_end_matches(']').to_ascii_lowercase();
+
+	let no_proxy = env_var(&["NO_PROXY", "no_proxy"]).unwrap_or_default();
+	let bypass = no_proxy
+		.split(',')
+		.map(str::trim)
+		.filter(|entry| !entry.is_empty())
+		.any(|entry| {
+			if entry == "*" {
+				return true;
+			}
+			let domain = entry
+				.split(':')
+				.next()
+				.unwrap_or(entry)
+				.trim_start_matches(['*', '.'])
+				.to_ascii_lowercase();
+			host == domain || host.ends_with(&format!(".{}", domain))
+		});
+	if bypass {
+		return None;
+	}
+
+	let names: &[&str] = if uri.scheme_str() == Some("https") {
+		&["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"]
+	} else {
+		&["HTTP_PROXY", "http_proxy", "ALL_PROXY", "all_proxy"]
+	};
+	env_var(names).and_then(|proxy| ureq::Proxy::new(&proxy).ok())
+}
+
+fn env_var(names: &[&str]) -> Option<String> {
+	names
+		.iter()
+		.find_map(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+}
+
+/// Authorization header value from explicit auth, or the user config's credentials for the URL's host when it is
+/// https, so they never travel in cleartext
+fn authorization_header(url: &str, auth: Option<&HttpAuth>) -> Result<Option<String>> {
+	let credential = match auth {
+		Some(HttpAuth { bearer: Some(token), .. }) => Some(Credential::Bearer(token.clone())),
+		Some(HttpAuth { basic: Some(basic), .. }) => Some(Credential::Basic {
+			user: basic.user.clone(),
+			pass: basic.pass.clone(),
+		}),
+		Some(_) => {
+			return Err(mlua::Error::RuntimeError(
+				"auth must contain either bearer or basic = { user, pass }".to_string(),
+			));
+		}
+		None => url
+			.parse::<http::Uri>()
+			.ok()
+			.filter(|uri| uri.scheme() == Some(&http::uri::Scheme::HTTPS))
+			.and_then(|uri| uri.host().map(str::to_string))
+			.and_then(|host| UserConfig::get().credentials_for(&host)),
+	};
+
+	Ok(credential.map(|credential| match credential {
+		Credential::Bearer(token) => format!("Bearer {}", token),
+		Credential::Basic { user, pass } => format!("Basic {}", BASE64_STANDARD.encode(format!("{}:{}", user, pass))),
+	}))
+}
+
+/// Run send until it succeeds, retrying connection errors, 429 and 5xx responses with exponential backoff
+/// Only idempotent methods are retried unless the transport opts into retry_unsafe
+fn with_retries<F>(
+	transport: &HttpTransportOptions,
+	method: &str,
+	mut send: F,
+) -> std::result::Result<http::Response<ureq::Body>, ureq::Error>
+where
+	F: FnMut() -> std::result::Result<http::Response<ureq::Body>, ureq::Error>,
+{
+	let idempotent = matches!(method, "GET" | "HEAD" | "PUT" | "DELETE" | "OPTIONS");
+	let retries = if idempotent || transport.retry_unsafe.unwrap_or(false) {
+		transport.retries.unwrap_or(0)
+	} else {
+		0
+	};
+	let mut backoff = transport.retry_backoff.filter(|b| b.is_finite() && *b >= 0.0).unwrap_or(1.0);
+
+	let mut attempt = 0;
+	loop {
+		let result = send();
+		let retryable = match &result {
+			Ok(response) => response.status() == 429 || response.status().is_server_error(),
+			Err(e) => matches!(
+				e,
+				ureq::Error::Io(_) | ureq::Error::Timeout(_) | ureq::Error::HostNotFound | ureq::Error::ConnectionFailed
+			),
+		};
+
+		if !retryable || attempt >= retries {
+			return result;
 		}
+
+		attempt += 1;
+		log::warn!(
+			"HTTP request failed, retrying in {:.1}s (attempt {}/{})",
+			backoff,
+			attempt,
+			retries
+		);
+		std::thread::sleep(Duration::from_secs_f64(backoff));
+		backoff *= 2.0;
 	}
 }
 
-fn get_cache_dir() -> Result<PathBuf> {
+fn response_table(lua: &Lua, mut response: http::Response<ureq::Body>, read_body: bool) -> Result<Table> {
+	let table = lua.create_table()?;
+	table.set("status", response.status().as_u16())?;
+	table.set("url", response.get_uri().to_string())?;
+
+	// Header names are lowercase, repeated headers are joined with ", "
+	let mut headers: HashMap<String, String> = HashMap::new();
+	for (name, value) in response.headers() {
+		let value = String::from_utf8_lossy(value.as_bytes());
+		headers
+			.entry(name.as_str().to_string())
+			.and_modify(|existing| {
+				existing.push_str(", ");
+				existing.push_str(&value);
+			})
+			.or_insert_with(|| value.to_string());
+	}
+	table.set("headers", headers)?;
+
+	if read_body {
+		let body = response.body_mut().read_to_string().map_err(mlua::Error::external)?;
+		table.set("body", body)?;
+	}
+
+	Ok(table)
+}
+
+/// Make http requests fail fast instead of touching the network; downloads are still served from the cache
+pub fn set_offline(offline: bool) {
+	OFFLINE.store(offline, Ordering::Relaxed);
+}
+
+fn is_offline() -> bool {
+	OFFLINE.load(Ordering::Relaxed)
+}
+
+fn ensure_online(url: &str) -> Result<()> {
+	if is_offline() {
+		return Err(ForgeError::Offline { url: url.to_string() }.into());
+	}
+	Ok(())
+}
+
+fn unix_now() -> u64 {
+	SystemTime::now()
+		.duration_since(UNIX_EPOCH)
+		.map(|elapsed| elapsed.as_secs())
+		.unwrap_or(0)
+}
+
+pub fn get_cache_dir() -> Result<PathBuf> {
 	let home = dirs::home_dir().ok_or_else(|| mlua::Error::RuntimeError("Could not find home directory".into()))?;
 	let cache_dir = home.join(".forge").join("downloads");
 	fs::create_dir_all(&cache_dir).map_err(mlua::Error::external)?;
 	Ok(cache_dir)
 }
 
-fn verify_hash(data: &[u8], blake3: Option<String>, sha256: Option<String>, url: &str) -> Result<()> {
-	if let Some(expected_blake3) = blake3 {
-		let mut hasher = Blake3Hasher::new();
-		hasher.update(data);
-		let actual = hasher.finalize().to_hex().to_string();
-		if actual != expected_blake3 {
-			return Err(mlua::Error::external(ForgeError::ChecksumMismatch {
-				url: url.to_string(),
-				expected: expected_blake3,
-				actual,
-			}));
-		}
+fn file_blake3(path: &Path) -> Result<String> {
+	let mut file = File::open(path).map_err(mlua::Error::external)?;
+	let mut hasher = Blake3Hasher::new();
+	std::io::copy(&mut file, &mut hasher).map_err(mlua::Error::external)?;
+	Ok(hasher.finalize().to_hex().to_string())
+}
+
+fn verify_file_hash(path: &Path, blake3: Option<&str>, sha256: Option<&str>, url: &str) -> Result<()> {
+	let (expected, actual) = if let Some(expected_blake3) = blake3 {
+		(expected_blake3, file_blake3(path)?)
 	} else if let Some(expected_sha256) = sha256 {
+		let mut file = File::open(path).map_err(mlua::Error::external)?;
 		let mut hasher = Sha256::new();
-		hasher.update(data);
-		let actual = format!("{:x}", hasher.finalize());
-		if actual != expected_sha256 {
-			return Err(mlua::Error::external(ForgeError::ChecksumMismatch {
-				url: url.to_string(),
-				expected: expected_sha256,
-				actual,
-			}));
-		}
+		std::io::copy(&mut file, &mut hasher).map_err(mlua::Error::external)?;
+		(expected_sha256, format!("{:x}", hasher.finalize()))
+	} else {
+		return Ok(());
+	};
+
+	if actual != expected {
+		return Err(mlua::Error::external(ForgeError::ChecksumMismatch {
+			url: url.to_string(),
+			expected: expected.to_string(),
+			actual,
+		}));
 	}
 	Ok(())
 }
 
+forge_lua_module!(http, HttpApi, "HTTP operations");
+
 pub fn create_http_table(lua: &Lua) -> Result<Table> {
 	HttpApi::create_http_table(lua)
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_only_idempotent_methods_retry_by_default() {
+		let attempts = |method: &str, retry_unsafe: Option<bool>| {
+			let transport = HttpTransportOptions {
+				retries: Some(2),
+				retry_backoff: Some(0.0),
+				retry_unsafe,
+				..Default::default()
+			};
+			let mut attempts = 0;
+			let _ = with_retries(&transport, method, || {
+				attempts += 1;
+				Err(ureq::Error::ConnectionFailed)
+			});
+			attempts
+		};
+
+		assert_eq!(attempts("GET", None), 3);
+		assert_eq!(attempts("PUT", None), 3);
+		assert_eq!(attempts("POST", None), 1);
+		assert_eq!(attempts("PATCH", Some(false)), 1);
+		assert_eq!(attempts("POST", Some(true)), 3);
+	}
+
+	#[test]
+	fn test_no_implicit_credentials_over_http() {
+		assert_eq!(authorization_header("http://example.com/file.tar.gz", None).unwrap(), None);
+		let auth = HttpAuth {
+			bearer: Some("token".to_string()),
+			basic: None,
+		};
+		assert_eq!(
+			authorization_header("http://example.com/file.tar.gz", Some(&auth)).unwrap(),
+			Some("Bearer token".to_string())
+		);
+	}
+}
@@ -1,46 +1,270 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
+use crate::eval_cache::Observation;
+use crate::import::{self, ImportError, ImportedRule};
+use crate::lua_api::action::ActionHandle;
 use crate::project::{Project, Rule};
+use crate::rsp::RspFormat;
 use crate::{error::ForgeError, lua_api};
-use mlua::{Lua, LuaSerdeExt, Table};
+use dashmap::DashMap;
+use mlua::{FromLua, Function, Lua, LuaSerdeExt, MetaMethod, Table, UserData, UserDataFields, UserDataMethods, Value};
+
+/// Rules forge.rule registered in a Lua state that have not been merged into the build graph yet
+#[derive(Default)]
+struct RegisteredRules(Vec<Rule>);
+
+/// Callbacks forge.on_graph_complete registered in a Lua state, taken after each FORGE file like its rules
+#[derive(Default)]
+struct GraphHooks(Vec<Function>);
+
+/// Handle returned by forge.rule, so other rules can be wired to it by reference instead of repeating its name
+/// and output paths
+#[derive(Clone)]
+pub struct RuleHandle {
+	name: String,
+	outputs: Vec<String>,
+}
+
+impl UserData for RuleHandle {
+	fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+		fields.add_field_method_get("name", |_, this| Ok(this.name.clone()));
+		fields.add_field_method_get("outputs", |_, this| Ok(this.outputs.clone()));
+	}
+
+	fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+		// The index-th output, counting from 1 like Lua does (default 1)
+		methods.add_method("output", |_, this, index: Option<usize>| {
+			let index = index.unwrap_or(1);
+			index
+				.checked_sub(1)
+				.and_then(|index| this.outputs.get(index))
+				.cloned()
+				.ok_or_else(|| {
+					mlua::Error::RuntimeError(format!(
+						"Rule '{}' has {} outputs, there is no output {}",
+						this.name,
+						this.outputs.len(),
+						index
+					))
+				})
+		});
+
+		// Make this rule run after other (a handle or a rule name), returning this handle for chaining
+		methods.add_method("depend_on", |lua, this, other: Value| {
+			let dependency = rule_name(lua, other)?;
+			let mut registered = lua.app_data_mut::<RegisteredRules>();
+			let rule = registered
+				.as_mut()
+				.and_then(|registered| registered.0.iter_mut().rev().find(|rule| rule.name == this.name))
+				.ok_or_else(|| {
+					mlua::Error::RuntimeError(format!(
+						"Rule '{}' was registered by another FORGE file, add dependencies where it is defined",
+						this.name
+					))
+				})?;
+			if !rule.dependencies.contains(&dependency) {
+				rule.dependencies.push(dependency);
+			}
+			Ok(this.clone())
+		});
+
+		methods.add_meta_method(MetaMethod::ToString, |_, this, ()| Ok(this.name.clone()));
+	}
+}
+
+/// The name of a rule given by its handle or by name
+fn rule_name(lua: &Lua, rule: Value) -> mlua::Result<String> {
+	match rule {
+		Value::UserData(handle) => Ok(handle.borrow::<RuleHandle>()?.name.clone()),
+		rule => String::from_lua(rule, lua),
+	}
+}
+
+/// The merged build graph as forge.on_graph_complete callbacks see it, once every FORGE file was evaluated and before
+/// any rule runs; rules the callback registers with forge.rule are added to it when the callback returns
+pub struct GraphView {
+	rules: Arc<DashMap<String, Rule>>,
+	outputs: Arc<DashMap<String, String>>,
+}
+
+impl GraphView {
+	pub fn new(rules: Arc<DashMap<String, Rule>>, outputs: Arc<DashMap<String, String>>) -> Self {
+		Self { rules, outputs }
+	}
+
+	fn rule_mut<'a>(&'a self, name: &str) -> mlua::Result<dashmap::mapref::one::RefMut<'a, String, Rule>> {
+		self.rules
+			.get_mut(name)
+			.ok_or_else(|| mlua::Error::RuntimeError(format!("There is no rule '{}' in the build graph", name)))
+	}
+}
+
+impl UserData for GraphView {
+	fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+		// Every rule of the graph, sorted by name; changing the tables does not change the graph
+		methods.add_method("rules", |lua, this, ()| {
+			let mut rules: Vec<Rule> = this.rules.iter().map(|rule| rule.value().clone()).collect();
+			rules.sort_by(|a, b| a.name.cmp(&b.name));
+			lua.to_value(&rules)
+		});
+
+		methods.add_method("rule", |lua, this, name: Value| {
+			let name = rule_name(lua, name)?;
+			match this.rules.get(&name) {
+				Some(rule) => lua.to_value(rule.value()),
+				None => Ok(Value::Nil),
+			}
+		});
+
+		methods.add_method("who_produces", |lua, this, path: String| {
+			let root = lua
+				.app_data_ref::<lua_api::project_path::ProjectRoot>()
+				.map(|root| root.0.clone());
+			let wanted = output_key(root.as_deref().unwrap_or(Path::new("")), &path);
+			Ok([path.as_str(), wanted.as_str()]
+				.into_iter()
+				.find_map(|candidate| this.outputs.get(candidate).map(|producer| producer.value().clone())))
+		});
+
+		methods.add_method("add_dependency", |lua, this, (rule, dependency): (Value, Value)| {
+			let name = rule_name(lua, rule)?;
+			let dependency = rule_name(lua, dependency)?;
+			let mut rule = this.rule_mut(&name)?;
+			if !rule.dependencies.contains(&dependency) {
+				rule.dependencies.push(dependency);
+			}
+			Ok(())
+		});
+
+		methods.add_method("add_tag", |lua, this, (rule, tag): (Value, String)| {
+			let name = rule_name(lua, rule)?;
+			let mut rule = this.rule_mut(&name)?;
+			if !rule.tags.contains(&tag) {
+				rule.tags.push(tag);
+			}
+			Ok(())
+		});
+
+		methods.add_method("set_env", |lua, this, (rule, key, value): (Value, String, String)| {
+			let name = rule_name(lua, rule)?;
+			this.rule_mut(&name)?.env.insert(key, value);
+			Ok(())
+		});
+
+		// Drop a rule and the outputs it claimed, returning whether it was there; rules depending on it will fail to
+		// schedule unless the callback rewires them
+		methods.add_method("remove", |lua, this, rule: Value| {
+			let name = rule_name(lua, rule)?;
+			let Some((_, rule)) = this.rules.remove(&name) else {
+				return Ok(false);
+			};
+			for output in &rule.outputs {
+				this.outputs.remove_if(output, |_, producer| *producer == name);
+			}
+			Ok(true)
+		});
+	}
+}
+
+/// Paths given directly or by a rule handle, which stands for the outputs of its rule
+fn input_paths(lua: &Lua, inputs: Vec<Value>) -> mlua::Result<Vec<String>> {
+	let mut paths = Vec::with_capacity(inputs.len());
+	for input in inputs {
+		match input {
+			Value::UserData(handle) => paths.extend(handle.borrow::<RuleHandle>()?.outputs.iter().cloned()),
+			input => paths.push(String::from_lua(input, lua)?),
+		}
+	}
+	Ok(paths)
+}
 
 pub fn setup_lua_environment(lua: &Lua, project: &Project) -> Result<(), ForgeError> {
 	let globals = lua.globals();
 	let forge_table = lua.create_table()?;
 
-	let project_path = project.path.to_string_lossy().to_string();
+	lua.set_app_data(lua_api::project_path::ProjectRoot(project.path.clone()));
+	if project.forge_root_config.build.restrict_fs {
+		let mut roots = vec![
+			project.path.clone(),
+			project.path.join(&project.forge_root_config.build.cache_dir),
+			std::env::temp_dir(),
+		];
+		roots.extend(lua_api::http::get_cache_dir().ok());
+		lua.set_app_data(lua_api::project_path::FsSandbox::new(roots));
+	}
+	lua_api::http::set_offline(project.config.offline);
+	lua.set_app_data(project.build_log.clone());
+	lua.set_app_data(project.lockfile.clone());
+	lua.set_app_data(RegisteredRules::default());
+	lua.set_app_data(GraphHooks::default());
+	lua.set_app_data(lua_api::sysroot::Sysroots(project.sysroots().clone()));
+	if let Some(toolchain) = &project.rust_toolchain {
+		lua.set_app_data(toolchain.clone());
+	}
+	if project.forge_root_config.build.reproducible {
+		lua_api::random::seed(lua_api::random::REPRODUCIBLE_SEED);
+	}
 	forge_table.set("config", lua.to_value(&project.config)?)?;
+	let toolchains: std::collections::BTreeMap<&str, _> = project
+		.toolchains()
+		.iter()
+		.map(|toolchain| (toolchain.name.as_str(), toolchain))
+		.collect();
+	forge_table.set("toolchains", lua.to_value(&toolchains)?)?;
+	forge_table.set("out_dir", project.out_dir.to_string_lossy().to_string())?;
 
-	forge_table.set("fs", lua_api::fs::create_fs_table(lua)?)?;
-	forge_table.set("http", lua_api::http::create_http_table(lua)?)?;
-	forge_table.set("parse", lua_api::parse::create_parse_table(lua)?)?;
-	forge_table.set("exec", lua_api::exec::create_exec_table(lua)?)?;
-	forge_table.set("semver", lua_api::semver::create_semver_table(lua)?)?;
-	forge_table.set("platform", lua_api::platform::create_platform_table(lua)?)?;
-	forge_table.set("path", lua_api::path::create_path_table(lua)?)?;
-	forge_table.set("string", lua_api::string::create_string_table(lua)?)?;
-	forge_table.set("hash", lua_api::hash::create_hash_table(lua)?)?;
-	forge_table.set("time", lua_api::time::create_time_table(lua)?)?;
-	forge_table.set("log", lua_api::log::create_log_table(lua)?)?;
-	forge_table.set("table", lua_api::table::create_table_table(lua)?)?;
-	forge_table.set("project", lua_api::project::create_project_table(lua, project_path.clone())?)?;
+	let sandbox = project.forge_root_config.build.lua_sandbox;
+	lua_api::sandbox::apply(lua, sandbox)?;
+	for module in lua_api::modules() {
+		if !lua_api::sandbox::allows_module(sandbox, module.name) {
+			log::debug!("forge.{} is not available under the {:?} Lua sandbox", module.name, sandbox);
+			continue;
+		}
+		forge_table.set(module.name, (module.create_table)(lua)?)?;
+	}
 
 	let prelude_path = project.path.join("prelude");
 
-	let build_graph = project.build_graph.clone();
-	let output_map = project.output_map.clone();
 	let project_path_for_rule = project.path.clone();
+	let cache_dir_for_rule = project.forge_root_config.build.cache_dir.clone();
+	let default_shell = project.forge_root_config.build.shell.clone();
 	let project_path_for_loader = project.path.clone();
 
-	let rule_fn = lua.create_function(move |_, tbl: Table| {
+	let rule_fn = lua.create_function(move |lua, tbl: Table| {
 		let name: String = tbl.get("name")?;
-		let command: String = tbl.get("command")?;
-		let args: Vec<String> = tbl.get("args").unwrap_or_default();
-		let inputs: Vec<String> = tbl.get("inputs").unwrap_or_default();
+		let command: Value = tbl.get("command")?;
+		let mut args: Vec<String> = tbl.get("args").unwrap_or_default();
+		let inputs: Vec<Value> = tbl.get("inputs").unwrap_or_default();
 		let outputs: Vec<String> = tbl.get("outputs").unwrap_or_default();
-		let dependencies: Vec<String> = tbl.get("dependencies").unwrap_or_default();
+		let dependencies: Vec<Value> = tbl.get("dependencies").unwrap_or_default();
 		let env: Option<Table> = tbl.get("env")?;
 		let workdir: Option<String> = tbl.get("workdir")?;
+		let fetch: bool = tbl.get::<Option<bool>>("fetch")?.unwrap_or(false);
+		let tags: Vec<String> = tbl.get::<Option<Vec<String>>>("tags")?.unwrap_or_default();
+		let pool: Option<String> = tbl.get("pool")?;
+		let worker: bool = tbl.get::<Option<bool>>("worker")?.unwrap_or(false);
+		let remote: Option<bool> = tbl.get("remote")?;
+		let version: Option<String> = tbl.get("version")?;
+		let script: Option<String> = tbl.get("script")?;
+		let shell: Option<Vec<String>> = tbl.get("shell")?;
+		let rsp_format = match tbl.get::<Option<String>>("rsp_format")? {
+			Some(format) => Some(RspFormat::parse(&format).ok_or_else(|| {
+				mlua::Error::RuntimeError(format!(
+					"Rule '{}' has rsp_format '{}', expected \"gcc\" or \"msvc\"",
+					name, format
+				))
+			})?),
+			None => None,
+		};
+		if let Some(version) = &version
+			&& let Err(e) = semver::Version::parse(version)
+		{
+			return Err(mlua::Error::RuntimeError(format!(
+				"Rule '{}' has version '{}', which is not valid semver: {}",
+				name, version, e
+			)));
+		}
 
 		let env_map: std::collections::HashMap<String, String> = if let Some(env_table) = env {
 			env_table
@@ -57,26 +281,128 @@ pub fn setup_lua_environment(lua: &Lua, project: &Project) -> Result<(), ForgeEr
 			project_path_for_rule.clone()
 		};
 
-		let rule = Rule {
+		// Handles stand for their rule's name among the dependencies
+		let mut input_paths = input_paths(lua, inputs)?;
+		let dependencies = dependencies
+			.into_iter()
+			.map(|dependency| rule_name(lua, dependency))
+			.collect::<mlua::Result<Vec<_>>>()?;
+
+		// A script is written to a file named after its content, which the shell runs before the rule's own args
+		let command = match &script {
+			Some(script) => {
+				if !command.is_nil() {
+					return Err(mlua::Error::RuntimeError(format!(
+						"Rule '{}' has both a command and a script, give only one",
+						name
+					)));
+				}
+				let shell = shell.unwrap_or_else(|| default_shell.clone());
+				let Some((program, shell_args)) = shell.split_first() else {
+					return Err(mlua::Error::RuntimeError(format!("Rule '{}' has an empty shell", name)));
+				};
+				let path = crate::project::script_path(&cache_dir_for_rule, script);
+				let script_arg = project_path_for_rule.join(&path).to_string_lossy().to_string();
+				args = shell_args.iter().cloned().chain([script_arg]).chain(args).collect();
+				input_paths.push(path);
+				Value::String(lua.create_string(program)?)
+			}
+			None => command,
+		};
+
+		// A forge.action callback runs in forge itself; the file defining it is tracked like a required module
+		let (command, action, modules) = match command {
+			Value::UserData(action) => {
+				let action = action.borrow::<ActionHandle>()?;
+				(
+					format!("forge.action {}", action.location),
+					Some(action.id.clone()),
+					action.source.iter().cloned().collect(),
+				)
+			}
+			command => (String::from_lua(command, lua)?, None, Vec::new()),
+		};
+
+		let handle = RuleHandle {
 			name: name.clone(),
+			outputs: outputs.clone(),
+		};
+		let rule = Rule {
+			name,
 			command,
 			args,
 			env: env_map,
-			inputs,
-			outputs: outputs.clone(),
+			inputs: input_paths,
+			outputs,
 			dependencies,
 			workdir: rule_workdir,
+			modules,
+			action,
+			fetch,
+			tags,
+			pool,
+			worker,
+			remote,
+			version,
+			script,
+			rsp_format,
+			..Default::default()
 		};
 
-		for output in &outputs {
-			output_map.insert(output.clone(), name.clone());
+		if let Some(mut registered) = lua.app_data_mut::<RegisteredRules>() {
+			registered.0.push(rule);
 		}
+		Ok(handle)
+	})?;
+
+	forge_table.set("rule", rule_fn.clone())?;
 
-		build_graph.insert(name, rule);
+	let project_path_for_install = project.path.clone();
+	let install_fn = lua.create_function(move |lua, tbl: Table| {
+		let src: Value = tbl.get("src")?;
+		let dest: String = tbl.get("dest")?;
+		let name: Option<String> = tbl.get("name")?;
+		if !crate::install::is_valid_dest(&dest) {
+			return Err(mlua::Error::RuntimeError(format!(
+				"forge.install dest '{}' must be a relative path inside the install prefix",
+				dest
+			)));
+		}
+
+		let sources = match src {
+			Value::Table(list) => list.sequence_values::<Value>().collect::<mlua::Result<Vec<_>>>()?,
+			src => vec![src],
+		};
+		let inputs = input_paths(lua, sources)?;
+		if inputs.is_empty() {
+			return Err(mlua::Error::RuntimeError(format!("forge.install to '{}' has no src", dest)));
+		}
+
+		let rule = Rule {
+			name: name.unwrap_or_else(|| format!("install {} -> {}", inputs.join(" "), dest)),
+			inputs,
+			workdir: project_path_for_install.clone(),
+			install: Some(dest),
+			..Default::default()
+		};
+		if let Some(mut registered) = lua.app_data_mut::<RegisteredRules>() {
+			registered.0.push(rule);
+		}
 		Ok(())
 	})?;
+	forge_table.set("install", install_fn)?;
+
+	add_rule_queries(lua, &forge_table, project.path.clone())?;
 
-	forge_table.set("rule", rule_fn)?;
+	let on_graph_complete_fn = lua.create_function(|lua, hook: Function| {
+		// The callback lives in this Lua state, so rules reused from the evaluation cache would lose it
+		lua_api::observations::mark_volatile(lua);
+		if let Some(mut hooks) = lua.app_data_mut::<GraphHooks>() {
+			hooks.0.push(hook);
+		}
+		Ok(())
+	})?;
+	forge_table.set("on_graph_complete", on_graph_complete_fn)?;
 
 	let sleep_fn = lua.create_function(|_, duration: f64| {
 		let duration = std::time::Duration::from_secs_f64(duration);
@@ -85,21 +411,78 @@ pub fn setup_lua_environment(lua: &Lua, project: &Project) -> Result<(), ForgeEr
 	})?;
 	forge_table.set("sleep", sleep_fn)?;
 
+	let version_fn = lua.create_function(|lua, ()| {
+		let info = lua.create_table()?;
+		info.set("version", env!("CARGO_PKG_VERSION"))?;
+		info.set("commit", option_env!("FORGE_GIT_COMMIT"))?;
+		info.set(
+			"features",
+			env!("FORGE_FEATURES")
+				.split(',')
+				.filter(|feature| !feature.is_empty())
+				.collect::<Vec<_>>(),
+		)?;
+		info.set("target", env!("FORGE_BUILD_TARGET"))?;
+		info.set("profile", if cfg!(debug_assertions) { "debug" } else { "release" })?;
+		Ok(info)
+	})?;
+	forge_table.set("version", version_fn)?;
+
+	let project_path_for_ninja = project.path.clone();
+	let rule_fn_for_ninja = rule_fn.clone();
+	let import_ninja_fn = lua.create_function(move |lua, path: String| {
+		import_build_file(
+			lua,
+			&rule_fn_for_ninja,
+			&project_path_for_ninja,
+			&path,
+			"ninja",
+			import::parse_ninja,
+		)
+	})?;
+	forge_table.set("import_ninja", import_ninja_fn)?;
+
+	let project_path_for_make = project.path.clone();
+	let import_make_fn = lua.create_function(
+		move |lua, (path, env): (String, Option<std::collections::HashMap<String, String>>)| {
+			let env = env.unwrap_or_default();
+			import_build_file(lua, &rule_fn, &project_path_for_make, &path, "make", |file| {
+				import::parse_make(file, &env)
+			})
+		},
+	)?;
+	forge_table.set("import_make", import_make_fn)?;
+
+	lua_api::action::install(lua, &forge_table)?;
+
 	let package: Table = globals.get("package")?;
+	// Names without a prefix are looked up in the project root, then in each configured package path
+	let search_dirs: Vec<PathBuf> = std::iter::once(project.path.clone())
+		.chain(
+			project
+				.forge_root_config
+				.lua
+				.package_paths
+				.iter()
+				.map(|dir| project.path.join(dir)),
+		)
+		.collect();
 	let prelude_loader = lua.create_function(move |lua, module_name: String| {
-		let mut path_to_try = PathBuf::new();
-		let mut module_display_name = module_name.clone();
-
-		if let Some(stripped) = module_name.strip_prefix("@prelude/") {
-			path_to_try = prelude_path.join(stripped);
-			module_display_name = format!("@prelude/{}", stripped);
-		} else if !module_name.starts_with('@') {
-			path_to_try = project_path_for_loader.join(&module_name);
-		}
+		let candidates: Vec<PathBuf> = if let Some(stripped) = module_name.strip_prefix("@prelude/") {
+			vec![prelude_path.join(stripped)]
+		} else if let Some(stripped) = module_name.strip_prefix("@workspace/") {
+			vec![project_path_for_loader.join(stripped)]
+		} else if module_name.starts_with('@') {
+			Vec::new()
+		} else {
+			search_dirs.iter().map(|dir| dir.join(&module_name)).collect()
+		};
 
-		if path_to_try.exists() {
+		if let Some(path_to_try) = candidates.into_iter().find(|candidate| candidate.exists()) {
 			let content = std::fs::read_to_string(&path_to_try).map_err(mlua::Error::external)?;
-			let chunk = lua.load(&content).set_name(&module_display_name);
+			lua_api::observations::record(lua, Observation::module(&path_to_try, content.as_bytes()));
+			// "@" marks the chunk name as a file, so errors and tracebacks read "path:line:" and can be mapped back
+			let chunk = lua.load(&content).set_name(format!("@{}", path_to_try.display()));
 			return Ok(Some(chunk.into_function()?));
 		}
 
@@ -109,60 +492,184 @@ pub fn setup_lua_environment(lua: &Lua, project: &Project) -> Result<(), ForgeEr
 	let searchers: Table = package.get("searchers").or_else(|_| package.get("loaders"))?;
 	searchers.set(2, prelude_loader)?;
 
+	lua_api::observations::install(lua)?;
+	let build = &project.forge_root_config.build;
+	if build.cache_evaluation && build.isolate_forge_files {
+		lua_api::observations::watch_host(lua, &forge_table)?;
+	}
+
 	globals.set("forge", forge_table)?;
 	Ok(())
 }
 
+/// Register the steps of an existing build file as rules named "<kind>:<target>" through rule_fn (forge.rule as
+/// created, whatever FORGE files later assign to the global), each running its command with sh from the directory
+/// of the build file
+fn import_build_file(
+	lua: &Lua,
+	rule_fn: &Function,
+	project_root: &Path,
+	path: &str,
+	kind: &str,
+	parse: impl FnOnce(&Path) -> Result<Vec<ImportedRule>, ImportError>,
+) -> mlua::Result<Vec<String>> {
+	lua_api::observations::mark_volatile(lua);
+	let file = lua_api::project_path::resolve(lua, path)?;
+	let mut rules = parse(&file).map_err(mlua::Error::external)?;
+
+	let dir = file.parent().unwrap_or(project_root).to_path_buf();
+	import::rebase(&mut rules, dir.strip_prefix(project_root).unwrap_or(&dir));
+
+	let mut names = Vec::with_capacity(rules.len());
+	for rule in rules {
+		let name = format!("{}:{}", kind, rule.name);
+		let spec = lua.create_table()?;
+		spec.set("name", name.as_str())?;
+		spec.set("command", "sh")?;
+		spec.set("args", vec!["-c".to_string(), rule.command])?;
+		spec.set("inputs", rule.inputs)?;
+		spec.set("outputs", rule.outputs)?;
+		spec.set(
+			"dependencies",
+			rule.dependencies
+				.iter()
+				.map(|dependency| format!("{}:{}", kind, dependency))
+				.collect::<Vec<_>>(),
+		)?;
+		spec.set("workdir", dir.to_string_lossy().to_string())?;
+		rule_fn.call::<()>(spec)?;
+		names.push(name);
+	}
+	Ok(names)
+}
+
+/// path relative to the project root with "/" separators when it is under it, so an output matches however it was
+/// spelled
+fn output_key(project_root: &Path, path: &str) -> String {
+	match Path::new(path).strip_prefix(project_root) {
+		Ok(relative) => relative.to_string_lossy().replace(std::path::MAIN_SEPARATOR, "/"),
+		Err(_) => path.trim_start_matches("./").to_string(),
+	}
+}
+
+/// Add forge.rules and forge.who_produces to forge_table. Both see only the rules of the FORGE file being evaluated:
+/// the other files are evaluated in parallel and merged afterwards, so the whole graph is queried through the graph
+/// forge.on_graph_complete callbacks get, and rules elsewhere are reached through dependencies naming their outputs
+fn add_rule_queries(lua: &Lua, forge_table: &Table, project_root: PathBuf) -> mlua::Result<()> {
+	let rules_fn = lua.create_function(|lua, ()| {
+		let registered = lua.app_data_ref::<RegisteredRules>();
+		let rules: Vec<&Rule> = registered
+			.iter()
+			.flat_map(|registered| registered.0.iter())
+			.filter(|rule| rule.install.is_none())
+			.collect();
+		lua.to_value(&rules)
+	})?;
+	forge_table.set("rules", rules_fn)?;
+
+	let who_produces_fn = lua.create_function(move |lua, path: String| {
+		let wanted = output_key(&project_root, &path);
+		let registered = lua.app_data_ref::<RegisteredRules>();
+		Ok(registered
+			.iter()
+			.flat_map(|registered| registered.0.iter())
+			.rev()
+			.find_map(|rule| {
+				rule.outputs
+					.iter()
+					.any(|output| output_key(&project_root, output) == wanted)
+					.then(|| RuleHandle {
+						name: rule.name.clone(),
+						outputs: rule.outputs.clone(),
+					})
+			}))
+	})?;
+	forge_table.set("who_produces", who_produces_fn)?;
+	Ok(())
+}
+
+/// Take the rules registered in lua since the last call, in registration order
+pub fn take_registered_rules(lua: &Lua) -> Vec<Rule> {
+	lua.app_data_mut::<RegisteredRules>()
+		.map(|mut registered| std::mem::take(&mut registered.0))
+		.unwrap_or_default()
+}
+
+/// Take the forge.on_graph_complete callbacks registered in lua since the last call, in registration order
+pub fn take_graph_hooks(lua: &Lua) -> Vec<Function> {
+	lua.app_data_mut::<GraphHooks>()
+		.map(|mut hooks| std::mem::take(&mut hooks.0))
+		.unwrap_or_default()
+}
+
+/// A fresh environment for one FORGE file: reads fall through to the globals, so forge and required modules
+/// stay visible, while globals the file assigns stay in its own table
+pub fn forge_file_environment(lua: &Lua) -> mlua::Result<Table> {
+	let environment = lua.create_table()?;
+	let metatable = lua.create_table()?;
+	metatable.set("__index", lua.globals())?;
+	environment.set_metatable(Some(metatable))?;
+	Ok(environment)
+}
+
+/// Release resources FORGE files acquired during the build, such as processes left running by exec.spawn
+pub fn teardown_lua_environment() {
+	lua_api::exec::kill_spawned_processes();
+}
+
 pub fn generate_types_lua() -> String {
 	let mut types = String::new();
 
 	types.push_str("-- Generated Lua type definitions for Forge APIs\n");
 	types.push_str("-- This file provides type hints for Lua language servers\n\n");
 
-	types.push_str(lua_api::fs::FsApi::fs_lua_type_definitions());
-	types.push('\n');
-	types.push_str(lua_api::http::HttpApi::http_lua_type_definitions());
-	types.push('\n');
-	types.push_str(lua_api::parse::ParseApi::parse_lua_type_definitions());
-	types.push('\n');
-	types.push_str(lua_api::exec::ExecApi::exec_lua_type_definitions());
-	types.push('\n');
-	types.push_str(lua_api::semver::SemverApi::semver_lua_type_definitions());
-	types.push('\n');
-	types.push_str(lua_api::platform::PlatformApi::platform_lua_type_definitions());
-	types.push('\n');
-	types.push_str(lua_api::path::PathApi::path_lua_type_definitions());
-	types.push('\n');
-	types.push_str(lua_api::string::StringApi::string_lua_type_definitions());
-	types.push('\n');
-	types.push_str(lua_api::hash::HashApi::hash_lua_type_definitions());
-	types.push('\n');
-	types.push_str(lua_api::time::TimeApi::time_lua_type_definitions());
-	types.push('\n');
-	types.push_str(lua_api::log::LogApi::log_lua_type_definitions());
-	types.push('\n');
-	types.push_str(lua_api::table::TableApi::table_lua_type_definitions());
-	types.push('\n');
-	types.push_str(lua_api::project::ProjectApi::project_lua_type_definitions());
-	types.push('\n');
+	for module in lua_api::modules() {
+		types.push_str((module.type_definitions)());
+		types.push('\n');
+	}
 
 	types.push_str("---@class Forge\n");
 	types.push_str("---@field config table Configuration table\n");
-	types.push_str("---@field fs Fs File system operations (all paths must be absolute)\n");
-	types.push_str("---@field http Http HTTP operations\n");
-	types.push_str("---@field parse Parse Parsing operations\n");
-	types.push_str("---@field exec Exec Command execution operations\n");
-	types.push_str("---@field semver Semver Semantic versioning operations\n");
-	types.push_str("---@field platform Platform Platform detection operations\n");
-	types.push_str("---@field path Path Path manipulation operations\n");
-	types.push_str("---@field string String String manipulation operations\n");
-	types.push_str("---@field hash Hash Hashing operations\n");
-	types.push_str("---@field time Time Time operations\n");
-	types.push_str("---@field log Log Logging operations\n");
-	types.push_str("---@field table Table Table operations\n");
-	types.push_str("---@field project Project Project context and utilities\n");
-	types.push_str("---@field rule fun(rule: table): nil Add a build rule\n");
+	types.push_str("---@field toolchains table<string, ForgeToolchain> Toolchains of FORGE_ROOT's [toolchains], by name\n");
+	types
+		.push_str("---@field out_dir string Absolute <cache_dir>/<target>/<profile> directory for this build's artifacts\n");
+	for module in lua_api::modules() {
+		types.push_str(&format!(
+			"---@field {} {} {}\n",
+			module.name,
+			capitalize_first_letter(module.name),
+			module.description
+		));
+	}
+	types.push_str(
+		"---@field rule fun(rule: table): ForgeRule Add a build rule; script = [[...]] in place of command runs it with [build] shell, rsp_format = \"gcc\"|\"msvc\" passes args too long for the OS as an @file\n",
+	);
+	types.push_str(
+		"---@field action fun(callback: fun(rule: ForgeActionContext)): userdata Use a Lua function as a rule's command, run at build time; it may only capture strings, numbers and booleans\n",
+	);
+	types.push_str(
+		"---@field install fun(install: { src: string|ForgeRule|(string|ForgeRule)[], dest: string, name: string? }): nil \
+		 Declare where forge install and forge package put files, relative to the prefix\n",
+	);
+	types.push_str(
+		"---@field rules fun(): table[] Snapshot of the rules this FORGE file registered so far: name, command, args, inputs, outputs, dependencies, tags; \
+		 use graph:rules() in forge.on_graph_complete for those of every FORGE file\n",
+	);
+	types.push_str(
+		"---@field who_produces fun(path: string): ForgeRule? The rule of this FORGE file with path among its outputs, nil when none; \
+		 use graph:who_produces(path) in forge.on_graph_complete to search every FORGE file\n",
+	);
+	types.push_str(
+		"---@field on_graph_complete fun(callback: fun(graph: ForgeGraph)) Run callback once every FORGE file is evaluated, before any rule runs\n",
+	);
 	types.push_str("---@field sleep fun(seconds: number): nil Sleep for specified seconds\n");
+	types.push_str("---@field version fun(): ForgeVersion Version and build information of the running forge binary\n");
+	types.push_str(
+		"---@field import_ninja fun(path: string): string[] Register the build edges of a build.ninja as rules, returns their names\n",
+	);
+	types.push_str(
+		"---@field import_make fun(path: string, env?: table<string, string>): string[] Register the targets of a Makefile as rules, returns their names; env gives the variables the Makefile does not set\n",
+	);
 	types.push('\n');
 
 	types.push_str("---@class Project\n");
@@ -170,8 +677,118 @@ pub fn generate_types_lua() -> String {
 	types.push_str(
 		"---@field resolve fun(path: string): string Convert relative path to absolute (relative to project root)\n",
 	);
+	types.push('\n');
+
+	types.push_str("---@class ForgeRule\n");
+	types.push_str("---@field name string Name of the rule\n");
+	types.push_str("---@field outputs string[] Outputs of the rule\n");
+	types.push_str("local ForgeRule = {}\n\n");
+	types.push_str("---Output of the rule, counting from 1\n---@param index integer?\n---@return string\n");
+	types.push_str("function ForgeRule:output(index) end\n\n");
+	types.push_str("---Run this rule after another one, given by handle or name\n---@param other ForgeRule|string\n");
+	types.push_str("---@return ForgeRule\nfunction ForgeRule:depend_on(other) end\n\n");
+
+	types.push_str("---@class ForgeGraph\n");
+	types.push_str("local ForgeGraph = {}\n\n");
+	types.push_str("---Snapshot of every rule of the build graph, sorted by name\n---@return table[]\n");
+	types.push_str("function ForgeGraph:rules() end\n\n");
+	types.push_str("---Snapshot of one rule, nil when the graph has none by that name\n---@param rule ForgeRule|string\n");
+	types.push_str("---@return table?\nfunction ForgeGraph:rule(rule) end\n\n");
+	types.push_str("---Name of the rule with path among its outputs, nil when none\n---@param path string\n");
+	types.push_str("---@return string?\nfunction ForgeGraph:who_produces(path) end\n\n");
+	types.push_str("---Run rule after dependency\n---@param rule ForgeRule|string\n---@param dependency ForgeRule|string\n");
+	types.push_str("function ForgeGraph:add_dependency(rule, dependency) end\n\n");
+	types.push_str("---Add a tag to rule\n---@param rule ForgeRule|string\n---@param tag string\n");
+	types.push_str("function ForgeGraph:add_tag(rule, tag) end\n\n");
+	types.push_str("---Set a variable of rule's environment\n---@param rule ForgeRule|string\n---@param key string\n");
+	types.push_str("---@param value string\nfunction ForgeGraph:set_env(rule, key, value) end\n\n");
+	types.push_str("---Remove rule from the graph, returns whether it was there\n---@param rule ForgeRule|string\n");
+	types.push_str("---@return boolean\nfunction ForgeGraph:remove(rule) end\n\n");
+
+	types.push_str("---@class ForgeActionContext\n");
+	types.push_str("---@field name string Name of the rule\n");
+	types.push_str("---@field inputs string[] Inputs of the rule\n");
+	types.push_str("---@field outputs string[] Outputs of the rule\n");
+	types.push_str("---@field workdir string Working directory of the rule\n");
+	types.push_str("---@field env table<string, string> Environment of the rule\n\n");
+
+	types.push_str("---@class ForgeToolchain\n");
+	types.push_str("---@field version string Version of the toolchain\n");
+	types.push_str("---@field root string Directory the toolchain was unpacked to\n");
+	types.push_str("---@field bin string[] Directories put in front of PATH for every rule\n");
+	types.push_str("---@field env table<string, string> Variables set for every rule that does not set them\n\n");
+
+	types.push_str("---@class ForgeVersion\n");
+	types.push_str("---@field version string Semantic version of forge\n");
+	types.push_str("---@field commit string? Git commit forge was built from\n");
+	types.push_str("---@field features string[] Cargo features enabled at build time\n");
+	types.push_str("---@field target string Target triple forge was built for\n");
+	types.push_str("---@field profile string Build profile (debug or release)\n");
 	types.push_str("\n---@type Forge\n");
 	types.push_str("forge = nil\n");
 
 	types
 }
+
+/// Class name lua_api gives a module's table, "pkg_config" is described by "Pkg_config"
+fn capitalize_first_letter(s: &str) -> String {
+	let mut chars = s.chars();
+	match chars.next() {
+		None => String::new(),
+		Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn library(name: &str, output: &str) -> Rule {
+		Rule {
+			name: name.to_string(),
+			command: "ar".to_string(),
+			outputs: vec![output.to_string()],
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn test_rule_queries_see_the_current_file() {
+		let root = PathBuf::from("/project");
+		let lua = Lua::new();
+		let forge = lua.create_table().unwrap();
+		add_rule_queries(&lua, &forge, root.clone()).unwrap();
+		lua.globals().set("forge", forge).unwrap();
+
+		lua.set_app_data(RegisteredRules(vec![library("foo", "libfoo.a")]));
+		let (count, producer): (usize, String) = lua
+			.load("return #forge.rules(), forge.who_produces('/project/libfoo.a').name")
+			.eval()
+			.unwrap();
+		assert_eq!((count, producer.as_str()), (1, "foo"));
+
+		// The next FORGE file does not see foo, which is already taken for the build graph
+		let merged = take_registered_rules(&lua);
+		lua.set_app_data(RegisteredRules(vec![library("bar", "libbar.a")]));
+		let (names, producer): (Vec<String>, Option<String>) = lua
+			.load("local names = {}\nfor _, rule in ipairs(forge.rules()) do table.insert(names, rule.name) end\nreturn names, forge.who_produces('libfoo.a')")
+			.eval()
+			.unwrap();
+		assert_eq!(names, vec!["bar"]);
+		assert_eq!(producer, None);
+
+		// The graph forge.on_graph_complete callbacks get has the rules of every file
+		let rules = Arc::new(DashMap::new());
+		let outputs = Arc::new(DashMap::new());
+		for rule in merged.into_iter().chain(take_registered_rules(&lua)) {
+			outputs.insert(rule.outputs[0].clone(), rule.name.clone());
+			rules.insert(rule.name.clone(), rule);
+		}
+		lua.globals().set("graph", GraphView::new(rules, outputs)).unwrap();
+		let (count, producer): (usize, String) = lua
+			.load("return #graph:rules(), graph:who_produces('libfoo.a')")
+			.eval()
+			.unwrap();
+		assert_eq!((count, producer.as_str()), (2, "foo"));
+	}
+}
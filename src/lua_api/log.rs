@@ -1,11 +1,35 @@
-use forge_macros::lua_api;
-use mlua::{Lua, Result, Table, UserData, UserDataMethods};
+use crate::build_log::{BuildLog, LogEvent};
+use forge_macros::{forge_lua_module, lua_api, lua_enum};
+use mlua::{Lua, LuaSerdeExt, Result, Table, UserData, UserDataMethods, Value};
 use std::io::{self, Write};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
 use std::sync::LazyLock;
 static PROGRESS_STATE: LazyLock<Mutex<Option<(u64, u64, String)>>> = LazyLock::new(|| Mutex::new(None));
 
+/// Log level taken by log.kv
+#[lua_enum]
+#[derive(Clone, Copy)]
+pub enum LogLevel {
+	Error,
+	Warn,
+	Info,
+	Debug,
+	Trace,
+}
+
+impl From<LogLevel> for log::Level {
+	fn from(level: LogLevel) -> Self {
+		match level {
+			LogLevel::Error => log::Level::Error,
+			LogLevel::Warn => log::Level::Warn,
+			LogLevel::Info => log::Level::Info,
+			LogLevel::Debug => log::Level::Debug,
+			LogLevel::Trace => log::Level::Trace,
+		}
+	}
+}
+
 #[derive(Clone)]
 pub struct LogApi;
 
@@ -20,71 +44,52 @@ impl LogApi {
 	}
 
 	/// Info level logging
-	fn info(message: String) -> Result<()> {
-		log::info!("{}", message);
+	fn info(lua: &Lua, message: String) -> Result<()> {
+		emit(lua, log::Level::Info, None, &message, None);
 		Ok(())
 	}
 
 	/// Warning level logging
-	fn warn(message: String) -> Result<()> {
-		log::warn!("{}", message);
+	fn warn(lua: &Lua, message: String) -> Result<()> {
+		emit(lua, log::Level::Warn, None, &message, None);
 		Ok(())
 	}
 
 	/// Error level logging
-	fn error(message: String) -> Result<()> {
-		log::error!("{}", message);
+	fn error(lua: &Lua, message: String) -> Result<()> {
+		emit(lua, log::Level::Error, None, &message, None);
 		Ok(())
 	}
 
 	/// Debug level logging
-	fn debug(message: String) -> Result<()> {
-		log::debug!("{}", message);
+	fn debug(lua: &Lua, message: String) -> Result<()> {
+		emit(lua, log::Level::Debug, None, &message, None);
 		Ok(())
 	}
 
 	/// Trace level logging
-	fn trace(message: String) -> Result<()> {
-		log::trace!("{}", message);
+	fn trace(lua: &Lua, message: String) -> Result<()> {
+		emit(lua, log::Level::Trace, None, &message, None);
 		Ok(())
 	}
 
+	/// Log message with structured fields, shown as "message key=value ..." and kept as fields in the build log
+	fn kv(lua: &Lua, level: LogLevel, message: String, fields: Table) -> Result<()> {
+		let fields: serde_json::Value = lua.from_value(Value::Table(fields))?;
+		emit(lua, level.into(), None, &message, Some(fields));
+		Ok(())
+	}
+
+	/// Logger whose messages are prefixed with "[name]" and tagged with the scope in the build log
+	/// Scopes nest: log.scope("deps"):scope("zlib") logs as "[deps/zlib]"
+	fn scope(name: String) -> Result<ScopedLogger> {
+		Ok(ScopedLogger { scope: name })
+	}
+
 	/// Progress logging
 	fn progress(current: u64, total: u64, message: Option<String>) -> Result<()> {
 		let message = message.unwrap_or_else(|| "Progress".to_string());
-
-		{
-			let mut progress_state = PROGRESS_STATE.lock().unwrap();
-			*progress_state = Some((current, total, message.clone()));
-		}
-
-		let percentage = if total > 0 {
-			(current as f64 / total as f64 * 100.0) as u32
-		} else {
-			0
-		};
-
-		let bar_width = 30;
-		let filled = (bar_width as f64 * current as f64 / total.max(1) as f64) as usize;
-		let empty = bar_width - filled;
-
-		let bar = format!(
-			"[{}{}] {}% ({}/{}) {}",
-			"=".repeat(filled),
-			" ".repeat(empty),
-			percentage,
-			current,
-			total,
-			message
-		);
-
-		eprint!("\r{}", bar);
-		io::stderr().flush().unwrap();
-
-		if current >= total {
-			eprintln!();
-		}
-
+		render_progress(current, total, &message);
 		Ok(())
 	}
 
@@ -102,6 +107,122 @@ impl LogApi {
 	}
 }
 
+/// Logger returned by log.scope, with the same level functions as log plus kv and scope
+#[derive(Clone)]
+pub struct ScopedLogger {
+	scope: String,
+}
+
+impl UserData for ScopedLogger {
+	fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+		for (name, level) in [
+			("error", log::Level::Error),
+			("warn", log::Level::Warn),
+			("info", log::Level::Info),
+			("debug", log::Level::Debug),
+			("trace", log::Level::Trace),
+		] {
+			methods.add_method(name, move |lua, this, message: String| {
+				emit(lua, level, Some(&this.scope), &message, None);
+				Ok(())
+			});
+		}
+
+		methods.add_method("kv", |lua, this, (level, message, fields): (LogLevel, String, Table)| {
+			let fields: serde_json::Value = lua.from_value(Value::Table(fields))?;
+			emit(lua, level.into(), Some(&this.scope), &message, Some(fields));
+			Ok(())
+		});
+
+		methods.add_method("scope", |_, this, name: String| {
+			Ok(ScopedLogger {
+				scope: format!("{}/{}", this.scope, name),
+			})
+		});
+	}
+}
+
+/// Log a message from Lua to the console and, during a build, to the build log so that messages from parallel
+/// rules and scopes can be told apart
+fn emit(lua: &Lua, level: log::Level, scope: Option<&str>, message: &str, fields: Option<serde_json::Value>) {
+	log::log!(level, "{}", format_line(scope, message, fields.as_ref()));
+
+	if let Some(build_log) = lua.app_data_ref::<Arc<BuildLog>>() {
+		let mut event = LogEvent::new("log").level(level_name(level)).message(message);
+		if let Some(scope) = scope {
+			event = event.scope(scope);
+		}
+		if let Some(fields) = fields {
+			event = event.fields(fields);
+		}
+		build_log.record(event);
+	}
+}
+
+fn format_line(scope: Option<&str>, message: &str, fields: Option<&serde_json::Value>) -> String {
+	let mut line = match scope {
+		Some(scope) => format!("[{}] {}", scope, message),
+		None => message.to_string(),
+	};
+	if let Some(serde_json::Value::Object(fields)) = fields {
+		for (key, value) in fields {
+			let value = match value {
+				serde_json::Value::String(value) => value.clone(),
+				value => value.to_string(),
+			};
+			line.push_str(&format!(" {}={}", key, value));
+		}
+	}
+	line
+}
+
+fn level_name(level: log::Level) -> &'static str {
+	match level {
+		log::Level::Error => "error",
+		log::Level::Warn => "warn",
+		log::Level::Info => "info",
+		log::Level::Debug => "debug",
+		log::Level::Trace => "trace",
+	}
+}
+
+/// Draw the progress bar on stderr, also used by Rust code such as http.download
+pub fn render_progress(current: u64, total: u64, message: &str) {
+	{
+		let mut progress_state = PROGRESS_STATE.lock().unwrap();
+		*progress_state = Some((current, total, message.to_string()));
+	}
+
+	let percentage = if total > 0 {
+		(current as f64 / total as f64 * 100.0) as u32
+	} else {
+		0
+	};
+
+	let bar_width = 30;
+	let filled = ((bar_width as f64 * current as f64 / total.max(1) as f64) as usize).min(bar_width);
+	let empty = bar_width - filled;
+
+	let bar = format!(
+		"[{}{}] {}% ({}/{}) {}",
+		"=".repeat(filled),
+		" ".repeat(empty),
+		percentage,
+		current,
+		total,
+		message
+	);
+
+	eprint!("\r{}", bar);
+	io::stderr().flush().unwrap();
+
+	if current >= total {
+		eprintln!();
+	}
+}
+
+forge_lua_module!(log, LogApi, "Logging operations");
+
 pub fn create_log_table(lua: &Lua) -> Result<Table> {
 	LogApi::create_log_table(lua)
 }
@@ -1,14 +1,50 @@
-mod exec;
-mod fs;
+pub mod action;
+pub mod archive;
+mod cargo;
+mod cc;
+mod cmake;
+mod crypto;
+mod docker;
+pub mod exec;
+pub mod fs;
 mod hash;
-mod http;
+pub mod http;
 pub mod init;
 mod log;
+mod net;
+pub mod observations;
 mod parse;
 mod path;
+mod pkg_config;
 mod platform;
 mod project;
+mod project_path;
+mod random;
+mod regex;
+mod rust;
+pub mod sandbox;
 mod semver;
 mod string;
+pub mod sysroot;
 mod table;
+mod template;
 mod time;
+mod uuid;
+
+/// An API table exposed as forge.<name>, registered with forge_macros::forge_lua_module!
+pub struct LuaModule {
+	pub name: &'static str,
+	/// Shown on the module's field of the Forge class in types.lua
+	pub description: &'static str,
+	pub create_table: fn(&mlua::Lua) -> mlua::Result<mlua::Table>,
+	pub type_definitions: fn() -> &'static str,
+}
+
+inventory::collect!(LuaModule);
+
+/// Every registered API module, sorted by name so the forge table and types.lua come out the same on every build
+pub fn modules() -> Vec<&'static LuaModule> {
+	let mut modules: Vec<_> = inventory::iter::<LuaModule>.into_iter().collect();
+	modules.sort_by_key(|module| module.name);
+	modules
+}
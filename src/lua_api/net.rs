@@ -0,0 +1,69 @@
+use forge_macros::{forge_lua_module, lua_api};
+use mlua::{Lua, Result, Table, UserData, UserDataMethods};
+use std::{
+	net::{TcpListener, TcpStream, ToSocketAddrs},
+	time::{Duration, Instant},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+const DEFAULT_WAIT_TIMEOUT_SECS: f64 = 30.0;
+
+#[derive(Clone)]
+pub struct NetApi;
+
+impl UserData for NetApi {
+	fn add_methods<M: UserDataMethods<Self>>(_methods: &mut M) {}
+}
+
+#[lua_api(name = "net")]
+impl NetApi {
+	pub fn new() -> Self {
+		Self
+	}
+
+	/// Get the hostname of this machine
+	fn hostname() -> Result<String> {
+		Ok(gethostname::gethostname().to_string_lossy().to_string())
+	}
+
+	/// Find a TCP port on 127.0.0.1 that is currently free
+	/// The port is released before returning, so bind it quickly
+	fn free_port() -> Result<u16> {
+		let listener = TcpListener::bind(("127.0.0.1", 0)).map_err(mlua::Error::external)?;
+		let port = listener.local_addr().map_err(mlua::Error::external)?.port();
+		Ok(port)
+	}
+
+	/// Wait until a TCP connection to host:port succeeds (timeout in seconds, default 30)
+	/// @return true once the port accepts connections, false if the timeout expired
+	fn wait_for_port(host: String, port: u16, timeout: Option<f64>) -> Result<bool> {
+		let timeout = timeout.unwrap_or(DEFAULT_WAIT_TIMEOUT_SECS);
+		if !timeout.is_finite() || timeout < 0.0 {
+			return Err(mlua::Error::RuntimeError(format!("Invalid timeout: {}", timeout)));
+		}
+		let deadline = Instant::now() + Duration::from_secs_f64(timeout);
+
+		loop {
+			// Resolve on every attempt, the host may only become resolvable once its service is up
+			if let Ok(addrs) = (host.as_str(), port).to_socket_addrs() {
+				for addr in addrs {
+					if TcpStream::connect_timeout(&addr, POLL_INTERVAL).is_ok() {
+						return Ok(true);
+					}
+				}
+			}
+
+			let now = Instant::now();
+			if now >= deadline {
+				return Ok(false);
+			}
+			std::thread::sleep(POLL_INTERVAL.min(deadline - now));
+		}
+	}
+}
+
+forge_lua_module!(net, NetApi, "Network utilities for integration tests");
+
+pub fn create_net_table(lua: &Lua) -> Result<Table> {
+	NetApi::create_net_table(lua)
+}
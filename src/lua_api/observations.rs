@@ -0,0 +1,221 @@
+use crate::eval_cache::Observation;
+use mlua::{Function, Lua, MultiValue, Result, Table, Value};
+use std::{collections::HashMap, path::PathBuf};
+
+/// What evaluating a FORGE file, or loading one of the modules it required, read from the host
+#[derive(Debug, Clone, Default)]
+pub struct Observed {
+	/// Read something that cannot be checked again cheaply, like a file's content, a command's output or the time
+	pub volatile: bool,
+	pub observations: Vec<Observation>,
+}
+
+impl Observed {
+	/// The Lua files loaded through require, dofile or loadfile, each once
+	pub fn modules(&self) -> Vec<PathBuf> {
+		let mut modules: Vec<PathBuf> = self
+			.observations
+			.iter()
+			.filter_map(|observation| match observation {
+				Observation::Module { path, .. } => Some(path.clone()),
+				_ => None,
+			})
+			.collect();
+		modules.sort();
+		modules.dedup();
+		modules
+	}
+
+	fn extend(&mut self, other: &Observed) {
+		self.volatile |= other.volatile;
+		self.observations.extend(other.observations.iter().cloned());
+	}
+}
+
+/// One frame for the FORGE file being evaluated and one for each require in progress, innermost last, plus what
+/// each module observed when it was first loaded, so files requiring it later inherit that too
+struct Recorder {
+	frames: Vec<Observed>,
+	modules: HashMap<String, Observed>,
+}
+
+/// Whether calling forge.<module>.<function>, or os.<function> for module "os", gives the same result as long as
+/// the FORGE file and configuration do not change
+fn cacheable(module: &str, function: &str) -> bool {
+	match module {
+		"crypto" | "log" | "parse" | "project" | "regex" | "semver" | "string" | "table" => true,
+		"path" => !matches!(function, "absolute" | "canonicalize" | "home"),
+		"platform" => function != "cwd",
+		"template" => function != "render_file",
+		// Reads FORGE_ROOT, which is part of every evaluation's key
+		"sysroot" => function == "get",
+		"hash" => matches!(function, "string" | "bytes"),
+		// Both record what they saw, which is checked again before reusing the rules
+		"fs" => matches!(function, "glob" | "exists"),
+		"os" => function == "getenv",
+		_ => false,
+	}
+}
+
+/// Start recording the modules FORGE files evaluated on lua require, dofile or loadfile, and what fs.glob and
+/// fs.exists saw
+pub fn install(lua: &Lua) -> Result<()> {
+	lua.set_app_data(Recorder {
+		frames: vec![Observed::default()],
+		modules: HashMap::new(),
+	});
+
+	let globals = lua.globals();
+	let require: Function = globals.get("require")?;
+	let tracked_require = lua.create_function(move |lua, name: String| {
+		if let Some(mut recorder) = lua.app_data_mut::<Recorder>() {
+			recorder.frames.push(Observed::default());
+		}
+		let result = require.call::<MultiValue>(name.as_str());
+		if let Some(mut recorder) = lua.app_data_mut::<Recorder>() {
+			let loaded = recorder.frames.pop().unwrap_or_default();
+			// Only the first successful require of a module runs it; later ones observe what that load did
+			let observed = if result.is_ok() {
+				recorder.modules.entry(name).or_insert(loaded).clone()
+			} else {
+				loaded
+			};
+			if let Some(frame) = recorder.frames.last_mut() {
+				frame.extend(&observed);
+			}
+		}
+		result
+	})?;
+	globals.set("require", tracked_require)?;
+
+	// Only there under the full sandbox; the file they run is read like a required module, stdin is not recorded
+	for name in ["dofile", "loadfile"] {
+		let Some(original) = globals.get::<Option<Function>>(name)? else {
+			continue;
+		};
+		let tracked = lua.create_function(move |lua, args: MultiValue| {
+			match args.front() {
+				Some(Value::String(path)) => {
+					let path = PathBuf::from(path.to_str()?.to_string());
+					let path = std::path::absolute(&path).unwrap_or(path);
+					match std::fs::read(&path) {
+						Ok(content) => record(lua, Observation::module(&path, &content)),
+						Err(_) => mark_volatile(lua),
+					}
+				}
+				_ => mark_volatile(lua),
+			}
+			original.call::<MultiValue>(args)
+		})?;
+		globals.set(name, tracked)?;
+	}
+	Ok(())
+}
+
+/// Also record os.getenv, and mark the evaluation volatile when it reaches any other part of the host that is
+/// not recorded, for evaluations that are cached
+pub fn watch_host(lua: &Lua, forge_table: &Table) -> Result<()> {
+	let globals = lua.globals();
+	for module in super::modules() {
+		if let Some(table) = forge_table.get::<Option<Table>>(module.name)? {
+			forge_table.set(module.name, watch(lua, table, module.name)?)?;
+		}
+	}
+
+	let os: Table = globals.get("os")?;
+	os.set(
+		"getenv",
+		lua.create_function(|lua, name: String| {
+			let value = std::env::var(&name).ok();
+			record(
+				lua,
+				Observation::Env {
+					name,
+					value: value.clone(),
+				},
+			);
+			Ok(value)
+		})?,
+	)?;
+	globals.set("os", watch(lua, os, "os")?)?;
+	if let Some(io) = globals.get::<Option<Table>>("io")? {
+		globals.set("io", watch(lua, io, "io")?)?;
+	}
+	Ok(())
+}
+
+/// A stand-in for table that marks the evaluation volatile when a field that is not cacheable is read
+fn watch(lua: &Lua, table: Table, module: &'static str) -> Result<Table> {
+	let proxy = lua.create_table()?;
+	let metatable = lua.create_table()?;
+
+	let inner = table.clone();
+	let index = lua.create_function(move |lua, (_, key): (Table, Value)| {
+		if let Value::String(name) = &key
+			&& !cacheable(module, &name.to_str()?)
+		{
+			mark_volatile(lua);
+		}
+		inner.get::<Value>(key)
+	})?;
+	metatable.set("__index", index)?;
+
+	let next: Function = lua.globals().get("next")?;
+	let pairs = lua.create_function(move |_, _: Table| Ok((next.clone(), table.clone(), Value::Nil)))?;
+	metatable.set("__pairs", pairs)?;
+
+	proxy.set_metatable(Some(metatable))?;
+	Ok(proxy)
+}
+
+/// Record an observation for the FORGE file or module being evaluated, if evaluations on lua are cached
+pub fn record(lua: &Lua, observation: Observation) {
+	if let Some(mut recorder) = lua.app_data_mut::<Recorder>()
+		&& let Some(frame) = recorder.frames.last_mut()
+	{
+		frame.observations.push(observation);
+	}
+}
+
+/// Keep the FORGE file being evaluated out of the cache
+pub fn mark_volatile(lua: &Lua) {
+	if let Some(mut recorder) = lua.app_data_mut::<Recorder>()
+		&& let Some(frame) = recorder.frames.last_mut()
+	{
+		frame.volatile = true;
+	}
+}
+
+/// What the FORGE file just evaluated on lua observed, resetting the recording for the next one
+pub fn take(lua: &Lua) -> Observed {
+	lua.app_data_mut::<Recorder>()
+		.and_then(|mut recorder| recorder.frames.first_mut().map(std::mem::take))
+		.unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_dofile_is_recorded() {
+		let dir = std::env::temp_dir().join(format!("forge-observations-test-{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let module = dir.join("helpers.lua");
+		std::fs::write(&module, "return 42").unwrap();
+
+		let lua = Lua::new();
+		install(&lua).unwrap();
+		let value: i64 = lua
+			.load(format!("return dofile({:?})", module.to_string_lossy()))
+			.eval()
+			.unwrap();
+		assert_eq!(value, 42);
+		lua.load("loadfile('missing.lua')").exec().unwrap();
+		let observed = take(&lua);
+		std::fs::remove_dir_all(&dir).unwrap();
+
+		assert_eq!(observed.modules(), vec![module]);
+		assert!(observed.volatile);
+	}
+}
@@ -1,4 +1,4 @@
-use forge_macros::lua_api;
+use forge_macros::{forge_lua_module, lua_api};
 use mlua::{Lua, LuaSerdeExt, Result, Table, UserData, UserDataMethods, Value};
 
 #[derive(Clone)]
@@ -25,8 +25,43 @@ impl ParseApi {
 		let value: toml::Value = toml::from_str(&toml_str).map_err(mlua::Error::external)?;
 		lua.to_value(&value)
 	}
+
+	/// Encode a Lua value as JSON (options: { pretty = true })
+	/// Tables decoded from JSON keep their array/object kind; use parse.array for empty arrays
+	fn json_encode(lua: &Lua, value: Value, options: Option<Table>) -> Result<String> {
+		let pretty = options
+			.as_ref()
+			.and_then(|opts| opts.get::<Option<bool>>("pretty").ok().flatten())
+			.unwrap_or(false);
+
+		let value: serde_json::Value = lua.from_value(value)?;
+		let encoded = if pretty {
+			serde_json::to_string_pretty(&value)
+		} else {
+			serde_json::to_string(&value)
+		};
+		encoded.map_err(mlua::Error::external)
+	}
+
+	/// Encode a Lua table as a TOML document
+	fn toml_encode(lua: &Lua, value: Value) -> Result<String> {
+		let value: toml::Value = lua.from_value(value)?;
+		toml::to_string(&value).map_err(mlua::Error::external)
+	}
+
+	/// Copy a sequence (or nil for an empty one) into a table that always encodes as an array
+	fn array(lua: &Lua, values: Option<Vec<Value>>) -> Result<Value> {
+		let values: Vec<serde_json::Value> = values
+			.unwrap_or_default()
+			.into_iter()
+			.map(|value| lua.from_value(value))
+			.collect::<Result<_>>()?;
+		lua.to_value(&values)
+	}
 }
 
+forge_lua_module!(parse, ParseApi, "Parsing operations");
+
 pub fn create_parse_table(lua: &Lua) -> Result<Table> {
 	ParseApi::create_parse_table(lua)
 }
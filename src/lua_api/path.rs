@@ -1,6 +1,6 @@
-use forge_macros::lua_api;
+use forge_macros::{forge_lua_module, lua_api};
 use mlua::{Lua, Result, Table, UserData, UserDataMethods};
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 
 #[derive(Clone)]
 pub struct PathApi;
@@ -15,10 +15,22 @@ impl PathApi {
 		Self
 	}
 
-	/// Join path components
+	/// Join path components; an absolute component (/x, C:\x, \\server\share) replaces everything before it
 	fn join(components: Vec<String>) -> Result<String> {
-		let path = components.iter().fold(PathBuf::new(), |acc, component| acc.join(component));
-		Ok(path.to_string_lossy().to_string())
+		let mut joined = String::new();
+		for component in &components {
+			let parsed = ParsedPath::parse(component);
+			if joined.is_empty() || parsed.rooted || !parsed.prefix.is_empty() {
+				joined = component.clone();
+			} else if !component.is_empty() {
+				let separator = ParsedPath::parse(&joined).separator();
+				if !joined.ends_with(|c: char| is_separator(c, separator == '\\')) {
+					joined.push(separator);
+				}
+				joined.push_str(component);
+			}
+		}
+		Ok(joined)
 	}
 
 	/// Get directory name (parent directory)
@@ -88,27 +100,80 @@ impl PathApi {
 		}
 	}
 
-	/// Normalize path (remove . and .. components)
+	/// Normalize path (remove . and .. components), keeping drive letters and UNC prefixes
+	/// Leading .. of relative paths is kept, .. above the root is dropped
 	fn normalize(path: String) -> Result<String> {
-		let path = Path::new(&path);
-		let mut components = Vec::new();
-
-		for component in path.components() {
-			match component {
-				std::path::Component::CurDir => {}
-				std::path::Component::ParentDir => {
-					if !components.is_empty() {
-						components.pop();
+		// Verbatim paths are passed to Windows untouched, .. has no meaning in them
+		if path.starts_with(r"\\?\") {
+			return Ok(path);
+		}
+
+		let parsed = ParsedPath::parse(&path);
+		let mut parts: Vec<&str> = Vec::new();
+		for part in &parsed.parts {
+			match *part {
+				"." => {}
+				".." => match parts.last() {
+					Some(last) if *last != ".." => {
+						parts.pop();
 					}
-				}
-				_ => {
-					components.push(component.as_os_str());
-				}
+					_ if parsed.rooted => {}
+					_ => parts.push(".."),
+				},
+				part => parts.push(part),
 			}
 		}
 
-		let normalized: PathBuf = components.iter().collect();
-		Ok(normalized.to_string_lossy().to_string())
+		Ok(parsed.render(&parts))
+	}
+
+	/// Convert separators to forward slashes
+	fn to_slash(path: String) -> Result<String> {
+		Ok(path.replace('\\', "/"))
+	}
+
+	/// Convert forward slashes to the separator of the host platform
+	fn from_slash(path: String) -> Result<String> {
+		Ok(path.replace('/', std::path::MAIN_SEPARATOR_STR))
+	}
+
+	/// Replace the extension of the last component, an empty extension removes it
+	/// The extension may be given with or without its leading dot
+	fn with_extension(path: String, extension: String) -> Result<String> {
+		let parsed = ParsedPath::parse(&path);
+		let Some(last) = parsed.parts.last().copied().filter(|last| !matches!(*last, "." | "..")) else {
+			return Ok(path);
+		};
+
+		let stem = match last.rfind('.') {
+			Some(dot) if dot > 0 => &last[..dot],
+			_ => last,
+		};
+		let extension = extension.trim_start_matches('.');
+		let file_name = if extension.is_empty() {
+			stem.to_string()
+		} else {
+			format!("{}.{}", stem, extension)
+		};
+
+		Ok(format!(
+			"{}{}",
+			&path[..path.len() - last.len() - trailing_separators(&path, parsed.windows)],
+			file_name
+		))
+	}
+
+	/// Compute path relative to base, inserting ".." where path is not below base
+	/// Both are normalized lexically first and must be either both absolute or both relative
+	fn relative_to(path: String, base: String) -> Result<String> {
+		relative_path(Path::new(&path), Path::new(&base))
+			.map(|relative| relative.to_string_lossy().to_string())
+			.ok_or_else(|| {
+				mlua::Error::RuntimeError(format!(
+					"Cannot make '{}' relative to '{}': paths must both be absolute or both relative, on the same root",
+					path, base
+				))
+			})
 	}
 
 	/// Get home directory
@@ -119,6 +184,128 @@ impl PathApi {
 	}
 }
 
+/// A path split the way Windows or POSIX would, independent of the host platform, so FORGE
+/// files written on one platform behave the same on the other
+struct ParsedPath<'a> {
+	/// Drive ("C:") or UNC share ("\\server\share"), empty for POSIX paths
+	prefix: &'a str,
+	rooted: bool,
+	parts: Vec<&'a str>,
+	/// Backslashes are separators, either because of the prefix or the host platform
+	windows: bool,
+}
+
+impl<'a> ParsedPath<'a> {
+	fn parse(path: &'a str) -> Self {
+		let bytes = path.as_bytes();
+		let mut windows = cfg!(windows);
+		let mut prefix_len = 0;
+
+		if bytes.len() >= 2 && bytes[1] == b':' && bytes[0].is_ascii_alphabetic() {
+			windows = true;
+			prefix_len = 2;
+		} else if path.starts_with(r"\\") || (windows && path.starts_with("//")) {
+			windows = true;
+			// \\server\share: the prefix runs up to the separator after the share name
+			let mut separators = 0;
+			prefix_len = path.len();
+			for (index, c) in path.char_indices().skip(2) {
+				if is_separator(c, true) {
+					separators += 1;
+					if separators == 2 {
+						prefix_len = index;
+						break;
+					}
+				}
+			}
+		}
+
+		let rest = &path[prefix_len..];
+		let rooted = rest.starts_with(|c: char| is_separator(c, windows)) || (prefix_len > 2);
+		let parts = rest
+			.split(|c: char| is_separator(c, windows))
+			.filter(|part| !part.is_empty())
+			.collect();
+
+		Self {
+			prefix: &path[..prefix_len],
+			rooted,
+			parts,
+			windows,
+		}
+	}
+
+	fn separator(&self) -> char {
+		if self.windows { '\\' } else { '/' }
+	}
+
+	fn render(&self, parts: &[&str]) -> String {
+		let separator = self.separator();
+		let mut rendered = self.prefix.replace(|c: char| is_separator(c, true), &separator.to_string());
+		if self.rooted {
+			rendered.push(separator);
+		}
+		rendered.push_str(&parts.join(&separator.to_string()));
+
+		if rendered.is_empty() { ".".to_string() } else { rendered }
+	}
+}
+
+fn is_separator(c: char, windows: bool) -> bool {
+	c == '/' || (windows && c == '\\')
+}
+
+fn trailing_separators(path: &str, windows: bool) -> usize {
+	path.len() - path.trim_end_matches(|c: char| is_separator(c, windows)).len()
+}
+
+/// Lexical relative path from base to path, None when they don't share a root
+pub fn relative_path(path: &Path, base: &Path) -> Option<PathBuf> {
+	if path.is_absolute() != base.is_absolute() {
+		return None;
+	}
+
+	let path = lexical_components(path);
+	let base = lexical_components(base);
+	let common = path.iter().zip(&base).take_while(|(a, b)| a == b).count();
+
+	// Differing prefixes (drive letters) or base climbing above the common part can't be expressed
+	if base[common..]
+		.iter()
+		.any(|component| matches!(component, Component::Prefix(_) | Component::RootDir | Component::ParentDir))
+	{
+		return None;
+	}
+
+	let mut relative: PathBuf = base[common..].iter().map(|_| Component::ParentDir).collect();
+	relative.extend(&path[common..]);
+	if relative.as_os_str().is_empty() {
+		relative.push(".");
+	}
+	Some(relative)
+}
+
+/// Components with "." removed and ".." applied, keeping leading ".." of relative paths
+fn lexical_components(path: &Path) -> Vec<Component<'_>> {
+	let mut components = Vec::new();
+	for component in path.components() {
+		match component {
+			Component::CurDir => {}
+			Component::ParentDir => match components.last() {
+				Some(Component::Normal(_)) => {
+					components.pop();
+				}
+				Some(Component::RootDir) | Some(Component::Prefix(_)) => {}
+				_ => components.push(component),
+			},
+			_ => components.push(component),
+		}
+	}
+	components
+}
+
+forge_lua_module!(path, PathApi, "Path manipulation operations");
+
 pub fn create_path_table(lua: &Lua) -> Result<Table> {
 	PathApi::create_path_table(lua)
 }
@@ -0,0 +1,144 @@
+use crate::lua_api::sandbox;
+use forge_macros::{LuaClass, forge_lua_module, lua_api};
+use mlua::{FromLua, Lua, LuaSerdeExt, Result, Table, UserData, UserDataMethods, Value};
+use serde::{Deserialize, Serialize};
+use std::process::Output;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum PkgConfigError {
+	#[error("pkg-config not found: {program} - {reason}")]
+	NotFound {
+		program: String,
+		reason: String,
+	},
+
+	#[error("pkg-config query failed for '{package}': {reason}")]
+	QueryFailed {
+		package: String,
+		reason: String,
+	},
+}
+
+/// Cross-compilation settings, usually taken straight from a target definition table
+#[derive(Debug, Default, Deserialize, Serialize, LuaClass)]
+pub struct PkgConfigTarget {
+	pub canonical_name: Option<String>,
+	pub sysroot: Option<String>,
+	pub pkg_config_path: Option<Vec<String>>,
+	pub pkg_config_libdir: Option<Vec<String>>,
+	#[serde(rename = "static")]
+	pub static_link: Option<bool>,
+}
+
+impl FromLua for PkgConfigTarget {
+	fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+		lua.from_value(value)
+	}
+}
+
+#[derive(Clone)]
+pub struct PkgConfigApi;
+
+impl UserData for PkgConfigApi {
+	fn add_methods<M: UserDataMethods<Self>>(_methods: &mut M) {}
+}
+
+#[lua_api(name = "pkg_config")]
+impl PkgConfigApi {
+	pub fn new() -> Self {
+		Self
+	}
+
+	/// Check if a package is known to pkg-config (target is an optional target definition)
+	fn exists(lua: &Lua, name: String, target: Option<PkgConfigTarget>) -> Result<bool> {
+		let output = run_pkg_config(lua, &target.unwrap_or_default(), &["--exists", name.as_str()])?;
+		Ok(output.status.success())
+	}
+
+	/// Get the compiler flags for a package
+	fn cflags(lua: &Lua, name: String, target: Option<PkgConfigTarget>) -> Result<Vec<String>> {
+		query_flags(lua, &name, &target.unwrap_or_default(), "--cflags")
+	}
+
+	/// Get the linker flags for a package
+	fn libs(lua: &Lua, name: String, target: Option<PkgConfigTarget>) -> Result<Vec<String>> {
+		query_flags(lua, &name, &target.unwrap_or_default(), "--libs")
+	}
+
+	/// Get the version of a package, or nil if it is not installed
+	fn version(lua: &Lua, name: String, target: Option<PkgConfigTarget>) -> Result<Option<String>> {
+		let output = run_pkg_config(lua, &target.unwrap_or_default(), &["--modversion", name.as_str()])?;
+		if !output.status.success() {
+			return Ok(None);
+		}
+		Ok(Some(String::from_utf8_lossy(&output.stdout).trim().to_string()))
+	}
+}
+
+fn query_flags(lua: &Lua, name: &str, target: &PkgConfigTarget, flag: &str) -> Result<Vec<String>> {
+	let mut args = vec![flag];
+	if target.static_link.unwrap_or(false) {
+		args.push("--static");
+	}
+	args.push(name);
+
+	let output = run_pkg_config(lua, target, &args)?;
+	if !output.status.success() {
+		return Err(mlua::Error::external(PkgConfigError::QueryFailed {
+			package: name.to_string(),
+			reason: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+		}));
+	}
+
+	Ok(String::from_utf8_lossy(&output.stdout)
+		.split_whitespace()
+		.map(|s| s.to_string())
+		.collect())
+}
+
+fn run_pkg_config(lua: &Lua, target: &PkgConfigTarget, args: &[&str]) -> Result<Output> {
+	let mut candidates = Vec::new();
+	if let Ok(program) = std::env::var("PKG_CONFIG") {
+		candidates.push(program);
+	} else {
+		if let Some(triple) = &target.canonical_name {
+			candidates.push(format!("{}-pkg-config", triple));
+		}
+		candidates.push("pkg-config".to_string());
+	}
+
+	let mut last_error = None;
+	for program in &candidates {
+		let mut cmd = sandbox::command(lua, program)?;
+		cmd.args(args);
+
+		if let Some(sysroot) = &target.sysroot {
+			cmd.env("PKG_CONFIG_SYSROOT_DIR", sysroot);
+			cmd.env("PKG_CONFIG_ALLOW_CROSS", "1");
+		}
+		if let Some(paths) = &target.pkg_config_path {
+			cmd.env("PKG_CONFIG_PATH", std::env::join_paths(paths).map_err(mlua::Error::external)?);
+		}
+		if let Some(paths) = &target.pkg_config_libdir {
+			cmd.env(
+				"PKG_CONFIG_LIBDIR",
+				std::env::join_paths(paths).map_err(mlua::Error::external)?,
+			);
+		}
+
+		match cmd.output() {
+			Ok(output) => return Ok(output),
+			Err(e) => last_error = Some((program.clone(), e.to_string())),
+		}
+	}
+
+	let (program, reason) = last_error.unwrap_or_else(|| ("pkg-config".to_string(), "no candidates".to_string()));
+	Err(mlua::Error::external(PkgConfigError::NotFound { program, reason }))
+}
+
+forge_lua_module!(pkg_config, PkgConfigApi, "pkg-config queries for system libraries");
+
+pub fn create_pkg_config_table(lua: &Lua) -> Result<Table> {
+	PkgConfigApi::create_pkg_config_table(lua)
+}
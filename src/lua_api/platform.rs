@@ -1,4 +1,4 @@
-use forge_macros::lua_api;
+use forge_macros::{forge_lua_module, lua_api};
 use mlua::{Lua, Result, Table, UserData, UserDataMethods};
 use std::env;
 
@@ -11,6 +11,14 @@ impl UserData for PlatformApi {
 
 #[lua_api(name = "platform")]
 impl PlatformApi {
+	/// Separator between path components, a backslash on Windows and "/" elsewhere
+	#[lua_const]
+	const SEPARATOR: &str = std::path::MAIN_SEPARATOR_STR;
+
+	/// Extension of executables including the dot, "" outside of Windows
+	#[lua_const]
+	const EXE_SUFFIX: &str = std::env::consts::EXE_SUFFIX;
+
 	pub fn new() -> Self {
 		Self
 	}
@@ -62,12 +70,12 @@ impl PlatformApi {
 
 	/// Get path separator
 	fn path_separator() -> Result<String> {
-		Ok(std::path::MAIN_SEPARATOR.to_string())
+		Ok(Self::SEPARATOR.to_string())
 	}
 
 	/// Get executable extension
 	fn exe_extension() -> Result<&'static str> {
-		Ok(if cfg!(target_os = "windows") { ".exe" } else { "" })
+		Ok(Self::EXE_SUFFIX)
 	}
 
 	/// Get current working directory
@@ -78,6 +86,8 @@ impl PlatformApi {
 	}
 }
 
+forge_lua_module!(platform, PlatformApi, "Platform detection operations");
+
 pub fn create_platform_table(lua: &Lua) -> Result<Table> {
 	PlatformApi::create_platform_table(lua)
 }
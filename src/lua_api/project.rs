@@ -1,4 +1,5 @@
-use forge_macros::lua_api;
+use crate::lua_api::project_path::ProjectRoot;
+use forge_macros::{forge_lua_module, lua_api};
 use mlua::{Lua, Result, Table, UserData, UserDataMethods};
 use std::path::Path;
 
@@ -25,8 +26,12 @@ impl ProjectApi {
 	}
 }
 
-pub fn create_project_table(lua: &Lua, project_path: String) -> Result<Table> {
+forge_lua_module!(project, ProjectApi, "Project context and utilities");
+
+pub fn create_project_table(lua: &Lua) -> Result<Table> {
 	let table = ProjectApi::create_project_table(lua)?;
-	table.set("root", project_path)?;
+	if let Some(root) = lua.app_data_ref::<ProjectRoot>() {
+		table.set("root", root.0.to_string_lossy().to_string())?;
+	}
 	Ok(table)
 }
@@ -0,0 +1,140 @@
+use mlua::{FromLua, Lua, Result, Value};
+use std::{
+	ops::Deref,
+	path::{Component, Path, PathBuf},
+};
+
+/// Root of the project whose FORGE files are evaluated by a Lua state, stored as app data
+pub struct ProjectRoot(pub PathBuf);
+
+/// Directories path arguments must stay inside when `[build] restrict_fs` is enabled, stored as app data
+pub struct FsSandbox {
+	roots: Vec<PathBuf>,
+}
+
+impl FsSandbox {
+	pub fn new(roots: Vec<PathBuf>) -> Self {
+		Self {
+			roots: roots.iter().filter_map(|root| canonicalize_lenient(root)).collect(),
+		}
+	}
+
+	pub fn check(&self, path: &Path) -> Result<()> {
+		if let Some(resolved) = canonicalize_lenient(path)
+			&& self.roots.iter().any(|root| resolved.starts_with(root))
+		{
+			return Ok(());
+		}
+
+		Err(mlua::Error::RuntimeError(format!(
+			"Path '{}' is outside the project root, build cache, download cache and temp dir (restrict_fs is enabled)",
+			path.display()
+		)))
+	}
+}
+
+/// Resolve path one component at a time the way the OS does: every part that exists is canonicalized, so symlinks
+/// are followed before a later ".." is applied, and the parts that don't exist yet are appended as they are.
+/// None when a ".." climbs above the root
+fn canonicalize_lenient(path: &Path) -> Option<PathBuf> {
+	let mut resolved = PathBuf::new();
+	for component in path.components() {
+		match component {
+			Component::Prefix(_) | Component::RootDir => resolved.push(component),
+			Component::CurDir => {}
+			Component::ParentDir => {
+				if !resolved.pop() {
+					return None;
+				}
+			}
+			Component::Normal(name) => {
+				resolved.push(name);
+				if let Ok(canonical) = resolved.canonicalize() {
+					resolved = canonical;
+				}
+			}
+		}
+	}
+	Some(resolved)
+}
+
+/// A path argument coming from Lua. Absolute paths are taken as-is, relative paths are
+/// resolved against the root of the project being evaluated. With `restrict_fs` the
+/// result must lie inside the `FsSandbox` roots.
+#[derive(Debug, Clone)]
+pub struct ProjectPath(PathBuf);
+
+impl ProjectPath {
+	pub fn into_path_buf(self) -> PathBuf {
+		self.0
+	}
+}
+
+impl Deref for ProjectPath {
+	type Target = Path;
+
+	fn deref(&self) -> &Path {
+		&self.0
+	}
+}
+
+impl FromLua for ProjectPath {
+	fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+		let path = String::from_lua(value, lua)?;
+		resolve(lua, &path).map(ProjectPath)
+	}
+}
+
+/// Resolve a path string the same way `ProjectPath` arguments are resolved
+pub fn resolve(lua: &Lua, path: &str) -> Result<PathBuf> {
+	if path.is_empty() {
+		return Err(mlua::Error::RuntimeError("Path cannot be empty".to_string()));
+	}
+
+	let path_buf = PathBuf::from(path);
+	let resolved = if path_buf.is_absolute() {
+		path_buf
+	} else {
+		match lua.app_data_ref::<ProjectRoot>() {
+			Some(root) => root.0.join(path_buf),
+			None => {
+				return Err(mlua::Error::RuntimeError(format!(
+					"Relative path '{}' used outside of a project, use an absolute path instead",
+					path
+				)));
+			}
+		}
+	};
+
+	if let Some(sandbox) = lua.app_data_ref::<FsSandbox>() {
+		sandbox.check(&resolved)?;
+	}
+	Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_sandbox_follows_symlinks_before_parent_dirs() {
+		let dir = std::env::temp_dir().join(format!("forge-project-path-test-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+		let root = dir.join("project");
+		std::fs::create_dir_all(root.join("src")).unwrap();
+		std::fs::create_dir_all(dir.join("outside/nested")).unwrap();
+		#[cfg(unix)]
+		std::os::unix::fs::symlink(dir.join("outside/nested"), root.join("link")).unwrap();
+
+		let sandbox = FsSandbox::new(vec![root.clone()]);
+		assert!(sandbox.check(&root.join("src/../new/file.txt")).is_ok());
+		assert!(sandbox.check(&root.join("missing/../../project/src")).is_ok());
+		assert!(sandbox.check(&root.join("../outside")).is_err());
+		// link/.. is outside/, not the project root as a lexical ".." would make it
+		#[cfg(unix)]
+		assert!(sandbox.check(&root.join("link/../secret.txt")).is_err());
+		assert_eq!(canonicalize_lenient(Path::new("/../etc")), None);
+
+		let _ = std::fs::remove_dir_all(&dir);
+	}
+}
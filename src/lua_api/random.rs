@@ -0,0 +1,73 @@
+use forge_macros::{forge_lua_module, lua_api};
+use mlua::{Lua, Result, Table, UserData, UserDataMethods};
+use rand::{RngCore, SeedableRng, rngs::StdRng};
+use std::sync::{LazyLock, Mutex};
+
+/// Seed used when the project enables reproducible builds without calling random.seed
+pub const REPRODUCIBLE_SEED: u64 = 0;
+
+/// Seeded generator in deterministic mode, None to draw from the OS-seeded thread generator
+static GENERATOR: LazyLock<Mutex<Option<StdRng>>> = LazyLock::new(|| Mutex::new(None));
+
+pub fn seed(seed: u64) {
+	*GENERATOR.lock().unwrap() = Some(StdRng::seed_from_u64(seed));
+}
+
+pub fn fill_bytes(buf: &mut [u8]) {
+	match GENERATOR.lock().unwrap().as_mut() {
+		Some(rng) => rng.fill_bytes(buf),
+		None => rand::rng().fill_bytes(buf),
+	}
+}
+
+/// A UUID for Lua scripts, following the seed; names forge picks itself, like temp files, use uuid::Uuid::new_v4 so
+/// that seeded builds running side by side can't collide
+pub fn uuid_v4() -> uuid::Uuid {
+	let mut bytes = [0u8; 16];
+	fill_bytes(&mut bytes);
+	uuid::Builder::from_random_bytes(bytes).into_uuid()
+}
+
+#[derive(Clone)]
+pub struct RandomApi;
+
+impl UserData for RandomApi {
+	fn add_methods<M: UserDataMethods<Self>>(_methods: &mut M) {}
+}
+
+#[lua_api(name = "random")]
+impl RandomApi {
+	pub fn new() -> Self {
+		Self
+	}
+
+	/// Generate n random bytes as a (binary) string
+	fn bytes(lua: &Lua, n: usize) -> Result<mlua::String> {
+		let mut buf = vec![0u8; n];
+		fill_bytes(&mut buf);
+		lua.create_string(&buf)
+	}
+
+	/// Generate n random bytes encoded as a lowercase hex string of length 2n
+	fn hex(n: usize) -> Result<String> {
+		let mut buf = vec![0u8; n];
+		fill_bytes(&mut buf);
+		Ok(buf.iter().map(|b| format!("{:02x}", b)).collect())
+	}
+
+	/// Switch to deterministic mode: the same seed always yields the same sequence
+	fn seed(seed: i64) -> Result<()> {
+		self::seed(seed as u64);
+		Ok(())
+	}
+}
+
+forge_lua_module!(
+	random,
+	RandomApi,
+	"Random bytes, deterministic when seeded or in reproducible builds"
+);
+
+pub fn create_random_table(lua: &Lua) -> Result<Table> {
+	RandomApi::create_random_table(lua)
+}
@@ -0,0 +1,100 @@
+use forge_macros::{forge_lua_module, lua_api};
+use mlua::{Lua, Result, Table, UserData, UserDataMethods};
+use regex::Regex;
+use std::collections::HashMap;
+use std::sync::LazyLock;
+use std::sync::Mutex;
+
+const MAX_CACHED_PATTERNS: usize = 256;
+
+static PATTERN_CACHE: LazyLock<Mutex<HashMap<String, Regex>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Clone)]
+pub struct RegexApi;
+
+impl UserData for RegexApi {
+	fn add_methods<M: UserDataMethods<Self>>(_methods: &mut M) {}
+}
+
+#[lua_api(name = "regex")]
+impl RegexApi {
+	pub fn new() -> Self {
+		Self
+	}
+
+	/// Check if the pattern matches anywhere in the text
+	fn is_match(pattern: String, text: String) -> Result<bool> {
+		Ok(compile(&pattern)?.is_match(&text))
+	}
+
+	/// Find the first match, returning { match, start, finish } with 1-based inclusive positions, or nil
+	fn find(lua: &Lua, pattern: String, text: String) -> Result<Option<Table>> {
+		let regex = compile(&pattern)?;
+		match regex.find(&text) {
+			Some(m) => Ok(Some(match_table(lua, &m)?)),
+			None => Ok(None),
+		}
+	}
+
+	/// Find all non-overlapping matches as a list of { match, start, finish } tables
+	fn find_all(lua: &Lua, pattern: String, text: String) -> Result<Vec<Table>> {
+		let regex = compile(&pattern)?;
+		regex.find_iter(&text).map(|m| match_table(lua, &m)).collect()
+	}
+
+	/// Get capture groups of the first match: [0] is the whole match, [1..n] the groups, named groups by name
+	fn captures(lua: &Lua, pattern: String, text: String) -> Result<Option<Table>> {
+		let regex = compile(&pattern)?;
+		let Some(caps) = regex.captures(&text) else {
+			return Ok(None);
+		};
+
+		let table = lua.create_table()?;
+		for (i, group) in caps.iter().enumerate() {
+			if let Some(group) = group {
+				table.set(i, group.as_str())?;
+			}
+		}
+		for name in regex.capture_names().flatten() {
+			if let Some(group) = caps.name(name) {
+				table.set(name, group.as_str())?;
+			}
+		}
+		Ok(Some(table))
+	}
+
+	/// Replace all matches; the replacement may reference groups as $1 or ${name}
+	fn replace_all(pattern: String, text: String, replacement: String) -> Result<String> {
+		let regex = compile(&pattern)?;
+		Ok(regex.replace_all(&text, replacement.as_str()).into_owned())
+	}
+}
+
+fn compile(pattern: &str) -> Result<Regex> {
+	let mut cache = PATTERN_CACHE.lock().unwrap();
+	if let Some(regex) = cache.get(pattern) {
+		return Ok(regex.clone());
+	}
+
+	let regex = Regex::new(pattern).map_err(|e| mlua::Error::RuntimeError(format!("Invalid regex '{}': {}", pattern, e)))?;
+
+	if cache.len() >= MAX_CACHED_PATTERNS {
+		cache.clear();
+	}
+	cache.insert(pattern.to_string(), regex.clone());
+	Ok(regex)
+}
+
+fn match_table(lua: &Lua, m: &regex::Match) -> Result<Table> {
+	let table = lua.create_table()?;
+	table.set("match", m.as_str())?;
+	table.set("start", m.start() + 1)?;
+	table.set("finish", m.end())?;
+	Ok(table)
+}
+
+forge_lua_module!(regex, RegexApi, "Regular expression matching");
+
+pub fn create_regex_table(lua: &Lua) -> Result<Table> {
+	RegexApi::create_regex_table(lua)
+}
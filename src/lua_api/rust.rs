@@ -0,0 +1,200 @@
+use crate::{
+	lua_api::sandbox,
+	rust_toolchain::{self, RustToolchain},
+};
+use forge_macros::{forge_lua_module, lua_api};
+use mlua::{Lua, LuaSerdeExt, Result, Table, UserData, UserDataMethods, Value};
+use std::collections::HashMap;
+use std::process::Command;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum RustToolError {
+	#[error("Failed to run {command}: {reason}")]
+	CommandFailed {
+		command: String,
+		reason: String,
+	},
+
+	#[error("Unexpected output from {command}: {reason}")]
+	InvalidOutput {
+		command: String,
+		reason: String,
+	},
+}
+
+#[derive(Clone)]
+pub struct RustApi;
+
+impl UserData for RustApi {
+	fn add_methods<M: UserDataMethods<Self>>(_methods: &mut M) {}
+}
+
+#[lua_api(name = "rust")]
+impl RustApi {
+	pub fn new() -> Self {
+		Self
+	}
+
+	/// Run `cargo metadata` for a manifest (defaults to ./Cargo.toml) and return the parsed result
+	fn metadata(lua: &Lua, manifest_path: Option<String>) -> Result<Value> {
+		let cargo = cargo_program();
+		let mut cmd = tool_command(lua, &cargo)?;
+		cmd.args(["metadata", "--format-version", "1"]);
+		if let Some(manifest_path) = &manifest_path {
+			cmd.args(["--manifest-path", manifest_path]);
+		}
+
+		let stdout = run_tool(&mut cmd, &cargo)?;
+		let value: serde_json::Value = serde_json::from_str(&stdout).map_err(|e| {
+			mlua::Error::external(RustToolError::InvalidOutput {
+				command: format!("{} metadata", cargo),
+				reason: e.to_string(),
+			})
+		})?;
+		lua.to_value(&value)
+	}
+
+	/// Get rustc version information (version, commit_hash, commit_date, host, release, llvm_version)
+	fn rustc_version(lua: &Lua) -> Result<Table> {
+		let info = rustc_verbose_version(lua)?;
+		let table = lua.create_table()?;
+		for (key, value) in info {
+			table.set(key, value)?;
+		}
+		Ok(table)
+	}
+
+	/// Get the host target triple reported by rustc
+	fn target_triple(lua: &Lua) -> Result<String> {
+		let info = rustc_verbose_version(lua)?;
+		info.get("host").cloned().ok_or_else(|| {
+			mlua::Error::external(RustToolError::InvalidOutput {
+				command: format!("{} -vV", rustc_program()),
+				reason: "missing 'host' line".to_string(),
+			})
+		})
+	}
+
+	/// Get the cfg values for a target triple (defaults to the host); keys with several values map to arrays
+	fn cfg(lua: &Lua, triple: Option<String>) -> Result<Table> {
+		let rustc = rustc_program();
+		let mut cmd = tool_command(lua, &rustc)?;
+		cmd.args(["--print", "cfg"]);
+		if let Some(triple) = &triple {
+			cmd.args(["--target", triple]);
+		}
+
+		let stdout = run_tool(&mut cmd, &rustc)?;
+		let mut flags = Vec::new();
+		let mut values: HashMap<String, Vec<String>> = HashMap::new();
+
+		for line in stdout.lines().map(str::trim).filter(|l| !l.is_empty()) {
+			match line.split_once('=') {
+				Some((key, value)) => values
+					.entry(key.to_string())
+					.or_default()
+					.push(value.trim_matches('"').to_string()),
+				None => flags.push(line.to_string()),
+			}
+		}
+
+		let table = lua.create_table()?;
+		for flag in flags {
+			table.set(flag, true)?;
+		}
+		for (key, mut entries) in values {
+			if entries.len() == 1 {
+				table.set(key, entries.remove(0))?;
+			} else {
+				table.set(key, entries)?;
+			}
+		}
+		Ok(table)
+	}
+
+	/// The toolchain the project's rust-toolchain.toml pins, which cargo and rustc run with in FORGE files and rules
+	/// @return { channel, components, targets, profile, version } or nil when the project pins none; version is
+	/// rustc's release under the channel, nil when rustc cannot run
+	fn toolchain(lua: &Lua) -> Result<Value> {
+		let Some(toolchain) = lua.app_data_ref::<RustToolchain>().map(|toolchain| toolchain.clone()) else {
+			return Ok(Value::Nil);
+		};
+		let table = lua.create_table()?;
+		table.set(
+			"version",
+			rustc_verbose_version(lua).ok().and_then(|info| info.get("version").cloned()),
+		)?;
+		table.set("channel", toolchain.channel)?;
+		table.set("components", toolchain.components)?;
+		table.set("targets", toolchain.targets)?;
+		table.set("profile", toolchain.profile)?;
+		Ok(Value::Table(table))
+	}
+}
+
+/// A command running program, under the toolchain the project pins when program is a rustup proxy
+pub fn tool_command(lua: &Lua, program: &str) -> Result<Command> {
+	let mut command = sandbox::command(lua, program)?;
+	if let Some(toolchain) = lua.app_data_ref::<RustToolchain>()
+		&& rust_toolchain::is_rustup_proxy(program)
+	{
+		command.env("RUSTUP_TOOLCHAIN", &toolchain.channel);
+	}
+	Ok(command)
+}
+
+pub fn cargo_program() -> String {
+	std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string())
+}
+
+fn rustc_program() -> String {
+	std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string())
+}
+
+fn run_tool(cmd: &mut Command, program: &str) -> Result<String> {
+	let output = cmd.output().map_err(|e| {
+		mlua::Error::external(RustToolError::CommandFailed {
+			command: program.to_string(),
+			reason: e.to_string(),
+		})
+	})?;
+
+	if !output.status.success() {
+		return Err(mlua::Error::external(RustToolError::CommandFailed {
+			command: program.to_string(),
+			reason: String::from_utf8_lossy(&output.stderr).trim().to_string(),
+		}));
+	}
+
+	Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn rustc_verbose_version(lua: &Lua) -> Result<HashMap<String, String>> {
+	let rustc = rustc_program();
+	let stdout = run_tool(tool_command(lua, &rustc)?.arg("-vV"), &rustc)?;
+
+	let mut info = HashMap::new();
+	for line in stdout.lines() {
+		if let Some((key, value)) = line.split_once(':') {
+			info.insert(key.trim().to_lowercase().replace(['-', ' '], "_"), value.trim().to_string());
+		} else if let Some(version) = line.strip_prefix("rustc ") {
+			info.insert(
+				"version".to_string(),
+				version.split_whitespace().next().unwrap_or_default().to_string(),
+			);
+		}
+	}
+
+	if let Some(release) = info.get("release").cloned() {
+		info.insert("version".to_string(), release);
+	}
+
+	Ok(info)
+}
+
+forge_lua_module!(rust, RustApi, "Cargo and rustc integration");
+
+pub fn create_rust_table(lua: &Lua) -> Result<Table> {
+	RustApi::create_rust_table(lua)
+}
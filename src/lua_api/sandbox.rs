@@ -0,0 +1,90 @@
+use crate::forge_root_config::LuaSandbox;
+use mlua::{Lua, Result, StdLib, Table};
+use std::{ffi::OsStr, process::Command};
+
+/// Standard library functions that run host programs, exit the process or load code without going through require
+const STANDARD_REMOVED: &[&str] = &[
+	"load",
+	"loadstring",
+	"dofile",
+	"loadfile",
+	"os.execute",
+	"os.exit",
+	"io.popen",
+	"package.loadlib",
+];
+
+/// Removed on top of STANDARD_REMOVED under strict, which also leaves out the io library
+const STRICT_REMOVED: &[&str] = &["os.remove", "os.rename", "os.tmpname"];
+
+/// forge APIs that run host programs or reach the network, unavailable under strict
+const STRICT_MODULES: &[&str] = &["cargo", "cmake", "docker", "exec", "http", "net", "pkg_config", "rust"];
+
+/// Libraries to open when creating the Lua state
+pub fn std_libs(sandbox: LuaSandbox) -> StdLib {
+	match sandbox {
+		LuaSandbox::Strict => StdLib::ALL_SAFE ^ StdLib::IO,
+		LuaSandbox::Standard | LuaSandbox::Full => StdLib::ALL_SAFE,
+	}
+}
+
+/// Whether forge.<name> is registered
+pub fn allows_module(sandbox: LuaSandbox, name: &str) -> bool {
+	sandbox != LuaSandbox::Strict || !STRICT_MODULES.contains(&name)
+}
+
+/// A Command running program for a forge API; every host program Lua starts is created here, so strict refuses it
+/// even when the module asking is not in STRICT_MODULES
+pub fn command(lua: &Lua, program: impl AsRef<OsStr>) -> Result<Command> {
+	if lua
+		.app_data_ref::<LuaSandbox>()
+		.is_some_and(|sandbox| *sandbox == LuaSandbox::Strict)
+	{
+		return Err(mlua::Error::RuntimeError(format!(
+			"Running '{}' is not allowed under the strict Lua sandbox",
+			program.as_ref().to_string_lossy()
+		)));
+	}
+	Ok(Command::new(program))
+}
+
+/// Remove the standard library functions the sandbox does not allow from the globals
+pub fn apply(lua: &Lua, sandbox: LuaSandbox) -> Result<()> {
+	lua.set_app_data(sandbox);
+	let removed: Vec<&str> = match sandbox {
+		LuaSandbox::Full => return Ok(()),
+		LuaSandbox::Standard => STANDARD_REMOVED.to_vec(),
+		LuaSandbox::Strict => STANDARD_REMOVED.iter().chain(STRICT_REMOVED).copied().collect(),
+	};
+
+	let globals = lua.globals();
+	for path in removed {
+		match path.split_once('.') {
+			Some((library, function)) => {
+				if let Some(library) = globals.get::<Option<Table>>(library)? {
+					library.set(function, mlua::Value::Nil)?;
+				}
+			}
+			None => globals.set(path, mlua::Value::Nil)?,
+		}
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_strict_refuses_host_programs() {
+		let lua = Lua::new();
+		apply(&lua, LuaSandbox::Standard).unwrap();
+		assert!(command(&lua, "true").is_ok());
+
+		apply(&lua, LuaSandbox::Strict).unwrap();
+		assert!(command(&lua, "true").is_err());
+		for module in ["cargo", "cmake", "exec", "pkg_config", "rust"] {
+			assert!(!allows_module(LuaSandbox::Strict, module));
+		}
+	}
+}
@@ -1,4 +1,4 @@
-use forge_macros::lua_api;
+use forge_macros::{forge_lua_module, lua_api};
 use mlua::{Lua, Result, Table, UserData, UserDataMethods, Value};
 use semver::{Version, VersionReq};
 use std::str::FromStr;
@@ -88,6 +88,8 @@ fn lua_version_table(lua: &Lua, version: &Version) -> Result<Table> {
 	Ok(table)
 }
 
+forge_lua_module!(semver, SemverApi, "Semantic versioning operations");
+
 pub fn create_semver_table(lua: &Lua) -> Result<Table> {
 	SemverApi::create_semver_table(lua)
 }
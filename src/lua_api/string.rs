@@ -1,4 +1,4 @@
-use forge_macros::lua_api;
+use forge_macros::{forge_lua_module, lua_api};
 use mlua::{Lua, Result, Table, UserData, UserDataMethods};
 
 #[derive(Clone)]
@@ -108,6 +108,8 @@ impl StringApi {
 	}
 }
 
+forge_lua_module!(string, StringApi, "String manipulation operations");
+
 pub fn create_string_table(lua: &Lua) -> Result<Table> {
 	StringApi::create_string_table(lua)
 }
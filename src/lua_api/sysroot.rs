@@ -0,0 +1,79 @@
+use crate::{lockfile::Lockfile, toolchains};
+use forge_macros::{LuaClass, forge_lua_module, lua_api};
+use mlua::{FromLua, Lua, LuaSerdeExt, Result, Table, UserData, UserDataMethods, Value};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, path::PathBuf, sync::Arc};
+
+/// The sysroots of FORGE_ROOT's [targets], by triple
+pub struct Sysroots(pub BTreeMap<String, PathBuf>);
+
+#[derive(Debug, Deserialize, Serialize, LuaClass)]
+pub struct SysrootFetchRequest {
+	pub triple: String,
+	pub url: String,
+	pub sha256: Option<String>,
+	pub blake3: Option<String>,
+	pub root: Option<String>,
+}
+
+impl FromLua for SysrootFetchRequest {
+	fn from_lua(value: Value, lua: &Lua) -> Result<Self> {
+		lua.from_value(value)
+	}
+}
+
+#[derive(Clone)]
+pub struct SysrootApi;
+
+impl UserData for SysrootApi {
+	fn add_methods<M: UserDataMethods<Self>>(_methods: &mut M) {}
+}
+
+#[lua_api(name = "sysroot")]
+impl SysrootApi {
+	pub fn new() -> Self {
+		Self
+	}
+
+	/// Download the sysroot archive of a target into ~/.forge/toolchains and unpack it there, once per url and checksum
+	/// Without a checksum, the archive must match the blake3 FORGE.lock recorded on its first fetch
+	/// @return Path of the sysroot
+	#[lua_table(request: SysrootFetchOptions {
+		/// Target triple the sysroot is for
+		triple: String,
+		/// URL of a .tar.gz, .tgz or .zip archive
+		url: String,
+		/// Expected SHA-256 of the archive
+		sha256: Option<String>,
+		/// Expected blake3 of the archive
+		blake3: Option<String>,
+		/// Directory of the archive that is the sysroot (default its top level)
+		root: Option<String>,
+	})]
+	fn fetch(lua: &Lua, request: SysrootFetchRequest) -> Result<String> {
+		let lockfile = lua.app_data_ref::<Arc<Lockfile>>().map(|lockfile| Arc::clone(&lockfile));
+		let path = toolchains::fetch_sysroot(
+			&request.triple,
+			&request.url,
+			request.sha256.as_deref(),
+			request.blake3.as_deref(),
+			request.root.as_deref(),
+			lockfile.as_deref(),
+		)
+		.map_err(|e| mlua::Error::RuntimeError(format!("Failed to fetch the sysroot of {}: {}", request.triple, e)))?;
+		Ok(path.to_string_lossy().to_string())
+	}
+
+	/// The sysroot FORGE_ROOT sets for a target triple in [targets.<triple>], nil when it sets none
+	fn get(lua: &Lua, triple: String) -> Result<Option<String>> {
+		Ok(lua
+			.app_data_ref::<Sysroots>()
+			.and_then(|sysroots| sysroots.0.get(&triple).map(|path| path.to_string_lossy().to_string())))
+	}
+}
+
+forge_lua_module!(sysroot, SysrootApi, "Cross-compilation sysroots");
+
+pub fn create_sysroot_table(lua: &Lua) -> Result<Table> {
+	SysrootApi::create_sysroot_table(lua)
+}
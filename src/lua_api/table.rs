@@ -1,4 +1,4 @@
-use forge_macros::lua_api;
+use forge_macros::{forge_lua_module, lua_api};
 use mlua::{Lua, Result, Table, UserData, UserDataMethods, Value};
 
 #[derive(Clone)]
@@ -78,6 +78,8 @@ impl TableApi {
 	}
 }
 
+forge_lua_module!(table, TableApi, "Table operations");
+
 pub fn create_table_table(lua: &Lua) -> Result<Table> {
 	TableApi::create_table_table(lua)
 }
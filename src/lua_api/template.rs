@@ -0,0 +1,90 @@
+use crate::lua_api::project_path::ProjectPath;
+use forge_macros::{forge_lua_module, lua_api};
+use minijinja::{Environment, UndefinedBehavior};
+use mlua::{Lua, LuaSerdeExt, Result, Table, UserData, UserDataMethods, Value};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum TemplateError {
+	#[error("Invalid template path: {path} - {reason}")]
+	InvalidPath {
+		path: String,
+		reason: String,
+	},
+
+	#[error("Template rendering failed: {template} - {reason}")]
+	RenderFailed {
+		template: String,
+		reason: String,
+	},
+}
+
+#[derive(Clone)]
+pub struct TemplateApi;
+
+impl UserData for TemplateApi {
+	fn add_methods<M: UserDataMethods<Self>>(_methods: &mut M) {}
+}
+
+#[lua_api(name = "template")]
+impl TemplateApi {
+	pub fn new() -> Self {
+		Self
+	}
+
+	/// Render a Jinja-style template string ({{ var }}, {% if %}, {% for %}) with the given variables
+	/// Referencing an undefined variable is an error
+	fn render(lua: &Lua, source: String, vars: Option<Table>) -> Result<String> {
+		render_template(lua, "<string>", &source, vars)
+	}
+
+	/// Render the template at src into dest (absolute or relative to the project root)
+	/// dest is only rewritten when its content changes, so dependent rules stay up to date
+	fn render_file(lua: &Lua, src: ProjectPath, dest: ProjectPath, vars: Option<Table>) -> Result<bool> {
+		let src_name = src.to_string_lossy().to_string();
+		let dest_path = dest.into_path_buf();
+
+		let source = std::fs::read_to_string(&*src).map_err(|e| {
+			mlua::Error::external(TemplateError::InvalidPath {
+				path: src_name.clone(),
+				reason: e.to_string(),
+			})
+		})?;
+		let rendered = render_template(lua, &src_name, &source, vars)?;
+
+		if std::fs::read_to_string(&dest_path).is_ok_and(|existing| existing == rendered) {
+			return Ok(false);
+		}
+
+		if let Some(parent) = dest_path.parent() {
+			std::fs::create_dir_all(parent).map_err(mlua::Error::external)?;
+		}
+		std::fs::write(&dest_path, rendered).map_err(mlua::Error::external)?;
+
+		Ok(true)
+	}
+}
+
+fn render_template(lua: &Lua, name: &str, source: &str, vars: Option<Table>) -> Result<String> {
+	let context: serde_json::Value = match vars {
+		Some(vars) => lua.from_value(Value::Table(vars))?,
+		None => serde_json::Value::Object(Default::default()),
+	};
+
+	let mut env = Environment::new();
+	env.set_undefined_behavior(UndefinedBehavior::Strict);
+	env.set_keep_trailing_newline(true);
+
+	env.render_named_str(name, source, context).map_err(|e| {
+		mlua::Error::external(TemplateError::RenderFailed {
+			template: name.to_string(),
+			reason: format!("{:#}", e),
+		})
+	})
+}
+
+forge_lua_module!(template, TemplateApi, "Text templating (Jinja syntax)");
+
+pub fn create_template_table(lua: &Lua) -> Result<Table> {
+	TemplateApi::create_template_table(lua)
+}
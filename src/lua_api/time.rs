@@ -1,4 +1,4 @@
-use forge_macros::lua_api;
+use forge_macros::{forge_lua_module, lua_api};
 use mlua::{Lua, Result, Table, UserData, UserDataMethods};
 use std::collections::HashMap;
 use std::sync::LazyLock;
@@ -86,6 +86,8 @@ impl TimeApi {
 	}
 }
 
+forge_lua_module!(time, TimeApi, "Time operations");
+
 pub fn create_time_table(lua: &Lua) -> Result<Table> {
 	TimeApi::create_time_table(lua)
 }
@@ -0,0 +1,28 @@
+use crate::lua_api::random;
+use forge_macros::{forge_lua_module, lua_api};
+use mlua::{Lua, Result, Table, UserData, UserDataMethods};
+
+#[derive(Clone)]
+pub struct UuidApi;
+
+impl UserData for UuidApi {
+	fn add_methods<M: UserDataMethods<Self>>(_methods: &mut M) {}
+}
+
+#[lua_api(name = "uuid")]
+impl UuidApi {
+	pub fn new() -> Self {
+		Self
+	}
+
+	/// Generate a random (version 4) UUID, deterministic when forge.random is seeded
+	fn v4() -> Result<String> {
+		Ok(random::uuid_v4().to_string())
+	}
+}
+
+forge_lua_module!(uuid, UuidApi, "UUID generation");
+
+pub fn create_uuid_table(lua: &Lua) -> Result<Table> {
+	UuidApi::create_uuid_table(lua)
+}
@@ -0,0 +1,163 @@
+use std::path::Path;
+use walkdir::WalkDir;
+
+/// Definitions for every module in the prelude directory, built from their LuaLS annotations
+/// Each module becomes a class named after its path (prelude/c/c.lua is `prelude.c.c`) holding the functions of the
+/// table it returns; standalone ---@class and ---@alias blocks are copied as they are
+pub fn prelude_type_definitions(prelude_dir: &Path) -> String {
+	let mut files: Vec<_> = WalkDir::new(prelude_dir)
+		.into_iter()
+		.filter_map(|entry| entry.ok())
+		.filter(|entry| entry.file_type().is_file() && entry.path().extension().is_some_and(|ext| ext == "lua"))
+		.map(|entry| entry.into_path())
+		.collect();
+	files.sort();
+
+	let mut types = String::new();
+	types.push_str("-- Prelude modules, type a require with e.g.\n");
+	types.push_str("--   ---@type prelude.c.c\n");
+	types.push_str("--   local c = require(\"@prelude/c/c.lua\")\n\n");
+
+	for file in files {
+		let Ok(content) = std::fs::read_to_string(&file) else {
+			log::warn!("Skipping unreadable prelude module {}", file.display());
+			continue;
+		};
+		let relative = file.strip_prefix(prelude_dir).unwrap_or(&file);
+		let module = relative.with_extension("").to_string_lossy().replace(['/', '\\'], ".");
+		types.push_str(&module_definitions(&module, &content));
+	}
+	types
+}
+
+/// Definitions for one prelude module, module being its dotted path below the prelude directory
+fn module_definitions(module: &str, content: &str) -> String {
+	let class = format!("prelude.{}", module);
+	let local = format!("prelude_{}", module.replace(['.', '-'], "_"));
+	// The table the module returns, usually "M"
+	let exported = content
+		.lines()
+		.rev()
+		.find_map(|line| line.trim().strip_prefix("return "))
+		.map(str::trim)
+		.filter(|name| name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'));
+
+	let mut out = format!("---@class {}\nlocal {} = {{}}\n\n", class, local);
+	let mut annotations: Vec<&str> = Vec::new();
+	for line in content.lines() {
+		if line.starts_with("---") {
+			annotations.push(line);
+			continue;
+		}
+
+		if let Some(exported) = exported
+			&& let Some((field, params)) = exported_function(line, exported)
+		{
+			for annotation in &annotations {
+				out.push_str(annotation);
+				out.push('\n');
+			}
+			out.push_str(&format!("function {}.{}({}) end\n\n", local, field, params));
+		} else if annotations
+			.iter()
+			.any(|annotation| annotation.starts_with("---@class") || annotation.starts_with("---@alias"))
+		{
+			for annotation in &annotations {
+				out.push_str(annotation);
+				out.push('\n');
+			}
+			out.push('\n');
+		}
+		annotations.clear();
+	}
+	out
+}
+
+/// Field name and parameter list of "function M.name(params)" or "M.name = function(params)" at the top level
+fn exported_function<'a>(line: &'a str, exported: &str) -> Option<(&'a str, &'a str)> {
+	let (field, rest) = match line.strip_prefix("function ") {
+		Some(rest) => {
+			let rest = rest.strip_prefix(exported)?.strip_prefix('.')?;
+			rest.split_once('(')?
+		}
+		None => {
+			let rest = line.strip_prefix(exported)?.strip_prefix('.')?;
+			let (field, value) = rest.split_once('=')?;
+			let params = value.trim_start().strip_prefix("function")?.trim_start().strip_prefix('(')?;
+			(field.trim_end(), params)
+		}
+	};
+	let params = rest.split_once(')')?.0.trim();
+	field
+		.chars()
+		.all(|c| c.is_ascii_alphanumeric() || c == '_')
+		.then_some((field, params))
+}
+
+/// A .luarc.json for the project: the generated definitions as a library, forge as a known global and the cache
+/// directory ignored; other settings already in existing are kept
+pub fn luarc(existing: Option<serde_json::Value>, types_file: &str, cache_dir: &str) -> serde_json::Value {
+	let mut luarc = match existing {
+		Some(serde_json::Value::Object(map)) => map,
+		_ => serde_json::Map::new(),
+	};
+
+	luarc
+		.entry("$schema")
+		.or_insert_with(|| "https://raw.githubusercontent.com/LuaLS/vscode-lua/master/setting/schema.json".into());
+	luarc.insert("runtime.version".to_string(), "Lua 5.4".into());
+
+	let mut add_to_list = |key: &str, value: &str| {
+		let list = luarc.entry(key).or_insert_with(|| serde_json::Value::Array(Vec::new()));
+		if !list.is_array() {
+			*list = serde_json::Value::Array(Vec::new());
+		}
+		let list = list.as_array_mut().unwrap();
+		if !list.iter().any(|item| item == value) {
+			list.push(value.into());
+		}
+	};
+	add_to_list("workspace.library", types_file);
+	add_to_list("workspace.ignoreDir", cache_dir);
+	add_to_list("diagnostics.globals", "forge");
+
+	serde_json::Value::Object(luarc)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_module_definitions() {
+		let content = "local common = require(\"@prelude/build_common.lua\")\n\
+			local M = {}\n\
+			---@alias CStandard \"c11\" | \"c17\"\n\
+			\n\
+			---Build an executable\n\
+			---@param tbl table\n\
+			function M.executable(tbl)\n\
+				local function helper() end\n\
+			end\n\
+			M.library = function(tbl, kind) end\n\
+			local function private() end\n\
+			return M\n";
+		let definitions = module_definitions("c.c", content);
+		assert_eq!(
+			definitions,
+			"---@class prelude.c.c\nlocal prelude_c_c = {}\n\n\
+			---@alias CStandard \"c11\" | \"c17\"\n\n\
+			---Build an executable\n---@param tbl table\nfunction prelude_c_c.executable(tbl) end\n\n\
+			function prelude_c_c.library(tbl, kind) end\n\n"
+		);
+	}
+
+	#[test]
+	fn test_luarc_keeps_existing_settings() {
+		let existing = serde_json::json!({ "diagnostics.globals": ["vim"], "hint.enable": true });
+		let luarc = luarc(Some(existing), "types.lua", "forge-out");
+		assert_eq!(luarc["hint.enable"], true);
+		assert_eq!(luarc["diagnostics.globals"], serde_json::json!(["vim", "forge"]));
+		assert_eq!(luarc["workspace.library"], serde_json::json!(["types.lua"]));
+	}
+}
@@ -1,14 +1,36 @@
 use anyhow::Result;
-use clap::{Parser, Subcommand};
+use clap::{ColorChoice, Parser, Subcommand};
 use std::path::{Path, PathBuf};
 
+mod audit;
+mod build_log;
 mod cache;
 mod config;
+mod diagnostic;
 mod error;
+mod eval_cache;
+mod executor;
+mod export;
+mod fingerprint;
 mod forge_root_config;
+mod import;
+mod install;
+mod interrupt;
+mod lockfile;
 mod lua_api;
+mod luals;
+mod metrics;
+mod pools;
 mod project;
-
+mod provenance;
+mod rsp;
+mod rust_toolchain;
+mod sbom;
+mod toolchains;
+mod user_config;
+mod workers;
+
+use std::io::Write;
 use std::process::Command;
 
 #[derive(Parser, Debug)]
@@ -27,10 +49,43 @@ struct Cli {
 	)]
 	target: Vec<String>,
 
+	#[arg(
+		long,
+		global = true,
+		help = "Fail network requests instead of making them; cached downloads are still used"
+	)]
+	offline: bool,
+
+	#[arg(long, global = true, value_enum, default_value_t = ColorChoice::Auto, help = "When to use colors in the output")]
+	color: ColorChoice,
+
+	#[arg(
+		long,
+		global = true,
+		default_value = "debug",
+		help = "Build profile, forge.config.profile in FORGE files; outputs go under <cache_dir>/<target>/<profile> with [build] isolate_outputs"
+	)]
+	profile: String,
+
+	#[arg(
+		long,
+		global = true,
+		value_enum,
+		default_value_t = ErrorFormat::Human,
+		help = "How to print the error that ends the run; json prints one object on stderr"
+	)]
+	error_format: ErrorFormat,
+
 	#[command(flatten)]
 	verbose: clap_verbosity_flag::Verbosity,
 }
 
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ErrorFormat {
+	Human,
+	Json,
+}
+
 #[derive(Subcommand, Debug)]
 enum Commands {
 	Build {
@@ -39,6 +94,29 @@ enum Commands {
 
 		#[arg(short, long, help = "Build specific component(s) (can be used multiple times)")]
 		component: Vec<String>,
+
+		#[arg(
+			long,
+			value_name = "GIT_REF",
+			help = "Only build rules affected by files changed since a git ref, and the rules depending on them"
+		)]
+		since: Option<String>,
+
+		#[arg(long, help = "Only build rules with this tag (can be used multiple times)")]
+		tag: Vec<String>,
+
+		#[arg(
+			long,
+			help = "Skip rules with this tag unless another rule needs them (can be used multiple times)"
+		)]
+		exclude_tag: Vec<String>,
+
+		#[arg(
+			long,
+			value_name = "PATH",
+			help = "Only build the rule producing this output, and what it needs (can be used multiple times)"
+		)]
+		output: Vec<String>,
 	},
 
 	Run {
@@ -75,24 +153,200 @@ enum Commands {
 	Types {
 		#[arg(short, long, help = "Output path for types.lua file", default_value = "types.lua")]
 		output: PathBuf,
+
+		#[arg(long, help = "Also write a .luarc.json in the project that loads the generated types")]
+		luarc: bool,
+	},
+
+	/// Write the evaluated build graph for another build system, without building anything
+	Export {
+		#[arg(long, value_enum, help = "Build system to export for")]
+		format: export::ExportFormat,
+
+		#[arg(short, long, help = "Output path (defaults to build.ninja in the project)")]
+		output: Option<PathBuf>,
+
+		#[arg(short, long, help = "Evaluate for specific target(s) (can be used multiple times)")]
+		target: Vec<String>,
+	},
+
+	/// Write a software bill of materials of the rules that set a version and the downloads of FORGE.lock
+	Sbom {
+		#[arg(long, value_enum, help = "SBOM format")]
+		format: sbom::SbomFormat,
+
+		#[arg(
+			short,
+			long,
+			help = "Output path (defaults to sbom.spdx.json or sbom.cdx.json in the project)"
+		)]
+		output: Option<PathBuf>,
+
+		#[arg(short, long, help = "Evaluate for specific target(s) (can be used multiple times)")]
+		target: Vec<String>,
+	},
+
+	/// Build, then copy the files declared with forge.install under a prefix
+	Install {
+		#[arg(long, help = "Directory to install into")]
+		prefix: PathBuf,
+
+		#[arg(short, long, help = "Build specific target(s) (can be used multiple times)")]
+		target: Vec<String>,
+	},
+
+	/// Build, then archive the files declared with forge.install with a manifest of what the package holds
+	Package {
+		#[arg(long, value_enum, default_value = "tar.gz", help = "Archive format")]
+		format: install::PackageFormat,
+
+		#[arg(short, long, help = "Output path (defaults to <cache_dir>/<project>-<version>.<format>)")]
+		output: Option<PathBuf>,
+
+		#[arg(short, long, help = "Build specific target(s) (can be used multiple times)")]
+		target: Vec<String>,
+	},
+
+	/// Replay the saved output of a rule's last run, or print the latest build log
+	Log {
+		#[arg(help = "Rule whose last stdout/stderr to print (omit for the latest build log)")]
+		rule: Option<String>,
+	},
+
+	/// Run the command of a rule again as its last run did, with the same args, env and workdir
+	Replay {
+		#[arg(help = "Rule whose last command to run")]
+		rule: String,
+
+		#[arg(
+			long,
+			help = "Open an interactive shell with the rule's env and workdir instead of running the command"
+		)]
+		shell: bool,
+	},
+
+	/// Inspect <cache_dir>/audit.jsonl, the record of every command rules have run
+	Audit {
+		#[command(subcommand)]
+		command: AuditCommand,
+	},
+
+	/// Print the fingerprint of this environment (OS, arch, libc, compiler versions, variables) artifacts are stored
+	/// with; artifacts with another fingerprint are not restored from the cache
+	Env {
+		#[arg(long, help = "Print the fingerprint as JSON instead")]
+		json: bool,
+	},
+
+	/// Print the names and tags of the evaluated rules, without building anything
+	List {
+		#[arg(short, long, help = "Evaluate for specific target(s) (can be used multiple times)")]
+		target: Vec<String>,
+
+		#[arg(long, help = "Only list rules with this tag (can be used multiple times)")]
+		tag: Vec<String>,
+
+		#[arg(long, help = "Leave out rules with this tag (can be used multiple times)")]
+		exclude_tag: Vec<String>,
+	},
+
+	/// Run only the rules marked fetch, and the downloads of every FORGE file, without building anything else
+	Fetch {
+		#[arg(short, long, help = "Evaluate for specific target(s) (can be used multiple times)")]
+		target: Vec<String>,
+	},
+
+	/// Check that the outputs on disk match a provenance manifest, and its signature when given a public key
+	Verify {
+		#[arg(help = "Manifest to check, e.g. forge-out/<target>/manifest.json")]
+		manifest: PathBuf,
+
+		#[arg(long, help = "Hex ed25519 public key the manifest's .sig must be signed with")]
+		public_key: Option<String>,
+	},
+
+	/// Manage FORGE.lock, the checksums of downloads that FORGE files do not pin
+	Lock {
+		#[command(subcommand)]
+		command: LockCommand,
 	},
 }
 
-fn main() -> Result<()> {
+#[derive(Subcommand, Debug)]
+enum AuditCommand {
+	/// Print every recorded run of a rule, oldest first
+	Show {
+		#[arg(help = "Rule whose runs to print")]
+		rule: String,
+
+		#[arg(long, help = "Print the entries as JSON lines instead")]
+		json: bool,
+	},
+}
+
+#[derive(Subcommand, Debug)]
+enum LockCommand {
+	/// Fetch every download without a checksum again and rewrite FORGE.lock with what they are now
+	Update {
+		#[arg(short, long, help = "Evaluate for specific target(s) (can be used multiple times)")]
+		target: Vec<String>,
+	},
+}
+
+fn main() {
 	let cli = Cli::parse();
 
-	env_logger::Builder::new().filter_level(cli.verbose.log_level_filter()).init();
+	let color = match cli.color {
+		ColorChoice::Always => true,
+		ColorChoice::Never => false,
+		ColorChoice::Auto => diagnostic::detect_color(),
+	};
+	diagnostic::set_color(color);
+	env_logger::Builder::new()
+		.filter_level(cli.verbose.log_level_filter())
+		.write_style(if color {
+			env_logger::WriteStyle::Always
+		} else {
+			env_logger::WriteStyle::Never
+		})
+		.init();
+	interrupt::install();
+
+	let error_format = cli.error_format;
+	if let Err(e) = run(cli) {
+		let report = error::ErrorReport::new(&e);
+		if error_format == ErrorFormat::Json {
+			eprintln!("{}", serde_json::to_string(&report).unwrap_or_default());
+		} else if let Some(diagnostic) = &report.diagnostic {
+			diagnostic.emit();
+		} else if report.kind == error::ErrorKind::Interrupted {
+			eprintln!("{}", e);
+		} else {
+			eprintln!("Error: {:?}", e);
+		}
+		std::process::exit(report.exit_code);
+	}
+}
 
+fn run(cli: Cli) -> Result<()> {
 	let project_path = std::fs::canonicalize(&cli.project)?;
 
 	match cli.command {
-		Some(Commands::Build { target, component }) => {
-			if target.is_empty() && component.is_empty() {
+		Some(Commands::Build {
+			target,
+			component,
+			since,
+			tag,
+			exclude_tag,
+			output,
+		}) => {
+			if target.is_empty() && component.is_empty() && output.is_empty() {
 				return Err(anyhow::anyhow!(
-					"No targets or components specified for build. Use --target and/or --component to specify what to build.\n\
+					"No targets, components or outputs specified for build. Use --target, --component and/or --output to specify what to build.\n\
 					Example: forge build --target linux_x64_debug\n\
 					         forge build --component math_utils\n\
-					         forge build --component math_utils --target linux_x64_debug"
+					         forge build --component math_utils --target linux_x64_debug\n\
+					         forge build --output forge-out/linux_x64_debug/libmath.a"
 				));
 			}
 
@@ -101,6 +355,8 @@ fn main() -> Result<()> {
 				target_filters: target,
 				component_filters: component,
 				test_mode: false,
+				offline: cli.offline,
+				profile: cli.profile.clone(),
 			};
 
 			log::info!("Building project at: {}", project_path.display());
@@ -112,6 +368,12 @@ fn main() -> Result<()> {
 			}
 
 			let mut project = project::Project::new(project_path, config)?;
+			project.selection = project::RuleSelection {
+				since,
+				tags: tag,
+				exclude_tags: exclude_tag,
+				outputs: output,
+			};
 			project.run()?;
 
 			println!("\nBuild completed successfully!");
@@ -126,6 +388,8 @@ fn main() -> Result<()> {
 					vec![]
 				},
 				test_mode: false,
+				offline: cli.offline,
+				profile: cli.profile.clone(),
 			};
 
 			log::info!("Building and running project at: {}", project_path.display());
@@ -157,6 +421,8 @@ fn main() -> Result<()> {
 					vec![]
 				},
 				test_mode: true,
+				offline: cli.offline,
+				profile: cli.profile.clone(),
 			};
 
 			log::info!("Building and testing project at: {}", project_path.display());
@@ -189,11 +455,192 @@ fn main() -> Result<()> {
 		Some(Commands::Migrate { force }) => {
 			migrate_to_forge_root(&project_path, force)?;
 		}
-		Some(Commands::Types { output }) => {
+		Some(Commands::Types { output, luarc }) => {
 			log::info!("Generating Lua type definitions to: {}", output.display());
-			let types_content = lua_api::init::generate_types_lua();
+			let mut types_content = lua_api::init::generate_types_lua();
+			let prelude_dir = project_path.join("prelude");
+			if prelude_dir.is_dir() {
+				types_content.push('\n');
+				types_content.push_str(&luals::prelude_type_definitions(&prelude_dir));
+			}
 			std::fs::write(&output, types_content)?;
 			println!("Generated types.lua at: {}", output.display());
+
+			if luarc {
+				write_luarc(&project_path, &output)?;
+			}
+		}
+		Some(Commands::Export { format, output, target }) => {
+			let config = config::Config {
+				verbosity: config::VerbosityWrapper(cli.verbose),
+				target_filters: target,
+				component_filters: vec![],
+				test_mode: false,
+				offline: cli.offline,
+				profile: cli.profile.clone(),
+			};
+
+			let mut project = project::Project::new(project_path.clone(), config)?;
+			project.evaluate()?;
+
+			let output = output.unwrap_or_else(|| project_path.join(format.default_file_name()));
+			std::fs::write(&output, format.render(&project_path, &project.rules()))?;
+			println!("Exported {} rules to: {}", project.build_graph.len(), output.display());
+		}
+		Some(Commands::Sbom { format, output, target }) => {
+			let config = config::Config {
+				verbosity: config::VerbosityWrapper(cli.verbose),
+				target_filters: target,
+				component_filters: vec![],
+				test_mode: false,
+				offline: cli.offline,
+				profile: cli.profile.clone(),
+			};
+
+			let mut project = project::Project::new(project_path.clone(), config)?;
+			project.evaluate()?;
+
+			let sbom = sbom::Sbom::new(
+				&project.forge_root_config.project.name,
+				&project.forge_root_config.project.version,
+				&project.rules(),
+				&project.lockfile.downloads(),
+			);
+			let output = output.unwrap_or_else(|| project_path.join(format.default_file_name()));
+			std::fs::write(&output, format.render(&sbom))?;
+			println!("Wrote SBOM of {} packages to: {}", sbom.packages.len(), output.display());
+		}
+		Some(Commands::Install { prefix, target }) => {
+			let config = config::Config {
+				verbosity: config::VerbosityWrapper(cli.verbose),
+				target_filters: target,
+				component_filters: vec![],
+				test_mode: false,
+				offline: cli.offline,
+				profile: cli.profile.clone(),
+			};
+
+			let mut project = project::Project::new(project_path.clone(), config)?;
+			project.run()?;
+
+			let installed = install::install(&project_path, &project.installs(), &prefix)?;
+			println!("Installed {} files to: {}", installed.len(), prefix.display());
+		}
+		Some(Commands::Package { format, output, target }) => {
+			let config = config::Config {
+				verbosity: config::VerbosityWrapper(cli.verbose),
+				target_filters: target,
+				component_filters: vec![],
+				test_mode: false,
+				offline: cli.offline,
+				profile: cli.profile.clone(),
+			};
+
+			let mut project = project::Project::new(project_path.clone(), config)?;
+			project.run()?;
+			let output = create_package(&project, format, output)?;
+			println!("Packaged project to: {}", output.display());
+		}
+		Some(Commands::Log { rule }) => {
+			show_log(&project_path, rule.as_deref())?;
+		}
+		Some(Commands::Replay { rule, shell }) => {
+			replay_rule(&project_path, &rule, shell)?;
+		}
+		Some(Commands::Audit {
+			command: AuditCommand::Show { rule, json },
+		}) => {
+			show_audit(&project_path, &rule, json)?;
+		}
+		Some(Commands::Env { json }) => {
+			show_env(&project_path, json)?;
+		}
+		Some(Commands::List {
+			target,
+			tag,
+			exclude_tag,
+		}) => {
+			let config = config::Config {
+				verbosity: config::VerbosityWrapper(cli.verbose),
+				target_filters: target,
+				component_filters: vec![],
+				test_mode: false,
+				offline: cli.offline,
+				profile: cli.profile.clone(),
+			};
+
+			let mut project = project::Project::new(project_path, config)?;
+			project.evaluate()?;
+
+			let selection = project::RuleSelection {
+				tags: tag,
+				exclude_tags: exclude_tag,
+				..Default::default()
+			};
+			let mut rules: Vec<_> = project
+				.rules()
+				.into_iter()
+				.filter(|rule| selection.matches_tags(rule))
+				.collect();
+			rules.sort_by(|a, b| a.name.cmp(&b.name));
+			for rule in rules {
+				if rule.tags.is_empty() {
+					println!("{}", rule.name);
+				} else {
+					println!("{} [{}]", rule.name, rule.tags.join(", "));
+				}
+			}
+		}
+		Some(Commands::Fetch { target }) => {
+			if cli.offline {
+				return Err(anyhow::anyhow!(
+					"forge fetch exists to fill the caches, it cannot run with --offline"
+				));
+			}
+
+			let config = config::Config {
+				verbosity: config::VerbosityWrapper(cli.verbose),
+				target_filters: target,
+				component_filters: vec![],
+				test_mode: false,
+				offline: false,
+				profile: cli.profile.clone(),
+			};
+
+			log::info!("Fetching for project at: {}", project_path.display());
+			let mut project = project::Project::new(project_path, config)?;
+			project.fetch()?;
+
+			println!("\nFetch completed successfully!");
+		}
+		Some(Commands::Verify { manifest, public_key }) => {
+			verify_manifest(&project_path, &manifest, public_key.as_deref())?;
+		}
+		Some(Commands::Lock {
+			command: LockCommand::Update { target },
+		}) => {
+			if cli.offline {
+				return Err(anyhow::anyhow!(
+					"forge lock update has to fetch downloads again, it cannot run with --offline"
+				));
+			}
+
+			let config = config::Config {
+				verbosity: config::VerbosityWrapper(cli.verbose),
+				target_filters: target,
+				component_filters: vec![],
+				test_mode: false,
+				offline: false,
+				profile: cli.profile.clone(),
+			};
+
+			let mut project = project::Project::new(project_path.clone(), config)?;
+			project.update_lockfile()?;
+			println!(
+				"Locked {} downloads in: {}",
+				project.lockfile.download_count(),
+				project_path.join(lockfile::LOCKFILE_NAME).display()
+			);
 		}
 		None => {
 			if cli.target.is_empty() {
@@ -208,6 +655,8 @@ fn main() -> Result<()> {
 				target_filters: cli.target,
 				component_filters: vec![],
 				test_mode: false,
+				offline: cli.offline,
+				profile: cli.profile.clone(),
 			};
 
 			log::info!("Building project at: {}", project_path.display());
@@ -336,9 +785,11 @@ fn run_component_target(project_path: &PathBuf, component_name: &str, target_nam
 		));
 	}
 
-	let component_executable = target_dir.join(component_name);
-	if is_executable(&component_executable) {
-		return run_executable(&component_executable, project_path);
+	for file_name in [executable_file_name(component_name), component_name.to_string()] {
+		let component_executable = target_dir.join(file_name);
+		if is_executable(&component_executable) {
+			return run_executable(&component_executable, project_path);
+		}
 	}
 
 	if let Some(executable) = find_executable_in_dir(&target_dir, Some(component_name)) {
@@ -365,10 +816,12 @@ fn run_main_executable(project_path: &PathBuf) -> Result<()> {
 	];
 
 	for name in possible_names {
-		let executable_path = project_path.join(&name);
-		if executable_path.exists() && executable_path.is_file() {
-			log::info!("Found executable: {}", executable_path.display());
-			return run_target(project_path, &name);
+		for file_name in [executable_file_name(&name), name.clone()] {
+			let executable_path = project_path.join(&file_name);
+			if executable_path.exists() && executable_path.is_file() {
+				log::info!("Found executable: {}", executable_path.display());
+				return run_target(project_path, &file_name);
+			}
 		}
 	}
 
@@ -377,7 +830,7 @@ fn run_main_executable(project_path: &PathBuf) -> Result<()> {
 		for entry in std::fs::read_dir(&forge_out)? {
 			let entry = entry?;
 			let path = entry.path();
-			if path.is_dir() && path.file_name().unwrap().to_string_lossy().contains("unknown-linux-gnu") {
+			if path.is_dir() && path.file_name().unwrap().to_string_lossy().contains(host_triple_suffix()) {
 				let debug_dir = path.join("debug");
 				if let Some(executable) = find_executable_in_dir(&debug_dir, None) {
 					let name = executable.file_name().unwrap().to_string_lossy().to_string();
@@ -405,6 +858,247 @@ fn run_main_executable(project_path: &PathBuf) -> Result<()> {
 	))
 }
 
+fn write_luarc(project_path: &Path, types_path: &Path) -> Result<()> {
+	let luarc_path = project_path.join(".luarc.json");
+	let existing = match std::fs::read_to_string(&luarc_path) {
+		Ok(content) => Some(
+			serde_json::from_str(&content)
+				.map_err(|e| anyhow::anyhow!("Failed to parse {}: {}", luarc_path.display(), e))?,
+		),
+		Err(_) => None,
+	};
+
+	let types_path = std::path::absolute(types_path)?;
+	let types_file = types_path
+		.strip_prefix(project_path)
+		.unwrap_or(&types_path)
+		.to_string_lossy()
+		.to_string();
+	let cache_dir = forge_root_config::ForgeRootConfig::load(project_path.join("FORGE_ROOT"))
+		.map(|config| config.build.cache_dir)
+		.unwrap_or_else(|_| "forge-out".to_string());
+
+	let luarc = luals::luarc(existing, &types_file, &cache_dir);
+	std::fs::write(&luarc_path, serde_json::to_string_pretty(&luarc)? + "\n")?;
+	println!("Wrote {}", luarc_path.display());
+	Ok(())
+}
+
+/// The cache directory FORGE_ROOT sets, for commands that only read what builds left there
+fn cache_dir(project_path: &Path) -> PathBuf {
+	let cache_dir = forge_root_config::ForgeRootConfig::load(project_path.join("FORGE_ROOT"))
+		.map(|config| config.build.cache_dir)
+		.unwrap_or_else(|_| "forge-out".to_string());
+	project_path.join(cache_dir)
+}
+
+fn show_log(project_path: &Path, rule: Option<&str>) -> Result<()> {
+	let logs_dir = cache_dir(project_path).join("logs");
+
+	let Some(rule) = rule else {
+		let latest = build_log::latest_build_log(&logs_dir)
+			.ok_or_else(|| anyhow::anyhow!("No build logs found in {}", logs_dir.display()))?;
+		print!("{}", std::fs::read_to_string(latest)?);
+		return Ok(());
+	};
+
+	let (stdout_path, stderr_path) = build_log::rule_output_paths(&logs_dir, rule);
+	if !stdout_path.exists() && !stderr_path.exists() {
+		return Err(anyhow::anyhow!(
+			"No saved output for rule '{}'. Rules restored from cache or up to date keep the output of their last run, \
+			so this rule has not run since logs were enabled.",
+			rule
+		));
+	}
+
+	if let Ok(stdout) = std::fs::read(&stdout_path) {
+		std::io::stdout().write_all(&stdout)?;
+	}
+	if let Ok(stderr) = std::fs::read(&stderr_path) {
+		std::io::stderr().write_all(&stderr)?;
+	}
+	Ok(())
+}
+
+fn show_audit(project_path: &Path, rule: &str, json: bool) -> Result<()> {
+	let path = cache_dir(project_path).join(audit::AUDIT_LOG_NAME);
+	let entries = audit::read_entries(&path, Some(rule))
+		.map_err(|e| anyhow::anyhow!("Failed to read the audit log {}: {}", path.display(), e))?;
+	if entries.is_empty() {
+		return Err(anyhow::anyhow!(
+			"No command of rule '{}' in {}. Rules that were up to date or restored from cache ran no command.",
+			rule,
+			path.display()
+		));
+	}
+
+	for (i, entry) in entries.iter().enumerate() {
+		if json {
+			println!("{}", serde_json::to_string(entry)?);
+		} else {
+			if i > 0 {
+				println!();
+			}
+			println!("{}", audit::format_entry(entry));
+		}
+	}
+	Ok(())
+}
+
+fn show_env(project_path: &Path, json: bool) -> Result<()> {
+	let forge_root_path = project_path.join("FORGE_ROOT");
+	let config = forge_root_config::ForgeRootConfig::load(&forge_root_path)
+		.map_err(|e| anyhow::anyhow!("Failed to load {}: {}", forge_root_path.display(), e))?;
+	let fingerprint = fingerprint::Fingerprint::capture(&config);
+	if json {
+		println!("{}", serde_json::to_string_pretty(&fingerprint)?);
+	} else {
+		println!("{}", fingerprint);
+	}
+	Ok(())
+}
+
+/// Run the command the audit log last recorded for rule, or a shell set up like it, with the terminal attached
+fn replay_rule(project_path: &Path, rule: &str, shell: bool) -> Result<()> {
+	let path = cache_dir(project_path).join(audit::AUDIT_LOG_NAME);
+	let entry = audit::read_entries(&path, Some(rule))
+		.ok()
+		.and_then(|mut entries| entries.pop())
+		.ok_or_else(|| {
+			anyhow::anyhow!(
+				"No recorded run of rule '{}' in {}; build it first so its command is audited",
+				rule,
+				path.display()
+			)
+		})?;
+	if entry.executor != "local" {
+		log::warn!("Rule '{}' last ran on {}, replaying it locally", rule, entry.executor);
+	}
+	let command_line = format!("{} {}", entry.command, entry.args.join(" "));
+
+	let mut command = if shell {
+		let program = if cfg!(windows) {
+			std::env::var("COMSPEC").unwrap_or_else(|_| "cmd".to_string())
+		} else {
+			std::env::var("SHELL").unwrap_or_else(|_| "/bin/sh".to_string())
+		};
+		println!(
+			"Shell in {} with the env of '{}'; the rule ran:\n  {}",
+			entry.cwd, rule, command_line
+		);
+		let mut command = Command::new(program);
+		command.env("FORGE_REPLAY_COMMAND", &command_line);
+		command
+	} else {
+		println!("$ {}", command_line);
+		let (program, args) = executor::script_command(&entry.command, &entry.args);
+		let mut command = Command::new(program);
+		command.args(args);
+		command
+	};
+	for (key, value) in &entry.env {
+		if value == audit::REDACTED {
+			log::warn!(
+				"{} was redacted from the audit log, replaying with the current environment's value",
+				key
+			);
+		} else {
+			command.env(key, value);
+		}
+	}
+	interrupt::defer_to_child();
+	let status = command
+		.current_dir(&entry.cwd)
+		.status()
+		.map_err(|e| anyhow::anyhow!("Failed to start {}: {}", entry.command, e))?;
+
+	if !shell && !status.success() {
+		return Err(error::ForgeError::BuildFailed {
+			rule: rule.to_string(),
+			error: match status.code() {
+				Some(code) => format!("command exited with code {}", code),
+				None => format!("command was terminated ({})", status),
+			},
+		}
+		.into());
+	}
+	Ok(())
+}
+
+/// Install the forge.install files into <cache_dir>/package/<name>-<version>/, then archive that directory
+fn create_package(project: &project::Project, format: install::PackageFormat, output: Option<PathBuf>) -> Result<PathBuf> {
+	let project_config = &project.forge_root_config.project;
+	let cache_dir = project.path.join(&project.forge_root_config.build.cache_dir);
+	let package_name = format!("{}-{}", project_config.name, project_config.version);
+	let staging_root = cache_dir.join("package");
+	let staging = staging_root.join(&package_name);
+	if staging.exists() {
+		std::fs::remove_dir_all(&staging)?;
+	}
+	std::fs::create_dir_all(&staging)?;
+
+	let files = install::install(&project.path, &project.installs(), &staging)?;
+	if files.is_empty() {
+		return Err(anyhow::anyhow!(
+			"Nothing to package: no FORGE file declares files with forge.install{{src, dest}}"
+		));
+	}
+	let manifest = install::PackageManifest {
+		name: project_config.name.clone(),
+		version: project_config.version.clone(),
+		files,
+	};
+	std::fs::write(
+		staging.join(install::PACKAGE_MANIFEST_NAME),
+		serde_json::to_vec_pretty(&manifest)?,
+	)?;
+
+	let output = output.unwrap_or_else(|| cache_dir.join(format!("{}.{}", package_name, format.extension())));
+	lua_api::archive::write_archive(
+		&staging_root,
+		Some(std::slice::from_ref(&package_name)),
+		&output,
+		format.extension(),
+		true,
+	)?;
+	Ok(output)
+}
+
+fn verify_manifest(project_path: &Path, manifest_path: &Path, public_key: Option<&str>) -> Result<()> {
+	let manifest = provenance::Manifest::load(manifest_path)?;
+
+	if let Some(public_key) = public_key {
+		let signature_path = provenance::signature_path(manifest_path);
+		let signature = std::fs::read_to_string(&signature_path)
+			.map_err(|e| anyhow::anyhow!("Failed to read signature {}: {}", signature_path.display(), e))?;
+		let signed = provenance::verify_signature(&std::fs::read(manifest_path)?, &signature, public_key.trim())
+			.map_err(|e| anyhow::anyhow!("{}: {}", signature_path.display(), e))?;
+		if !signed {
+			return Err(anyhow::anyhow!(
+				"{} is not signed by the given public key",
+				manifest_path.display()
+			));
+		}
+		println!("Signature of {} is valid", manifest_path.display());
+	}
+
+	let problems = manifest.verify(project_path);
+	for problem in &problems {
+		eprintln!("{}", problem);
+	}
+	if !problems.is_empty() {
+		return Err(anyhow::anyhow!(
+			"{} of {} artifacts do not match {}",
+			problems.len(),
+			manifest.artifacts.len(),
+			manifest_path.display()
+		));
+	}
+
+	println!("All {} artifacts match {}", manifest.artifacts.len(), manifest_path.display());
+	Ok(())
+}
+
 fn clean_project(project_path: &Path) -> Result<()> {
 	let forge_out_path = project_path.join("forge-out");
 
@@ -490,8 +1184,12 @@ fn run_component_target_test_mode(project_path: &PathBuf, component_name: &str,
 	))
 }
 
+/// Extensions Windows runs a file by, which stand in for the executable bit elsewhere
+#[cfg(not(unix))]
+const EXECUTABLE_EXTENSIONS: &[&str] = &["exe", "bat", "cmd", "com", "ps1"];
+
 #[cfg(unix)]
-fn set_executable_permissions(path: &PathBuf) -> Result<()> {
+fn set_executable_permissions(path: &Path) -> Result<()> {
 	use std::os::unix::fs::PermissionsExt;
 	let mut perms = std::fs::metadata(path)?.permissions();
 	perms.set_mode(0o755);
@@ -499,7 +1197,13 @@ fn set_executable_permissions(path: &PathBuf) -> Result<()> {
 	Ok(())
 }
 
-fn is_executable(path: &PathBuf) -> bool {
+/// Files are executable by their extension on Windows, there is no permission to set
+#[cfg(not(unix))]
+fn set_executable_permissions(_path: &Path) -> Result<()> {
+	Ok(())
+}
+
+fn is_executable(path: &Path) -> bool {
 	if !path.is_file() {
 		return false;
 	}
@@ -507,21 +1211,38 @@ fn is_executable(path: &PathBuf) -> bool {
 	#[cfg(unix)]
 	{
 		use std::os::unix::fs::PermissionsExt;
-		if let Ok(metadata) = std::fs::metadata(path) {
-			return metadata.permissions().mode() & 0o111 != 0;
-		}
+		std::fs::metadata(path).is_ok_and(|metadata| metadata.permissions().mode() & 0o111 != 0)
 	}
 
 	#[cfg(not(unix))]
 	{
-		return path.extension().map_or(true, |ext| ext == "exe");
+		path.extension()
+			.and_then(|extension| extension.to_str())
+			.is_some_and(|extension| {
+				EXECUTABLE_EXTENSIONS
+					.iter()
+					.any(|known| extension.eq_ignore_ascii_case(known))
+			})
 	}
+}
 
-	false
+/// File name of the executable name is built as on the host, name.exe on Windows
+fn executable_file_name(name: &str) -> String {
+	format!("{}{}", name, std::env::consts::EXE_SUFFIX)
+}
+
+/// What the forge-out directories of host builds are named after, part of the host's target triple
+fn host_triple_suffix() -> &'static str {
+	if cfg!(windows) {
+		"pc-windows"
+	} else if cfg!(target_os = "macos") {
+		"apple-darwin"
+	} else {
+		"unknown-linux-gnu"
+	}
 }
 
 fn execute_binary(executable_path: &PathBuf, project_path: &PathBuf) -> Result<()> {
-	#[cfg(unix)]
 	set_executable_permissions(executable_path)?;
 
 	log::info!("Executing: {}", executable_path.display());
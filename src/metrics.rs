@@ -0,0 +1,283 @@
+use crate::{lua_api, project::BuildSummary, user_config::MetricsUserConfig};
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use std::{collections::HashMap, time::Duration};
+
+/// Metrics are pushed after the build; a collector that does not answer in time must not hold forge up
+const PUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// One value of a build metric
+#[derive(Debug, Clone, PartialEq)]
+struct Sample {
+	name: &'static str,
+	help: &'static str,
+	labels: Vec<(&'static str, String)>,
+	value: f64,
+}
+
+impl Sample {
+	fn new(name: &'static str, help: &'static str, value: f64) -> Self {
+		Self {
+			name,
+			help,
+			labels: Vec::new(),
+			value,
+		}
+	}
+
+	fn label(mut self, name: &'static str, value: &str) -> Self {
+		self.labels.push((name, value.to_string()));
+		self
+	}
+}
+
+/// Send the metrics of a finished build to the endpoints of config; failures only warn, the build result stands
+pub fn push(config: &MetricsUserConfig, project: &str, summary: &BuildSummary, success: bool) {
+	if config.otlp_endpoint.is_none() && config.pushgateway.is_none() {
+		return;
+	}
+	let samples = samples(summary, success);
+
+	if let Some(endpoint) = &config.pushgateway {
+		let url = format!(
+			"{}/metrics/job/{}/project@base64/{}",
+			endpoint.trim_end_matches('/'),
+			config.job.as_deref().unwrap_or("forge"),
+			// base64 so project names with / or spaces still form one path segment
+			URL_SAFE_NO_PAD.encode(project)
+		);
+		let body = prometheus_text(&samples, &config.labels);
+		if let Err(e) = send("PUT", &url, "text/plain; version=0.0.4", body, &config.headers) {
+			log::warn!("Failed to push build metrics to {}: {}", url, e);
+		}
+	}
+
+	if let Some(endpoint) = &config.otlp_endpoint {
+		let endpoint = endpoint.trim_end_matches('/');
+		let url = if endpoint.ends_with("/v1/metrics") {
+			endpoint.to_string()
+		} else {
+			format!("{}/v1/metrics", endpoint)
+		};
+		let body = otlp_json(&samples, project, &config.labels).to_string();
+		if let Err(e) = send("POST", &url, "application/json", body, &config.headers) {
+			log::warn!("Failed to export build metrics to {}: {}", url, e);
+		}
+	}
+}
+
+fn samples(summary: &BuildSummary, success: bool) -> Vec<Sample> {
+	let (downloaded, downloads_cached) = lua_api::http::download_counts();
+	let rules = "Rules of the build by how they were satisfied";
+	let downloads = "Downloads of the build by where they came from";
+	let mut samples = vec![
+		Sample::new("forge_build_duration_seconds", "Wall time of the build", summary.seconds),
+		Sample::new(
+			"forge_build_success",
+			"1 when the build succeeded, 0 when it failed or was interrupted",
+			if success { 1.0 } else { 0.0 },
+		),
+		Sample::new("forge_build_rules_total", "Rules of the build", summary.total as f64),
+		Sample::new("forge_build_rules", rules, summary.up_to_date as f64).label("outcome", "up_to_date"),
+		Sample::new("forge_build_rules", rules, summary.restored as f64).label("outcome", "restored"),
+		Sample::new("forge_build_rules", rules, summary.built as f64).label("outcome", "built"),
+		Sample::new("forge_build_rules", rules, summary.failed as f64).label("outcome", "failed"),
+		Sample::new("forge_build_rules", rules, summary.skipped as f64).label("outcome", "skipped"),
+		Sample::new(
+			"forge_build_restored_bytes",
+			"Bytes of outputs restored from the artifact cache",
+			summary.bytes_restored as f64,
+		),
+		Sample::new("forge_build_downloads", downloads, downloaded as f64).label("source", "network"),
+		Sample::new("forge_build_downloads", downloads, downloads_cached as f64).label("source", "cache"),
+	];
+	if let Some(rate) = summary.cache_hit_rate() {
+		samples.push(Sample::new(
+			"forge_build_cache_hit_ratio",
+			"Share of the rules that ran which needed no command",
+			rate / 100.0,
+		));
+	}
+	for timing in &summary.slowest {
+		samples.push(
+			Sample::new(
+				"forge_rule_duration_seconds",
+				"Wall time of the slowest rules the build ran",
+				timing.seconds,
+			)
+			.label("rule", &timing.rule),
+		);
+	}
+	for failure in &summary.failures {
+		samples.push(Sample::new("forge_rule_failed", "1 for each rule that failed", 1.0).label("rule", &failure.rule));
+	}
+	samples
+}
+
+/// The Prometheus text exposition format, as a pushgateway takes it
+fn prometheus_text(samples: &[Sample], labels: &HashMap<String, String>) -> String {
+	let mut extra: Vec<(&String, &String)> = labels.iter().collect();
+	extra.sort();
+
+	let mut text = String::new();
+	let mut previous = None;
+	for sample in samples {
+		if previous != Some(sample.name) {
+			text.push_str(&format!(
+				"# HELP {} {}\n# TYPE {} gauge\n",
+				sample.name, sample.help, sample.name
+			));
+			previous = Some(sample.name);
+		}
+		let pairs: Vec<String> = sample
+			.labels
+			.iter()
+			.map(|(name, value)| (name.to_string(), value))
+			.chain(extra.iter().map(|(name, value)| (name.to_string(), *value)))
+			.map(|(name, value)| format!("{}=\"{}\"", name, escape_label(value)))
+			.collect();
+		if pairs.is_empty() {
+			text.push_str(&format!("{} {}\n", sample.name, sample.value));
+		} else {
+			text.push_str(&format!("{}{{{}}} {}\n", sample.name, pairs.join(","), sample.value));
+		}
+	}
+	text
+}
+
+fn escape_label(value: &str) -> String {
+	value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// An OTLP ExportMetricsServiceRequest in its JSON encoding, each metric a gauge
+fn otlp_json(samples: &[Sample], project: &str, labels: &HashMap<String, String>) -> serde_json::Value {
+	let attribute = |key: &str, value: &str| serde_json::json!({ "key": key, "value": { "stringValue": value } });
+	let now = std::time::SystemTime::now()
+		.duration_since(std::time::UNIX_EPOCH)
+		.unwrap_or_default()
+		.as_nanos()
+		.to_string();
+
+	let mut resource = vec![attribute("service.name", "forge"), attribute("forge.project", project)];
+	let mut extra: Vec<(&String, &String)> = labels.iter().collect();
+	extra.sort();
+	resource.extend(extra.into_iter().map(|(key, value)| attribute(key, value)));
+
+	let mut metrics: Vec<serde_json::Value> = Vec::new();
+	for sample in samples {
+		let point = serde_json::json!({
+			"timeUnixNano": now,
+			"asDouble": sample.value,
+			"attributes": sample.labels.iter().map(|(key, value)| attribute(key, value)).collect::<Vec<_>>(),
+		});
+		match metrics.iter_mut().find(|metric| metric["name"] == sample.name) {
+			Some(metric) => metric["gauge"]["dataPoints"].as_array_mut().unwrap().push(point),
+			None => metrics.push(serde_json::json!({
+				"name": sample.name,
+				"description": sample.help,
+				"gauge": { "dataPoints": [point] },
+			})),
+		}
+	}
+
+	serde_json::json!({
+		"resourceMetrics": [{
+			"resource": { "attributes": resource },
+			"scopeMetrics": [{
+				"scope": { "name": "forge", "version": env!("CARGO_PKG_VERSION") },
+				"metrics": metrics,
+			}],
+		}],
+	})
+}
+
+fn send(method: &str, url: &str, content_type: &str, body: String, headers: &HashMap<String, String>) -> Result<(), String> {
+	let agent: ureq::Agent = ureq::Agent::config_builder()
+		.timeout_global(Some(PUSH_TIMEOUT))
+		.build()
+		.into();
+	let mut request = if method == "PUT" { agent.put(url) } else { agent.post(url) };
+	request = request.header("Content-Type", content_type);
+	for (name, value) in headers {
+		request = request.header(name, value);
+	}
+	request.send(body).map(|_| ()).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::project::{RuleFailure, RuleTiming};
+
+	#[test]
+	fn test_metric_encodings() {
+		let samples = vec![
+			Sample::new("forge_build_duration_seconds", "Wall time of the build", 1.5),
+			Sample::new("forge_build_rules", "Rules", 3.0).label("outcome", "built"),
+			Sample::new("forge_build_rules", "Rules", 0.0).label("outcome", "failed"),
+		];
+		let labels = HashMap::from([("host".to_string(), "ci \"1\"".to_string())]);
+
+		assert_eq!(
+			prometheus_text(&samples, &labels),
+			"# HELP forge_build_duration_seconds Wall time of the build\n\
+			# TYPE forge_build_duration_seconds gauge\n\
+			forge_build_duration_seconds{host=\"ci \\\"1\\\"\"} 1.5\n\
+			# HELP forge_build_rules Rules\n\
+			# TYPE forge_build_rules gauge\n\
+			forge_build_rules{outcome=\"built\",host=\"ci \\\"1\\\"\"} 3\n\
+			forge_build_rules{outcome=\"failed\",host=\"ci \\\"1\\\"\"} 0\n"
+		);
+
+		let otlp = otlp_json(&samples, "demo", &labels);
+		let metrics = &otlp["resourceMetrics"][0]["scopeMetrics"][0]["metrics"];
+		assert_eq!(metrics.as_array().unwrap().len(), 2);
+		assert_eq!(metrics[1]["gauge"]["dataPoints"].as_array().unwrap().len(), 2);
+		assert_eq!(metrics[1]["gauge"]["dataPoints"][0]["asDouble"], 3.0);
+		assert_eq!(
+			otlp["resourceMetrics"][0]["resource"]["attributes"][1]["value"]["stringValue"],
+			"demo"
+		);
+	}
+
+	#[test]
+	fn test_build_samples() {
+		let summary = BuildSummary {
+			total: 6,
+			up_to_date: 2,
+			built: 1,
+			failed: 1,
+			skipped: 2,
+			seconds: 3.0,
+			slowest: vec![RuleTiming {
+				rule: "compile".to_string(),
+				seconds: 2.5,
+			}],
+			failures: vec![RuleFailure {
+				rule: "link".to_string(),
+				error: "undefined reference".to_string(),
+			}],
+			..Default::default()
+		};
+		let samples = samples(&summary, false);
+
+		let by_outcome: f64 = samples
+			.iter()
+			.filter(|sample| sample.name == "forge_build_rules")
+			.map(|sample| sample.value)
+			.sum();
+		assert_eq!(by_outcome, 6.0);
+		assert!(
+			samples.contains(
+				&Sample::new(
+					"forge_rule_duration_seconds",
+					"Wall time of the slowest rules the build ran",
+					2.5
+				)
+				.label("rule", "compile")
+			)
+		);
+		assert!(
+			samples.contains(&Sample::new("forge_rule_failed", "1 for each rule that failed", 1.0).label("rule", "link"))
+		);
+	}
+}
@@ -0,0 +1,89 @@
+use std::{
+	collections::HashMap,
+	sync::{Condvar, Mutex},
+};
+
+/// The [build.pools] of FORGE_ROOT, each limiting how many of the rules in it run at once regardless of how many
+/// rules run in parallel overall
+pub struct Pools {
+	pools: HashMap<String, Pool>,
+}
+
+struct Pool {
+	depth: usize,
+	running: Mutex<usize>,
+	freed: Condvar,
+}
+
+/// A running rule's place in its pool, given back when dropped
+pub struct PoolSlot<'a> {
+	pool: &'a Pool,
+}
+
+impl Pools {
+	pub fn new(depths: &HashMap<String, usize>) -> Self {
+		Self {
+			pools: depths
+				.iter()
+				.map(|(name, depth)| {
+					(
+						name.clone(),
+						Pool {
+							depth: *depth,
+							running: Mutex::new(0),
+							freed: Condvar::new(),
+						},
+					)
+				})
+				.collect(),
+		}
+	}
+
+	pub fn contains(&self, name: &str) -> bool {
+		self.pools.contains_key(name)
+	}
+
+	/// Wait until the pool called name has room for one more rule, None if there is no such pool
+	pub fn acquire(&self, name: &str) -> Option<PoolSlot<'_>> {
+		let pool = self.pools.get(name)?;
+		let mut running = pool.running.lock().unwrap();
+		while *running >= pool.depth {
+			running = pool.freed.wait(running).unwrap();
+		}
+		*running += 1;
+		Some(PoolSlot { pool })
+	}
+}
+
+impl Drop for PoolSlot<'_> {
+	fn drop(&mut self) {
+		*self.pool.running.lock().unwrap() -= 1;
+		self.pool.freed.notify_one();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::atomic::{AtomicUsize, Ordering};
+
+	#[test]
+	fn test_pool_depth() {
+		let pools = Pools::new(&HashMap::from([("linkers".to_string(), 2)]));
+		assert!(pools.acquire("missing").is_none());
+
+		let running = AtomicUsize::new(0);
+		let peak = AtomicUsize::new(0);
+		std::thread::scope(|scope| {
+			for _ in 0..8 {
+				scope.spawn(|| {
+					let _slot = pools.acquire("linkers").unwrap();
+					peak.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+					std::thread::sleep(std::time::Duration::from_millis(5));
+					running.fetch_sub(1, Ordering::SeqCst);
+				});
+			}
+		});
+		assert!((1..=2).contains(&peak.load(Ordering::SeqCst)));
+	}
+}
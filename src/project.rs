@@ -1,20 +1,46 @@
-use crate::{cache::BuildCache, config::Config, error::ForgeError, forge_root_config::ForgeRootConfig, lua_api};
+use crate::{
+	audit::{self, AUDIT_LOG_NAME, AuditEntry, AuditLog},
+	build_log::{BuildLog, LogEvent},
+	cache::{BuildCache, RestoreMarker},
+	config::{Config, OutputMode},
+	diagnostic::Diagnostic,
+	error::ForgeError,
+	eval_cache::{EvalCache, EvalCacheEntry},
+	executor::{Executor, FileAccesses, LocalExecutor, RemoteExecutor, SshExecutor, TracingExecutor},
+	fingerprint::Fingerprint,
+	forge_root_config::{EnforceDeps, ForgeRootConfig},
+	interrupt,
+	lockfile::{LOCKFILE_NAME, Lockfile},
+	lua_api, metrics,
+	pools::Pools,
+	provenance,
+	rsp::{self, RspFormat},
+	rust_toolchain::{self, RustToolchain},
+	toolchains::{self, Toolchain},
+	user_config::UserConfig,
+	workers::Workers,
+};
 use anyhow::Context;
 use blake3::Hasher;
 use dashmap::DashMap;
 use ignore::WalkBuilder;
-use mlua::{Lua, UserData};
+use mlua::{Function, Lua, LuaOptions, UserData};
 use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
 use std::{
 	borrow::Cow,
-	collections::HashMap,
+	collections::{BTreeMap, HashMap, HashSet},
+	fmt,
 	path::{Path, PathBuf},
-	sync::Arc,
-	time::Instant,
+	sync::{
+		Arc, OnceLock,
+		atomic::{AtomicBool, AtomicUsize, Ordering},
+	},
+	time::{Duration, Instant},
 };
 use walkdir::WalkDir;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Rule {
 	pub name: String,
 	pub command: String,
@@ -24,20 +50,305 @@ pub struct Rule {
 	pub outputs: Vec<String>,
 	pub dependencies: Vec<String>,
 	pub workdir: PathBuf,
+	/// Lua modules the FORGE file that registered this rule loaded through require
+	#[serde(default)]
+	pub modules: Vec<PathBuf>,
+	/// Id of the forge.action callback run in place of command
+	#[serde(default)]
+	pub action: Option<String>,
+	/// Only fetches something later rules use, run by forge fetch
+	#[serde(default)]
+	pub fetch: bool,
+	/// Free-form labels to select rules by, like "codegen" or "slow"
+	#[serde(default)]
+	pub tags: Vec<String>,
+	/// [build.pools] entry limiting how many rules like this one run at once
+	#[serde(default)]
+	pub pool: Option<String>,
+	/// Run command as a persistent worker, kept alive between rules and sent each one's args over stdin
+	#[serde(default)]
+	pub worker: bool,
+	/// Run on the [build.remote] server or [build.remote_hosts] (true) or locally (false), instead of as all_rules says
+	#[serde(default)]
+	pub remote: Option<bool>,
+	/// Semver version of what the rule produces, making it a package in forge sbom
+	#[serde(default)]
+	pub version: Option<String>,
+	/// Shell script the rule runs in place of a command, from the file script_path names, which is among its inputs
+	#[serde(default)]
+	pub script: Option<String>,
+	/// Format of the @file args go in when they are too long for the OS to start the command with
+	#[serde(default)]
+	pub rsp_format: Option<RspFormat>,
+	/// Set by forge.install: where under the install prefix the inputs go; such rules run nothing and are kept out
+	/// of the build graph
+	#[serde(default)]
+	pub install: Option<String>,
 }
 
 impl UserData for Rule {}
 
-#[derive(Clone)]
+/// <cache_dir>/scripts/<hash>.sh, relative to the project root, where the script of a rule is written
+pub fn script_path(cache_dir: &str, script: &str) -> String {
+	let hash = blake3::hash(script.as_bytes()).to_hex();
+	format!("{}/scripts/{}.sh", cache_dir.trim_end_matches('/'), &hash[..16])
+}
+
+/// Point rule at the outputs moved maps to where isolate_outputs moves them: its inputs and outputs get the new
+/// relative path, while args, env values and words of its script naming one of those inputs or outputs (whole or
+/// after an "=") get the absolute one, since they are read from the workdir; a changed script also moves to its new
+/// script_path. Words naming an output the rule does not declare are left alone, even when another rule produces it
+fn isolate_rule(rule: &mut Rule, moved: &HashMap<String, String>, project_root: &Path, cache_dir: &str) {
+	let declared: HashMap<String, String> = rule
+		.inputs
+		.iter()
+		.chain(&rule.outputs)
+		.filter_map(|path| moved.get_key_value(path.as_str()))
+		.map(|(path, isolated)| (path.clone(), isolated.clone()))
+		.collect();
+	let isolated = |value: &str| -> Option<String> {
+		if let Some(isolated) = declared.get(value) {
+			return Some(project_root.join(isolated).to_string_lossy().to_string());
+		}
+		let (flag, value) = value.split_once('=')?;
+		let isolated = declared.get(value)?;
+		Some(format!("{}={}", flag, project_root.join(isolated).display()))
+	};
+
+	for path in rule.outputs.iter_mut().chain(rule.inputs.iter_mut()) {
+		if let Some(isolated) = moved.get(path.as_str()) {
+			*path = isolated.clone();
+		}
+	}
+	for value in rule.args.iter_mut().chain(rule.env.values_mut()) {
+		if let Some(isolated) = isolated(value) {
+			*value = isolated;
+		}
+	}
+
+	let Some(script) = &rule.script else {
+		return;
+	};
+	let mut rewritten = String::with_capacity(script.len());
+	let mut word = String::new();
+	for c in script.chars().chain(std::iter::once('\n')) {
+		if c.is_whitespace() || "\"'`;|&()<>".contains(c) {
+			rewritten.push_str(&isolated(&word).unwrap_or_else(|| word.clone()));
+			rewritten.push(c);
+			word.clear();
+		} else {
+			word.push(c);
+		}
+	}
+	rewritten.pop();
+	if rewritten != *script {
+		let (old, new) = (script_path(cache_dir, script), script_path(cache_dir, &rewritten));
+		let (old_arg, new_arg) = (project_root.join(&old), project_root.join(&new));
+		for input in rule.inputs.iter_mut().filter(|input| **input == old) {
+			*input = new.clone();
+		}
+		for arg in rule.args.iter_mut().filter(|arg| Path::new(arg.as_str()) == old_arg) {
+			*arg = new_arg.to_string_lossy().to_string();
+		}
+		rule.script = Some(rewritten);
+	}
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum RuleOutcome {
+	UpToDate,
+	Restored {
+		/// Size of the outputs copied out of the cache, leaving out those that already matched
+		bytes: u64,
+	},
+	Built,
+}
+
+/// How many rules the build summary lists as the slowest
+const SLOWEST_RULES: usize = 5;
+
+/// How each rule of a build was satisfied
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BuildSummary {
+	/// Rules of the build, each counted in exactly one of the fields below
+	pub total: usize,
+	pub up_to_date: usize,
+	pub restored: usize,
+	pub built: usize,
+	pub failed: usize,
+	/// Rules never started because an earlier batch failed
+	pub skipped: usize,
+	pub bytes_restored: u64,
+	/// Wall time of the whole build
+	pub seconds: f64,
+	/// The rules that took longest to build, slowest first
+	pub slowest: Vec<RuleTiming>,
+	pub failures: Vec<RuleFailure>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RuleTiming {
+	pub rule: String,
+	pub seconds: f64,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct RuleFailure {
+	pub rule: String,
+	/// First error line of the rule's output, or the error itself when it printed nothing
+	pub error: String,
+}
+
+impl BuildSummary {
+	fn record(&mut self, rule: &str, outcome: RuleOutcome, elapsed: Duration) {
+		match outcome {
+			RuleOutcome::UpToDate => self.up_to_date += 1,
+			RuleOutcome::Restored { bytes } => {
+				self.restored += 1;
+				self.bytes_restored += bytes;
+			}
+			RuleOutcome::Built => {
+				self.built += 1;
+				self.slowest.push(RuleTiming {
+					rule: rule.to_string(),
+					seconds: elapsed.as_secs_f64(),
+				});
+				self.slowest.sort_by(|a, b| b.seconds.total_cmp(&a.seconds));
+				self.slowest.truncate(SLOWEST_RULES);
+			}
+		}
+	}
+
+	/// One line of the counts, for the build log
+	fn counts(&self) -> String {
+		format!(
+			"{} up-to-date, {} restored from cache, {} built, {} failed, {} skipped",
+			self.up_to_date, self.restored, self.built, self.failed, self.skipped
+		)
+	}
+
+	pub fn cached(&self) -> usize {
+		self.up_to_date + self.restored
+	}
+
+	/// Share of the rules that ran which needed no command, None when none ran
+	pub fn cache_hit_rate(&self) -> Option<f64> {
+		let ran = self.cached() + self.built + self.failed;
+		(ran > 0).then(|| self.cached() as f64 / ran as f64 * 100.0)
+	}
+}
+
+impl fmt::Display for BuildSummary {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(f, "Build summary")?;
+		writeln!(f, "  executed  {:>6}", self.built)?;
+		writeln!(
+			f,
+			"  cached    {:>6}  ({} up-to-date, {} restored from cache)",
+			self.cached(),
+			self.up_to_date,
+			self.restored
+		)?;
+		writeln!(f, "  failed    {:>6}", self.failed)?;
+		write!(f, "  skipped   {:>6}", self.skipped)?;
+		if let Some(rate) = self.cache_hit_rate() {
+			write!(f, "\n  cache hit rate {:.1}%", rate)?;
+		}
+
+		if !self.slowest.is_empty() {
+			write!(f, "\nSlowest rules")?;
+			for timing in &self.slowest {
+				write!(f, "\n  {:>8.2}s  {}", timing.seconds, timing.rule)?;
+			}
+		}
+		if !self.failures.is_empty() {
+			write!(f, "\nFailures")?;
+			for failure in &self.failures {
+				write!(f, "\n  {}: {}", failure.rule, failure.error)?;
+			}
+		}
+		Ok(())
+	}
+}
+
+/// Which of the evaluated rules a build runs, on top of the targets and components FORGE files are evaluated for
+/// What one FORGE evaluation worker produced
+#[derive(Default)]
+struct EvaluatedFiles {
+	lua: Option<Lua>,
+	/// The rules of each FORGE file loaded, by its index
+	rules: Vec<(usize, Result<Vec<Rule>, ForgeError>)>,
+	hooks: Vec<GraphHook>,
+}
+
+/// A forge.on_graph_complete callback, with the FORGE file that registered it and the Lua state it lives in
+struct GraphHook {
+	index: usize,
+	forge_file: PathBuf,
+	lua: Lua,
+	function: Function,
+}
+
+#[derive(Debug, Default)]
+pub struct RuleSelection {
+	/// Only the rules affected by files changed since this git ref, with the rules they depend on
+	pub since: Option<String>,
+	/// Only the rules with one of these tags, with the rules they depend on
+	pub tags: Vec<String>,
+	/// Leave out the rules with one of these tags, unless a selected rule depends on them
+	pub exclude_tags: Vec<String>,
+	/// Only the rules producing these outputs, with the rules they depend on
+	pub outputs: Vec<String>,
+}
+
+impl RuleSelection {
+	/// Whether rule passes the tag filters
+	pub fn matches_tags(&self, rule: &Rule) -> bool {
+		(self.tags.is_empty() || rule.tags.iter().any(|tag| self.tags.contains(tag)))
+			&& !rule.tags.iter().any(|tag| self.exclude_tags.contains(tag))
+	}
+}
+
 pub struct Project {
 	pub path: PathBuf,
 	pub config: Config,
+	pub selection: RuleSelection,
 	pub forge_root_config: ForgeRootConfig,
 	pub build_graph: Arc<DashMap<String, Rule>>,
 	pub output_map: Arc<DashMap<String, String>>,
-	pub cache: BuildCache,
+	/// <cache_dir>/<target>/<profile>, where the artifacts of this build belong; forge.out_dir in FORGE files
+	pub out_dir: PathBuf,
+	/// forge.install rules by name, outside the build graph since they run nothing
+	installs: DashMap<String, Rule>,
+	pub cache: Arc<BuildCache>,
+	eval_cache: EvalCache,
+	module_hashes: DashMap<PathBuf, blake3::Hash>,
+	/// forge.action callbacks by id, kept with the Lua states that evaluated them
+	actions: DashMap<String, lua_api::action::Action>,
+	/// Checksums of the downloads FORGE files did not pin, shared with every Lua state
+	pub lockfile: Arc<Lockfile>,
+	pools: Pools,
+	workers: Workers,
+	/// The [toolchains] of FORGE_ROOT, provisioned before FORGE files are evaluated
+	toolchains: Vec<Toolchain>,
+	/// The sysroots of [targets] in FORGE_ROOT by triple, provisioned with the toolchains
+	sysroots: BTreeMap<String, PathBuf>,
+	/// What the project's rust-toolchain.toml pins, which cargo, rustc and the other rustup proxies run with
+	pub rust_toolchain: Option<RustToolchain>,
+	/// rustc's version under rust_toolchain, part of the hash of every rule running a rustup proxy
+	rust_version: OnceLock<Option<String>>,
+	/// Captured when the first artifact is stored or restored, running compilers to get their versions
+	fingerprint: OnceLock<Fingerprint>,
+	/// The [build.remote] server when it has an endpoint, the [build.remote_hosts] otherwise
+	remote_executor: Option<Box<dyn Executor>>,
+	/// Runs local rules under strace when [build] enforce_deps is on
+	tracing_executor: Option<TracingExecutor>,
+	pub build_log: Arc<BuildLog>,
+	audit_log: Arc<AuditLog>,
 	cas_path: PathBuf,
-	lua: Lua,
+	restore_marker_path: PathBuf,
+	/// Drives FORGE file evaluation, so async Lua API functions can await instead of blocking
+	runtime: tokio::runtime::Runtime,
 }
 
 impl Project {
@@ -60,7 +371,15 @@ impl Project {
 		})?;
 
 		let output_dir = path.join(&forge_root_config.build.cache_dir);
+		let out_dir = output_dir
+			.join(if config.target_filters.is_empty() {
+				"default".to_string()
+			} else {
+				config.target_filters.join("+")
+			})
+			.join(&config.profile);
 		let cas_path = output_dir.join("cas");
+		let restore_marker_path = output_dir.join("restores");
 		std::fs::create_dir_all(&output_dir)?;
 		std::fs::create_dir_all(&cas_path)?;
 
@@ -70,67 +389,772 @@ impl Project {
 
 		let cache_path = output_dir.join("cache.json");
 		let cache = BuildCache::load(&cache_path);
+		let eval_cache = EvalCache::load(&output_dir.join("eval_cache.json"));
+		let lockfile = Arc::new(Lockfile::load(&path.join(LOCKFILE_NAME))?);
+		let pools = Pools::new(&forge_root_config.build.pools);
+		let workers = Workers::new(
+			&output_dir.join("workers"),
+			Duration::from_secs(forge_root_config.build.worker_timeout),
+		);
+		let runtime = tokio::runtime::Builder::new_multi_thread().enable_all().build()?;
+		let remote = &forge_root_config.build.remote;
+		let remote_executor: Option<Box<dyn Executor>> = match remote.endpoint.as_deref() {
+			Some(endpoint) => Some(Box::new(RemoteExecutor::new(
+				endpoint,
+				&remote.instance_name,
+				&path,
+				runtime.handle().clone(),
+			)?)),
+			None if !forge_root_config.build.remote_hosts.is_empty() => Some(Box::new(SshExecutor::new(
+				&forge_root_config.build.remote_hosts,
+				&path,
+				&forge_root_config.project.name,
+			))),
+			None => None,
+		};
+		let build_log = Arc::new(BuildLog::create(&output_dir.join("logs"))?);
+		let audit_log = Arc::new(AuditLog::open(&output_dir.join(AUDIT_LOG_NAME))?);
+		let tracing_executor = match forge_root_config.build.enforce_deps {
+			EnforceDeps::Off => None,
+			_ if TracingExecutor::available() => Some(TracingExecutor::new(&output_dir.join("traces"))),
+			_ => {
+				log::warn!("[build] enforce_deps needs strace on Linux, rules run untraced");
+				None
+			}
+		};
+		let rust_toolchain = RustToolchain::detect(&path)?;
 
 		cache.validate_and_clean(&path);
+		cache.recover_interrupted_restores(&path, &restore_marker_path);
+
+		// A second Ctrl-C exits without unwinding, keep what finished before it like the first one does
+		let cache = Arc::new(cache);
+		let exit_cache = cache.clone();
+		let exit_audit_log = audit_log.clone();
+		interrupt::set_exit_hook(Some(Box::new(move || {
+			if let Err(e) = exit_cache.save(&cache_path) {
+				log::warn!("Failed to save build cache: {}", e);
+			}
+			exit_audit_log.sync();
+		})));
 
 		Ok(Self {
 			path,
 			config,
+			selection: RuleSelection::default(),
 			forge_root_config,
 			build_graph: Arc::new(DashMap::new()),
 			output_map: Arc::new(DashMap::new()),
+			out_dir,
+			installs: DashMap::new(),
 			cache,
+			eval_cache,
+			module_hashes: DashMap::new(),
+			actions: DashMap::new(),
+			lockfile,
+			pools,
+			workers,
+			toolchains: Vec::new(),
+			sysroots: BTreeMap::new(),
+			rust_toolchain,
+			rust_version: OnceLock::new(),
+			fingerprint: OnceLock::new(),
+			remote_executor,
+			tracing_executor,
+			build_log,
+			audit_log,
 			cas_path,
-			lua: Lua::new(),
+			restore_marker_path,
+			runtime,
 		})
 	}
 
-	fn setup_lua_environment(&self) -> Result<(), ForgeError> {
-		lua_api::init::setup_lua_environment(&self.lua, self)?;
-		Ok(())
+	/// A Lua state with the forge API set up, as FORGE files are evaluated in
+	fn create_lua(&self) -> Result<Lua, ForgeError> {
+		let lua = Lua::new_with(
+			lua_api::sandbox::std_libs(self.forge_root_config.build.lua_sandbox),
+			LuaOptions::default(),
+		)?;
+		lua_api::init::setup_lua_environment(&lua, self)?;
+		Ok(lua)
 	}
 
 	pub fn run(&mut self) -> Result<(), ForgeError> {
-		self.setup_lua_environment()?;
+		let result = self.evaluate_and_build();
+		self.finish(result)
+	}
 
-		let forge_files = self.find_forge_files(&self.path)?;
+	/// Evaluate the FORGE files into the build graph without running any rule
+	pub fn evaluate(&mut self) -> Result<(), ForgeError> {
+		let result = self.evaluate_forge_files();
+		self.finish(result)
+	}
 
-		for forge_file in &forge_files {
-			log::debug!("Loading FORGE file: {}", forge_file.display());
-			let content = std::fs::read_to_string(forge_file)?;
+	/// Evaluate the FORGE files fetching every download without a checksum again, and rewrite FORGE.lock with
+	/// what they are now
+	pub fn update_lockfile(&mut self) -> Result<(), ForgeError> {
+		self.lockfile = Arc::new(Lockfile::update(&self.path.join(LOCKFILE_NAME)));
+		self.evaluate()
+	}
 
-			if content.trim().is_empty() {
-				return Err(ForgeError::InvalidForgeFile {
-					file: forge_file.display().to_string(),
-					error: "FORGE file is empty".to_string(),
-					suggestion: "Add build rules to your FORGE file".to_string(),
-				});
+	/// Evaluate the FORGE files and run only the fetch rules and what they depend on, filling the download cache and
+	/// the build cache without building anything else
+	pub fn fetch(&mut self) -> Result<(), ForgeError> {
+		let result = self.evaluate_and_fetch();
+		self.finish(result)
+	}
+
+	/// The rules of the evaluated build graph
+	pub fn rules(&self) -> Vec<Rule> {
+		self.build_graph.iter().map(|rule| rule.value().clone()).collect()
+	}
+
+	/// The toolchains provisioned for the FORGE files, sorted by name
+	pub fn toolchains(&self) -> &[Toolchain] {
+		&self.toolchains
+	}
+
+	/// The sysroots of FORGE_ROOT's [targets], by triple
+	pub fn sysroots(&self) -> &BTreeMap<String, PathBuf> {
+		&self.sysroots
+	}
+
+	/// The forge.install rules of the evaluated FORGE files, sorted by name
+	pub fn installs(&self) -> Vec<Rule> {
+		let mut installs: Vec<Rule> = self.installs.iter().map(|rule| rule.value().clone()).collect();
+		installs.sort_by(|a, b| a.name.cmp(&b.name));
+		installs
+	}
+
+	fn finish(&self, result: Result<(), ForgeError>) -> Result<(), ForgeError> {
+		lua_api::init::teardown_lua_environment();
+		if let Err(e) = &result
+			&& let Some(diagnostic) = e.diagnostic()
+		{
+			self.build_log.record(LogEvent::new("diagnostic").diagnostic(diagnostic));
+		}
+		result
+	}
+
+	fn evaluate_and_build(&mut self) -> Result<(), ForgeError> {
+		self.evaluate_forge_files()?;
+		self.apply_selection()?;
+		if let Err(e) = self.execute_build_graph() {
+			// Keep what finished before Ctrl-C, so the next build does not run it again
+			if let ForgeError::Interrupted = e {
+				self.save_cache()?;
 			}
+			return Err(e);
+		}
 
-			if !content.contains("rule") && !content.contains("require") {
-				return Err(ForgeError::InvalidForgeFile {
-					file: forge_file.display().to_string(),
-					error: "No build rules found".to_string(),
-					suggestion: "Add at least one rule() call to define build steps".to_string(),
-				});
+		self.save_cache()?;
+		self.write_manifests()?;
+
+		Ok(())
+	}
+
+	/// Write <cache_dir>/<target>/manifest.json for every target built, signed when the user config has a signing key
+	fn write_manifests(&self) -> Result<(), ForgeError> {
+		let mut rules = self.rules();
+		rules.sort_by(|a, b| a.name.cmp(&b.name));
+
+		let mut tools: HashMap<(&str, &Path), provenance::Tool> = HashMap::new();
+		for rule in rules.iter().filter(|rule| rule.action.is_none()) {
+			tools
+				.entry((rule.command.as_str(), rule.workdir.as_path()))
+				.or_insert_with(|| provenance::tool(&rule.command, &rule.workdir));
+		}
+
+		let mut artifacts = rules
+			.par_iter()
+			.map(|rule| -> std::io::Result<Vec<provenance::Artifact>> {
+				let mut inputs = Vec::new();
+				for input in &rule.inputs {
+					let path = self.path.join(input);
+					if path.is_file() {
+						inputs.push(provenance::Material {
+							path: input.clone(),
+							blake3: provenance::hash_file(&path)?.0,
+						});
+					}
+				}
+				inputs.sort_by(|a, b| a.path.cmp(&b.path));
+
+				let tool = rule
+					.action
+					.is_none()
+					.then(|| tools[&(rule.command.as_str(), rule.workdir.as_path())].clone());
+				let mut artifacts = Vec::new();
+				for output in &rule.outputs {
+					let path = self.path.join(output);
+					if !path.is_file() {
+						continue;
+					}
+					let (blake3, size) = provenance::hash_file(&path)?;
+					artifacts.push(provenance::Artifact {
+						path: output.clone(),
+						blake3,
+						size,
+						rule: rule.name.clone(),
+						inputs: inputs.clone(),
+						tool: tool.clone(),
+					});
+				}
+				Ok(artifacts)
+			})
+			.collect::<std::io::Result<Vec<_>>>()?
+			.into_iter()
+			.flatten()
+			.collect::<Vec<_>>();
+		artifacts.sort_by(|a, b| a.path.cmp(&b.path));
+
+		let targets = if self.config.target_filters.is_empty() {
+			vec!["default".to_string()]
+		} else {
+			self.config.target_filters.clone()
+		};
+		let key_path = UserConfig::get().signing.key_path();
+		for target in &targets {
+			let mut manifest = provenance::Manifest::new(
+				&self.forge_root_config.project.name,
+				target,
+				self.forge_root_config.build.reproducible,
+			);
+			manifest.artifacts = artifacts.clone();
+			let path = self
+				.path
+				.join(&self.forge_root_config.build.cache_dir)
+				.join(target)
+				.join(provenance::MANIFEST_NAME);
+			manifest
+				.write(&path, key_path.as_deref())
+				.with_context(|| format!("Failed to write provenance manifest {}", path.display()))?;
+			log::info!("Provenance manifest written to {}", path.display());
+		}
+		Ok(())
+	}
+
+	fn evaluate_and_fetch(&mut self) -> Result<(), ForgeError> {
+		self.evaluate_forge_files()?;
+		let selected = self.select_rules(|rule| rule.fetch);
+		log::info!("Running {} fetch rules", selected);
+		if let Err(e) = self.execute_build_graph() {
+			if let ForgeError::Interrupted = e {
+				self.save_cache()?;
 			}
+			return Err(e);
+		}
+
+		self.save_cache()?;
+
+		Ok(())
+	}
+
+	fn save_cache(&self) -> Result<(), ForgeError> {
+		let cache_path = self.path.join("forge-out").join("cache.json");
+		self.cache.save(&cache_path).context("Failed to save build cache")?;
+		Ok(())
+	}
+
+	/// Narrow the build graph down to the rules self.selection asks for
+	fn apply_selection(&self) -> Result<(), ForgeError> {
+		if !self.selection.outputs.is_empty() {
+			let mut producers = HashSet::new();
+			for output in &self.selection.outputs {
+				let producer = self.producer_of(output).ok_or_else(|| {
+					anyhow::anyhow!(
+						"No rule produces '{}'; outputs are matched relative to the project root, see `forge list` for the rules",
+						output
+					)
+				})?;
+				producers.insert(producer);
+			}
+			self.select_rules(|rule| producers.contains(&rule.name));
+		}
+
+		if let Some(git_ref) = &self.selection.since {
+			let changed = self.changed_files_since(git_ref)?;
+			// Which rules a FORGE file, the prelude or FORGE_ROOT affect is not tracked, so all of them are
+			if let Some(file) = changed.iter().find(|file| self.affects_evaluation(file)) {
+				log::info!("{} changed since {}, building every rule", file.display(), git_ref);
+				return Ok(());
+			}
+
+			let affected = self.affected_rules(&changed);
+			log::info!(
+				"{} files changed since {}, affecting {} rules",
+				changed.len(),
+				git_ref,
+				affected.len()
+			);
+			self.select_rules(|rule| affected.contains(&rule.name));
+		}
+
+		if !self.selection.tags.is_empty() || !self.selection.exclude_tags.is_empty() {
+			let selected = self.select_rules(|rule| self.selection.matches_tags(rule));
+			log::info!("{} rules match the tag filters", selected);
+		}
+		Ok(())
+	}
+
+	/// Files under the project changed between git_ref and the working tree, including untracked ones, as absolute
+	/// paths
+	fn changed_files_since(&self, git_ref: &str) -> Result<HashSet<PathBuf>, ForgeError> {
+		let mut changed = HashSet::new();
+		for args in [
+			&["diff", "--name-only", "--relative", git_ref, "--"][..],
+			&["ls-files", "--others", "--exclude-standard"][..],
+		] {
+			let output = std::process::Command::new("git")
+				.args(args)
+				.current_dir(&self.path)
+				.output()
+				.context("Failed to run git")?;
+			if !output.status.success() {
+				return Err(anyhow::anyhow!(
+					"git {} failed: {}",
+					args.join(" "),
+					String::from_utf8_lossy(&output.stderr).trim()
+				)
+				.into());
+			}
+			changed.extend(
+				String::from_utf8_lossy(&output.stdout)
+					.lines()
+					.filter(|line| !line.is_empty())
+					.map(|line| self.normalize_path(Path::new(line))),
+			);
+		}
+		Ok(changed)
+	}
+
+	/// Whether a change to file can change the rules FORGE files register
+	fn affects_evaluation(&self, file: &Path) -> bool {
+		file.starts_with(self.path.join("prelude"))
+			|| file
+				.file_name()
+				.is_some_and(|name| name == "FORGE" || name == "FORGE_ROOT" || name == "FORGE.lock")
+	}
+
+	/// path relative to the project, or absolute, as an absolute path without "." components
+	fn normalize_path(&self, path: &Path) -> PathBuf {
+		self.path.join(path).components().collect()
+	}
+
+	/// Rules with a changed input or Lua module, and every rule depending on one of them
+	fn affected_rules(&self, changed: &HashSet<PathBuf>) -> HashSet<String> {
+		let mut dependents: HashMap<String, Vec<String>> = HashMap::new();
+		let mut pending = Vec::new();
+		for rule in self.build_graph.iter() {
+			let inputs_changed = rule
+				.inputs
+				.iter()
+				.map(Path::new)
+				.chain(rule.modules.iter().map(PathBuf::as_path))
+				.any(|path| changed.contains(&self.normalize_path(path)));
+			if inputs_changed {
+				pending.push(rule.name.clone());
+			}
+
+			for input in &rule.inputs {
+				if let Some(producer) = self.output_map.get(input) {
+					dependents
+						.entry(producer.value().clone())
+						.or_default()
+						.push(rule.name.clone());
+				}
+			}
+			for dependency in &rule.dependencies {
+				dependents.entry(dependency.clone()).or_default().push(rule.name.clone());
+			}
+		}
+
+		let mut affected = HashSet::new();
+		while let Some(name) = pending.pop() {
+			if affected.insert(name.clone()) {
+				pending.extend(dependents.get(&name).into_iter().flatten().cloned());
+			}
+		}
+		affected
+	}
+
+	/// Keep only the rules selected matches in the build graph, along with every rule they depend on, returning how
+	/// many matched
+	fn select_rules(&self, selected: impl Fn(&Rule) -> bool) -> usize {
+		let mut pending: Vec<String> = self
+			.build_graph
+			.iter()
+			.filter(|rule| selected(rule.value()))
+			.map(|rule| rule.key().clone())
+			.collect();
+		let matched = pending.len();
+
+		let mut kept = HashSet::new();
+		while let Some(name) = pending.pop() {
+			if !kept.insert(name.clone()) {
+				continue;
+			}
+			if let Some(rule) = self.build_graph.get(&name) {
+				pending.extend(
+					rule.inputs
+						.iter()
+						.filter_map(|input| self.output_map.get(input).map(|producer| producer.value().clone())),
+				);
+				pending.extend(rule.dependencies.iter().cloned());
+			}
+		}
+		self.build_graph.retain(|name, _| kept.contains(name));
+		matched
+	}
+
+	/// Evaluate the FORGE files on one Lua state per worker, then merge the rules each file registered into the
+	/// build graph in file order, so the graph and its conflict warnings do not depend on which worker finished first
+	fn evaluate_forge_files(&mut self) -> Result<(), ForgeError> {
+		lua_api::http::set_offline(self.config.offline);
+		self.toolchains = toolchains::provision(&self.forge_root_config.toolchains, Some(&self.lockfile))?;
+		self.sysroots = toolchains::provision_sysroots(&self.forge_root_config.targets, &self.path, Some(&self.lockfile))?;
+		let forge_files = self.find_forge_files(&self.path)?;
+
+		let build = &self.forge_root_config.build;
+		// Shared globals and the shared forge.random sequence both depend on evaluation order
+		let workers = if !build.isolate_forge_files || build.reproducible {
+			1
+		} else {
+			rayon::current_num_threads().min(forge_files.len()).max(1)
+		};
+		log::debug!("Evaluating {} FORGE files on {} Lua states", forge_files.len(), workers);
+
+		let next = AtomicUsize::new(0);
+		let failed = AtomicBool::new(false);
+		let evaluated: Vec<EvaluatedFiles> = std::thread::scope(|scope| {
+			let handles: Vec<_> = (0..workers)
+				.map(|_| scope.spawn(|| self.evaluation_worker(&forge_files, &next, &failed)))
+				.collect();
+			handles
+				.into_iter()
+				.map(|handle| handle.join().expect("FORGE evaluation worker panicked"))
+				.collect()
+		});
+		let mut results = Vec::new();
+		let mut hooks = Vec::new();
+		for worker in evaluated {
+			results.extend(worker.rules);
+			hooks.extend(worker.hooks);
+		}
+		results.sort_by_key(|(index, _)| *index);
+		hooks.sort_by_key(|hook| hook.index);
+
+		for (_, rules) in results {
+			self.register_rules(rules?)?;
+		}
+		self.run_graph_hooks(hooks)?;
+		if self.forge_root_config.build.isolate_outputs {
+			self.isolate_outputs()?;
+		}
+		self.resolve_output_dependencies();
+
+		self.lockfile.save().context("Failed to write FORGE.lock")?;
+
+		if self.caches_evaluation() {
+			self.eval_cache.retain(&forge_files);
+			let eval_cache_path = self
+				.path
+				.join(&self.forge_root_config.build.cache_dir)
+				.join("eval_cache.json");
+			self.eval_cache
+				.save(&eval_cache_path)
+				.context("Failed to save evaluation cache")?;
+		}
 
-			if let Err(e) = self.lua.load(&content).exec() {
+		Ok(())
+	}
+
+	/// Load FORGE files until none are left or one failed, returning the rules of each by its index
+	/// The worker's Lua state is only created once a FORGE file misses the evaluation cache
+	fn evaluation_worker(&self, forge_files: &[PathBuf], next: &AtomicUsize, failed: &AtomicBool) -> EvaluatedFiles {
+		let mut evaluated = EvaluatedFiles::default();
+		while !failed.load(Ordering::Relaxed) {
+			let index = next.fetch_add(1, Ordering::Relaxed);
+			let Some(forge_file) = forge_files.get(index) else {
+				break;
+			};
+			let result = self.load_forge_file(&mut evaluated.lua, forge_file);
+			if result.is_err() {
+				failed.store(true, Ordering::Relaxed);
+			}
+			if let Some(lua) = &evaluated.lua {
+				evaluated
+					.hooks
+					.extend(lua_api::init::take_graph_hooks(lua).into_iter().map(|function| GraphHook {
+						index,
+						forge_file: forge_file.clone(),
+						lua: lua.clone(),
+						function,
+					}));
+			}
+			evaluated.rules.push((index, result));
+		}
+		evaluated
+	}
+
+	/// Call the forge.on_graph_complete callbacks in the order of the FORGE files registering them, each adding the
+	/// rules it registers to the graph before the next one runs
+	fn run_graph_hooks(&self, hooks: Vec<GraphHook>) -> Result<(), ForgeError> {
+		for hook in hooks {
+			let graph = lua_api::init::GraphView::new(self.build_graph.clone(), self.output_map.clone());
+			if let Err(e) = self.runtime.block_on(hook.function.call_async::<()>(graph)) {
 				return Err(ForgeError::LuaError {
-					file: forge_file.display().to_string(),
+					file: hook.forge_file.display().to_string(),
 					error: e,
 				});
 			}
+			self.register_rules(lua_api::init::take_registered_rules(&hook.lua))?;
 		}
+		Ok(())
+	}
 
-		self.execute_build_graph()?;
+	fn caches_evaluation(&self) -> bool {
+		let build = &self.forge_root_config.build;
+		build.cache_evaluation && build.isolate_forge_files
+	}
 
-		let cache_path = self.path.join("forge-out").join("cache.json");
-		self.cache.save(&cache_path).context("Failed to save build cache")?;
+	/// Hash of what every evaluation of a FORGE file with this content depends on besides its observations
+	fn evaluation_key(&self, content: &str) -> Result<String, ForgeError> {
+		let mut hasher = Hasher::new();
+		hasher.update(env!("CARGO_PKG_VERSION").as_bytes());
+		// Through serde_json::Value, whose maps are sorted, so global_env hashes the same on every run
+		for config in [
+			serde_json::to_value(&self.config),
+			serde_json::to_value(&self.forge_root_config),
+		] {
+			let config = config.map_err(|e| ForgeError::Other(e.into()))?;
+			hasher.update(config.to_string().as_bytes());
+		}
+		hasher.update(content.as_bytes());
+		Ok(hasher.finalize().to_hex().to_string())
+	}
+
+	/// The rules a FORGE file registers, from the evaluation cache or by evaluating it on lua
+	fn load_forge_file(&self, lua: &mut Option<Lua>, forge_file: &Path) -> Result<Vec<Rule>, ForgeError> {
+		log::debug!("Loading FORGE file: {}", forge_file.display());
+		let content = std::fs::read_to_string(forge_file)?;
+
+		if content.trim().is_empty() {
+			return Err(ForgeError::InvalidForgeFile {
+				file: forge_file.display().to_string(),
+				error: "FORGE file is empty".to_string(),
+				suggestion: "Add build rules to your FORGE file".to_string(),
+			});
+		}
+
+		if !content.contains("rule") && !content.contains("require") {
+			return Err(ForgeError::InvalidForgeFile {
+				file: forge_file.display().to_string(),
+				error: "No build rules found".to_string(),
+				suggestion: "Add at least one rule() call to define build steps".to_string(),
+			});
+		}
+
+		let key = self.evaluation_key(&content)?;
+		if self.caches_evaluation()
+			&& let Some(rules) = self.eval_cache.lookup(forge_file, &key)
+		{
+			log::debug!("Reusing {} cached rules of {}", rules.len(), forge_file.display());
+			return Ok(rules);
+		}
+
+		let lua = match lua {
+			Some(lua) => lua,
+			None => lua.insert(self.create_lua()?),
+		};
+
+		// Named after the file so Lua error messages carry "path:line:"; mlua runs chunks under a message
+		// handler that appends the stack traceback, which the diagnostic maps back to files.
+		// The chunk runs as a coroutine on the runtime so async API functions can yield while they wait
+		let mut chunk = lua.load(&content).set_name(format!("@{}", forge_file.display()));
+		if self.forge_root_config.build.isolate_forge_files {
+			chunk = chunk.set_environment(lua_api::init::forge_file_environment(lua)?);
+		}
+		if let Err(e) = self.runtime.block_on(chunk.exec_async()) {
+			return Err(ForgeError::LuaError {
+				file: forge_file.display().to_string(),
+				error: e,
+			});
+		}
+
+		let observed = lua_api::observations::take(lua);
+		let modules = observed.modules();
+		let mut rules = lua_api::init::take_registered_rules(lua);
+		for (name, action) in lua_api::action::take_registered_actions(lua) {
+			self.actions.insert(name, action);
+		}
+		for rule in &mut rules {
+			rule.modules.extend(modules.iter().cloned());
+			rule.modules.sort();
+			rule.modules.dedup();
+		}
+
+		if self.caches_evaluation() {
+			if observed.volatile {
+				log::debug!(
+					"Not caching {}: it read state of the host that cannot be checked",
+					forge_file.display()
+				);
+				self.eval_cache.remove(forge_file);
+			} else {
+				self.eval_cache.store(
+					forge_file,
+					EvalCacheEntry {
+						key,
+						observations: observed.observations,
+						rules: rules.clone(),
+					},
+				);
+			}
+		}
+		Ok(rules)
+	}
+
+	/// Add rules to the build graph, warning about names and outputs already registered, and write the scripts of
+	/// script rules, which rules reused from the evaluation cache may not find anymore
+	fn register_rules(&self, rules: Vec<Rule>) -> Result<(), ForgeError> {
+		for mut rule in rules {
+			if rule.install.is_some() {
+				self.installs.insert(rule.name.clone(), rule);
+				continue;
+			}
+			self.write_script(&rule)?;
+			toolchains::apply(&self.toolchains, &mut rule.env);
+			if let Some(toolchain) = &self.rust_toolchain
+				&& rust_toolchain::is_rustup_proxy(&rule.command)
+			{
+				rule.env
+					.entry("RUSTUP_TOOLCHAIN".to_string())
+					.or_insert_with(|| toolchain.channel.clone());
+			}
 
+			for output in &rule.outputs {
+				if let Some(previous) = self.output_map.insert(output.clone(), rule.name.clone())
+					&& previous != rule.name
+				{
+					Diagnostic::warning(format!(
+						"output '{}' is produced by both rule '{}' and rule '{}'",
+						output, previous, rule.name
+					))
+					.with_help("Each output should come from a single rule; the last definition wins.")
+					.emit();
+				}
+			}
+
+			if self.build_graph.contains_key(&rule.name) {
+				Diagnostic::warning(format!("rule '{}' is defined more than once", rule.name))
+					.with_help("Rule names must be unique; the last definition replaces the earlier ones.")
+					.emit();
+			}
+			self.build_graph.insert(rule.name.clone(), rule);
+		}
 		Ok(())
 	}
 
+	/// Write the script of a script rule where script_path puts it, unless a rule with the same script already did
+	fn write_script(&self, rule: &Rule) -> Result<(), ForgeError> {
+		if let Some(script) = &rule.script {
+			let path = self.path.join(script_path(&self.forge_root_config.build.cache_dir, script));
+			if !path.exists() {
+				if let Some(parent) = path.parent() {
+					std::fs::create_dir_all(parent)?;
+				}
+				std::fs::write(&path, script)
+					.with_context(|| format!("Failed to write the script of rule '{}'", rule.name))?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Move every relative output outside the cache directory under out_dir, rewriting the rules and installs that
+	/// name it (see isolate_rule)
+	fn isolate_outputs(&self) -> Result<(), ForgeError> {
+		let cache_dir = self.forge_root_config.build.cache_dir.trim_end_matches('/');
+		let Ok(out_dir) = self.out_dir.strip_prefix(&self.path) else {
+			return Ok(());
+		};
+		let out_dir = out_dir.to_string_lossy().replace(std::path::MAIN_SEPARATOR, "/");
+		let moved: HashMap<String, String> = self
+			.output_map
+			.iter()
+			.map(|entry| entry.key().clone())
+			.filter(|output| {
+				!Path::new(output).is_absolute() && output != cache_dir && !output.starts_with(&format!("{}/", cache_dir))
+			})
+			.map(|output| {
+				let isolated = format!("{}/{}", out_dir, output.trim_start_matches("./"));
+				(output, isolated)
+			})
+			.collect();
+		if moved.is_empty() {
+			return Ok(());
+		}
+		log::debug!("Moving {} outputs under {}", moved.len(), out_dir);
+
+		for mut rule in self.build_graph.iter_mut().chain(self.installs.iter_mut()) {
+			let rule = rule.value_mut();
+			isolate_rule(rule, &moved, &self.path, cache_dir);
+			self.write_script(rule)?;
+		}
+		for (output, isolated) in moved {
+			if let Some((_, producer)) = self.output_map.remove(&output) {
+				self.output_map.insert(isolated, producer);
+			}
+		}
+		Ok(())
+	}
+
+	/// Replace the dependencies naming an output rather than a rule by the rule producing it, so FORGE files can
+	/// depend on what prelude helpers build without knowing the names they give their rules
+	fn resolve_output_dependencies(&self) {
+		// Collected first: looking rules up while iter_mut holds a shard of the graph would deadlock
+		let resolved: Vec<(String, Vec<String>)> = self
+			.build_graph
+			.iter()
+			.filter_map(|rule| {
+				let mut changed = false;
+				let dependencies: Vec<String> = rule
+					.dependencies
+					.iter()
+					.map(|dependency| {
+						if self.build_graph.contains_key(dependency) {
+							return dependency.clone();
+						}
+						match self.producer_of(dependency) {
+							Some(producer) => {
+								changed = true;
+								producer
+							}
+							None => dependency.clone(),
+						}
+					})
+					.collect();
+				changed.then(|| (rule.key().clone(), dependencies))
+			})
+			.collect();
+		for (name, dependencies) in resolved {
+			if let Some(mut rule) = self.build_graph.get_mut(&name) {
+				rule.dependencies = dependencies;
+			}
+		}
+	}
+
+	/// The rule whose outputs include output, given relative to the project root, with a leading ./, or absolute
+	pub fn producer_of(&self, output: &str) -> Option<String> {
+		let relative = match Path::new(output).strip_prefix(&self.path) {
+			Ok(relative) => relative.to_string_lossy().replace(std::path::MAIN_SEPARATOR, "/"),
+			Err(_) => output.trim_start_matches("./").to_string(),
+		};
+		[output, relative.as_str()]
+			.into_iter()
+			.find_map(|candidate| self.output_map.get(candidate).map(|producer| producer.value().clone()))
+	}
+
 	fn find_forge_files(&self, path: &Path) -> Result<Vec<PathBuf>, ForgeError> {
 		let mut forge_files = Vec::new();
 		let discovery_config = &self.forge_root_config.discovery;
@@ -161,7 +1185,8 @@ impl Project {
 			return Err(ForgeError::NoForgeFilesFound { searched_paths });
 		}
 
-		forge_files.sort();
+		// Parents before their subdirectories and siblings by name, whatever order the filesystem lists them in
+		forge_files.sort_by(|a, b| a.parent().cmp(&b.parent()).then_with(|| a.cmp(b)));
 		forge_files.dedup();
 
 		Ok(forge_files)
@@ -226,7 +1251,8 @@ impl Project {
 	}
 
 	fn is_path_excluded(&self, path: &Path, config: &crate::forge_root_config::DiscoveryConfig) -> bool {
-		let path_str = path.to_string_lossy();
+		// Patterns are written with "/" whatever the platform
+		let path_str = path.to_string_lossy().replace(std::path::MAIN_SEPARATOR, "/");
 
 		if path_str.contains(&self.forge_root_config.build.cache_dir) {
 			return true;
@@ -311,10 +1337,24 @@ impl Project {
 		for arg in &rule.args {
 			hasher.update(arg.as_bytes());
 		}
+		if let Some(script) = &rule.script {
+			hasher.update(script.as_bytes());
+		}
 		for (key, val) in &rule.env {
 			hasher.update(key.as_bytes());
 			hasher.update(val.as_bytes());
 		}
+		// A channel like "stable" moves to new releases, which must rebuild what the old one built
+		if rust_toolchain::is_rustup_proxy(&rule.command) {
+			let version = self
+				.rust_version
+				.get_or_init(|| rust_toolchain::rustc_version(self.rust_toolchain.as_ref()));
+			hasher.update(version.as_deref().unwrap_or_default().as_bytes());
+		}
+		// Editing a prelude module can change the rules generated through it, so their hashes follow its content
+		for module in &rule.modules {
+			hasher.update(self.module_hash(module).as_bytes());
+		}
 
 		let input_hashes: Result<Vec<String>, ForgeError> = rule
 			.inputs
@@ -363,6 +1403,95 @@ impl Project {
 		Ok(hasher.finalize().to_hex().to_string())
 	}
 
+	/// Run a rule whose command is a forge.action callback, logging it like a command
+	fn run_action(&self, rule: &Rule, action: &str) -> Result<(), ForgeError> {
+		self.build_log.record(LogEvent::new("rule_started").rule(&rule.name));
+		let rule_start = Instant::now();
+		let result = match self.actions.get(action) {
+			Some(action) => lua_api::action::run(action.value(), rule).map_err(|e| e.to_string()),
+			None => Err("its forge.action callback was not loaded in this build".to_string()),
+		};
+		self.build_log.record(
+			LogEvent::new(if result.is_ok() { "rule_finished" } else { "rule_failed" })
+				.rule(&rule.name)
+				.duration_ms(rule_start.elapsed().as_millis()),
+		);
+		result.map_err(|error| ForgeError::BuildFailed {
+			rule: rule.name.clone(),
+			error,
+		})
+	}
+
+	/// Where rule's command runs: on the [build.remote] server or [build.remote_hosts] when the rule or all_rules
+	/// asks for it, locally otherwise
+	fn executor(&self, rule: &Rule) -> Result<&dyn Executor, ForgeError> {
+		if !rule.remote.unwrap_or(self.forge_root_config.build.remote.all_rules) {
+			return Ok(match &self.tracing_executor {
+				Some(tracing) => tracing as &dyn Executor,
+				None => &LocalExecutor,
+			});
+		}
+		match &self.remote_executor {
+			Some(remote) => Ok(remote.as_ref()),
+			None => Err(ForgeError::BuildFailed {
+				rule: rule.name.clone(),
+				error: "remote = true, but FORGE_ROOT has neither a [build.remote] endpoint nor [build.remote_hosts]"
+					.to_string(),
+			}),
+		}
+	}
+
+	/// Send the rule's args to a persistent worker running its command, starting one if none is idle
+	fn run_worker(&self, rule: &Rule) -> Result<(), ForgeError> {
+		let arguments: Vec<String> = self
+			.expand_args(&rule.args)?
+			.into_iter()
+			.map(|argument| argument.into_owned())
+			.collect();
+
+		self.build_log.record(LogEvent::new("rule_started").rule(&rule.name));
+		let rule_start = Instant::now();
+		let result = self
+			.workers
+			.run(&rule.command, &arguments, &rule.inputs, &rule.workdir, &rule.env);
+		let exit_code = result.as_ref().ok().map(|response| response.exit_code);
+		self.build_log.record(
+			LogEvent::new(if exit_code == Some(0) {
+				"rule_finished"
+			} else {
+				"rule_failed"
+			})
+			.rule(&rule.name)
+			.exit_code(exit_code)
+			.duration_ms(rule_start.elapsed().as_millis()),
+		);
+
+		let response = result.map_err(|e| ForgeError::BuildFailed {
+			rule: rule.name.clone(),
+			error: format!("persistent worker failed: {}", e),
+		})?;
+		self.build_log.save_rule_output(&rule.name, response.output.as_bytes(), &[]);
+		if response.exit_code != 0 {
+			replay_rule_output(&rule.name, "failed", response.output.as_bytes(), &[]);
+			return Err(ForgeError::BuildFailed {
+				rule: rule.name.clone(),
+				error: format!("worker request exited with code {}", response.exit_code),
+			});
+		}
+		if self.config.output_mode() == OutputMode::Verbose {
+			replay_rule_output(&rule.name, "finished", response.output.as_bytes(), &[]);
+		}
+		Ok(())
+	}
+
+	/// Hash of a Lua module's content, read once per build however many rules came from it
+	fn module_hash(&self, module: &Path) -> blake3::Hash {
+		*self
+			.module_hashes
+			.entry(module.to_path_buf())
+			.or_insert_with(|| blake3::hash(&std::fs::read(module).unwrap_or_default()))
+	}
+
 	fn expand_args<'a>(&'a self, args: &'a [String]) -> Result<Vec<Cow<'a, str>>, ForgeError> {
 		let mut final_args = Vec::new();
 		for arg in args {
@@ -390,20 +1519,64 @@ impl Project {
 	}
 
 	fn execute_build_graph(&self) -> Result<(), ForgeError> {
+		self.check_pools()?;
 		let batches = self.create_parallel_batches()?;
 		let total_rules: usize = batches.iter().map(|batch| batch.len()).sum();
 		let mut completed_rules = 0;
+		let mut summary = BuildSummary {
+			total: total_rules,
+			..Default::default()
+		};
 		let start_time = Instant::now();
+		self.build_log
+			.record(LogEvent::new("build_started").message(format!("{} rules", total_rules)));
 
 		for (i, batch) in batches.iter().enumerate() {
+			if interrupt::interrupted() {
+				summary.skipped = total_rules - completed_rules;
+				summary.seconds = start_time.elapsed().as_secs_f64();
+				self.report_summary(&summary);
+				return Err(ForgeError::Interrupted);
+			}
 			let batch_start = Instant::now();
 			log::info!("\nExecuting batch {}/{}: {:?}", i + 1, batches.len(), batch);
 
-			let results: Vec<Result<(), ForgeError>> =
-				batch.par_iter().map(|rule_name| self.execute_rule(rule_name)).collect();
+			let results: Vec<(Result<RuleOutcome, ForgeError>, Duration)> = batch
+				.par_iter()
+				.map(|rule_name| {
+					let rule_start = Instant::now();
+					(self.execute_rule(rule_name), rule_start.elapsed())
+				})
+				.collect();
+
+			let mut first_error = None;
+			let mut stopped = 0;
+			for (rule_name, (result, elapsed)) in batch.iter().zip(results) {
+				match result {
+					Ok(outcome) => summary.record(rule_name, outcome, elapsed),
+					Err(ForgeError::Interrupted) => {
+						stopped += 1;
+						first_error.get_or_insert(ForgeError::Interrupted);
+					}
+					Err(e) => {
+						summary.failed += 1;
+						summary.failures.push(RuleFailure {
+							rule: rule_name.clone(),
+							error: self
+								.build_log
+								.error_line(rule_name)
+								.unwrap_or_else(|| e.to_string().lines().next().unwrap_or_default().to_string()),
+						});
+						first_error.get_or_insert(e);
+					}
+				}
+			}
 
-			for result in results {
-				result?;
+			if let Some(e) = first_error {
+				summary.skipped = total_rules - completed_rules - batch.len() + stopped;
+				summary.seconds = start_time.elapsed().as_secs_f64();
+				self.report_summary(&summary);
+				return Err(e);
 			}
 
 			completed_rules += batch.len();
@@ -434,16 +1607,72 @@ impl Project {
 			total_rules,
 			batches.len()
 		);
+		summary.seconds = total_elapsed.as_secs_f64();
+		self.report_summary(&summary);
+
+		Ok(())
+	}
 
+	/// Fail before running anything if a rule names a pool FORGE_ROOT does not define
+	fn check_pools(&self) -> Result<(), ForgeError> {
+		for rule in self.build_graph.iter() {
+			if let Some(pool) = &rule.pool
+				&& !self.pools.contains(pool)
+			{
+				return Err(ForgeError::BuildFailed {
+					rule: rule.name.clone(),
+					error: format!("pool '{}' is not defined in [build.pools] of FORGE_ROOT", pool),
+				});
+			}
+		}
 		Ok(())
 	}
 
-	fn execute_rule<'a>(&'a self, rule_name: &'a str) -> Result<(), ForgeError> {
+	fn report_summary(&self, summary: &BuildSummary) {
+		debug_assert_eq!(
+			summary.total,
+			summary.cached() + summary.built + summary.failed + summary.skipped,
+			"every rule is counted once"
+		);
+		if self.config.output_mode() != OutputMode::Quiet {
+			println!("{}", summary);
+		}
+		let event = if interrupt::interrupted() {
+			"build_interrupted"
+		} else if summary.failed > 0 {
+			"build_failed"
+		} else {
+			"build_finished"
+		};
+		self.build_log.record(LogEvent::new(event).message(summary.counts()));
+		log::info!("Build log written to {}", self.build_log.path().display());
+
+		let summary_path = self.path.join(&self.forge_root_config.build.cache_dir).join("summary.json");
+		let written = serde_json::to_string_pretty(summary)
+			.map_err(std::io::Error::other)
+			.and_then(|json| std::fs::write(&summary_path, json));
+		if let Err(e) = written {
+			log::warn!("Failed to write build summary to {}: {}", summary_path.display(), e);
+		}
+
+		metrics::push(
+			&UserConfig::get().metrics,
+			&self.forge_root_config.project.name,
+			summary,
+			summary.failed == 0 && !interrupt::interrupted(),
+		);
+	}
+
+	fn execute_rule<'a>(&'a self, rule_name: &'a str) -> Result<RuleOutcome, ForgeError> {
+		if interrupt::interrupted() {
+			return Err(ForgeError::Interrupted);
+		}
 		let rule_ref = self.build_graph.get(rule_name).unwrap();
 		let (should_build, new_hash_opt) = self.needs_rebuild(rule_ref.value())?;
 
 		if !should_build {
-			return Ok(());
+			self.build_log.record(LogEvent::new("rule_up_to_date").rule(rule_name));
+			return Ok(RuleOutcome::UpToDate);
 		}
 		let new_hash = new_hash_opt.ok_or_else(|| {
 			ForgeError::Other(anyhow::anyhow!(
@@ -454,15 +1683,26 @@ impl Project {
 
 		let artifact_path = self.cas_path.join(&new_hash);
 
-		if artifact_path.exists() {
+		if artifact_path.exists() && self.compatible_artifact(rule_name, &artifact_path) {
 			log::info!("Restoring rule '{}' outputs from cache", rule_name);
 
-			let is_compressed = if let Some(metadata) = self.cache.artifact_metadata.get(rule_name) {
-				metadata.compressed
-			} else {
-				false
+			let metadata = self.cache.artifact_metadata.get(rule_name).map(|m| m.value().clone());
+			let is_compressed = metadata.as_ref().is_some_and(|metadata| metadata.compressed);
+			// What the artifact holds, when the rule's metadata describes this artifact rather than another build
+			let stored_hashes = metadata
+				.filter(|metadata| metadata.artifact == new_hash)
+				.map(|metadata| metadata.output_hashes)
+				.unwrap_or_default();
+
+			let marker = RestoreMarker {
+				rule: rule_name.to_string(),
+				outputs: rule_ref.value().outputs.clone(),
 			};
+			let marker_path = marker
+				.write(&self.restore_marker_path)
+				.context("Failed to write restore marker")?;
 
+			let mut bytes = 0;
 			for output_rel_path in &rule_ref.value().outputs {
 				let output_filename = Path::new(output_rel_path)
 					.file_name()
@@ -475,14 +1715,28 @@ impl Project {
 					std::fs::create_dir_all(parent)?;
 				}
 
+				let src_path = if is_compressed {
+					artifact_path.join(&output_filename).with_extension("lz4")
+				} else {
+					artifact_path.join(&output_filename)
+				};
+				if is_compressed && !src_path.exists() {
+					continue;
+				}
+
+				if dest_path.is_file()
+					&& let Some(stored_hash) = stored_hashes.get(output_rel_path)
+					&& *stored_hash == self.hash_file_contents(&dest_path, false)?
+				{
+					log::debug!("Output '{}' already matches the cached artifact, keeping it", output_rel_path);
+					continue;
+				}
+
+				let staging_path = dest_path.with_file_name(format!(".{}.forge-restore", output_filename));
 				if is_compressed {
-					let compressed_path = artifact_path.join(&output_filename).with_extension("lz4");
-					if compressed_path.exists() {
-						self.decompress_file(&compressed_path, &dest_path)?;
-					}
+					self.decompress_file(&src_path, &staging_path)?;
 				} else {
-					let src_path = artifact_path.join(&output_filename);
-					std::fs::copy(&src_path, &dest_path).with_context(|| {
+					std::fs::copy(&src_path, &staging_path).with_context(|| {
 						format!(
 							"Failed to copy cached artifact from {} to {}",
 							src_path.display(),
@@ -490,11 +1744,16 @@ impl Project {
 						)
 					})?;
 				}
+				std::fs::rename(&staging_path, &dest_path)?;
+				bytes += std::fs::metadata(&dest_path).map_or(0, |metadata| metadata.len());
 			}
 			self.cache.rule_hashes.insert(rule_name.to_string(), new_hash);
-			return Ok(());
+			std::fs::remove_file(&marker_path)?;
+			self.build_log.record(LogEvent::new("rule_restored").rule(rule_name));
+			return Ok(RuleOutcome::Restored { bytes });
 		}
 
+		let _pool_slot = rule_ref.value().pool.as_deref().and_then(|pool| self.pools.acquire(pool));
 		log::info!("Running rule: '{}'", rule_name);
 
 		for output in &rule_ref.value().outputs {
@@ -503,38 +1762,117 @@ impl Project {
 			}
 		}
 
-		let final_args = self.expand_args(&rule_ref.value().args)?;
-		let mut cmd = std::process::Command::new(&rule_ref.value().command);
+		let mut audit_entry = None;
+		if let Some(action) = &rule_ref.value().action {
+			self.run_action(rule_ref.value(), action)?;
+		} else if rule_ref.value().worker {
+			self.run_worker(rule_ref.value())?;
+		} else {
+			let mut final_args: Vec<String> = self
+				.expand_args(&rule_ref.value().args)?
+				.into_iter()
+				.map(|argument| argument.into_owned())
+				.collect();
+			let executor = self.executor(rule_ref.value())?;
+			// Only local commands read the file from this machine
+			if let Some(format) = rule_ref.value().rsp_format
+				&& executor.name() == "local"
+				&& rsp::exceeds_limit(&rule_ref.value().command, &final_args, &rule_ref.value().env)
+			{
+				final_args = vec![self.write_response_file(rule_name, format, &final_args)?];
+			}
 
-		let args_refs: Vec<&str> = final_args.iter().map(|cow| cow.as_ref()).collect();
-		cmd.args(&args_refs)
-			.envs(&rule_ref.value().env)
-			.current_dir(&rule_ref.value().workdir);
+			log::debug!(
+				"Executing command: {:?} {:?} (workdir: {:?}, on {})",
+				rule_ref.value().command,
+				final_args,
+				rule_ref.value().workdir,
+				executor.name()
+			);
 
-		log::debug!(
-			"Executing command: {:?} {:?} (workdir: {:?})",
-			cmd.get_program(),
-			cmd.get_args().collect::<Vec<_>>(),
-			rule_ref.value().workdir
-		);
+			let inputs = self.input_hashes(rule_ref.value());
+			self.build_log.record(LogEvent::new("rule_started").rule(rule_name));
+			let rule_start = Instant::now();
+			let output = executor.execute(rule_ref.value(), &final_args)?;
+			let duration_ms = rule_start.elapsed().as_millis();
+			self.build_log.save_rule_output(rule_name, &output.stdout, &output.stderr);
+			self.build_log.record(
+				LogEvent::new(if output.success() { "rule_finished" } else { "rule_failed" })
+					.rule(rule_name)
+					.exit_code(output.exit_code)
+					.duration_ms(duration_ms),
+			);
 
-		let output = cmd.output()?;
+			let undeclared = match &output.accesses {
+				Some(accesses) if output.success() => self.undeclared_accesses(rule_ref.value(), accesses),
+				_ => Vec::new(),
+			};
+			let enforced = !undeclared.is_empty() && self.forge_root_config.build.enforce_deps == EnforceDeps::Error;
 
-		if !output.status.success() {
-			let stderr = String::from_utf8_lossy(&output.stderr);
-			let stdout = String::from_utf8_lossy(&output.stdout);
-			return Err(ForgeError::BuildFailed {
+			let entry = AuditEntry {
+				time: chrono::Local::now().to_rfc3339(),
 				rule: rule_name.to_string(),
-				error: format!("STDOUT:\n{}\n\nSTDERR:\n{}", stdout, stderr),
-			});
+				executor: executor.name().to_string(),
+				command: rule_ref.value().command.clone(),
+				args: final_args,
+				cwd: rule_ref.value().workdir.display().to_string(),
+				env: audit::env_diff(&rule_ref.value().env, &self.forge_root_config.build.audit_redact),
+				duration_ms,
+				exit_code: output.exit_code,
+				inputs,
+				outputs: BTreeMap::new(),
+			};
+			// Failed commands are audited now, successful ones once their outputs are hashed below
+			if output.success() && !enforced {
+				audit_entry = Some(entry);
+			} else {
+				self.audit_log.record(&entry);
+			}
+
+			if !output.success() && interrupt::interrupted() {
+				return Err(ForgeError::Interrupted);
+			}
+			if !output.success() {
+				replay_rule_output(rule_name, "failed", &output.stdout, &output.stderr);
+				return Err(ForgeError::BuildFailed {
+					rule: rule_name.to_string(),
+					error: match output.exit_code {
+						Some(code) => format!("command exited with code {}", code),
+						None => format!("command was terminated ({})", output.status),
+					},
+				});
+			}
+			if enforced {
+				return Err(ForgeError::UndeclaredDependencies {
+					rule: rule_name.to_string(),
+					accesses: undeclared.join("\n"),
+				});
+			}
+			if !undeclared.is_empty() {
+				log::warn!(
+					"Rule '{}' accessed files it does not declare:\n{}",
+					rule_name,
+					undeclared.join("\n")
+				);
+			}
+			if self.config.output_mode() == OutputMode::Verbose {
+				replay_rule_output(rule_name, "finished", &output.stdout, &output.stderr);
+			}
 		}
 
 		std::fs::create_dir_all(&artifact_path)?;
+		let previous_metadata = self.cache.artifact_metadata.get(rule_name).map(|m| m.value().clone());
+		let previous_artifact_path = self.cache.rule_hashes.get(rule_name).map(|h| self.cas_path.join(h.value()));
+		let mut all_outputs_unchanged = previous_metadata.is_some();
+
 		let mut artifact_metadata = crate::cache::ArtifactMetadata {
 			size: 0,
 			created: std::time::SystemTime::now(),
 			compressed: false,
 			dependencies: rule_ref.value().inputs.clone(),
+			output_hashes: HashMap::new(),
+			output_mtimes: HashMap::new(),
+			artifact: new_hash.clone(),
 		};
 
 		for output_rel_path in &rule_ref.value().outputs {
@@ -550,19 +1888,82 @@ impl Project {
 			let src_metadata = std::fs::metadata(&src_path)?;
 			artifact_metadata.size += src_metadata.len();
 
-			if src_metadata.len() > 1024 * 1024 {
-				let compressed_path = dest_path.with_extension("lz4");
-				self.compress_file(&src_path, &compressed_path)?;
-				artifact_metadata.compressed = true;
+			let content_hash = self.hash_file_contents(&src_path, false)?;
+			let unchanged = previous_metadata
+				.as_ref()
+				.and_then(|m| m.output_hashes.get(output_rel_path))
+				.is_some_and(|previous_hash| *previous_hash == content_hash);
+			all_outputs_unchanged &= unchanged;
+
+			let previous_mtime = previous_metadata
+				.as_ref()
+				.and_then(|m| m.output_mtimes.get(output_rel_path).copied());
+			if unchanged && let Some(previous_mtime) = previous_mtime {
+				log::debug!(
+					"Output '{}' is identical to the previous build, preserving its mtime",
+					output_rel_path
+				);
+				std::fs::File::options()
+					.write(true)
+					.open(&src_path)?
+					.set_modified(previous_mtime)?;
+				artifact_metadata
+					.output_mtimes
+					.insert(output_rel_path.clone(), previous_mtime);
+			} else {
+				artifact_metadata
+					.output_mtimes
+					.insert(output_rel_path.clone(), src_metadata.modified()?);
+			}
+
+			let is_large = src_metadata.len() > 1024 * 1024;
+			let stored_path = if is_large {
+				dest_path.with_extension("lz4")
+			} else {
+				dest_path.clone()
+			};
+
+			let reused = unchanged
+				&& previous_artifact_path.as_ref().is_some_and(|previous| {
+					stored_path
+						.file_name()
+						.is_some_and(|name| std::fs::hard_link(previous.join(name), &stored_path).is_ok())
+				});
+
+			if reused {
+				log::debug!("Reusing cached artifact for unchanged output '{}'", output_rel_path);
+			} else if is_large {
+				self.compress_file(&src_path, &stored_path)?;
 			} else {
-				std::fs::copy(&src_path, &dest_path).with_context(|| {
+				std::fs::copy(&src_path, &stored_path).with_context(|| {
 					format!(
 						"Failed to copy artifact from {} to cache at {}",
 						src_path.display(),
-						dest_path.display()
+						stored_path.display()
 					)
 				})?;
 			}
+
+			if is_large {
+				artifact_metadata.compressed = true;
+			}
+			artifact_metadata.output_hashes.insert(output_rel_path.clone(), content_hash);
+		}
+
+		if all_outputs_unchanged && let Some(previous) = &previous_metadata {
+			artifact_metadata.created = previous.created;
+		}
+		self.fingerprint()
+			.save(&artifact_path)
+			.context("Failed to write the fingerprint of the artifact")?;
+
+		if let Some(mut entry) = audit_entry {
+			entry.outputs = artifact_metadata
+				.output_hashes
+				.iter()
+				.map(|(path, hash)| (path.clone(), hash.clone()))
+				.collect();
+			self.audit_log.record(&entry);
 		}
 
 		self.cache.artifact_metadata.insert(rule_name.to_string(), artifact_metadata);
@@ -576,7 +1977,7 @@ impl Project {
 			}
 		}
 
-		Ok(())
+		Ok(RuleOutcome::Built)
 	}
 
 	fn create_parallel_batches(&self) -> Result<Vec<Vec<String>>, ForgeError> {
@@ -768,6 +2169,46 @@ impl Project {
 		Ok(())
 	}
 
+	/// Write args to <cache_dir>/rsp/<hash of the rule name>.rsp, returning the @file argument standing for them
+	fn write_response_file(&self, rule_name: &str, format: RspFormat, args: &[String]) -> Result<String, ForgeError> {
+		let dir = self.path.join(&self.forge_root_config.build.cache_dir).join("rsp");
+		std::fs::create_dir_all(&dir)?;
+		let hash = blake3::hash(rule_name.as_bytes()).to_hex();
+		let path = dir.join(format!("{}.rsp", &hash[..16]));
+		std::fs::write(&path, format.render(args))
+			.with_context(|| format!("Failed to write the response file of rule '{}'", rule_name))?;
+		log::debug!(
+			"Passing the {} args of rule '{}' in {}",
+			args.len(),
+			rule_name,
+			path.display()
+		);
+		Ok(format!("@{}", path.display()))
+	}
+
+	/// The fingerprint of the environment this build runs in
+	pub fn fingerprint(&self) -> &Fingerprint {
+		self.fingerprint.get_or_init(|| Fingerprint::capture(&self.forge_root_config))
+	}
+
+	/// Whether the artifact at artifact_path was built in an environment whose outputs work here, warning when not;
+	/// artifacts stored before fingerprints were recorded are trusted
+	fn compatible_artifact(&self, rule_name: &str, artifact_path: &Path) -> bool {
+		let Some(artifact) = Fingerprint::load(artifact_path) else {
+			return true;
+		};
+		let incompatibilities = self.fingerprint().incompatibilities(&artifact);
+		if incompatibilities.is_empty() {
+			return true;
+		}
+		log::warn!(
+			"Not restoring rule '{}' from cache, its artifact was built in another environment ({})",
+			rule_name,
+			incompatibilities.join(", ")
+		);
+		false
+	}
+
 	fn compress_file<'a>(&'a self, src: &'a Path, dest: &'a Path) -> Result<(), ForgeError> {
 		use lz4::EncoderBuilder;
 		use std::io::Write;
@@ -780,6 +2221,73 @@ impl Project {
 		Ok(())
 	}
 
+	/// The files of the project a traced rule read without listing them in inputs, or wrote without listing them in
+	/// outputs, as "reads <path>" and "writes <path>" lines
+	fn undeclared_accesses(&self, rule: &Rule, accesses: &FileAccesses) -> Vec<String> {
+		let relative = |path: &Path| {
+			let relative = path.strip_prefix(&self.path).ok()?;
+			let relative = relative.to_string_lossy().replace(std::path::MAIN_SEPARATOR, "/");
+			(!relative.is_empty() && !relative.starts_with(".git/")).then_some(relative)
+		};
+		// Declaring a directory covers everything under it
+		let declared = |path: &str, declared: &[String]| {
+			declared.iter().any(|entry| {
+				let entry = entry.trim_start_matches("./").trim_end_matches('/');
+				path == entry || path.strip_prefix(entry).is_some_and(|rest| rest.starts_with('/'))
+			})
+		};
+
+		let mut undeclared = Vec::new();
+		for path in &accesses.reads {
+			if let Some(path) = relative(path)
+				&& !declared(&path, &rule.inputs)
+				&& !declared(&path, &rule.outputs)
+				&& !accesses
+					.writes
+					.iter()
+					.any(|written| relative(written).as_deref() == Some(path.as_str()))
+			{
+				undeclared.push(format!("  reads {}", path));
+			}
+		}
+		// Files written and removed again, like temporary files, are left out
+		for path in accesses.writes.iter().filter(|path| path.is_file()) {
+			if let Some(path) = relative(path)
+				&& !declared(&path, &rule.outputs)
+			{
+				undeclared.push(format!("  writes {}", path));
+			}
+		}
+		undeclared
+	}
+
+	/// blake3 of each input file of rule as it is now; inputs that are not files are left out
+	fn input_hashes(&self, rule: &Rule) -> BTreeMap<String, String> {
+		rule.inputs
+			.iter()
+			.filter_map(|input| {
+				let path = self.path.join(input);
+				if !path.is_file() {
+					return None;
+				}
+				self.hash_file_contents(&path, false).ok().map(|hash| (input.clone(), hash))
+			})
+			.collect()
+	}
+
+	fn hash_file_contents<'a>(&'a self, path: &'a Path, compressed: bool) -> Result<String, ForgeError> {
+		let file = std::fs::File::open(path)?;
+		let mut hasher = Hasher::new();
+		if compressed {
+			let mut decoder = lz4::Decoder::new(file)?;
+			std::io::copy(&mut decoder, &mut hasher)?;
+		} else {
+			let mut reader = std::io::BufReader::new(file);
+			std::io::copy(&mut reader, &mut hasher)?;
+		}
+		Ok(hasher.finalize().to_hex().to_string())
+	}
+
 	fn decompress_file<'a>(&'a self, src: &'a Path, dest: &'a Path) -> Result<(), ForgeError> {
 		use lz4::Decoder;
 		use std::io::Read;
@@ -792,3 +2300,173 @@ impl Project {
 		Ok(())
 	}
 }
+
+/// Print the captured output of a rule to stderr in one block, so rules running in parallel do not interleave
+fn replay_rule_output(rule_name: &str, status: &str, stdout: &[u8], stderr: &[u8]) {
+	use std::io::Write;
+
+	if stdout.is_empty() && stderr.is_empty() {
+		return;
+	}
+
+	let mut terminal = std::io::stderr().lock();
+	let _ = writeln!(terminal, "--- output of {} rule '{}' ---", status, rule_name);
+	let _ = terminal.write_all(stdout);
+	let _ = terminal.write_all(stderr);
+	if !stdout.ends_with(b"\n") && !stderr.ends_with(b"\n") {
+		let _ = writeln!(terminal);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn config() -> Config {
+		Config {
+			verbosity: crate::config::VerbosityWrapper(Default::default()),
+			target_filters: Vec::new(),
+			component_filters: Vec::new(),
+			test_mode: false,
+			offline: true,
+			profile: "debug".to_string(),
+		}
+	}
+
+	#[test]
+	fn test_unchanged_output_keeps_mtime() {
+		let root = std::env::temp_dir().join(format!("forge-project-mtime-test-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&root);
+		std::fs::create_dir_all(root.join("prelude")).unwrap();
+		std::fs::write(root.join("FORGE_ROOT"), "[project]\nname = \"mtime\"\n").unwrap();
+		std::fs::write(
+			root.join("FORGE"),
+			"forge.rule({ name = 'first', command = 'sh', args = { '-c', 'head -c 1 input.txt > first.txt' }, \
+			 inputs = { 'input.txt' }, outputs = { 'first.txt' } })\n",
+		)
+		.unwrap();
+		let modified = || std::fs::metadata(root.join("first.txt")).unwrap().modified().unwrap();
+
+		std::fs::write(root.join("input.txt"), "a1").unwrap();
+		Project::new(root.clone(), config()).unwrap().run().unwrap();
+		let built = modified();
+
+		// Rebuilt for the new input, but first.txt, which no rule reads, comes out the same
+		std::fs::write(root.join("input.txt"), "a2").unwrap();
+		Project::new(root.clone(), config()).unwrap().run().unwrap();
+		assert_eq!(std::fs::read_to_string(root.join("first.txt")).unwrap(), "a");
+		assert_eq!(modified(), built);
+
+		std::fs::remove_dir_all(&root).unwrap();
+	}
+
+	#[test]
+	fn test_evaluate_dependencies() {
+		let root = std::env::temp_dir().join(format!("forge-project-deps-test-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&root);
+		std::fs::create_dir_all(root.join("prelude")).unwrap();
+		std::fs::write(root.join("FORGE_ROOT"), "[project]\nname = \"deps\"\n").unwrap();
+		std::fs::write(
+			root.join("FORGE"),
+			"forge.rule({ name = 'a', command = 'touch', args = { 'liba.a' }, outputs = { 'liba.a' } })\n\
+			 forge.rule({ name = 'b', command = 'true', dependencies = { 'a' } })\n\
+			 forge.rule({ name = 'c', command = 'true', dependencies = { 'liba.a' } })\n",
+		)
+		.unwrap();
+
+		// On a thread, so a deadlock fails the test instead of hanging it
+		let (sender, receiver) = std::sync::mpsc::channel();
+		let project_root = root.clone();
+		std::thread::spawn(move || {
+			let mut project = Project::new(project_root, config()).unwrap();
+			project.evaluate().unwrap();
+			let _ = sender.send(project.rules());
+		});
+		let mut rules = receiver.recv_timeout(std::time::Duration::from_secs(60)).unwrap();
+		rules.sort_by(|a, b| a.name.cmp(&b.name));
+		std::fs::remove_dir_all(&root).unwrap();
+
+		let dependencies: Vec<(&str, Vec<String>)> = rules
+			.iter()
+			.map(|rule| (rule.name.as_str(), rule.dependencies.clone()))
+			.collect();
+		assert_eq!(
+			dependencies,
+			vec![("a", vec![]), ("b", vec!["a".to_string()]), ("c", vec!["a".to_string()])]
+		);
+	}
+
+	#[test]
+	fn test_isolated_outputs_install() {
+		let root = std::env::temp_dir().join(format!("forge-project-test-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&root);
+		let isolated = "forge-out/host/debug/app";
+		std::fs::create_dir_all(root.join("forge-out/host/debug")).unwrap();
+		std::fs::write(root.join(isolated), "binary").unwrap();
+		let moved = HashMap::from([("app".to_string(), isolated.to_string())]);
+		let absolute = root.join(isolated).to_string_lossy().to_string();
+
+		let script = "cc main.c -o app && strip 'app'\n";
+		let old_script = script_path("forge-out", script);
+		let mut build = Rule {
+			name: "app".to_string(),
+			command: "sh".to_string(),
+			args: vec![root.join(&old_script).to_string_lossy().to_string()],
+			env: HashMap::from([
+				("OUT".to_string(), "app".to_string()),
+				("FLAGS".to_string(), "--out=app".to_string()),
+				("APPLICATION".to_string(), "application".to_string()),
+			]),
+			inputs: vec!["main.c".to_string(), old_script],
+			outputs: vec!["app".to_string()],
+			workdir: root.clone(),
+			script: Some(script.to_string()),
+			..Default::default()
+		};
+		isolate_rule(&mut build, &moved, &root, "forge-out");
+		let new_script = format!("cc main.c -o {} && strip '{}'\n", absolute, absolute);
+		assert_eq!(build.script.as_deref(), Some(new_script.as_str()));
+		assert_eq!(
+			build.inputs,
+			vec!["main.c".to_string(), script_path("forge-out", &new_script)]
+		);
+		assert_eq!(
+			build.args,
+			vec![root.join(script_path("forge-out", &new_script)).to_string_lossy().to_string()]
+		);
+		assert_eq!(build.outputs, vec![isolated]);
+		assert_eq!(build.env["OUT"], absolute);
+		assert_eq!(build.env["FLAGS"], format!("--out={}", absolute));
+		assert_eq!(build.env["APPLICATION"], "application");
+
+		// Not declared as an input or output, so app here is a package name rather than the moved file
+		let mut package = Rule {
+			name: "package".to_string(),
+			command: "cargo".to_string(),
+			args: vec!["build".to_string(), "-p".to_string(), "app".to_string()],
+			env: HashMap::from([("PACKAGE".to_string(), "app".to_string())]),
+			workdir: root.clone(),
+			script: Some("echo app\n".to_string()),
+			..Default::default()
+		};
+		isolate_rule(&mut package, &moved, &root, "forge-out");
+		assert_eq!(package.args, vec!["build", "-p", "app"]);
+		assert_eq!(package.env["PACKAGE"], "app");
+		assert_eq!(package.script.as_deref(), Some("echo app\n"));
+
+		let mut install = Rule {
+			name: "install app".to_string(),
+			inputs: vec!["app".to_string()],
+			workdir: root.clone(),
+			install: Some("bin/".to_string()),
+			..Default::default()
+		};
+		isolate_rule(&mut install, &moved, &root, "forge-out");
+		let prefix = root.join("prefix");
+		let files = crate::install::install(&root, &[install], &prefix).unwrap();
+		assert_eq!(files.len(), 1);
+		assert_eq!(std::fs::read_to_string(prefix.join("bin/app")).unwrap(), "binary");
+
+		std::fs::remove_dir_all(&root).unwrap();
+	}
+}
@@ -0,0 +1,272 @@
+use base64::Engine;
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Deserialize, Serialize};
+use std::{
+	io::Read,
+	path::{Path, PathBuf},
+	process::{Command, Stdio},
+};
+
+/// Written to <cache_dir>/<target>/ after every successful build
+pub const MANIFEST_NAME: &str = "manifest.json";
+
+/// Every artifact a build produced for a target and how it was produced, in the spirit of an SLSA provenance record
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Manifest {
+	pub builder: Builder,
+	pub project: String,
+	pub target: String,
+	/// RFC 3339, left out of reproducible builds
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub created: Option<String>,
+	/// Sorted by path
+	pub artifacts: Vec<Artifact>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Builder {
+	pub id: String,
+	pub version: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Artifact {
+	/// Relative to the project root
+	pub path: String,
+	pub blake3: String,
+	pub size: u64,
+	/// Name of the rule that produced it
+	pub rule: String,
+	/// The rule's inputs that existed when the manifest was written, sorted by path
+	pub inputs: Vec<Material>,
+	/// None for forge.action rules, which run no command
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub tool: Option<Tool>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Material {
+	pub path: String,
+	pub blake3: String,
+}
+
+/// The command a rule ran, identified by the binary's hash
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Tool {
+	pub command: String,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub path: Option<String>,
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub blake3: Option<String>,
+	/// First line of `<tool> --version`, asked only of tools found on PATH; project scripts are identified by their
+	/// hash alone since running them could do anything
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub version: Option<String>,
+}
+
+impl Manifest {
+	pub fn new(project: &str, target: &str, reproducible: bool) -> Self {
+		Self {
+			builder: Builder {
+				id: "forge".to_string(),
+				version: env!("CARGO_PKG_VERSION").to_string(),
+			},
+			project: project.to_string(),
+			target: target.to_string(),
+			created: (!reproducible).then(|| chrono::Local::now().to_rfc3339()),
+			artifacts: Vec::new(),
+		}
+	}
+
+	/// Write the manifest to path, and its signature to path.sig when signing_key_file is set
+	pub fn write(&self, path: &Path, signing_key_file: Option<&Path>) -> anyhow::Result<()> {
+		if let Some(parent) = path.parent() {
+			std::fs::create_dir_all(parent)?;
+		}
+		let json = serde_json::to_vec_pretty(self)?;
+		std::fs::write(path, &json)?;
+
+		let signature_path = signature_path(path);
+		match signing_key_file {
+			Some(key_file) => std::fs::write(&signature_path, sign(&json, key_file)?)?,
+			None if signature_path.exists() => std::fs::remove_file(&signature_path)?,
+			None => {}
+		}
+		Ok(())
+	}
+
+	pub fn load(path: &Path) -> anyhow::Result<Self> {
+		let json = std::fs::read(path).map_err(|e| anyhow::anyhow!("Failed to read manifest {}: {}", path.display(), e))?;
+		serde_json::from_slice(&json).map_err(|e| anyhow::anyhow!("Invalid manifest {}: {}", path.display(), e))
+	}
+
+	/// How the files under project_root differ from the artifacts listed, one line per artifact that does not match
+	pub fn verify(&self, project_root: &Path) -> Vec<String> {
+		let mut problems = Vec::new();
+		for artifact in &self.artifacts {
+			match hash_file(&project_root.join(&artifact.path)) {
+				Err(e) => problems.push(format!("{}: {}", artifact.path, e)),
+				Ok((_, size)) if size != artifact.size => problems.push(format!(
+					"{}: {} bytes, the manifest lists {}",
+					artifact.path, size, artifact.size
+				)),
+				Ok((blake3, _)) if blake3 != artifact.blake3 => problems.push(format!(
+					"{}: blake3 {}, the manifest lists {}",
+					artifact.path, blake3, artifact.blake3
+				)),
+				Ok(_) => {}
+			}
+		}
+		problems
+	}
+}
+
+/// Where the signature of the manifest at path goes
+pub fn signature_path(path: &Path) -> PathBuf {
+	let mut signature = path.as_os_str().to_os_string();
+	signature.push(".sig");
+	PathBuf::from(signature)
+}
+
+/// Base64 ed25519 signature of data with the key in key_file
+fn sign(data: &[u8], key_file: &Path) -> anyhow::Result<String> {
+	let hex = std::fs::read_to_string(key_file)
+		.map_err(|e| anyhow::anyhow!("Failed to read signing key {}: {}", key_file.display(), e))?;
+	let key = signing_key(hex.trim()).map_err(|e| anyhow::anyhow!("Signing key {}: {}", key_file.display(), e))?;
+	Ok(sign_with(data, &key))
+}
+
+/// An ed25519 key from its hex-encoded 32-byte seed
+pub fn signing_key(hex: &str) -> Result<SigningKey, String> {
+	decode_hex(hex)
+		.and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+		.map(|seed| SigningKey::from_bytes(&seed))
+		.ok_or_else(|| "an ed25519 key must be 64 hex characters".to_string())
+}
+
+/// Base64 ed25519 signature of data
+pub fn sign_with(data: &[u8], key: &SigningKey) -> String {
+	base64::engine::general_purpose::STANDARD.encode(key.sign(data).to_bytes())
+}
+
+/// Hex-encoded public half of key, what verify_signature takes
+pub fn public_key(key: &SigningKey) -> String {
+	key.verifying_key().to_bytes().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Whether signature, base64 as sign_with writes it, is data signed by the key whose hex public_key is given;
+/// errors only for a malformed signature or key
+pub fn verify_signature(data: &[u8], signature: &str, public_key: &str) -> Result<bool, String> {
+	let key = decode_hex(public_key)
+		.and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+		.and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok())
+		.ok_or_else(|| "an ed25519 public key must be 64 hex characters".to_string())?;
+	let signature = base64::engine::general_purpose::STANDARD
+		.decode(signature.trim())
+		.ok()
+		.and_then(|bytes| Signature::from_slice(&bytes).ok())
+		.ok_or_else(|| "an ed25519 signature must be 64 base64-encoded bytes".to_string())?;
+	Ok(key.verify(data, &signature).is_ok())
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+	if !hex.len().is_multiple_of(2) {
+		return None;
+	}
+	(0..hex.len())
+		.step_by(2)
+		.map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+		.collect()
+}
+
+/// blake3 and size of the file at path
+pub fn hash_file(path: &Path) -> std::io::Result<(String, u64)> {
+	let mut hasher = blake3::Hasher::new();
+	let size = std::io::copy(&mut std::fs::File::open(path)?, &mut hasher)?;
+	Ok((hasher.finalize().to_hex().to_string(), size))
+}
+
+/// Identify command as run from workdir
+pub fn tool(command: &str, workdir: &Path) -> Tool {
+	let on_path = !command.contains(['/', std::path::MAIN_SEPARATOR]);
+	let path = if on_path {
+		crate::lua_api::exec::find_executable(command)
+	} else {
+		crate::lua_api::exec::find_executable(&workdir.join(command).to_string_lossy())
+	};
+
+	Tool {
+		command: command.to_string(),
+		blake3: path.as_deref().and_then(|path| hash_file(path).ok()).map(|(hash, _)| hash),
+		version: path.as_deref().filter(|_| on_path).and_then(tool_version),
+		path: path.map(|path| path.display().to_string()),
+	}
+}
+
+fn tool_version(path: &Path) -> Option<String> {
+	let mut child = Command::new(path)
+		.arg("--version")
+		.stdin(Stdio::null())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::null())
+		.spawn()
+		.ok()?;
+	let mut stdout = String::new();
+	child.stdout.take()?.read_to_string(&mut stdout).ok()?;
+	if !child.wait().ok()?.success() {
+		return None;
+	}
+	stdout
+		.lines()
+		.map(str::trim)
+		.find(|line| !line.is_empty())
+		.map(str::to_string)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_signed_manifest() {
+		let dir = std::env::temp_dir().join(format!("forge-provenance-test-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&dir);
+		std::fs::create_dir_all(&dir).unwrap();
+		let key_file = dir.join("signing.key");
+		std::fs::write(&key_file, format!("{}\n", "07".repeat(32))).unwrap();
+
+		let mut manifest = Manifest::new("demo", "linux", true);
+		manifest.artifacts.push(Artifact {
+			path: "build/demo".to_string(),
+			blake3: "ab".repeat(32),
+			size: 3,
+			rule: "link".to_string(),
+			inputs: Vec::new(),
+			tool: None,
+		});
+		let path = dir.join("linux").join(MANIFEST_NAME);
+		manifest.write(&path, Some(&key_file)).unwrap();
+
+		let json = std::fs::read(&path).unwrap();
+		let mut read = Manifest::load(&path).unwrap();
+		assert_eq!(read.created, None);
+		assert_eq!(read.artifacts[0].rule, "link");
+
+		let signature = std::fs::read_to_string(signature_path(&path)).unwrap();
+		let public = public_key(&signing_key(&"07".repeat(32)).unwrap());
+		assert_eq!(verify_signature(&json, &signature, &public), Ok(true));
+		assert_eq!(verify_signature(b"tampered", &signature, &public), Ok(false));
+		assert!(verify_signature(&json, "not base64", &public).is_err());
+		assert!(signing_key("07").is_err());
+
+		assert_eq!(read.verify(&dir).len(), 1);
+		std::fs::create_dir_all(dir.join("build")).unwrap();
+		std::fs::write(dir.join("build/demo"), "abc").unwrap();
+		read.artifacts[0].blake3 = hash_file(&dir.join("build/demo")).unwrap().0;
+		assert!(read.verify(&dir).is_empty());
+
+		manifest.write(&path, None).unwrap();
+		assert!(!signature_path(&path).exists());
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}
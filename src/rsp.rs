@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Longest single argument Linux accepts (MAX_ARG_STRLEN), whatever room is left in ARG_MAX
+#[cfg(unix)]
+const MAX_ARG_LEN: usize = 32 * 4096;
+
+/// Room kept free under the limit for what the estimate leaves out, like the auxiliary vector
+#[cfg(unix)]
+const HEADROOM: usize = 4096;
+
+/// How a tool reads the @file it is given in place of its arguments
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RspFormat {
+	/// GCC, Clang and binutils: whitespace separates arguments, quotes group them and a backslash escapes any
+	/// character
+	Gcc,
+	/// MSVC's cl and link: arguments are quoted like a Windows command line
+	Msvc,
+}
+
+impl RspFormat {
+	pub fn parse(name: &str) -> Option<Self> {
+		match name {
+			"gcc" => Some(Self::Gcc),
+			"msvc" => Some(Self::Msvc),
+			_ => None,
+		}
+	}
+
+	/// The content of a response file holding args, one per line
+	pub fn render(self, args: &[String]) -> String {
+		let mut content = String::new();
+		for arg in args {
+			match self {
+				Self::Gcc => content.push_str(&quote_gcc(arg)),
+				Self::Msvc => content.push_str(&quote_msvc(arg)),
+			}
+			content.push('\n');
+		}
+		content
+	}
+}
+
+/// Whether starting command with args would fail with E2BIG, the environment counting towards the limit as well
+#[cfg(unix)]
+pub fn exceeds_limit(command: &str, args: &[String], env: &HashMap<String, String>) -> bool {
+	if args.iter().any(|arg| arg.len() >= MAX_ARG_LEN) {
+		return true;
+	}
+	// SAFETY: sysconf has no memory safety requirements
+	let limit = match unsafe { libc::sysconf(libc::_SC_ARG_MAX) } {
+		max if max > 0 => max as usize,
+		_ => MAX_ARG_LEN,
+	};
+	let pointer = std::mem::size_of::<usize>();
+	let size: usize = std::iter::once(command.len())
+		.chain(args.iter().map(String::len))
+		.chain(std::env::vars_os().map(|(key, value)| key.len() + value.len() + 1))
+		.chain(env.iter().map(|(key, value)| key.len() + value.len() + 1))
+		.map(|len| len + 1 + pointer)
+		.sum();
+	size + HEADROOM > limit
+}
+
+/// Whether the command line of command with args is longer than the 32767 characters Windows allows
+#[cfg(not(unix))]
+pub fn exceeds_limit(command: &str, args: &[String], _env: &HashMap<String, String>) -> bool {
+	let length: usize = std::iter::once(command.len())
+		.chain(args.iter().map(|arg| quote_msvc(arg).len()))
+		.map(|len| len + 1)
+		.sum();
+	length > 32767
+}
+
+fn quote_gcc(arg: &str) -> String {
+	if !arg.is_empty() && !arg.chars().any(|c| c.is_whitespace() || matches!(c, '"' | '\'' | '\\')) {
+		return arg.to_string();
+	}
+	let mut quoted = String::from("\"");
+	for c in arg.chars() {
+		if matches!(c, '"' | '\\') {
+			quoted.push('\\');
+		}
+		quoted.push(c);
+	}
+	quoted.push('"');
+	quoted
+}
+
+/// Quote arg the way CommandLineToArgvW splits it back: backslashes only escape when they come before a quote
+fn quote_msvc(arg: &str) -> String {
+	if !arg.is_empty() && !arg.chars().any(|c| matches!(c, ' ' | '\t' | '\n' | '"')) {
+		return arg.to_string();
+	}
+	let mut quoted = String::from("\"");
+	let mut backslashes = 0;
+	for c in arg.chars() {
+		match c {
+			'\\' => backslashes += 1,
+			'"' => {
+				quoted.push_str(&"\\".repeat(backslashes * 2 + 1));
+				backslashes = 0;
+			}
+			_ => {
+				quoted.push_str(&"\\".repeat(backslashes));
+				backslashes = 0;
+			}
+		}
+		if c != '\\' {
+			quoted.push(c);
+		}
+	}
+	quoted.push_str(&"\\".repeat(backslashes * 2));
+	quoted.push('"');
+	quoted
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_response_file_quoting() {
+		let args = [
+			"-o".to_string(),
+			"out dir/app".to_string(),
+			r#"-DNAME="x""#.to_string(),
+			r"C:\lib\".to_string(),
+			String::new(),
+		];
+		assert_eq!(
+			RspFormat::Gcc.render(&args),
+			"-o\n\"out dir/app\"\n\"-DNAME=\\\"x\\\"\"\n\"C:\\\\lib\\\\\"\n\"\"\n"
+		);
+		assert_eq!(
+			RspFormat::Msvc.render(&args),
+			"-o\n\"out dir/app\"\n\"-DNAME=\\\"x\\\"\"\nC:\\lib\\\n\"\"\n"
+		);
+		assert_eq!(quote_msvc(r"a b\"), r#""a b\\""#);
+
+		assert!(!exceeds_limit(
+			"cc",
+			&["-c".to_string(), "main.c".to_string()],
+			&HashMap::new()
+		));
+		assert!(exceeds_limit("ld", &vec!["x".repeat(1000); 40_000], &HashMap::new()));
+	}
+}
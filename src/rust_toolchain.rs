@@ -0,0 +1,120 @@
+use serde::{Deserialize, Serialize};
+use std::{path::Path, process::Command};
+
+/// Where rustup looks for the toolchain a project pins, in order; the second is the legacy plain-text form
+const TOOLCHAIN_FILES: [&str; 2] = ["rust-toolchain.toml", "rust-toolchain"];
+
+/// rustup proxies, which run the toolchain RUSTUP_TOOLCHAIN names
+const RUSTUP_PROXIES: [&str; 8] = [
+	"cargo",
+	"rustc",
+	"rustdoc",
+	"rustfmt",
+	"cargo-fmt",
+	"cargo-clippy",
+	"clippy-driver",
+	"rust-analyzer",
+];
+
+/// The toolchain rust-toolchain.toml pins
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RustToolchain {
+	pub channel: String,
+	#[serde(default)]
+	pub components: Vec<String>,
+	#[serde(default)]
+	pub targets: Vec<String>,
+	pub profile: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ToolchainFile {
+	toolchain: RustToolchain,
+}
+
+impl RustToolchain {
+	/// The toolchain pinned at the project root, None when it pins none
+	pub fn detect(project_root: &Path) -> anyhow::Result<Option<Self>> {
+		for name in TOOLCHAIN_FILES {
+			let path = project_root.join(name);
+			let Ok(content) = std::fs::read_to_string(&path) else {
+				continue;
+			};
+			return Self::parse(&content)
+				.map(Some)
+				.map_err(|e| anyhow::anyhow!("Invalid {}: {}", path.display(), e));
+		}
+		Ok(None)
+	}
+
+	/// A rust-toolchain.toml, or a legacy rust-toolchain file holding only the channel
+	fn parse(content: &str) -> Result<Self, String> {
+		let trimmed = content.trim();
+		if !trimmed.is_empty() && !trimmed.contains(['\n', '=', '[']) {
+			return Ok(Self {
+				channel: trimmed.to_string(),
+				components: Vec::new(),
+				targets: Vec::new(),
+				profile: None,
+			});
+		}
+		toml::from_str::<ToolchainFile>(content)
+			.map(|file| file.toolchain)
+			.map_err(|e| e.to_string())
+	}
+}
+
+/// Whether command is a rustup proxy, so the pinned toolchain applies to it
+pub fn is_rustup_proxy(command: &str) -> bool {
+	Path::new(command)
+		.file_stem()
+		.and_then(|stem| stem.to_str())
+		.is_some_and(|stem| RUSTUP_PROXIES.contains(&stem))
+}
+
+/// rustc's release and commit under toolchain, or the default toolchain, like "1.79.0 129f3b9964af4d4a709d1383930ade12dfe7c081"
+pub fn rustc_version(toolchain: Option<&RustToolchain>) -> Option<String> {
+	let mut command = Command::new(std::env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string()));
+	if let Some(toolchain) = toolchain {
+		command.env("RUSTUP_TOOLCHAIN", &toolchain.channel);
+	}
+	let output = command.arg("-vV").output().ok().filter(|output| output.status.success())?;
+	let stdout = String::from_utf8_lossy(&output.stdout);
+	let field = |name: &str| {
+		stdout
+			.lines()
+			.find_map(|line| line.strip_prefix(name))
+			.map(|value| value.trim().to_string())
+	};
+	let release = field("release:")?;
+	Some(match field("commit-hash:") {
+		Some(commit) => format!("{} {}", release, commit),
+		None => release,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_parse_toolchain_file() {
+		let toolchain = RustToolchain::parse(
+			"[toolchain]\nchannel = \"1.79.0\"\ncomponents = [\"clippy\"]\ntargets = [\"wasm32-unknown-unknown\"]\n",
+		)
+		.unwrap();
+		assert_eq!(toolchain.channel, "1.79.0");
+		assert_eq!(toolchain.components, ["clippy"]);
+		assert_eq!(toolchain.targets, ["wasm32-unknown-unknown"]);
+
+		assert_eq!(
+			RustToolchain::parse("nightly-2024-05-01\n").unwrap().channel,
+			"nightly-2024-05-01"
+		);
+		assert!(RustToolchain::parse("[toolchain]\ncomponents = []\n").is_err());
+
+		assert!(is_rustup_proxy("cargo"));
+		assert!(is_rustup_proxy("/home/me/.cargo/bin/rustc.exe"));
+		assert!(!is_rustup_proxy("cc"));
+	}
+}
@@ -0,0 +1,307 @@
+use crate::project::Rule;
+use serde_json::{Value, json};
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Software bill of materials formats forge sbom writes, both as JSON
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+pub enum SbomFormat {
+	/// SPDX 2.3
+	Spdx,
+	/// CycloneDX 1.5
+	Cyclonedx,
+}
+
+impl SbomFormat {
+	pub fn default_file_name(self) -> &'static str {
+		match self {
+			SbomFormat::Spdx => "sbom.spdx.json",
+			SbomFormat::Cyclonedx => "sbom.cdx.json",
+		}
+	}
+
+	pub fn render(self, sbom: &Sbom) -> String {
+		let document = match self {
+			SbomFormat::Spdx => to_spdx(sbom),
+			SbomFormat::Cyclonedx => to_cyclonedx(sbom),
+		};
+		serde_json::to_string_pretty(&document).unwrap_or_default()
+	}
+}
+
+/// The project and what it is made of: rules that set a version and the downloads of FORGE.lock
+#[derive(Debug)]
+pub struct Sbom {
+	pub name: String,
+	pub version: String,
+	/// Sorted by id
+	pub packages: Vec<Package>,
+	/// Ids of the packages nothing else in the project depends on, which the project itself depends on
+	pub roots: Vec<String>,
+}
+
+#[derive(Debug)]
+pub struct Package {
+	/// Rule name or download URL
+	pub id: String,
+	pub name: String,
+	pub version: Option<String>,
+	pub download: Option<String>,
+	pub blake3: Option<String>,
+	/// Ids of the packages this one is built from, sorted
+	pub depends_on: Vec<String>,
+}
+
+impl Sbom {
+	/// Rules with a version become packages depending on the nearest versioned rules they are built from, looking
+	/// through unversioned rules via dependencies and the producers of inputs; downloads are (url, blake3) pairs
+	pub fn new(name: &str, version: &str, rules: &[Rule], downloads: &[(String, String)]) -> Self {
+		let by_name: HashMap<&str, &Rule> = rules.iter().map(|rule| (rule.name.as_str(), rule)).collect();
+		let producers: HashMap<&str, &str> = rules
+			.iter()
+			.flat_map(|rule| rule.outputs.iter().map(|output| (output.as_str(), rule.name.as_str())))
+			.collect();
+
+		let mut packages = Vec::new();
+		let mut depended_on = HashSet::new();
+		for rule in rules.iter().filter(|rule| rule.version.is_some()) {
+			let mut depends_on = BTreeSet::new();
+			let mut visited = HashSet::from([rule.name.as_str()]);
+			let mut stack = direct_dependencies(rule, &by_name, &producers);
+			while let Some(name) = stack.pop() {
+				if !visited.insert(name) {
+					continue;
+				}
+				let dependency = by_name[name];
+				if dependency.version.is_some() {
+					depends_on.insert(name.to_string());
+				} else {
+					stack.extend(direct_dependencies(dependency, &by_name, &producers));
+				}
+			}
+			depended_on.extend(depends_on.iter().cloned());
+			packages.push(Package {
+				id: rule.name.clone(),
+				name: rule.name.clone(),
+				version: rule.version.clone(),
+				download: None,
+				blake3: None,
+				depends_on: depends_on.into_iter().collect(),
+			});
+		}
+		for (url, blake3) in downloads {
+			let file_name = url
+				.split(['?', '#'])
+				.next()
+				.and_then(|path| path.trim_end_matches('/').rsplit('/').next())
+				.filter(|name| !name.is_empty())
+				.unwrap_or(url);
+			packages.push(Package {
+				id: url.clone(),
+				name: file_name.to_string(),
+				version: version_in(file_name),
+				download: Some(url.clone()),
+				blake3: Some(blake3.clone()),
+				depends_on: Vec::new(),
+			});
+		}
+		packages.sort_by(|a, b| a.id.cmp(&b.id));
+
+		let roots = packages
+			.iter()
+			.filter(|package| !depended_on.contains(&package.id))
+			.map(|package| package.id.clone())
+			.collect();
+		Self {
+			name: name.to_string(),
+			version: version.to_string(),
+			packages,
+			roots,
+		}
+	}
+}
+
+/// The rules that rule names as dependencies or that produce its inputs
+fn direct_dependencies<'a>(
+	rule: &'a Rule,
+	by_name: &HashMap<&'a str, &'a Rule>,
+	producers: &HashMap<&'a str, &'a str>,
+) -> Vec<&'a str> {
+	rule.dependencies
+		.iter()
+		.map(String::as_str)
+		.chain(rule.inputs.iter().filter_map(|input| producers.get(input.as_str()).copied()))
+		.filter(|name| by_name.contains_key(name))
+		.collect()
+}
+
+/// The first MAJOR.MINOR.PATCH in a download's file name, like 5.0.2 in raylib-5.0.2.tar.gz
+fn version_in(file_name: &str) -> Option<String> {
+	let pattern = regex::Regex::new(r"\d+\.\d+\.\d+").ok()?;
+	pattern
+		.find_iter(file_name)
+		.find(|found| semver::Version::parse(found.as_str()).is_ok())
+		.map(|found| found.as_str().to_string())
+}
+
+fn to_spdx(sbom: &Sbom) -> Value {
+	let ids: HashMap<&str, String> = sbom
+		.packages
+		.iter()
+		.enumerate()
+		.map(|(i, package)| (package.id.as_str(), format!("SPDXRef-Package-{}", i + 1)))
+		.collect();
+
+	let mut packages = vec![json!({
+		"name": sbom.name,
+		"SPDXID": "SPDXRef-Project",
+		"versionInfo": sbom.version,
+		"downloadLocation": "NOASSERTION",
+		"filesAnalyzed": false,
+	})];
+	for package in &sbom.packages {
+		let mut entry = json!({
+			"name": package.name,
+			"SPDXID": ids[package.id.as_str()],
+			"downloadLocation": package.download.as_deref().unwrap_or("NOASSERTION"),
+			"filesAnalyzed": false,
+		});
+		if let Some(version) = &package.version {
+			entry["versionInfo"] = json!(version);
+		}
+		if let Some(blake3) = &package.blake3 {
+			entry["checksums"] = json!([{ "algorithm": "BLAKE3", "checksumValue": blake3 }]);
+		}
+		packages.push(entry);
+	}
+
+	let mut relationships = vec![json!({
+		"spdxElementId": "SPDXRef-DOCUMENT",
+		"relationshipType": "DESCRIBES",
+		"relatedSpdxElement": "SPDXRef-Project",
+	})];
+	let depends =
+		|from: &str, to: &str| json!({ "spdxElementId": from, "relationshipType": "DEPENDS_ON", "relatedSpdxElement": to });
+	relationships.extend(sbom.roots.iter().map(|root| depends("SPDXRef-Project", &ids[root.as_str()])));
+	for package in &sbom.packages {
+		relationships.extend(
+			package
+				.depends_on
+				.iter()
+				.map(|dependency| depends(&ids[package.id.as_str()], &ids[dependency.as_str()])),
+		);
+	}
+
+	json!({
+		"spdxVersion": "SPDX-2.3",
+		"dataLicense": "CC0-1.0",
+		"SPDXID": "SPDXRef-DOCUMENT",
+		"name": format!("{}-{}", sbom.name, sbom.version),
+		"documentNamespace": format!(
+			"https://spdx.org/spdxdocs/{}-{}-{}",
+			sbom.name,
+			sbom.version,
+			uuid::Uuid::new_v4()
+		),
+		"creationInfo": {
+			"created": chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+			"creators": [format!("Tool: forge-{}", env!("CARGO_PKG_VERSION"))],
+		},
+		"packages": packages,
+		"relationships": relationships,
+	})
+}
+
+fn to_cyclonedx(sbom: &Sbom) -> Value {
+	let project_ref = format!("{}@{}", sbom.name, sbom.version);
+	let components: Vec<Value> = sbom
+		.packages
+		.iter()
+		.map(|package| {
+			let mut component = json!({
+				"type": if package.download.is_some() { "file" } else { "library" },
+				"bom-ref": package.id,
+				"name": package.name,
+			});
+			if let Some(version) = &package.version {
+				component["version"] = json!(version);
+			}
+			if let Some(blake3) = &package.blake3 {
+				component["hashes"] = json!([{ "alg": "BLAKE3", "content": blake3 }]);
+			}
+			if let Some(download) = &package.download {
+				component["externalReferences"] = json!([{ "type": "distribution", "url": download }]);
+			}
+			component
+		})
+		.collect();
+
+	let mut dependencies = vec![json!({ "ref": project_ref, "dependsOn": sbom.roots })];
+	dependencies.extend(
+		sbom.packages
+			.iter()
+			.map(|package| json!({ "ref": package.id, "dependsOn": package.depends_on })),
+	);
+
+	json!({
+		"bomFormat": "CycloneDX",
+		"specVersion": "1.5",
+		"serialNumber": format!("urn:uuid:{}", uuid::Uuid::new_v4()),
+		"version": 1,
+		"metadata": {
+			"timestamp": chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+			"tools": [{ "name": "forge", "version": env!("CARGO_PKG_VERSION") }],
+			"component": {
+				"type": "application",
+				"bom-ref": project_ref,
+				"name": sbom.name,
+				"version": sbom.version,
+			},
+		},
+		"components": components,
+		"dependencies": dependencies,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::path::PathBuf;
+
+	fn rule(name: &str, version: Option<&str>, inputs: &[&str], outputs: &[&str], dependencies: &[&str]) -> Rule {
+		Rule {
+			name: name.to_string(),
+			command: "cc".to_string(),
+			inputs: inputs.iter().map(|s| s.to_string()).collect(),
+			outputs: outputs.iter().map(|s| s.to_string()).collect(),
+			dependencies: dependencies.iter().map(|s| s.to_string()).collect(),
+			workdir: PathBuf::from("/project"),
+			version: version.map(str::to_string),
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn test_sbom_packages() {
+		let rules = vec![
+			rule("app", Some("1.0.0"), &["app.o"], &["app"], &[]),
+			rule("app.o", None, &["app.c", "libmath.a"], &["app.o"], &[]),
+			rule("libmath", Some("0.3.1"), &[], &["libmath.a"], &["fetch_zlib"]),
+			rule("fetch_zlib", None, &[], &["zlib"], &[]),
+		];
+		let downloads = vec![("https://zlib.net/zlib-1.3.1.tar.gz?mirror=1".to_string(), "ab".repeat(32))];
+		let sbom = Sbom::new("demo", "0.1.0", &rules, &downloads);
+
+		let ids: Vec<&str> = sbom.packages.iter().map(|package| package.id.as_str()).collect();
+		assert_eq!(ids, ["app", "https://zlib.net/zlib-1.3.1.tar.gz?mirror=1", "libmath"]);
+		assert_eq!(sbom.packages[0].depends_on, ["libmath"]);
+		assert!(sbom.packages[2].depends_on.is_empty());
+		assert_eq!(sbom.packages[1].name, "zlib-1.3.1.tar.gz");
+		assert_eq!(sbom.packages[1].version.as_deref(), Some("1.3.1"));
+		assert_eq!(sbom.roots, ["app", "https://zlib.net/zlib-1.3.1.tar.gz?mirror=1"]);
+
+		let spdx: Value = serde_json::from_str(&SbomFormat::Spdx.render(&sbom)).unwrap();
+		assert_eq!(spdx["packages"].as_array().unwrap().len(), 4);
+		let cyclonedx: Value = serde_json::from_str(&SbomFormat::Cyclonedx.render(&sbom)).unwrap();
+		assert_eq!(cyclonedx["dependencies"][1]["dependsOn"], json!(["libmath"]));
+	}
+}
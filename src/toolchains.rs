@@ -0,0 +1,239 @@
+use crate::{
+	error::ForgeError,
+	forge_root_config::{SysrootConfig, TargetConfig, ToolchainConfig},
+	lockfile::Lockfile,
+	lua_api,
+};
+use serde::Serialize;
+use std::{
+	collections::{BTreeMap, HashMap},
+	path::{Path, PathBuf},
+};
+
+/// A toolchain unpacked under ~/.forge/toolchains, as FORGE files see it in forge.toolchains.<name>
+#[derive(Debug, Clone, Serialize)]
+pub struct Toolchain {
+	#[serde(skip)]
+	pub name: String,
+	pub version: String,
+	pub root: PathBuf,
+	/// Absolute, in the order they go in front of PATH
+	pub bin: Vec<PathBuf>,
+	pub env: BTreeMap<String, String>,
+}
+
+/// "<os>-<arch>" of the running forge, what [toolchains.<name>.platforms] are keyed by
+pub fn platform() -> String {
+	format!("{}-{}", std::env::consts::OS, std::env::consts::ARCH)
+}
+
+/// Download and unpack every toolchain that is not in ~/.forge/toolchains yet, sorted by name
+pub fn provision(
+	configs: &HashMap<String, ToolchainConfig>,
+	lockfile: Option<&Lockfile>,
+) -> Result<Vec<Toolchain>, ForgeError> {
+	let mut names: Vec<&String> = configs.keys().collect();
+	names.sort();
+
+	let mut toolchains = Vec::with_capacity(names.len());
+	for name in names {
+		let toolchain = provision_one(name, &configs[name], lockfile).map_err(|error| ForgeError::Toolchain {
+			name: name.clone(),
+			error,
+		})?;
+		toolchains.push(toolchain);
+	}
+	Ok(toolchains)
+}
+
+fn provision_one(name: &str, config: &ToolchainConfig, lockfile: Option<&Lockfile>) -> Result<Toolchain, String> {
+	let platform = platform();
+	let archive = config
+		.archive(&platform)
+		.ok_or_else(|| format!("no archive for {}, add it to [toolchains.{}.platforms]", platform, name))?;
+	let variables = [
+		("version", config.version.as_str()),
+		("os", std::env::consts::OS),
+		("arch", std::env::consts::ARCH),
+		("platform", platform.as_str()),
+	];
+	let url = expand(&archive.url, &variables);
+	let dir = store(name, &url, archive.sha256.as_deref(), archive.blake3.as_deref(), lockfile)?;
+
+	let root = match &config.root {
+		Some(root) => dir.join(expand(root, &variables)),
+		None => dir,
+	};
+	let root_str = root.to_string_lossy().to_string();
+	let mut variables = variables.to_vec();
+	variables.push(("root", &root_str));
+	Ok(Toolchain {
+		name: name.to_string(),
+		version: config.version.clone(),
+		bin: config.bin.iter().map(|bin| root.join(expand(bin, &variables))).collect(),
+		env: config
+			.env
+			.iter()
+			.map(|(key, value)| (key.clone(), expand(value, &variables)))
+			.collect(),
+		root,
+	})
+}
+
+/// The sysroots of FORGE_ROOT's [targets], by triple; archives are downloaded into ~/.forge/toolchains like toolchains
+pub fn provision_sysroots(
+	targets: &HashMap<String, TargetConfig>,
+	project_root: &Path,
+	lockfile: Option<&Lockfile>,
+) -> Result<BTreeMap<String, PathBuf>, ForgeError> {
+	let mut sysroots = BTreeMap::new();
+	for (triple, target) in targets {
+		let Some(sysroot) = &target.sysroot else {
+			continue;
+		};
+		let path = match sysroot {
+			SysrootConfig::Path(path) => project_root.join(path),
+			SysrootConfig::Archive {
+				url,
+				sha256,
+				blake3,
+				root,
+			} => fetch_sysroot(triple, url, sha256.as_deref(), blake3.as_deref(), root.as_deref(), lockfile).map_err(
+				|error| ForgeError::Toolchain {
+					name: format!("sysroot of {}", triple),
+					error,
+				},
+			)?,
+		};
+		sysroots.insert(triple.clone(), path);
+	}
+	Ok(sysroots)
+}
+
+/// Download the sysroot archive of triple into ~/.forge/toolchains unless it is there already, root being the
+/// directory of the archive that is the sysroot
+pub fn fetch_sysroot(
+	triple: &str,
+	url: &str,
+	sha256: Option<&str>,
+	blake3: Option<&str>,
+	root: Option<&str>,
+	lockfile: Option<&Lockfile>,
+) -> Result<PathBuf, String> {
+	let dir = store(&format!("sysroot-{}", triple), url, sha256, blake3, lockfile)?;
+	Ok(match root {
+		Some(root) => dir.join(root),
+		None => dir,
+	})
+}
+
+/// ~/.forge/toolchains/<name>-<key>, with the archive at url unpacked in it
+/// The key hashes url and the checksums, so changing either unpacks a fresh copy next to the old one
+fn store(
+	name: &str,
+	url: &str,
+	sha256: Option<&str>,
+	blake3: Option<&str>,
+	lockfile: Option<&Lockfile>,
+) -> Result<PathBuf, String> {
+	let mut hasher = blake3::Hasher::new();
+	for part in [Some(url), sha256, blake3] {
+		hasher.update(part.unwrap_or("").as_bytes());
+		hasher.update(&[0]);
+	}
+	let key = &hasher.finalize().to_hex()[..16];
+	let home = dirs::home_dir().ok_or("could not find the home directory")?;
+	let dir = home.join(".forge").join("toolchains").join(format!("{}-{}", name, key));
+
+	if !dir.exists() {
+		log::info!("Unpacking {} from {} into {}", name, url, dir.display());
+		let file = lua_api::http::download_file(url, blake3, sha256, lockfile).map_err(|e| e.to_string())?;
+		unpack(&file, &dir)?;
+	}
+	Ok(dir)
+}
+
+/// Extract archive beside dir and move it in place once complete, so an interrupted extraction is never used
+fn unpack(archive: &Path, dir: &Path) -> Result<(), String> {
+	let mut partial = dir.as_os_str().to_os_string();
+	partial.push(".partial");
+	let partial = PathBuf::from(partial);
+	if partial.exists() {
+		std::fs::remove_dir_all(&partial).map_err(|e| e.to_string())?;
+	}
+	lua_api::fs::extract_archive(archive, &partial).map_err(|e| e.to_string())?;
+	if let Err(e) = std::fs::rename(&partial, dir) {
+		let _ = std::fs::remove_dir_all(&partial);
+		// Another forge unpacked the same toolchain meanwhile
+		if !dir.exists() {
+			return Err(format!("Failed to move the toolchain to {}: {}", dir.display(), e));
+		}
+	}
+	Ok(())
+}
+
+/// Replace every {name} of variables in template
+fn expand(template: &str, variables: &[(&str, &str)]) -> String {
+	variables.iter().fold(template.to_string(), |expanded, (name, value)| {
+		expanded.replace(&format!("{{{}}}", name), value)
+	})
+}
+
+/// Put the bin directories of toolchains in front of the PATH of env, or of forge's own PATH when env has none,
+/// and add the variables of toolchains env does not set
+pub fn apply(toolchains: &[Toolchain], env: &mut HashMap<String, String>) {
+	for toolchain in toolchains {
+		for (key, value) in &toolchain.env {
+			env.entry(key.clone()).or_insert_with(|| value.clone());
+		}
+	}
+
+	let mut path: Vec<PathBuf> = toolchains
+		.iter()
+		.flat_map(|toolchain| toolchain.bin.iter().cloned())
+		.collect();
+	if path.is_empty() {
+		return;
+	}
+	match env.get("PATH") {
+		Some(existing) => path.extend(std::env::split_paths(existing)),
+		None => path.extend(std::env::var_os("PATH").iter().flat_map(std::env::split_paths)),
+	}
+	if let Ok(joined) = std::env::join_paths(path) {
+		env.insert("PATH".to_string(), joined.to_string_lossy().to_string());
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_apply_toolchains() {
+		assert_eq!(
+			expand("zig-{os}-{version}/{version}", &[("version", "0.13.0"), ("os", "linux")]),
+			"zig-linux-0.13.0/0.13.0"
+		);
+
+		let toolchain = Toolchain {
+			name: "llvm".to_string(),
+			version: "18.1.8".to_string(),
+			root: PathBuf::from("/tc/llvm"),
+			bin: vec![PathBuf::from("/tc/llvm/bin")],
+			env: BTreeMap::from([
+				("CC".to_string(), "/tc/llvm/bin/clang".to_string()),
+				("AR".to_string(), "/tc/llvm/bin/llvm-ar".to_string()),
+			]),
+		};
+		let mut env = HashMap::from([
+			("CC".to_string(), "gcc".to_string()),
+			("PATH".to_string(), "/usr/bin".to_string()),
+		]);
+		apply(&[toolchain], &mut env);
+
+		assert_eq!(env["CC"], "gcc");
+		assert_eq!(env["AR"], "/tc/llvm/bin/llvm-ar");
+		let path: Vec<PathBuf> = std::env::split_paths(&env["PATH"]).collect();
+		assert_eq!(path, [PathBuf::from("/tc/llvm/bin"), PathBuf::from("/usr/bin")]);
+	}
+}
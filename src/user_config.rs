@@ -0,0 +1,247 @@
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::HashMap,
+	path::{Path, PathBuf},
+	process::Command,
+	sync::LazyLock,
+};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum UserConfigError {
+	#[error("Failed to read user config: {0}")]
+	Io(#[from] std::io::Error),
+
+	#[error("Failed to parse user config TOML: {0}")]
+	Toml(#[from] toml::de::Error),
+}
+
+/// Per-user settings from ~/.forge/config.toml, kept out of FORGE files so secrets stay local
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct UserConfig {
+	#[serde(default)]
+	pub http: HttpUserConfig,
+	#[serde(default)]
+	pub signing: SigningUserConfig,
+	#[serde(default)]
+	pub metrics: MetricsUserConfig,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct HttpUserConfig {
+	/// Look up credentials in the netrc file for hosts without a credential helper (default false)
+	#[serde(default)]
+	pub use_netrc: bool,
+	/// Also send the netrc "default" entry to hosts without a machine entry of their own (default false)
+	#[serde(default)]
+	pub netrc_default: bool,
+	/// Netrc file to read (defaults to $NETRC, then ~/.netrc)
+	pub netrc_path: Option<String>,
+	/// Commands that print a bearer token for a host, e.g. "github.com" = ["gh", "auth", "token"]
+	#[serde(default)]
+	pub credential_helpers: HashMap<String, Vec<String>>,
+}
+
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct SigningUserConfig {
+	/// File holding a hex-encoded 32-byte ed25519 seed; when set, build manifests are signed with it
+	pub key_file: Option<String>,
+}
+
+/// Where to send the metrics of each build; nothing is sent when neither endpoint is set
+#[derive(Deserialize, Serialize, Debug, Clone, Default)]
+pub struct MetricsUserConfig {
+	/// OTLP/HTTP collector, e.g. "http://localhost:4318"; metrics are posted as JSON to <otlp_endpoint>/v1/metrics
+	pub otlp_endpoint: Option<String>,
+	/// Prometheus pushgateway, e.g. "http://localhost:9091"
+	pub pushgateway: Option<String>,
+	/// Pushgateway job name (defaults to "forge")
+	pub job: Option<String>,
+	/// Labels added to every metric, such as the host or CI pipeline
+	#[serde(default)]
+	pub labels: HashMap<String, String>,
+	/// Headers sent with each push, such as authorization for a hosted collector
+	#[serde(default)]
+	pub headers: HashMap<String, String>,
+}
+
+impl SigningUserConfig {
+	/// key_file with a leading ~/ expanded to the home directory
+	pub fn key_path(&self) -> Option<PathBuf> {
+		let key_file = self.key_file.as_deref()?;
+		match key_file.strip_prefix("~/") {
+			Some(rest) => dirs::home_dir().map(|home| home.join(rest)),
+			None => Some(PathBuf::from(key_file)),
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Credential {
+	Bearer(String),
+	Basic {
+		user: String,
+		pass: String,
+	},
+}
+
+static USER_CONFIG: LazyLock<UserConfig> = LazyLock::new(|| {
+	let Some(path) = UserConfig::default_path() else {
+		return UserConfig::default();
+	};
+	if !path.exists() {
+		return UserConfig::default();
+	}
+
+	UserConfig::load(&path).unwrap_or_else(|e| {
+		log::warn!("Ignoring {}: {}", path.display(), e);
+		UserConfig::default()
+	})
+});
+
+impl UserConfig {
+	pub fn default_path() -> Option<PathBuf> {
+		dirs::home_dir().map(|home| home.join(".forge").join("config.toml"))
+	}
+
+	pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, UserConfigError> {
+		let content = std::fs::read_to_string(path)?;
+		Ok(toml::from_str(&content)?)
+	}
+
+	/// The config of the current user, loaded once
+	pub fn get() -> &'static UserConfig {
+		&USER_CONFIG
+	}
+
+	/// Credentials for host from a credential helper, falling back to the netrc file
+	pub fn credentials_for(&self, host: &str) -> Option<Credential> {
+		if let Some(helper) = self.http.credential_helpers.get(host) {
+			match run_credential_helper(helper) {
+				Some(token) => return Some(Credential::Bearer(token)),
+				None => log::warn!("Credential helper for {} did not produce a token", host),
+			}
+		}
+
+		if !self.http.use_netrc {
+			return None;
+		}
+
+		let netrc_path = self
+			.http
+			.netrc_path
+			.clone()
+			.map(PathBuf::from)
+			.or_else(|| std::env::var_os("NETRC").map(PathBuf::from))
+			.or_else(|| dirs::home_dir().map(|home| home.join(".netrc")))?;
+		let content = std::fs::read_to_string(netrc_path).ok()?;
+		netrc_lookup(&content, host, self.http.netrc_default)
+	}
+}
+
+fn run_credential_helper(helper: &[String]) -> Option<String> {
+	let (program, args) = helper.split_first()?;
+	let output = Command::new(program).args(args).output().ok()?;
+	if !output.status.success() {
+		return None;
+	}
+
+	let token = String::from_utf8_lossy(&output.stdout).trim().to_string();
+	(!token.is_empty()).then_some(token)
+}
+
+/// Find the login and password for host in netrc content, using the "default" entry as a fallback when use_default
+pub fn netrc_lookup(content: &str, host: &str, use_default: bool) -> Option<Credential> {
+	let mut tokens = content.split_whitespace().peekable();
+	let mut default = None;
+
+	while let Some(token) = tokens.next() {
+		let is_match = match token {
+			"machine" => tokens.next() == Some(host),
+			"default" => false,
+			_ => continue,
+		};
+
+		let (mut user, mut pass) = (None, None);
+		while let Some(&key) = tokens.peek() {
+			if matches!(key, "machine" | "default") {
+				break;
+			}
+			tokens.next();
+			match key {
+				"login" => user = tokens.next(),
+				"password" => pass = tokens.next(),
+				"account" => {
+					tokens.next();
+				}
+				_ => {}
+			}
+		}
+
+		let credential = user.zip(pass).map(|(user, pass)| Credential::Basic {
+			user: user.to_string(),
+			pass: pass.to_string(),
+		});
+		if is_match {
+			return credential;
+		}
+		if use_default && token == "default" && default.is_none() {
+			default = credential;
+		}
+	}
+
+	default
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_default_user_config() {
+		let config: UserConfig = toml::from_str("").unwrap();
+		assert!(!config.http.use_netrc && !config.http.netrc_default);
+		assert!(config.http.credential_helpers.is_empty());
+		assert!(config.signing.key_path().is_none());
+		assert!(config.metrics.otlp_endpoint.is_none() && config.metrics.pushgateway.is_none());
+	}
+
+	#[test]
+	fn test_credential_helpers_parse() {
+		let config: UserConfig = toml::from_str(
+			r#"
+			[http]
+			use_netrc = true
+			[http.credential_helpers]
+			"github.com" = ["gh", "auth", "token"]
+			"#,
+		)
+		.unwrap();
+		assert!(config.http.use_netrc);
+		assert_eq!(config.http.credential_helpers["github.com"], vec!["gh", "auth", "token"]);
+	}
+
+	#[test]
+	fn test_netrc_lookup() {
+		let netrc = "machine example.com login alice password secret\n\
+			machine other.org\n  login bob\n  password hunter2\n\
+			default login anon password guest\n";
+
+		assert_eq!(
+			netrc_lookup(netrc, "other.org", false),
+			Some(Credential::Basic {
+				user: "bob".to_string(),
+				pass: "hunter2".to_string()
+			})
+		);
+		assert_eq!(netrc_lookup(netrc, "unknown.net", false), None);
+		assert_eq!(
+			netrc_lookup(netrc, "unknown.net", true),
+			Some(Credential::Basic {
+				user: "anon".to_string(),
+				pass: "guest".to_string()
+			})
+		);
+		assert_eq!(netrc_lookup("machine a login x", "a", false), None);
+	}
+}
@@ -0,0 +1,253 @@
+use crate::interrupt;
+use serde::{Deserialize, Serialize};
+use std::{
+	collections::{BTreeMap, HashMap},
+	fs::File,
+	io::{BufRead, BufReader, Write},
+	path::{Path, PathBuf},
+	process::{Child, ChildStdin, Command, Stdio},
+	sync::{
+		Mutex,
+		atomic::{AtomicU64, Ordering},
+		mpsc::{self, Receiver, RecvTimeoutError},
+	},
+	time::Duration,
+};
+
+/// Passed to the tool of a rule with worker = true when starting it, as Bazel does for persistent workers
+const PERSISTENT_WORKER_FLAG: &str = "--persistent_worker";
+
+/// One line on a worker's stdin, in the JSON flavour of Bazel's worker protocol
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct WorkRequest<'a> {
+	arguments: &'a [String],
+	inputs: Vec<WorkInput<'a>>,
+	request_id: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct WorkInput<'a> {
+	path: &'a str,
+}
+
+/// One line on a worker's stdout, answering a WorkRequest
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkResponse {
+	#[serde(default)]
+	pub exit_code: i32,
+	/// Everything the tool would have printed for this request
+	#[serde(default)]
+	pub output: String,
+}
+
+/// Tools that can serve several rules share a worker when they run with the same command, directory and environment
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct WorkerKey {
+	command: String,
+	workdir: PathBuf,
+	env: BTreeMap<String, String>,
+}
+
+struct Worker {
+	child: Child,
+	stdin: ChildStdin,
+	/// Lines of the worker's stdout, read on their own thread so waiting for an answer can time out
+	lines: Receiver<std::io::Result<String>>,
+}
+
+/// Tool processes of rules with worker = true, kept alive between the rules they run for until forge exits
+pub struct Workers {
+	/// Where the stderr of each worker goes
+	log_dir: PathBuf,
+	/// How long one request may take before the worker is considered hung
+	timeout: Duration,
+	idle: Mutex<HashMap<WorkerKey, Vec<Worker>>>,
+	next_request: AtomicU64,
+	next_worker: AtomicU64,
+}
+
+impl Workers {
+	pub fn new(log_dir: &Path, timeout: Duration) -> Self {
+		Self {
+			log_dir: log_dir.to_path_buf(),
+			timeout,
+			idle: Mutex::new(HashMap::new()),
+			next_request: AtomicU64::new(0),
+			next_worker: AtomicU64::new(0),
+		}
+	}
+
+	/// Run command with arguments on an idle worker, starting one when every worker for it is busy
+	pub fn run(
+		&self,
+		command: &str,
+		arguments: &[String],
+		inputs: &[String],
+		workdir: &Path,
+		env: &HashMap<String, String>,
+	) -> std::io::Result<WorkResponse> {
+		let key = WorkerKey {
+			command: command.to_string(),
+			workdir: workdir.to_path_buf(),
+			env: env.iter().map(|(name, value)| (name.clone(), value.clone())).collect(),
+		};
+		let idle = self.idle.lock().unwrap().get_mut(&key).and_then(Vec::pop);
+		let mut worker = match idle {
+			Some(worker) => worker,
+			None => self.spawn(&key)?,
+		};
+
+		let request = WorkRequest {
+			arguments,
+			inputs: inputs.iter().map(|path| WorkInput { path }).collect(),
+			request_id: self.next_request.fetch_add(1, Ordering::Relaxed),
+		};
+		match worker.send(&request, self.timeout) {
+			Ok(response) => {
+				self.idle.lock().unwrap().entry(key).or_default().push(worker);
+				Ok(response)
+			}
+			Err(e) => {
+				// A worker that broke the protocol or hung cannot be trusted with the next request
+				worker.stop();
+				Err(e)
+			}
+		}
+	}
+
+	fn spawn(&self, key: &WorkerKey) -> std::io::Result<Worker> {
+		std::fs::create_dir_all(&self.log_dir)?;
+		let name = Path::new(&key.command)
+			.file_name()
+			.map_or_else(|| "worker".into(), |name| name.to_string_lossy());
+		let log = File::create(self.log_dir.join(format!(
+			"{}-{}.log",
+			name,
+			self.next_worker.fetch_add(1, Ordering::Relaxed)
+		)))?;
+
+		log::debug!("Starting persistent worker: {} {}", key.command, PERSISTENT_WORKER_FLAG);
+		let mut command = Command::new(&key.command);
+		command
+			.arg(PERSISTENT_WORKER_FLAG)
+			.envs(&key.env)
+			.current_dir(&key.workdir)
+			.stdin(Stdio::piped())
+			.stdout(Stdio::piped())
+			.stderr(log);
+		// Its own process group, which Ctrl-C kills like the group of a running rule
+		#[cfg(unix)]
+		{
+			use std::os::unix::process::CommandExt;
+			command.process_group(0);
+		}
+		let mut child = command.spawn()?;
+		interrupt::track(child.id());
+		let stdin = child
+			.stdin
+			.take()
+			.ok_or_else(|| std::io::Error::other("worker has no stdin"))?;
+		let stdout = child
+			.stdout
+			.take()
+			.ok_or_else(|| std::io::Error::other("worker has no stdout"))?;
+		let (sender, lines) = mpsc::channel();
+		std::thread::spawn(move || {
+			for line in BufReader::new(stdout).lines() {
+				if sender.send(line).is_err() {
+					break;
+				}
+			}
+		});
+		Ok(Worker { child, stdin, lines })
+	}
+}
+
+impl Worker {
+	fn send(&mut self, request: &WorkRequest, timeout: Duration) -> std::io::Result<WorkResponse> {
+		let mut line = serde_json::to_vec(request)?;
+		line.push(b'\n');
+		self.stdin.write_all(&line)?;
+		self.stdin.flush()?;
+
+		let response = match self.lines.recv_timeout(timeout) {
+			Ok(line) => line?,
+			Err(RecvTimeoutError::Timeout) => {
+				return Err(std::io::Error::new(
+					std::io::ErrorKind::TimedOut,
+					format!("worker did not answer within {}s", timeout.as_secs_f64()),
+				));
+			}
+			Err(RecvTimeoutError::Disconnected) => return Err(std::io::Error::other("worker exited before answering")),
+		};
+		serde_json::from_str(&response)
+			.map_err(|e| std::io::Error::other(format!("worker answered with invalid JSON: {}", e)))
+	}
+
+	/// Kill the worker along with anything it started
+	fn stop(mut self) {
+		let pid = self.child.id();
+		// SAFETY: kill has no memory safety requirements, a negative pid addresses the process group
+		#[cfg(unix)]
+		unsafe {
+			libc::kill(-(pid as i32), libc::SIGKILL);
+		}
+		let _ = self.child.kill();
+		let _ = self.child.wait();
+		interrupt::untrack(pid);
+	}
+}
+
+impl Drop for Workers {
+	fn drop(&mut self) {
+		for worker in self
+			.idle
+			.get_mut()
+			.unwrap()
+			.values_mut()
+			.flat_map(|workers| workers.drain(..))
+		{
+			worker.stop();
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[cfg(unix)]
+	#[test]
+	fn test_hung_worker_times_out() {
+		use std::os::unix::fs::PermissionsExt;
+
+		let dir = std::env::temp_dir().join(format!("forge-workers-test-{}", std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		let answering = dir.join("answering");
+		std::fs::write(
+			&answering,
+			"#!/bin/sh\nwhile read line; do echo '{\"exitCode\":0,\"output\":\"ok\"}'; done\n",
+		)
+		.unwrap();
+		let hung = dir.join("hung");
+		std::fs::write(&hung, "#!/bin/sh\nread line\nsleep 30\n").unwrap();
+		for script in [&answering, &hung] {
+			std::fs::set_permissions(script, std::fs::Permissions::from_mode(0o755)).unwrap();
+		}
+
+		let workers = Workers::new(&dir.join("logs"), Duration::from_millis(500));
+		let run = |command: &Path| workers.run(&command.to_string_lossy(), &[], &[], &dir, &HashMap::new());
+		assert_eq!(run(&answering).unwrap().output, "ok");
+		assert_eq!(run(&answering).unwrap().output, "ok");
+
+		let start = std::time::Instant::now();
+		let error = run(&hung).unwrap_err();
+		assert_eq!(error.kind(), std::io::ErrorKind::TimedOut);
+		assert!(start.elapsed() < Duration::from_secs(10));
+
+		drop(workers);
+		std::fs::remove_dir_all(&dir).unwrap();
+	}
+}